@@ -1,6 +1,13 @@
 //! Compact binary representations of nucleic acid kmers
+use crate::errors::SequenceError;
+use crate::sequence::Strand;
+use crate::simd::{encode_2bit_bases, INVALID_2BIT};
+
 pub type BitKmerSeq = u64;
 pub type BitKmer = (BitKmerSeq, u8);
+/// Packed kmers for k > 32
+pub type LongBitKmerSeq = u128;
+pub type LongBitKmer = (LongBitKmerSeq, u8);
 
 const NUC2BIT_LOOKUP: [Option<u8>; 256] = {
     let mut lookup = [None; 256];
@@ -21,86 +28,235 @@ fn nuc2bti_lookup_nocheck(nuc: u8) -> Option<u8> {
     unsafe { *NUC2BIT_LOOKUP.get_unchecked(nuc as usize) }
 }
 
-/// Takes a `BitKmer` and adds a new base on the end, optionally loping off the
-/// first base if the resulting kmer is too long.
-fn extend_kmer(kmer: &mut BitKmer, new_char: u8) -> bool {
-    if let Some(new_char_int) = nuc2bti_lookup_nocheck(new_char) {
-        let new_kmer = (kmer.0 << 2) + new_char_int as BitKmerSeq;
+/// Unsigned integers that can hold a 2-bit packed kmer: `u64` for k <= 32 and
+/// `u128` for k <= 64.
+pub trait KmerBits: Copy + Ord + std::hash::Hash + std::fmt::Debug {
+    /// Longest kmer that fits in the type
+    const MAX_K: u8;
+    const ZERO: Self;
 
-        // mask out any overflowed bits
-        kmer.0 = new_kmer & (BitKmerSeq::pow(2, u32::from(2 * kmer.1)) - 1) as BitKmerSeq;
-        true
-    } else {
-        false
-    }
+    /// Shifts a new base (in its 2-bit form) on the end of a kmer of length `k`,
+    /// dropping the first base.
+    fn push_base(self, base: u8, k: u8) -> Self;
+
+    /// Shifts a new base (in its 2-bit form) on the start of a kmer of length
+    /// `k`, dropping the last base.
+    fn push_base_front(self, base: u8, k: u8) -> Self;
+
+    /// Reverse complements a kmer of length `k`
+    fn reverse_complement(self, k: u8) -> Self;
+
+    /// The 2-bit form of the base at position `i` of a kmer of length `k`
+    fn base_at(self, k: u8, i: u8) -> u8;
 }
 
-/// Used for the `BitNuclKmer` iterator to handle skipping invalid bases.
-fn update_position(
-    start_pos: &mut usize,
-    kmer: &mut BitKmer,
-    buffer: &[u8],
-    initial: bool,
-) -> bool {
-    // check if we have enough "physical" space for one more kmer
-    if *start_pos + kmer.1 as usize > buffer.len() {
-        return false;
+impl KmerBits for u64 {
+    const MAX_K: u8 = 32;
+    const ZERO: Self = 0;
+
+    #[inline]
+    fn push_base(self, base: u8, k: u8) -> Self {
+        let mask = if k >= Self::MAX_K {
+            !0
+        } else {
+            (1 << (2 * k)) - 1
+        };
+        ((self << 2) | Self::from(base)) & mask
     }
 
-    let (mut kmer_len, stop_len) = if initial {
-        (0, (kmer.1 - 1) as usize)
-    } else {
-        ((kmer.1 - 1) as usize, kmer.1 as usize)
-    };
+    #[inline]
+    fn push_base_front(self, base: u8, k: u8) -> Self {
+        (self >> 2) | (Self::from(base) << (2 * (k - 1)))
+    }
+
+    #[inline]
+    fn reverse_complement(self, k: u8) -> Self {
+        // inspired from https://www.biostars.org/p/113640/
+        let mut new_kmer = self;
+        // reverse it
+        new_kmer =
+            (new_kmer >> 2 & 0x3333_3333_3333_3333) | (new_kmer & 0x3333_3333_3333_3333) << 2;
+        new_kmer =
+            (new_kmer >> 4 & 0x0F0F_0F0F_0F0F_0F0F) | (new_kmer & 0x0F0F_0F0F_0F0F_0F0F) << 4;
+        new_kmer =
+            (new_kmer >> 8 & 0x00FF_00FF_00FF_00FF) | (new_kmer & 0x00FF_00FF_00FF_00FF) << 8;
+        new_kmer =
+            (new_kmer >> 16 & 0x0000_FFFF_0000_FFFF) | (new_kmer & 0x0000_FFFF_0000_FFFF) << 16;
+        new_kmer =
+            (new_kmer >> 32 & 0x0000_0000_FFFF_FFFF) | (new_kmer & 0x0000_0000_FFFF_FFFF) << 32;
+        // complement it
+        new_kmer ^= 0xFFFF_FFFF_FFFF_FFFF;
+        // shift it to the right size
+        new_kmer
+            .checked_shr(2 * u32::from(Self::MAX_K - k))
+            .unwrap_or(0)
+    }
+
+    #[inline]
+    fn base_at(self, k: u8, i: u8) -> u8 {
+        ((self >> (2 * (k - 1 - i))) & 3) as u8
+    }
+}
+
+impl KmerBits for u128 {
+    const MAX_K: u8 = 64;
+    const ZERO: Self = 0;
 
-    let cur_kmer = kmer;
-    while kmer_len < stop_len {
-        if extend_kmer(cur_kmer, buffer[*start_pos + kmer_len]) {
-            kmer_len += 1;
+    #[inline]
+    fn push_base(self, base: u8, k: u8) -> Self {
+        let mask = if k >= Self::MAX_K {
+            !0
         } else {
-            kmer_len = 0;
-            *cur_kmer = (0u64, cur_kmer.1);
-            *start_pos += kmer_len + 1;
-            if *start_pos + cur_kmer.1 as usize > buffer.len() {
-                return false;
-            }
-        }
+            (1 << (2 * k)) - 1
+        };
+        ((self << 2) | Self::from(base)) & mask
+    }
+
+    #[inline]
+    fn push_base_front(self, base: u8, k: u8) -> Self {
+        (self >> 2) | (Self::from(base) << (2 * (k - 1)))
+    }
+
+    #[inline]
+    fn reverse_complement(self, k: u8) -> Self {
+        // same as for u64 but reversing the two halves first
+        let high = (self >> 64) as u64;
+        let low = self as u64;
+        let reversed = (u128::from(low.reverse_complement(32)) << 64)
+            | u128::from(high.reverse_complement(32));
+        reversed
+            .checked_shr(2 * u32::from(Self::MAX_K - k))
+            .unwrap_or(0)
+    }
+
+    #[inline]
+    fn base_at(self, k: u8, i: u8) -> u8 {
+        ((self >> (2 * (k - 1 - i))) & 3) as u8
+    }
+}
+
+/// Takes a `BitKmer` and adds a new base on the end, optionally loping off the
+/// first base if the resulting kmer is too long.
+fn extend_kmer<T: KmerBits>(kmer: &mut (T, u8), new_char: u8) -> bool {
+    if let Some(new_char_int) = nuc2bti_lookup_nocheck(new_char) {
+        kmer.0 = kmer.0.push_base(new_char_int, kmer.1);
+        true
+    } else {
+        false
     }
-    true
 }
 
-pub struct BitNuclKmer<'a> {
+/// How many bases `BitNuclKmer` converts to 2-bit codes at once
+const CODE_BLOCK: usize = 64;
+
+/// Iterator over the valid (ACGT-only) kmers of a sequence in their 2-bit form.
+///
+/// By default kmers are stored in a `u64` so k can be at most 32; use
+/// `BitNuclKmer::new_long` (or `Sequence::long_bit_kmers`) to get `u128`
+/// kmers for k up to 64.
+///
+/// It can also be iterated from the end of the sequence with
+/// `.rev()`/`.next_back()`.
+pub struct BitNuclKmer<'a, T: KmerBits = BitKmerSeq> {
     start_pos: usize,
-    cur_kmer: BitKmer,
+    cur_kmer: (T, u8),
+    /// the part of the sequence not consumed from the back yet
     buffer: &'a [u8],
     canonical: bool,
+    /// the last kmer returned by `next_back`, if it was at the end of `buffer`
+    back_kmer: Option<T>,
+    /// 2-bit codes of `buffer[codes_start..codes_end]`, see `code_at`
+    codes: [u8; CODE_BLOCK],
+    codes_start: usize,
+    codes_end: usize,
 }
 
 impl<'a> BitNuclKmer<'a> {
     pub fn new(slice: &'a [u8], k: u8, canonical: bool) -> BitNuclKmer<'a> {
-        let mut kmer = (0u64, k);
-        let mut start_pos = 0;
-        update_position(&mut start_pos, &mut kmer, slice, true);
+        Self::with_kmer_type(slice, k, canonical)
+    }
+}
+
+impl<'a> BitNuclKmer<'a, LongBitKmerSeq> {
+    pub fn new_long(slice: &'a [u8], k: u8, canonical: bool) -> Self {
+        Self::with_kmer_type(slice, k, canonical)
+    }
+}
 
-        BitNuclKmer {
-            start_pos,
-            cur_kmer: kmer,
+impl<'a, T: KmerBits> BitNuclKmer<'a, T> {
+    fn with_kmer_type(slice: &'a [u8], k: u8, canonical: bool) -> Self {
+        assert!(
+            k <= T::MAX_K,
+            "k can be at most {} for this kmer type",
+            T::MAX_K
+        );
+        let mut kmers = BitNuclKmer {
+            start_pos: 0,
+            cur_kmer: (T::ZERO, k),
             buffer: slice,
             canonical,
+            back_kmer: None,
+            codes: [0; CODE_BLOCK],
+            codes_start: 0,
+            codes_end: 0,
+        };
+        kmers.update_position(true);
+        kmers
+    }
+
+    /// The 2-bit code of the base at `i` (or `INVALID_2BIT`). The sequence is
+    /// converted a block at a time with SIMD instead of base by base.
+    #[inline]
+    fn code_at(&mut self, i: usize) -> u8 {
+        if i < self.codes_start || i >= self.codes_end {
+            let end = (i + CODE_BLOCK).min(self.buffer.len());
+            encode_2bit_bases(&self.buffer[i..end], &mut self.codes[..end - i]);
+            self.codes_start = i;
+            self.codes_end = end;
         }
+        self.codes[i - self.codes_start]
+    }
+
+    /// Moves to the next valid kmer, skipping invalid bases. When `initial` is
+    /// set, only the first k - 1 bases are loaded.
+    fn update_position(&mut self, initial: bool) -> bool {
+        let k = self.cur_kmer.1 as usize;
+        // check if we have enough "physical" space for one more kmer
+        if self.start_pos + k > self.buffer.len() {
+            return false;
+        }
+
+        let (mut kmer_len, stop_len) = if initial { (0, k - 1) } else { (k - 1, k) };
+
+        while kmer_len < stop_len {
+            let code = self.code_at(self.start_pos + kmer_len);
+            if code == INVALID_2BIT {
+                // restart right after the invalid base
+                self.start_pos += kmer_len + 1;
+                kmer_len = 0;
+                self.cur_kmer.0 = T::ZERO;
+                if self.start_pos + k > self.buffer.len() {
+                    return false;
+                }
+            } else {
+                self.cur_kmer.0 = self.cur_kmer.0.push_base(code, self.cur_kmer.1);
+                kmer_len += 1;
+            }
+        }
+        true
     }
 }
 
-impl Iterator for BitNuclKmer<'_> {
-    type Item = (usize, BitKmer, bool);
+impl<T: KmerBits> Iterator for BitNuclKmer<'_, T> {
+    type Item = (usize, (T, u8), bool);
 
-    fn next(&mut self) -> Option<(usize, BitKmer, bool)> {
-        if !update_position(&mut self.start_pos, &mut self.cur_kmer, self.buffer, false) {
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.update_position(false) {
             return None;
         }
         self.start_pos += 1;
         if self.canonical {
-            let (kmer, was_rc) = canonical(self.cur_kmer);
+            let (kmer, was_rc) = canonical_bits(self.cur_kmer);
             Some((self.start_pos - 1, kmer, was_rc))
         } else {
             Some((self.start_pos - 1, self.cur_kmer, false))
@@ -108,30 +264,208 @@ impl Iterator for BitNuclKmer<'_> {
     }
 }
 
+impl<T: KmerBits> DoubleEndedIterator for BitNuclKmer<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let k = self.cur_kmer.1;
+        loop {
+            let end = self.buffer.len();
+            if end < k as usize || end - (k as usize) < self.start_pos {
+                return None;
+            }
+            let pos = end - k as usize;
+            let kmer = match self.back_kmer {
+                // the previous kmer started right after this one
+                Some(previous) => match nuc2bti_lookup_nocheck(self.buffer[pos]) {
+                    Some(base) => previous.push_base_front(base, k),
+                    None => {
+                        self.back_kmer = None;
+                        self.buffer = &self.buffer[..pos];
+                        continue;
+                    }
+                },
+                None => {
+                    let window = &self.buffer[pos..];
+                    if let Some(bad) = window
+                        .iter()
+                        .rposition(|b| nuc2bti_lookup_nocheck(*b).is_none())
+                    {
+                        self.buffer = &self.buffer[..pos + bad];
+                        continue;
+                    }
+                    let mut kmer = (T::ZERO, k);
+                    for base in window {
+                        extend_kmer(&mut kmer, *base);
+                    }
+                    kmer.0
+                }
+            };
+            // drop the last base so the front can't go past this kmer either
+            self.buffer = &self.buffer[..end - 1];
+            self.back_kmer = Some(kmer);
+            if self.canonical {
+                let (kmer, was_rc) = canonical_bits((kmer, k));
+                return Some((pos, kmer, was_rc));
+            }
+            return Some((pos, (kmer, k), false));
+        }
+    }
+}
+
+/// A canonical kmer in 2-bit form along with where it was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CanonicalBitKmer {
+    /// The canonical kmer
+    pub kmer: BitKmerSeq,
+    pub k: u8,
+    /// Position of the kmer in the sequence
+    pub pos: usize,
+    /// `Reverse` if `kmer` is the reverse complement of the sequence at `pos`
+    pub strand: Strand,
+}
+
+impl CanonicalBitKmer {
+    /// The canonical kmer as a `BitKmer`
+    pub fn bit_kmer(&self) -> BitKmer {
+        (self.kmer, self.k)
+    }
+
+    /// The kmer as it appears in the sequence
+    pub fn original(&self) -> BitKmer {
+        match self.strand {
+            Strand::Forward => self.bit_kmer(),
+            Strand::Reverse => reverse_complement(self.bit_kmer()),
+        }
+    }
+
+    pub fn is_reverse(&self) -> bool {
+        self.strand.is_reverse()
+    }
+
+    /// End (exclusive) of the kmer in the sequence
+    pub fn end(&self) -> usize {
+        self.pos + self.k as usize
+    }
+
+    /// The canonical kmer as bases
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bitmer_to_bytes(self.bit_kmer())
+    }
+}
+
+/// Same as `BitNuclKmer` with `canonical` set, but returns `CanonicalBitKmer`s
+pub struct CanonicalBitKmers<'a>(BitNuclKmer<'a>);
+
+impl<'a> CanonicalBitKmers<'a> {
+    pub fn new(slice: &'a [u8], k: u8) -> Self {
+        CanonicalBitKmers(BitNuclKmer::new(slice, k, true))
+    }
+}
+
+impl Iterator for CanonicalBitKmers<'_> {
+    type Item = CanonicalBitKmer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (pos, (kmer, k), was_rc) = self.0.next()?;
+        Some(CanonicalBitKmer {
+            kmer,
+            k,
+            pos,
+            strand: if was_rc {
+                Strand::Reverse
+            } else {
+                Strand::Forward
+            },
+        })
+    }
+}
+
+/// Iterator over the packed "care" positions of each window of a spaced seed
+/// (see `kmer::SpacedKmers`). Windows where any care position isn't ACGT are
+/// skipped; the other positions can contain anything.
+///
+/// The returned kmers have a length equal to the number of care positions,
+/// which needs to be at most 32. Note that when `canonical` is set, the kmer is
+/// compared to the reverse complement of its care positions, which is only the
+/// same as the seed applied to the other strand for symmetric patterns.
+pub struct SpacedBitKmers<'a> {
+    care: Vec<usize>,
+    span: usize,
+    start_pos: usize,
+    buffer: &'a [u8],
+    canonical: bool,
+}
+
+impl<'a> SpacedBitKmers<'a> {
+    pub fn new(buffer: &'a [u8], pattern: &[bool], canonical: bool) -> Self {
+        assert!(
+            pattern.first() == Some(&true) && pattern.last() == Some(&true),
+            "Spaced seeds need to start and end with a care position"
+        );
+        let care = crate::kmer::care_positions(pattern);
+        assert!(
+            care.len() <= 32,
+            "Spaced seeds can have at most 32 care positions"
+        );
+        SpacedBitKmers {
+            care,
+            span: pattern.len(),
+            start_pos: 0,
+            buffer,
+            canonical,
+        }
+    }
+}
+
+impl Iterator for SpacedBitKmers<'_> {
+    type Item = (usize, BitKmer, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        'windows: while self.start_pos + self.span <= self.buffer.len() {
+            let pos = self.start_pos;
+            self.start_pos += 1;
+            let mut kmer = 0;
+            for i in &self.care {
+                match nuc2bti_lookup_nocheck(self.buffer[pos + i]) {
+                    Some(b) => kmer = (kmer << 2) | BitKmerSeq::from(b),
+                    None => continue 'windows,
+                }
+            }
+            let kmer = (kmer, self.care.len() as u8);
+            if self.canonical {
+                let (kmer, was_rc) = canonical(kmer);
+                return Some((pos, kmer, was_rc));
+            }
+            return Some((pos, kmer, false));
+        }
+        None
+    }
+}
+
 /// Reverse complement a `BitKmer` (reverses the sequence and swaps A<>T and G<>C)
 pub fn reverse_complement(kmer: BitKmer) -> BitKmer {
-    // FIXME: this is not going to work with BitKmers of u128 or u32
-    // inspired from https://www.biostars.org/p/113640/
-    let mut new_kmer = kmer.0;
-    // reverse it
-    new_kmer = (new_kmer >> 2 & 0x3333_3333_3333_3333) | (new_kmer & 0x3333_3333_3333_3333) << 2;
-    new_kmer = (new_kmer >> 4 & 0x0F0F_0F0F_0F0F_0F0F) | (new_kmer & 0x0F0F_0F0F_0F0F_0F0F) << 4;
-    new_kmer = (new_kmer >> 8 & 0x00FF_00FF_00FF_00FF) | (new_kmer & 0x00FF_00FF_00FF_00FF) << 8;
-    new_kmer = (new_kmer >> 16 & 0x0000_FFFF_0000_FFFF) | (new_kmer & 0x0000_FFFF_0000_FFFF) << 16;
-    new_kmer = (new_kmer >> 32 & 0x0000_0000_FFFF_FFFF) | (new_kmer & 0x0000_0000_FFFF_FFFF) << 32;
-    // complement it
-    new_kmer ^= 0xFFFF_FFFF_FFFF_FFFF;
-    // shift it to the right size
-    new_kmer >>= 2 * (32 - kmer.1);
-    (new_kmer, kmer.1)
+    (kmer.0.reverse_complement(kmer.1), kmer.1)
 }
 
 /// Return the lexigraphically lowest of the `BitKmer` and its reverse complement and
 /// whether the returned kmer is the `reverse_complement` (true) or the original (false)
 pub fn canonical(kmer: BitKmer) -> (BitKmer, bool) {
-    let rc = reverse_complement(kmer);
-    if kmer.0 > rc.0 {
-        (rc, true)
+    canonical_bits(kmer)
+}
+
+/// Same as `reverse_complement` for `LongBitKmer`s
+pub fn reverse_complement_long(kmer: LongBitKmer) -> LongBitKmer {
+    (kmer.0.reverse_complement(kmer.1), kmer.1)
+}
+
+/// Same as `canonical` for `LongBitKmer`s
+pub fn canonical_long(kmer: LongBitKmer) -> (LongBitKmer, bool) {
+    canonical_bits(kmer)
+}
+
+fn canonical_bits<T: KmerBits>(kmer: (T, u8)) -> ((T, u8), bool) {
+    let rc = kmer.0.reverse_complement(kmer.1);
+    if kmer.0 > rc {
+        ((rc, kmer.1), true)
     } else {
         (kmer, false)
     }
@@ -156,28 +490,245 @@ pub fn minimizer(kmer: BitKmer, minmer_size: u8) -> BitKmer {
     (lowest, kmer.1)
 }
 
+/// Returns all the kmers at a Hamming distance of 1 to `d` of `kmer` (so not
+/// including the kmer itself), each exactly once. Useful for error-tolerant
+/// matching of barcodes or for correcting kmers against a spectrum.
+///
+/// There are `3k` neighbours at distance 1 and `9k(k-1)/2` more at distance 2
+/// so this quickly grows for larger `d`.
+///
+/// ```
+/// use needletail::bitkmer::{hamming_neighbors, BitKmer, BitKmerExt};
+///
+/// let kmer = BitKmer::from_bytes(b"AC").unwrap();
+/// let neighbors = hamming_neighbors(kmer, 1);
+/// assert_eq!(neighbors.len(), 6);
+/// assert!(neighbors.contains(&BitKmer::from_bytes(b"AG").unwrap()));
+/// ```
+pub fn hamming_neighbors(kmer: BitKmer, d: u8) -> Vec<BitKmer> {
+    fn add_neighbors(kmer: BitKmer, start: u8, d: u8, neighbors: &mut Vec<BitKmer>) {
+        for pos in start..kmer.1 {
+            let shift = 2 * (kmer.1 - 1 - pos);
+            // xor-ing a base with 1, 2 and 3 gives each of the 3 other bases
+            for substitution in 1..4 {
+                let neighbor = (kmer.0 ^ (substitution << shift), kmer.1);
+                neighbors.push(neighbor);
+                if d > 1 {
+                    add_neighbors(neighbor, pos + 1, d - 1, neighbors);
+                }
+            }
+        }
+    }
+
+    let mut neighbors = Vec::new();
+    if d > 0 {
+        add_neighbors(kmer, 0, d, &mut neighbors);
+    }
+    neighbors
+}
+
+/// Scrambles the bits of a `BitKmerSeq` so that "similar" kmers (e.g. poly-A runs)
+/// don't end up with similar values. This is the invertible integer hash used by
+/// minimap2 and is what the sampling schemes (strobemers, minimizers, etc) order
+/// kmers by.
+#[inline]
+pub fn mix_hash(kmer: BitKmerSeq) -> u64 {
+    let mut key = (!kmer).wrapping_add(kmer << 21);
+    key ^= key >> 24;
+    key = key.wrapping_add(key << 3).wrapping_add(key << 8);
+    key ^= key >> 14;
+    key = key.wrapping_add(key << 2).wrapping_add(key << 4);
+    key ^= key >> 28;
+    key.wrapping_add(key << 31)
+}
+
 pub fn bitmer_to_bytes(kmer: BitKmer) -> Vec<u8> {
-    let mut new_kmer = kmer.0;
-    let mut new_kmer_str = Vec::new();
-    // we're reading the bases off from the "high" end of the integer so we need to do some
-    // math to figure out where they start (this helps us just pop the bases on the end
-    // of the working buffer as we read them off "left to right")
-    let offset = (kmer.1 - 1) * 2;
-    let bitmask = BitKmerSeq::pow(2, u32::from(2 * kmer.1 - 1))
-        + BitKmerSeq::pow(2, u32::from(2 * kmer.1 - 2));
-
-    for _ in 0..kmer.1 {
-        let new_char = (new_kmer & bitmask) >> offset;
-        new_kmer <<= 2;
-        new_kmer_str.push(match new_char {
+    bits_to_bytes(kmer)
+}
+
+/// Same as `bitmer_to_bytes` for `LongBitKmer`s
+pub fn long_bitmer_to_bytes(kmer: LongBitKmer) -> Vec<u8> {
+    bits_to_bytes(kmer)
+}
+
+fn bits_to_bytes<T: KmerBits>(kmer: (T, u8)) -> Vec<u8> {
+    (0..kmer.1)
+        .map(|i| match kmer.0.base_at(kmer.1, i) {
             0 => b'A',
             1 => b'C',
             2 => b'G',
             3 => b'T',
             _ => unreachable!("Mathematical impossibility"),
-        });
+        })
+        .collect()
+}
+
+/// Convenience methods to build, decode and print `BitKmer`s.
+///
+/// ```
+/// use needletail::bitkmer::{BitKmer, BitKmerExt};
+///
+/// let kmer = BitKmer::from_bytes(b"ACGT").unwrap();
+/// assert_eq!(kmer, (0b00_01_10_11, 4));
+/// assert_eq!(kmer.to_vec(), b"ACGT");
+/// assert_eq!(format!("{}", kmer.display()), "ACGT");
+/// assert!(BitKmer::from_bytes(b"ACNT").is_err());
+/// ```
+pub trait BitKmerExt: Sized {
+    /// Packs a sequence of at most 32 ACGT bases (in any case)
+    fn from_bytes(seq: &[u8]) -> Result<Self, SequenceError>;
+
+    /// Decodes the kmer back to its bases, same as `bitmer_to_bytes`
+    fn to_vec(&self) -> Vec<u8>;
+
+    /// Returns an object implementing `Display` to print the bases of the kmer
+    fn display(&self) -> BitKmerDisplay;
+
+    fn to_string(&self) -> String {
+        self.display().to_string()
+    }
+}
+
+impl BitKmerExt for BitKmer {
+    fn from_bytes(seq: &[u8]) -> Result<Self, SequenceError> {
+        if seq.len() > BitKmerSeq::MAX_K as usize {
+            return Err(SequenceError::TooLong {
+                length: seq.len(),
+                max: BitKmerSeq::MAX_K as usize,
+            });
+        }
+        let mut kmer = (0, seq.len() as u8);
+        for (position, base) in seq.iter().enumerate() {
+            if !extend_kmer(&mut kmer, *base) {
+                return Err(SequenceError::InvalidBase {
+                    position,
+                    base: *base,
+                });
+            }
+        }
+        Ok(kmer)
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        bitmer_to_bytes(*self)
+    }
+
+    fn display(&self) -> BitKmerDisplay {
+        BitKmerDisplay(*self)
+    }
+}
+
+/// Helper struct for printing `BitKmer`s with `format!` and `{}`, see
+/// `BitKmerExt::display`
+#[derive(Debug, Clone, Copy)]
+pub struct BitKmerDisplay(BitKmer);
+
+impl std::fmt::Display for BitKmerDisplay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // only ACGT so always valid UTF-8
+        f.write_str(std::str::from_utf8(&bitmer_to_bytes(self.0)).unwrap())
+    }
+}
+
+/// A 2-bit packed kmer whose length is part of its type, so kmers of different
+/// lengths can't be mixed up and collections keyed on kmers don't need to store
+/// or check lengths at runtime. `K` must be between 1 and 32.
+///
+/// Ordering is lexicographic on the bases.
+///
+/// ```
+/// use needletail::bitkmer::Kmer;
+///
+/// let kmer = Kmer::<4>::from_slice(b"ACGG").unwrap();
+/// assert_eq!(kmer.reverse_complement().to_bytes(), b"CCGT");
+/// assert!(Kmer::<4>::from_slice(b"ACG").is_none());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Kmer<const K: usize>(BitKmerSeq);
+
+impl<const K: usize> Kmer<K> {
+    const VALID_K: () = assert!(K > 0 && K <= 32, "Kmer<K> only supports 1 <= K <= 32");
+
+    /// Packs a slice of exactly `K` ACGT bases (in any case), returning `None`
+    /// otherwise.
+    pub fn from_slice(seq: &[u8]) -> Option<Self> {
+        let () = Self::VALID_K;
+        if seq.len() != K {
+            return None;
+        }
+        let mut bits: BitKmerSeq = 0;
+        for base in seq {
+            bits = (bits << 2) | BitKmerSeq::from(nuc2bti_lookup_nocheck(*base)?);
+        }
+        Some(Kmer(bits))
+    }
+
+    /// Wraps already packed bits, ignoring any bit past the `2 * K` lowest ones
+    pub fn from_bits(bits: BitKmerSeq) -> Self {
+        let () = Self::VALID_K;
+        Kmer(bits & Self::mask())
+    }
+
+    #[inline]
+    fn mask() -> BitKmerSeq {
+        !0 >> (64 - 2 * K)
+    }
+
+    /// The packed bits, with the first base in the highest bits
+    #[inline]
+    pub fn bits(&self) -> BitKmerSeq {
+        self.0
+    }
+
+    #[inline]
+    pub fn reverse_complement(&self) -> Self {
+        Kmer(self.0.reverse_complement(K as u8))
+    }
+
+    /// Returns the lowest of the kmer and its reverse complement and whether the
+    /// reverse complement was picked
+    #[inline]
+    pub fn canonical(&self) -> (Self, bool) {
+        let rc = self.reverse_complement();
+        if rc < *self {
+            (rc, true)
+        } else {
+            (*self, false)
+        }
+    }
+
+    /// The 4 kmers that can follow this one in a sequence (dropping the first
+    /// base and adding A, C, G or T), in that order
+    pub fn successors(&self) -> [Self; 4] {
+        [0u8, 1, 2, 3].map(|b| Kmer(self.0.push_base(b, K as u8)))
+    }
+
+    /// The 4 kmers that can precede this one in a sequence (dropping the last
+    /// base and adding A, C, G or T in front), in that order
+    pub fn predecessors(&self) -> [Self; 4] {
+        [0u8, 1, 2, 3].map(|b| Kmer((self.0 >> 2) | (BitKmerSeq::from(b) << (2 * (K - 1)))))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bits_to_bytes((self.0, K as u8))
+    }
+}
+
+impl<const K: usize> std::fmt::Debug for Kmer<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Kmer<{}>({})",
+            K,
+            String::from_utf8_lossy(&self.to_bytes())
+        )
+    }
+}
+
+impl<const K: usize> From<Kmer<K>> for BitKmer {
+    fn from(kmer: Kmer<K>) -> Self {
+        (kmer.0, K as u8)
     }
-    new_kmer_str
 }
 
 #[cfg(test)]
@@ -245,6 +796,76 @@ mod tests {
         assert_eq!(kmer_iter.next(), None);
     }
 
+    #[test]
+    fn can_kmerize_with_strands() {
+        let kmers: Vec<CanonicalBitKmer> = CanonicalBitKmers::new(b"ACGTNTTAC", 3).collect();
+        assert_eq!(kmers.len(), 4);
+        assert_eq!(kmers[0].to_bytes(), b"ACG");
+        assert_eq!(kmers[0].strand, Strand::Forward);
+        // TTA -> TAA
+        assert_eq!(kmers[2].pos, 5);
+        assert_eq!(kmers[2].end(), 8);
+        assert!(kmers[2].is_reverse());
+        assert_eq!(bitmer_to_bytes(kmers[2].original()), b"TTA");
+        for (kmer, (pos, bit_kmer, was_rc)) in
+            kmers.iter().zip(BitNuclKmer::new(b"ACGTNTTAC", 3, true))
+        {
+            assert_eq!(
+                (kmer.pos, kmer.bit_kmer(), kmer.is_reverse()),
+                (pos, bit_kmer, was_rc)
+            );
+        }
+    }
+
+    #[test]
+    fn can_kmerize_backwards() {
+        let seq = b"ACGTNACGTACGTTNNAC";
+        for k in [1, 3, 4, 7] {
+            let forward: Vec<_> = BitNuclKmer::new(seq, k, true).collect();
+            let mut backward: Vec<_> = BitNuclKmer::new(seq, k, true).rev().collect();
+            backward.reverse();
+            assert_eq!(forward, backward);
+        }
+        let forward: Vec<_> = BitNuclKmer::new_long(seq, 3, false).collect();
+        let mut backward: Vec<_> = BitNuclKmer::new_long(seq, 3, false).rev().collect();
+        backward.reverse();
+        assert_eq!(forward, backward);
+
+        // both ends meet in the middle without overlapping
+        let mut kmers = BitNuclKmer::new(seq, 3, false);
+        let mut positions = Vec::new();
+        while let Some((pos, _, _)) = kmers.next() {
+            positions.push(pos);
+            if let Some((pos, _, _)) = kmers.next_back() {
+                positions.push(pos);
+            }
+        }
+        positions.sort();
+        assert_eq!(positions, vec![0, 1, 5, 6, 7, 8, 9, 10, 11]);
+    }
+
+    #[test]
+    fn can_kmerize_spaced_seeds() {
+        let pattern = [true, false, true];
+        let mut kmer_iter = SpacedBitKmers::new(b"ACGTNAT", &pattern, false);
+        assert_eq!(kmer_iter.next(), Some((0, (0b0010, 2), false)));
+        assert_eq!(kmer_iter.next(), Some((1, (0b0111, 2), false)));
+        // the N in TNA is in a "don't care" position but GTN and NAT are skipped
+        assert_eq!(kmer_iter.next(), Some((3, (0b1100, 2), false)));
+        assert_eq!(kmer_iter.next(), None);
+
+        // gapped kmers are symmetric so canonical kmers are strand-independent
+        let pattern = crate::kmer::gapped_seed(2, 2);
+        let fwd: Vec<_> = SpacedBitKmers::new(b"ACGTTGCA", &pattern, true)
+            .map(|(_, k, _)| k)
+            .collect();
+        let mut rev: Vec<_> = SpacedBitKmers::new(b"TGCAACGT", &pattern, true)
+            .map(|(_, k, _)| k)
+            .collect();
+        rev.reverse();
+        assert_eq!(fwd, rev);
+    }
+
     #[test]
     fn test_reverse_complement() {
         assert_eq!(reverse_complement((0b00_0000, 3)).0, 0b11_1111);
@@ -253,6 +874,75 @@ mod tests {
         assert_eq!(reverse_complement((0b0001_1011, 4)).0, 0b0001_1011);
     }
 
+    #[test]
+    fn can_kmerize_long_kmers() {
+        let seq = b"ACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTAC";
+        let kmers: Vec<_> = BitNuclKmer::new_long(seq, 40, false).collect();
+        assert_eq!(kmers.len(), 3);
+        assert_eq!(long_bitmer_to_bytes(kmers[0].1), &seq[..40]);
+        assert_eq!(long_bitmer_to_bytes(kmers[2].1), &seq[2..]);
+
+        // ACGT... is its own reverse complement for even lengths
+        let (kmer, _) = kmers[0].1;
+        assert_eq!(reverse_complement_long((kmer, 40)).0, kmer);
+        let (_, was_rc) = canonical_long((kmers[1].1 .0, 40));
+        assert!(!was_rc);
+        assert_eq!(
+            long_bitmer_to_bytes(reverse_complement_long((kmers[1].1 .0, 40))),
+            crate::Sequence::reverse_complement(&seq[1..41])
+        );
+
+        // k = 32 still works with the u64 version
+        let kmers: Vec<_> = BitNuclKmer::new(seq, 32, true).collect();
+        assert_eq!(kmers.len(), seq.len() - 31);
+        assert_eq!(bitmer_to_bytes(kmers[0].1), &seq[..32]);
+    }
+
+    #[test]
+    fn test_const_kmer() {
+        let kmer = Kmer::<3>::from_slice(b"acg").unwrap();
+        assert_eq!(kmer.bits(), 0b00_01_10);
+        assert_eq!(format!("{kmer:?}"), "Kmer<3>(ACG)");
+        assert_eq!(kmer.reverse_complement().to_bytes(), b"CGT");
+        assert_eq!(kmer.canonical(), (kmer, false));
+        assert!(Kmer::<3>::from_slice(b"ANG").is_none());
+        assert_eq!(BitKmer::from(kmer), (0b00_01_10, 3));
+        assert_eq!(Kmer::<3>::from_bits(0b11_00_01_10), kmer);
+
+        let succ: Vec<Vec<u8>> = kmer.successors().iter().map(|k| k.to_bytes()).collect();
+        assert_eq!(succ, vec![b"CGA", b"CGC", b"CGG", b"CGT"]);
+        let pred: Vec<Vec<u8>> = kmer.predecessors().iter().map(|k| k.to_bytes()).collect();
+        assert_eq!(pred, vec![b"AAC", b"CAC", b"GAC", b"TAC"]);
+
+        let a = Kmer::<32>::from_slice(&[b'T'; 32]).unwrap();
+        assert_eq!(a.reverse_complement().to_bytes(), vec![b'A'; 32]);
+        assert!(Kmer::<2>::from_slice(b"AC") < Kmer::<2>::from_slice(b"CA"));
+    }
+
+    #[test]
+    fn test_hamming_neighbors() {
+        let kmer = BitKmer::from_bytes(b"ACGTA").unwrap();
+        assert!(hamming_neighbors(kmer, 0).is_empty());
+
+        let d1 = hamming_neighbors(kmer, 1);
+        assert_eq!(d1.len(), 15);
+
+        let mut d2 = hamming_neighbors(kmer, 2);
+        assert_eq!(d2.len(), 15 + 9 * 10);
+        d2.sort();
+        d2.dedup();
+        assert_eq!(d2.len(), 15 + 9 * 10);
+        for neighbor in d2 {
+            let distance = kmer
+                .to_vec()
+                .iter()
+                .zip(neighbor.to_vec().iter())
+                .filter(|(a, b)| a != b)
+                .count();
+            assert!(distance == 1 || distance == 2);
+        }
+    }
+
     #[test]
     fn test_minimizer() {
         assert_eq!(minimizer((0b00_1011, 3), 2).0, 0b0010);
@@ -280,6 +970,30 @@ mod tests {
         assert_eq!(bitmer_to_bytes((0 as BitKmerSeq, 3)), b"AAA");
     }
 
+    #[test]
+    fn test_bitkmer_ext() {
+        assert_eq!(BitKmer::from_bytes(b"TTA"), Ok((60, 3)));
+        assert_eq!(BitKmer::from_bytes(b""), Ok((0, 0)));
+        assert_eq!(
+            BitKmer::from_bytes(b"ACGU"),
+            Err(SequenceError::InvalidBase {
+                position: 3,
+                base: b'U'
+            })
+        );
+        assert_eq!(
+            BitKmer::from_bytes(&[b'A'; 33]),
+            Err(SequenceError::TooLong {
+                length: 33,
+                max: 32
+            })
+        );
+        let kmer = BitKmer::from_bytes(&[b'T'; 32]).unwrap();
+        assert_eq!(kmer.0, !0);
+        assert_eq!(kmer.to_string(), "T".repeat(32));
+        assert_eq!((60, 3).to_vec(), b"TTA");
+    }
+
     pub fn bytes_to_bitmer(kmer: &[u8]) -> BitKmer {
         let k = kmer.len() as u8;
 
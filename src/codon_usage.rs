@@ -0,0 +1,206 @@
+//! Codon usage tables and relative synonymous codon usage (RSCU)
+use std::ops::{AddAssign, Range};
+
+use crate::errors::{ParseError, SequenceError};
+use crate::parser::FastxReader;
+use crate::sequence::Strand;
+use crate::translate::{CodonTable, Frame};
+use crate::Sequence;
+
+/// Index of an unambiguous codon in the NCBI order (TTT, TTC, ..., GGG)
+#[inline]
+fn codon_index(codon: &[u8]) -> Option<usize> {
+    let mut index = 0;
+    for base in codon {
+        index = index * 4
+            + match base.to_ascii_uppercase() {
+                b'T' | b'U' => 0,
+                b'C' => 1,
+                b'A' => 2,
+                b'G' => 3,
+                _ => return None,
+            };
+    }
+    Some(index)
+}
+
+/// The codon at `index` in the NCBI order
+pub fn codon_at(index: usize) -> [u8; 3] {
+    const BASES: &[u8; 4] = b"TCAG";
+    [BASES[index / 16], BASES[(index / 4) % 4], BASES[index % 4]]
+}
+
+/// Counts of the 64 codons, in the NCBI order (see `codon_at`), across any
+/// number of sequences. Codons with anything but ACGT/U are skipped.
+///
+/// ```
+/// use needletail::codon_usage::CodonUsage;
+/// use needletail::translate::{CodonTable, Frame};
+///
+/// let mut usage = CodonUsage::new(CodonTable::STANDARD);
+/// usage.add_sequence(b"ATGCTGCTGTTATAA", Frame::forward(0));
+/// assert_eq!(usage.count(b"CTG"), 2);
+/// // 2 of the 3 leucine codons out of 6 synonymous codons
+/// assert_eq!(usage.rscu(b"CTG"), 4.);
+/// assert_eq!(usage.rscu(b"CTC"), 0.);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodonUsage {
+    table: CodonTable,
+    counts: [u64; 64],
+}
+
+impl CodonUsage {
+    /// The table is used to group synonymous codons for the RSCU
+    pub fn new(table: CodonTable) -> Self {
+        CodonUsage {
+            table,
+            counts: [0; 64],
+        }
+    }
+
+    pub fn table(&self) -> CodonTable {
+        self.table
+    }
+
+    /// Counts the codons of `seq` in the given frame
+    pub fn add_sequence(&mut self, seq: &[u8], frame: Frame) {
+        let rc;
+        let strand_seq = match frame.strand {
+            Strand::Forward => seq,
+            Strand::Reverse => {
+                rc = seq.reverse_complement();
+                &rc[..]
+            }
+        };
+        let Some(framed) = strand_seq.get(frame.offset as usize..) else {
+            return;
+        };
+        for codon in framed.chunks_exact(3) {
+            if let Some(index) = codon_index(codon) {
+                self.counts[index] += 1;
+            }
+        }
+    }
+
+    /// Counts the codons of a CDS given by its coordinates on the forward
+    /// strand of `seq` (e.g. from a GFF file, 0-based and end-exclusive)
+    pub fn add_cds(
+        &mut self,
+        seq: &[u8],
+        range: Range<usize>,
+        strand: Strand,
+    ) -> Result<(), SequenceError> {
+        let cds = seq.subseq(range, strand)?;
+        self.add_sequence(&cds, Frame::forward(0));
+        Ok(())
+    }
+
+    /// Counts the codons of every record of a reader in the given frame, e.g.
+    /// of a FASTA file of CDSs
+    pub fn add_reader(
+        &mut self,
+        reader: &mut dyn FastxReader,
+        frame: Frame,
+    ) -> Result<(), ParseError> {
+        while let Some(record) = reader.next() {
+            self.add_sequence(&record?.seq(), frame);
+        }
+        Ok(())
+    }
+
+    /// The counts of the 64 codons in the NCBI order
+    pub fn counts(&self) -> &[u64; 64] {
+        &self.counts
+    }
+
+    pub fn count(&self, codon: &[u8; 3]) -> u64 {
+        codon_index(codon).map_or(0, |i| self.counts[i])
+    }
+
+    /// Total number of codons counted
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Fraction of all the codons that are `codon`
+    pub fn frequency(&self, codon: &[u8; 3]) -> f64 {
+        match self.total() {
+            0 => 0.,
+            total => self.count(codon) as f64 / total as f64,
+        }
+    }
+
+    /// Relative synonymous codon usage of `codon`: its count divided by the
+    /// mean count of the codons coding for the same amino acid (or stop).
+    /// 1 means no bias, 0 if none of these codons were seen.
+    pub fn rscu(&self, codon: &[u8; 3]) -> f64 {
+        codon_index(codon).map_or(0., |i| self.rscu_at(i))
+    }
+
+    fn rscu_at(&self, index: usize) -> f64 {
+        let aa = self.table.amino_acid_at(index);
+        let (n_synonymous, total) = (0..64)
+            .filter(|i| self.table.amino_acid_at(*i) == aa)
+            .fold((0, 0), |(n, total), i| (n + 1, total + self.counts[i]));
+        if total == 0 {
+            return 0.;
+        }
+        self.counts[index] as f64 * n_synonymous as f64 / total as f64
+    }
+
+    /// The RSCU of the 64 codons in the NCBI order
+    pub fn rscu_table(&self) -> [f64; 64] {
+        std::array::from_fn(|i| self.rscu_at(i))
+    }
+}
+
+impl AddAssign<&CodonUsage> for CodonUsage {
+    fn add_assign(&mut self, other: &CodonUsage) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn codon_order_matches_the_tables() {
+        for i in 0..64 {
+            let codon = codon_at(i);
+            assert_eq!(codon_index(&codon), Some(i));
+            assert_eq!(
+                CodonTable::STANDARD.translate_codon(&codon),
+                CodonTable::STANDARD.amino_acid_at(i)
+            );
+        }
+        assert_eq!(codon_index(b"ANG"), None);
+    }
+
+    #[test]
+    fn can_count_frames_and_cds() {
+        let mut usage = CodonUsage::new(CodonTable::STANDARD);
+        // ATG AAA TGA on the reverse strand
+        let seq = b"CCTCATTTCATGG";
+        usage.add_cds(seq, 2..11, Strand::Reverse).unwrap();
+        assert_eq!(usage.count(b"ATG"), 1);
+        assert_eq!(usage.count(b"AAA"), 1);
+        assert_eq!(usage.count(b"TGA"), 1);
+        assert_eq!(usage.rscu(b"AAA"), 2.);
+        assert!(usage.add_cds(seq, 10..20, Strand::Forward).is_err());
+
+        let mut reader = parse_fastx_reader(&b">a\nATGAAA\n>b\nAAGNNN\n"[..]).unwrap();
+        let mut other = CodonUsage::new(CodonTable::STANDARD);
+        other
+            .add_reader(reader.as_mut(), Frame::forward(0))
+            .unwrap();
+        usage += &other;
+        assert_eq!(usage.total(), 6);
+        assert_eq!(usage.rscu(b"AAA"), 4. / 3.);
+        assert_eq!(usage.rscu_table().iter().filter(|r| **r > 0.).count(), 4);
+    }
+}
@@ -0,0 +1,171 @@
+//! Low-complexity (simple repeat) detection: kmer entropy and DUST.
+use std::ops::Range;
+
+use crate::bitkmer::BitNuclKmer;
+
+/// Shannon entropy of the distribution of the valid kmers of size `k` (k <= 12)
+/// of the sequence, normalized to [0, 1] by the highest entropy possible given
+/// the number of kmers. Homopolymers get 0 and sequences where every kmer is
+/// different get 1; sequences without any valid kmer get 0.
+pub fn kmer_entropy(seq: &[u8], k: u8) -> f64 {
+    assert!(k > 0 && k <= 12, "k needs to be between 1 and 12");
+    let mut counts = vec![0u32; 1 << (2 * k)];
+    let mut total = 0u32;
+    for (_, (kmer, _), _) in BitNuclKmer::new(seq, k, false) {
+        counts[kmer as usize] += 1;
+        total += 1;
+    }
+    if total <= 1 {
+        return 0.;
+    }
+    let total = f64::from(total);
+    let entropy: f64 = counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = f64::from(*c) / total;
+            -p * p.log2()
+        })
+        .sum();
+    let max_entropy = (2. * f64::from(k)).min(total.log2());
+    entropy / max_entropy
+}
+
+/// The parameters of the DUST algorithm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DustParams {
+    /// Window size in bases
+    pub window: usize,
+    /// Score threshold, in tenths of a repeated triplet per triplet as in
+    /// `dustmasker` and sdust
+    pub threshold: f64,
+}
+
+impl Default for DustParams {
+    /// The values of `dustmasker` and minimap2's sdust: a window of 64 bases
+    /// and a threshold of 20
+    fn default() -> Self {
+        DustParams {
+            window: 64,
+            threshold: 20.,
+        }
+    }
+}
+
+/// Finds the low-complexity intervals of a sequence with the symmetric DUST
+/// algorithm of sdust.
+///
+/// The score of an interval is `sum(c_t * (c_t - 1) / 2) / (l - 1)`, with
+/// `c_t` the number of times the triplet (3-mer) `t` occurs in it and `l` its
+/// number of triplets. An interval of at most `window` bases is "perfect" if
+/// its score is above `threshold / 10` and at least the score of all the
+/// perfect intervals it contains; the low-complexity intervals are the union
+/// of the perfect intervals. Triplets containing non-ACGT bases are not counted.
+///
+/// ```
+/// use needletail::complexity::{dust, DustParams};
+///
+/// let seq = [&b"ACGGTCAGTTGACCATGATCGGATC"[..], &b"CA".repeat(30), b"GATTACATTGGACCTAGCAATC"].concat();
+/// let intervals = dust(&seq, DustParams::default());
+/// assert_eq!(intervals, vec![25..85]);
+/// ```
+pub fn dust(seq: &[u8], params: DustParams) -> Vec<Range<usize>> {
+    assert!(
+        params.window > 3,
+        "the window needs to hold several triplets"
+    );
+    let mut triplets = vec![None; seq.len().saturating_sub(2)];
+    for (pos, (kmer, _), _) in BitNuclKmer::new(seq, 3, false) {
+        triplets[pos] = Some(kmer as u8);
+    }
+    let n_window = params.window - 2;
+    let threshold = params.threshold / 10.;
+    // the perfect intervals of the current window as (first triplet, last
+    // triplet, score), sorted by decreasing start
+    let mut perfect: Vec<(usize, usize, f64)> = Vec::new();
+    let mut found: Vec<Range<usize>> = Vec::new();
+    let mut counts = [0u32; 64];
+
+    for end in 0..triplets.len() {
+        let window_start = end.saturating_sub(n_window - 1);
+        perfect.retain(|p| p.0 >= window_start);
+        let mut new_perfect = Vec::new();
+        let mut repeats = 0u64;
+        let mut n_valid = 0u32;
+        // best score of the perfect intervals contained in start..=end
+        let mut best_contained = 0f64;
+        let mut next_perfect = 0;
+        for start in (window_start..=end).rev() {
+            while next_perfect < perfect.len() && perfect[next_perfect].0 >= start {
+                best_contained = best_contained.max(perfect[next_perfect].2);
+                next_perfect += 1;
+            }
+            let Some(t) = triplets[start] else {
+                continue;
+            };
+            repeats += u64::from(counts[t as usize]);
+            counts[t as usize] += 1;
+            n_valid += 1;
+            if n_valid < 2 {
+                continue;
+            }
+            let score = repeats as f64 / f64::from(n_valid - 1);
+            if score > threshold && score >= best_contained {
+                best_contained = score;
+                new_perfect.push((start, end, score));
+            }
+        }
+        counts = [0; 64];
+        found.extend(new_perfect.iter().map(|p| p.0..p.1 + 3));
+        perfect.extend(new_perfect);
+        perfect.sort_by_key(|p| std::cmp::Reverse(p.0));
+    }
+
+    found.sort_by_key(|r| r.start);
+    let mut intervals: Vec<Range<usize>> = Vec::new();
+    for range in found {
+        match intervals.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => intervals.push(range),
+        }
+    }
+    intervals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RANDOM: &[u8] =
+        b"ACGGTCAGTTGACCATGATCGGATCGATTACATTGGACCTAGCAATCGGCTAAGCTTGCAGTACCGTAGAGTCCA";
+
+    #[test]
+    fn entropy_of_simple_sequences() {
+        assert_eq!(kmer_entropy(b"AAAAAAAAAAAA", 3), 0.);
+        assert_eq!(kmer_entropy(b"AC", 3), 0.);
+        assert!(kmer_entropy(&b"CA".repeat(20), 3) < 0.3);
+        assert!(kmer_entropy(RANDOM, 3) > 0.8);
+        // every kmer is different
+        assert!((kmer_entropy(b"ACGTTGCA", 4) - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dust_finds_repeats() {
+        assert!(dust(RANDOM, DustParams::default()).is_empty());
+        assert!(dust(b"", DustParams::default()).is_empty());
+        assert_eq!(dust(&b"A".repeat(30), DustParams::default()), vec![0..30]);
+
+        let seq = [&RANDOM[..30], &b"T".repeat(40), &RANDOM[30..]].concat();
+        assert_eq!(dust(&seq, DustParams::default()), vec![30..70]);
+        // Ns break the triplets but not the window
+        let seq = [
+            &RANDOM[..30],
+            &b"T".repeat(20),
+            b"N",
+            &b"T".repeat(20),
+            &RANDOM[30..],
+        ]
+        .concat();
+        assert_eq!(dust(&seq, DustParams::default()), vec![30..71]);
+    }
+}
@@ -0,0 +1,417 @@
+//! Canonical kmer counting and kmer spectrum (abundance histogram) computation.
+//!
+//! The spectrum is the input of genome size/heterozygosity estimation tools
+//! like GenomeScope: index `i` of the histogram is the number of distinct
+//! kmers seen exactly `i` times.
+//!
+//! `KmerCounts` keeps everything in memory while `DiskKmerCounter` partitions
+//! the kmers to temporary files first (as done by DSK) so the memory used stays
+//! under a given budget, at the cost of some disk I/O.
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::bitkmer::{canonical, mix_hash, BitKmerSeq};
+use crate::errors::ParseError;
+use crate::parser::{parse_fastx_file, FastxReader};
+use crate::Sequence;
+
+/// How the kmers are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountingMode {
+    /// Every distinct kmer is counted
+    Exact,
+    /// Only the kmers whose hash falls in the lowest `1 / scale` of the hash
+    /// space are counted (as in FracMinHash). Since the subsampling is done on
+    /// distinct kmers, their abundances are unaffected and the histogram is
+    /// scaled back up by `scale`, using roughly `scale` times less memory.
+    Sketched(u64),
+}
+
+/// Counts of canonical kmers
+#[derive(Debug, Clone)]
+pub struct KmerCounts {
+    k: u8,
+    mode: CountingMode,
+    counts: HashMap<BitKmerSeq, u64>,
+}
+
+impl KmerCounts {
+    pub fn new(k: u8, mode: CountingMode) -> Self {
+        assert!(k > 0 && k <= 32);
+        if let CountingMode::Sketched(scale) = mode {
+            assert!(scale > 0);
+        }
+        KmerCounts {
+            k,
+            mode,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Counts all the valid canonical kmers of a sequence
+    pub fn add_sequence(&mut self, seq: &[u8]) {
+        let max_hash = match self.mode {
+            CountingMode::Exact => u64::MAX,
+            CountingMode::Sketched(scale) => u64::MAX / scale,
+        };
+        for (_, (kmer, _), _) in seq.bit_kmers(self.k, true) {
+            if max_hash == u64::MAX || mix_hash(kmer) <= max_hash {
+                *self.counts.entry(kmer).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Counts the kmers of every record of a reader
+    pub fn add_reader(&mut self, reader: &mut dyn FastxReader) -> Result<(), ParseError> {
+        while let Some(record) = reader.next() {
+            let record = record?;
+            self.add_sequence(&record.normalize(false));
+        }
+        Ok(())
+    }
+
+    /// How many times the given kmer (in 2-bit form) was seen; its reverse
+    /// complement is counted as well. Always 0 for a kmer that was not sampled
+    /// when sketching.
+    pub fn get(&self, kmer: BitKmerSeq) -> u64 {
+        let (canonical, _) = canonical((kmer, self.k));
+        self.counts.get(&canonical.0).copied().unwrap_or(0)
+    }
+
+    pub fn k(&self) -> u8 {
+        self.k
+    }
+
+    /// Number of distinct kmers counted
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BitKmerSeq, u64)> + '_ {
+        self.counts.iter().map(|(k, v)| (*k, *v))
+    }
+
+    /// The abundance histogram: index `i` is the (estimated, if sketching)
+    /// number of distinct kmers seen exactly `i` times. Index 0 is always 0.
+    pub fn histogram(&self) -> Vec<u64> {
+        let max = self.counts.values().copied().max().unwrap_or(0);
+        let mut histogram = vec![0; max as usize + 1];
+        for count in self.counts.values() {
+            histogram[*count as usize] += 1;
+        }
+        if let CountingMode::Sketched(scale) = self.mode {
+            for n in histogram.iter_mut() {
+                *n *= scale;
+            }
+        }
+        histogram
+    }
+}
+
+/// Counts the canonical kmers of a FASTA/FASTQ file and returns the kmer
+/// spectrum (see `KmerCounts::histogram`).
+///
+/// ```
+/// use needletail::count::{kmer_spectrum, CountingMode};
+///
+/// let histogram = kmer_spectrum("tests/data/test.fa", 3, CountingMode::Exact).unwrap();
+/// assert_eq!(histogram[0], 0);
+/// ```
+pub fn kmer_spectrum<P: AsRef<Path>>(
+    path: P,
+    k: u8,
+    mode: CountingMode,
+) -> Result<Vec<u64>, ParseError> {
+    let mut reader = parse_fastx_file(path)?;
+    let mut counts = KmerCounts::new(k, mode);
+    counts.add_reader(&mut *reader)?;
+    Ok(counts.histogram())
+}
+
+/// Rough size of a kmer and its count in a `HashMap`, including its overhead
+const HASHMAP_ENTRY_SIZE: usize = 32;
+/// How many times a partition too big for the budget can be split again
+const MAX_SPLIT_DEPTH: u32 = 3;
+/// Used to get unique names for the temporary directories
+static TMP_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The temporary directory of a counting run, removed when dropped
+struct TmpDir(PathBuf);
+
+impl TmpDir {
+    fn new(parent: &Path) -> std::io::Result<Self> {
+        let name = format!(
+            "needletail-kmers-{}-{}",
+            std::process::id(),
+            TMP_DIR_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = parent.join(name);
+        fs::create_dir_all(&path)?;
+        Ok(TmpDir(path))
+    }
+}
+
+impl Drop for TmpDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Which partition a kmer goes to; each split level looks at different bits
+/// of the hash.
+#[inline]
+fn partition_of(kmer: BitKmerSeq, depth: u32, n_partitions: usize) -> usize {
+    ((mix_hash(kmer).rotate_left(16 * depth) & 0xffff) as usize) % n_partitions
+}
+
+/// Spreads kmers over partition files
+struct PartitionWriter {
+    paths: Vec<PathBuf>,
+    writers: Vec<BufWriter<File>>,
+    depth: u32,
+}
+
+impl PartitionWriter {
+    fn new(dir: &Path, prefix: &str, depth: u32, n_partitions: usize) -> std::io::Result<Self> {
+        let paths: Vec<PathBuf> = (0..n_partitions)
+            .map(|i| dir.join(format!("{prefix}{i}")))
+            .collect();
+        let writers = paths
+            .iter()
+            .map(|p| File::create(p).map(BufWriter::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PartitionWriter {
+            paths,
+            writers,
+            depth,
+        })
+    }
+
+    #[inline]
+    fn push(&mut self, kmer: BitKmerSeq) -> std::io::Result<()> {
+        let partition = partition_of(kmer, self.depth, self.writers.len());
+        self.writers[partition].write_all(&kmer.to_le_bytes())
+    }
+
+    fn finish(self) -> std::io::Result<Vec<PathBuf>> {
+        for mut writer in self.writers {
+            writer.flush()?;
+        }
+        Ok(self.paths)
+    }
+}
+
+fn read_partition(
+    path: &Path,
+) -> std::io::Result<impl Iterator<Item = std::io::Result<BitKmerSeq>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(std::iter::from_fn(move || {
+        let mut buf = [0; 8];
+        match reader.read_exact(&mut buf) {
+            Ok(()) => Some(Ok(u64::from_le_bytes(buf))),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => None,
+            Err(e) => Some(Err(e)),
+        }
+    }))
+}
+
+/// Exact canonical kmer counting with bounded memory, for datasets whose
+/// distinct kmers don't fit in RAM.
+///
+/// The kmers are first written to temporary partition files by hash and each
+/// partition is then counted in memory on its own. Partitions that would
+/// still need more memory than the budget are split further.
+///
+/// ```
+/// use needletail::count::DiskKmerCounter;
+///
+/// let counter = DiskKmerCounter::new(5, 1 << 20);
+/// let histogram = counter.histogram_file("tests/data/28S.fasta").unwrap();
+/// assert!(histogram.iter().sum::<u64>() > 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DiskKmerCounter {
+    k: u8,
+    memory_budget: usize,
+    partitions: usize,
+    tmp_dir: PathBuf,
+}
+
+impl DiskKmerCounter {
+    /// `memory_budget` is in bytes. The temporary files go to the system
+    /// temporary directory by default.
+    pub fn new(k: u8, memory_budget: usize) -> Self {
+        assert!(k > 0 && k <= 32);
+        DiskKmerCounter {
+            k,
+            memory_budget: memory_budget.max(HASHMAP_ENTRY_SIZE),
+            partitions: 16,
+            tmp_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// Where to create the temporary partition files
+    pub fn tmp_dir<P: AsRef<Path>>(mut self, tmp_dir: P) -> Self {
+        self.tmp_dir = tmp_dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// How many partitions to write the kmers to initially (16 by default)
+    pub fn partitions(mut self, partitions: usize) -> Self {
+        assert!(partitions > 0);
+        self.partitions = partitions;
+        self
+    }
+
+    /// Counts the kmers of every record of the reader and calls `f` once for
+    /// every distinct canonical kmer with its count, in no particular order.
+    pub fn count_reader<F: FnMut(BitKmerSeq, u64)>(
+        &self,
+        reader: &mut dyn FastxReader,
+        mut f: F,
+    ) -> Result<(), ParseError> {
+        let dir = TmpDir::new(&self.tmp_dir)?;
+        let mut writer = PartitionWriter::new(&dir.0, "p", 0, self.partitions)?;
+        while let Some(record) = reader.next() {
+            let record = record?;
+            for (_, (kmer, _), _) in record.normalize(false).bit_kmers(self.k, true) {
+                writer.push(kmer)?;
+            }
+        }
+        let partitions = writer.finish()?;
+        for path in partitions {
+            self.count_partition(&path, 1, &mut f)?;
+        }
+        Ok(())
+    }
+
+    fn count_partition<F: FnMut(BitKmerSeq, u64)>(
+        &self,
+        path: &Path,
+        depth: u32,
+        f: &mut F,
+    ) -> Result<(), ParseError> {
+        // every kmer occurrence takes 8 bytes so that's an upper bound on the
+        // number of distinct kmers
+        let n_kmers = fs::metadata(path)?.len() as usize / 8;
+        let needed = n_kmers * HASHMAP_ENTRY_SIZE;
+        if needed > self.memory_budget && depth <= MAX_SPLIT_DEPTH {
+            let n_partitions = needed.div_ceil(self.memory_budget).min(1024);
+            let prefix = format!("{}_", path.file_name().unwrap().to_string_lossy());
+            let dir = path.parent().unwrap();
+            let mut writer = PartitionWriter::new(dir, &prefix, depth, n_partitions)?;
+            for kmer in read_partition(path)? {
+                writer.push(kmer?)?;
+            }
+            let sub_partitions = writer.finish()?;
+            fs::remove_file(path)?;
+            for sub in sub_partitions {
+                self.count_partition(&sub, depth + 1, f)?;
+            }
+            return Ok(());
+        }
+
+        let mut counts: HashMap<BitKmerSeq, u64> = HashMap::new();
+        for kmer in read_partition(path)? {
+            *counts.entry(kmer?).or_insert(0) += 1;
+        }
+        fs::remove_file(path)?;
+        for (kmer, count) in counts {
+            f(kmer, count);
+        }
+        Ok(())
+    }
+
+    /// Same as `count_reader` for a file
+    pub fn count_file<P: AsRef<Path>, F: FnMut(BitKmerSeq, u64)>(
+        &self,
+        path: P,
+        f: F,
+    ) -> Result<(), ParseError> {
+        let mut reader = parse_fastx_file(path)?;
+        self.count_reader(&mut *reader, f)
+    }
+
+    /// Counts the kmers of a file and returns the kmer spectrum
+    /// (see `KmerCounts::histogram`)
+    pub fn histogram_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u64>, ParseError> {
+        let mut histogram = vec![0];
+        self.count_file(path, |_, count| {
+            let count = count as usize;
+            if count >= histogram.len() {
+                histogram.resize(count + 1, 0);
+            }
+            histogram[count] += 1;
+        })?;
+        Ok(histogram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn can_count_canonical_kmers() {
+        let mut counts = KmerCounts::new(3, CountingMode::Exact);
+        counts.add_sequence(b"AAATTTNAAA");
+        // AAA x2 + TTT (=AAA) and AAT + ATT (=AAT)
+        assert_eq!(counts.get(0b000000), 3);
+        assert_eq!(counts.get(0b111111), 3);
+        assert_eq!(counts.get(0b000011), 2);
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts.histogram(), vec![0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn can_count_a_reader() {
+        let mut reader = parse_fastx_reader(&b">a\nACGT\n>b\nacgt\n"[..]).unwrap();
+        let mut counts = KmerCounts::new(4, CountingMode::Exact);
+        counts.add_reader(&mut *reader).unwrap();
+        assert_eq!(counts.histogram(), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn sketched_counts_are_a_subset() {
+        let seq = b"ACGTAGCTAGCTAGGATCGATCGACTAGCATCAGCTAGCGACTAGCATTTTTTTTTT";
+        let mut exact = KmerCounts::new(5, CountingMode::Exact);
+        exact.add_sequence(seq);
+        let mut sketched = KmerCounts::new(5, CountingMode::Sketched(4));
+        sketched.add_sequence(seq);
+        assert!(sketched.len() < exact.len());
+        for (kmer, count) in sketched.iter() {
+            assert_eq!(exact.get(kmer), count);
+        }
+        assert!(sketched.histogram().iter().all(|n| n % 4 == 0));
+    }
+
+    #[test]
+    fn disk_counting_matches_in_memory_counting() {
+        let mut exact = KmerCounts::new(7, CountingMode::Exact);
+        let mut reader = parse_fastx_file("tests/data/28S.fasta").unwrap();
+        exact.add_reader(&mut *reader).unwrap();
+
+        // a tiny budget to force splitting the partitions
+        let counter = DiskKmerCounter::new(7, 4096).partitions(4);
+        let mut n_kmers = 0;
+        counter
+            .count_file("tests/data/28S.fasta", |kmer, count| {
+                assert_eq!(exact.get(kmer), count);
+                n_kmers += 1;
+            })
+            .unwrap();
+        assert_eq!(n_kmers, exact.len());
+        assert_eq!(
+            counter.histogram_file("tests/data/28S.fasta").unwrap(),
+            exact.histogram()
+        );
+    }
+}
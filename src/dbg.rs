@@ -0,0 +1,197 @@
+//! Minimal de Bruijn graph utilities on top of a set of canonical kmers, e.g.
+//! the solid kmers of a `count::KmerCounts`.
+//!
+//! The graph is bidirected: a node is a canonical kmer, and queries are made
+//! on oriented kmers (either the canonical kmer or its reverse complement).
+use std::collections::HashSet;
+
+use crate::bitkmer::{bitmer_to_bytes, canonical, reverse_complement, BitKmerSeq, KmerBits};
+use crate::count::KmerCounts;
+use crate::Sequence;
+
+const BASES: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+/// A de Bruijn graph of order `k` (k <= 32) stored as a set of canonical kmers
+#[derive(Debug, Clone)]
+pub struct DeBruijnGraph {
+    k: u8,
+    kmers: HashSet<BitKmerSeq>,
+}
+
+impl DeBruijnGraph {
+    pub fn new(k: u8) -> Self {
+        assert!(k > 0 && k <= 32);
+        DeBruijnGraph {
+            k,
+            kmers: HashSet::new(),
+        }
+    }
+
+    /// Builds the graph from the kmers seen at least `min_count` times
+    pub fn from_counts(counts: &KmerCounts, min_count: u64) -> Self {
+        let mut graph = Self::new(counts.k());
+        graph.kmers.extend(
+            counts
+                .iter()
+                .filter(|(_, count)| *count >= min_count)
+                .map(|(kmer, _)| kmer),
+        );
+        graph
+    }
+
+    /// Adds every valid kmer of the sequence to the graph
+    pub fn add_sequence(&mut self, seq: &[u8]) {
+        self.kmers
+            .extend(seq.bit_kmers(self.k, true).map(|(_, (kmer, _), _)| kmer));
+    }
+
+    /// Adds a kmer in either orientation
+    pub fn insert(&mut self, kmer: BitKmerSeq) {
+        self.kmers.insert(self.canonical(kmer));
+    }
+
+    pub fn k(&self) -> u8 {
+        self.k
+    }
+
+    /// Number of (canonical) kmers
+    pub fn len(&self) -> usize {
+        self.kmers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kmers.is_empty()
+    }
+
+    #[inline]
+    fn canonical(&self, kmer: BitKmerSeq) -> BitKmerSeq {
+        canonical((kmer, self.k)).0 .0
+    }
+
+    /// Whether the kmer or its reverse complement is in the graph
+    pub fn contains(&self, kmer: BitKmerSeq) -> bool {
+        self.kmers.contains(&self.canonical(kmer))
+    }
+
+    /// The kmers in the graph following `kmer` (i.e. overlapping its last
+    /// k - 1 bases), in the orientation that follows it
+    pub fn successors(&self, kmer: BitKmerSeq) -> Vec<BitKmerSeq> {
+        (0..4)
+            .map(|base| kmer.push_base(base, self.k))
+            .filter(|next| self.contains(*next))
+            .collect()
+    }
+
+    /// The kmers in the graph preceding `kmer` (i.e. overlapping its first
+    /// k - 1 bases), in the orientation that precedes it
+    pub fn predecessors(&self, kmer: BitKmerSeq) -> Vec<BitKmerSeq> {
+        (0..4)
+            .map(|base| kmer.push_base_front(base, self.k))
+            .filter(|previous| self.contains(*previous))
+            .collect()
+    }
+
+    /// Walks forward from `start` as long as the path doesn't branch,
+    /// returning the bases added
+    fn extend(&self, start: BitKmerSeq, visited: &mut HashSet<BitKmerSeq>) -> Vec<u8> {
+        let mut bases = Vec::new();
+        let mut current = start;
+        loop {
+            let successors = self.successors(current);
+            if successors.len() != 1 {
+                break;
+            }
+            let next = successors[0];
+            if self.predecessors(next).len() != 1 || !visited.insert(self.canonical(next)) {
+                break;
+            }
+            bases.push(BASES[(next & 3) as usize]);
+            current = next;
+        }
+        bases
+    }
+
+    /// Returns the unitigs of the graph: the maximal non-branching paths,
+    /// spelled as sequences. Every kmer is in exactly one unitig.
+    ///
+    /// ```
+    /// use needletail::dbg::DeBruijnGraph;
+    ///
+    /// let mut graph = DeBruijnGraph::new(5);
+    /// graph.add_sequence(b"CCGTAATGCCTTTCCCTAAC");
+    /// let unitigs = graph.unitigs();
+    /// assert_eq!(unitigs.len(), 1);
+    /// assert_eq!(unitigs[0].len(), 20);
+    /// ```
+    pub fn unitigs(&self) -> Vec<Vec<u8>> {
+        let mut visited = HashSet::with_capacity(self.kmers.len());
+        let mut unitigs = Vec::new();
+        for kmer in &self.kmers {
+            if !visited.insert(*kmer) {
+                continue;
+            }
+            let right = self.extend(*kmer, &mut visited);
+            let left = self.extend(reverse_complement((*kmer, self.k)).0, &mut visited);
+            let mut unitig = left.reverse_complement();
+            unitig.extend(bitmer_to_bytes((*kmer, self.k)));
+            unitig.extend(right);
+            unitigs.push(unitig);
+        }
+        unitigs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitkmer::BitKmerExt;
+    use crate::count::CountingMode;
+
+    fn kmer(s: &[u8]) -> BitKmerSeq {
+        crate::bitkmer::BitKmer::from_bytes(s).unwrap().0
+    }
+
+    #[test]
+    fn can_query_neighbours() {
+        let mut graph = DeBruijnGraph::new(3);
+        graph.add_sequence(b"ACGTT");
+        // CGT is the reverse complement of ACG and GTT of AAC
+        assert_eq!(graph.len(), 2);
+        assert!(graph.contains(kmer(b"AAC")));
+        assert!(!graph.contains(kmer(b"TTT")));
+        assert_eq!(graph.successors(kmer(b"ACG")), vec![kmer(b"CGT")]);
+        assert_eq!(graph.successors(kmer(b"CGT")), vec![kmer(b"GTT")]);
+        assert_eq!(graph.successors(kmer(b"AAC")), vec![kmer(b"ACG")]);
+        assert_eq!(graph.predecessors(kmer(b"GTT")), vec![kmer(b"CGT")]);
+        assert!(graph.predecessors(kmer(b"AAC")).is_empty());
+    }
+
+    #[test]
+    fn unitigs_cover_all_kmers() {
+        // two sequences sharing a kmer in the middle make a branching graph
+        let mut counts = KmerCounts::new(5, CountingMode::Exact);
+        counts.add_sequence(b"ACGTAGCTAGGATCGATTACA");
+        counts.add_sequence(b"TTTTTTGCTAGGACCCATGA");
+        let graph = DeBruijnGraph::from_counts(&counts, 1);
+        let unitigs = graph.unitigs();
+        assert!(unitigs.len() > 1);
+
+        let mut from_unitigs = DeBruijnGraph::new(5);
+        let mut n_kmers = 0;
+        for unitig in &unitigs {
+            from_unitigs.add_sequence(unitig);
+            n_kmers += unitig.len() - 5 + 1;
+        }
+        assert_eq!(n_kmers, graph.len());
+        assert_eq!(from_unitigs.kmers, graph.kmers);
+    }
+
+    #[test]
+    fn can_filter_by_count() {
+        let mut counts = KmerCounts::new(4, CountingMode::Exact);
+        counts.add_sequence(b"ACGTAC");
+        counts.add_sequence(b"ACGT");
+        assert_eq!(DeBruijnGraph::from_counts(&counts, 2).len(), 1);
+        assert_eq!(DeBruijnGraph::from_counts(&counts, 1).len(), counts.len());
+    }
+}
@@ -0,0 +1,241 @@
+//! Distances between sequences: Hamming and edit (Levenshtein) distances,
+//! e.g. for barcode correction or adapter matching
+//!
+//! All the comparisons are byte-wise and case-sensitive: normalize the
+//! sequences first if needed.
+
+const LOW_BITS: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+/// Number of positions where `a` and `b` differ, compared 8 bytes at a time.
+/// Panics if they're not of the same length.
+///
+/// ```
+/// use needletail::distance::hamming;
+///
+/// assert_eq!(hamming(b"ACGTACGT", b"ACCTACGA"), 2);
+/// ```
+pub fn hamming(a: &[u8], b: &[u8]) -> usize {
+    assert_eq!(a.len(), b.len(), "sequences need to be of the same length");
+    let mut chunks_a = a.chunks_exact(8);
+    let mut chunks_b = b.chunks_exact(8);
+    let mut distance = 0;
+    for (ca, cb) in (&mut chunks_a).zip(&mut chunks_b) {
+        let x =
+            u64::from_ne_bytes(ca.try_into().unwrap()) ^ u64::from_ne_bytes(cb.try_into().unwrap());
+        // sets the high bit of every non-zero byte
+        let nonzero = (((x & LOW_BITS) + LOW_BITS) | x) & HIGH_BITS;
+        distance += nonzero.count_ones() as usize;
+    }
+    distance
+        + chunks_a
+            .remainder()
+            .iter()
+            .zip(chunks_b.remainder())
+            .filter(|(x, y)| x != y)
+            .count()
+}
+
+/// For every byte value, the positions of the pattern where it occurs
+fn pattern_masks(pattern: &[u8]) -> Box<[u64; 256]> {
+    let mut peq = Box::new([0u64; 256]);
+    for (i, c) in pattern.iter().enumerate() {
+        peq[*c as usize] |= 1 << i;
+    }
+    peq
+}
+
+/// Edit distance (mismatches, insertions and deletions all cost 1) between
+/// `a` and `b`. Uses Myers' bit-parallel algorithm when one of them is at
+/// most 64 bytes long and the classic dynamic programming otherwise.
+///
+/// ```
+/// use needletail::distance::levenshtein;
+///
+/// assert_eq!(levenshtein(b"ACGTACGT", b"ACTACGGT"), 2);
+/// ```
+pub fn levenshtein(a: &[u8], b: &[u8]) -> usize {
+    let (pattern, text) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if pattern.is_empty() {
+        return text.len();
+    }
+    if pattern.len() > 64 {
+        return levenshtein_dp(a, b);
+    }
+
+    let peq = pattern_masks(pattern);
+    let last = 1u64 << (pattern.len() - 1);
+    let (mut pv, mut mv) = (!0u64, 0u64);
+    let mut score = pattern.len();
+    for c in text {
+        let eq = peq[*c as usize];
+        let xv = eq | mv;
+        let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+        let ph = mv | !(xh | pv);
+        let mh = pv & xh;
+        if ph & last != 0 {
+            score += 1;
+        } else if mh & last != 0 {
+            score -= 1;
+        }
+        // the first row of the matrix increases by one at every column
+        let ph = (ph << 1) | 1;
+        let mh = mh << 1;
+        pv = mh | !(xv | ph);
+        mv = ph & xv;
+    }
+    score
+}
+
+fn levenshtein_dp(a: &[u8], b: &[u8]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(x != y);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Edit distance between `a` and `b` if it's at most `max_dist`, only
+/// filling the band of the dynamic programming matrix within `max_dist` of
+/// the diagonal: O(max_dist * len) instead of O(len^2).
+///
+/// ```
+/// use needletail::distance::banded_levenshtein;
+///
+/// assert_eq!(banded_levenshtein(b"ACGTACGT", b"ACTACGGT", 2), Some(2));
+/// assert_eq!(banded_levenshtein(b"ACGTACGT", b"ACTACGGT", 1), None);
+/// ```
+pub fn banded_levenshtein(a: &[u8], b: &[u8], max_dist: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max_dist {
+        return None;
+    }
+    // anything outside of the band is at least max_dist + 1
+    let outside = max_dist + 1;
+    let mut row: Vec<usize> = (0..=b.len()).map(|j| j.min(outside)).collect();
+    for (i, x) in a.iter().enumerate() {
+        let i = i + 1;
+        let start = i.saturating_sub(max_dist).max(1);
+        let end = (i + max_dist).min(b.len());
+        let mut diagonal = row[start - 1];
+        row[start - 1] = if start == 1 { i.min(outside) } else { outside };
+        let mut row_min = row[start - 1];
+        for j in start..=end {
+            let substitution = diagonal + usize::from(*x != b[j - 1]);
+            diagonal = row[j];
+            row[j] = substitution
+                .min(row[j - 1] + 1)
+                .min(diagonal + 1)
+                .min(outside);
+            row_min = row_min.min(row[j]);
+        }
+        if end < b.len() {
+            row[end + 1] = outside;
+        }
+        if row_min > max_dist {
+            return None;
+        }
+    }
+    Some(row[b.len()]).filter(|d| *d <= max_dist)
+}
+
+/// Finds where `pattern` (at most 64 bytes long) occurs in `text` with at
+/// most `max_dist` edits, returning the end position (exclusive) of every
+/// matching substring of `text` and its distance. Uses Myers' bit-parallel
+/// algorithm.
+///
+/// ```
+/// use needletail::distance::find_approximate;
+///
+/// let hits = find_approximate(b"GATTACA", b"TTTGATCACATTT", 1);
+/// assert_eq!(hits, vec![(10, 1)]);
+/// ```
+pub fn find_approximate(pattern: &[u8], text: &[u8], max_dist: usize) -> Vec<(usize, usize)> {
+    assert!(
+        !pattern.is_empty() && pattern.len() <= 64,
+        "the pattern needs to be between 1 and 64 bytes long"
+    );
+    let peq = pattern_masks(pattern);
+    let last = 1u64 << (pattern.len() - 1);
+    let (mut pv, mut mv) = (!0u64, 0u64);
+    let mut score = pattern.len();
+    let mut hits = Vec::new();
+    for (j, c) in text.iter().enumerate() {
+        let eq = peq[*c as usize];
+        let xv = eq | mv;
+        let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+        let ph = mv | !(xh | pv);
+        let mh = pv & xh;
+        if ph & last != 0 {
+            score += 1;
+        } else if mh & last != 0 {
+            score -= 1;
+        }
+        // unlike `levenshtein` the match can start anywhere in the text so
+        // the first row stays at 0
+        let ph = ph << 1;
+        let mh = mh << 1;
+        pv = mh | !(xv | ph);
+        mv = ph & xv;
+        if score <= max_dist {
+            hits.push((j + 1, score));
+        }
+    }
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEQS: [&[u8]; 7] = [
+        b"",
+        b"A",
+        b"ACGT",
+        b"AGGTT",
+        b"TTTTACGTAAAA",
+        b"ACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTA",
+        b"ACGTACGTACGTACGTACGTACGTACGAACGTACGTACGTACGTACGTACGTTACGTACGTACGTACGTAAC",
+    ];
+
+    #[test]
+    fn hamming_counts_all_chunks() {
+        let a = b"ACGTACGTACGTACGTACG";
+        let mut b = *a;
+        assert_eq!(hamming(a, &b), 0);
+        b[0] = b'T';
+        b[7] = b'A';
+        b[8] = b'C';
+        b[18] = b'A';
+        assert_eq!(hamming(a, &b), 4);
+        assert_eq!(hamming(b"", b""), 0);
+    }
+
+    #[test]
+    fn edit_distances_agree() {
+        for a in SEQS {
+            for b in SEQS {
+                let expected = levenshtein_dp(a, b);
+                assert_eq!(levenshtein(a, b), expected);
+                for max_dist in [0, 1, 2, 5, 100] {
+                    let banded = banded_levenshtein(a, b, max_dist);
+                    assert_eq!(banded, Some(expected).filter(|d| *d <= max_dist));
+                }
+            }
+        }
+        assert_eq!(levenshtein(b"kitten", b"sitting"), 3);
+    }
+
+    #[test]
+    fn can_find_approximate_matches() {
+        assert_eq!(find_approximate(b"ACGT", b"TTACGTTT", 0), vec![(6, 0)]);
+        // one deletion in the text
+        let hits = find_approximate(b"ACGTACGT", b"TTTACGTCGTTTT", 1);
+        assert_eq!(hits, vec![(10, 1)]);
+    }
+}
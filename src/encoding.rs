@@ -0,0 +1,361 @@
+//! Compact in-memory encodings of whole nucleic acid sequences
+use std::ops::Range;
+
+use crate::bitkmer::{BitKmer, BitKmerSeq, KmerBits};
+use crate::errors::SequenceError;
+use crate::simd::{encode_2bit_bases, INVALID_2BIT};
+
+const BASES: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+/// Packs a sequence at 4 bases per byte (the first base in the highest bits of
+/// the first byte). Anything that isn't ACGT is stored as an `A` and its position
+/// is recorded in the returned list of runs so it can be restored as an `N`.
+///
+/// Case is not preserved.
+///
+/// ```
+/// use needletail::encoding::{decode_2bit, encode_2bit};
+///
+/// let (packed, n_runs) = encode_2bit(b"ACGTNNAC");
+/// assert_eq!(packed, vec![0b00_01_10_11, 0b00_00_00_01]);
+/// assert_eq!(n_runs, vec![4..6]);
+/// assert_eq!(decode_2bit(&packed, 8, &n_runs), b"ACGTNNAC");
+/// ```
+pub fn encode_2bit(seq: &[u8]) -> (Vec<u8>, Vec<Range<usize>>) {
+    let mut packed = vec![0u8; seq.len().div_ceil(4)];
+    let mut n_runs: Vec<Range<usize>> = Vec::new();
+    // the chunks are a multiple of 4 so every chunk starts on a new byte
+    let mut codes = [0u8; 256];
+    for (c, chunk) in seq.chunks(codes.len()).enumerate() {
+        let codes = &mut codes[..chunk.len()];
+        encode_2bit_bases(chunk, codes);
+        let offset = c * 256;
+        for (i, code) in codes.iter().enumerate() {
+            let i = offset + i;
+            if *code == INVALID_2BIT {
+                match n_runs.last_mut() {
+                    Some(run) if run.end == i => run.end += 1,
+                    _ => n_runs.push(i..i + 1),
+                }
+            } else {
+                packed[i / 4] |= code << (6 - 2 * (i % 4));
+            }
+        }
+    }
+    (packed, n_runs)
+}
+
+/// Decodes the first `len` bases of a sequence packed with `encode_2bit`
+pub fn decode_2bit(packed: &[u8], len: usize, n_runs: &[Range<usize>]) -> Vec<u8> {
+    let mut seq: Vec<u8> = (0..len)
+        .map(|i| BASES[get_2bit(packed, i) as usize])
+        .collect();
+    for run in n_runs {
+        seq[run.clone()].fill(b'N');
+    }
+    seq
+}
+
+#[inline]
+fn get_2bit(packed: &[u8], i: usize) -> u8 {
+    (packed[i / 4] >> (6 - 2 * (i % 4))) & 3
+}
+
+/// A sequence held in memory at 2 bits per base, see `encode_2bit`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TwoBitSequence {
+    packed: Vec<u8>,
+    len: usize,
+    n_runs: Vec<Range<usize>>,
+}
+
+impl TwoBitSequence {
+    pub fn new(seq: &[u8]) -> Self {
+        let (packed, n_runs) = encode_2bit(seq);
+        TwoBitSequence {
+            packed,
+            len: seq.len(),
+            n_runs,
+        }
+    }
+
+    /// Number of bases in the sequence
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The packed bases, 4 per byte
+    pub fn packed(&self) -> &[u8] {
+        &self.packed
+    }
+
+    /// The ranges of positions that were not ACGT in the original sequence
+    pub fn n_runs(&self) -> &[Range<usize>] {
+        &self.n_runs
+    }
+
+    fn is_n(&self, i: usize) -> bool {
+        // runs are sorted and non-overlapping
+        let idx = self.n_runs.partition_point(|r| r.end <= i);
+        self.n_runs.get(idx).is_some_and(|r| r.contains(&i))
+    }
+
+    /// Returns the base at position `i`, panicking if out of bounds
+    pub fn get(&self, i: usize) -> u8 {
+        assert!(i < self.len, "index {i} out of bounds");
+        if self.is_n(i) {
+            b'N'
+        } else {
+            BASES[get_2bit(&self.packed, i) as usize]
+        }
+    }
+
+    /// Decodes a part of the sequence
+    pub fn slice(&self, range: Range<usize>) -> Vec<u8> {
+        assert!(range.start <= range.end && range.end <= self.len);
+        range.map(|i| self.get(i)).collect()
+    }
+
+    /// Decodes the whole sequence
+    pub fn to_vec(&self) -> Vec<u8> {
+        decode_2bit(&self.packed, self.len, &self.n_runs)
+    }
+
+    /// Iterates over the kmers of the sequence that don't overlap an N-run,
+    /// directly from the packed data.
+    pub fn bit_kmers(&self, k: u8) -> PackedKmers<'_> {
+        assert!(k > 0 && k <= BitKmerSeq::MAX_K);
+        PackedKmers {
+            seq: self,
+            k,
+            pos: 0,
+            run_idx: 0,
+            kmer: 0,
+            valid: 0,
+        }
+    }
+}
+
+/// Iterator over the kmers of a `TwoBitSequence`, see `TwoBitSequence::bit_kmers`
+pub struct PackedKmers<'a> {
+    seq: &'a TwoBitSequence,
+    k: u8,
+    /// next base to add
+    pos: usize,
+    run_idx: usize,
+    kmer: BitKmerSeq,
+    /// number of valid bases at the end of `kmer`
+    valid: usize,
+}
+
+impl Iterator for PackedKmers<'_> {
+    type Item = (usize, BitKmer);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.seq.len {
+            let i = self.pos;
+            self.pos += 1;
+            while self.run_idx < self.seq.n_runs.len() && self.seq.n_runs[self.run_idx].end <= i {
+                self.run_idx += 1;
+            }
+            if self
+                .seq
+                .n_runs
+                .get(self.run_idx)
+                .is_some_and(|r| r.contains(&i))
+            {
+                self.valid = 0;
+                continue;
+            }
+            self.kmer = self.kmer.push_base(get_2bit(&self.seq.packed, i), self.k);
+            self.valid += 1;
+            if self.valid >= self.k as usize {
+                return Some((i + 1 - self.k as usize, (self.kmer, self.k)));
+            }
+        }
+        None
+    }
+}
+
+/// Lookup from a 4-bit code to its IUPAC letter. Each bit of the code is one of
+/// the bases the letter can stand for: A is `0b0001`, C `0b0010`, G `0b0100` and
+/// T `0b1000` (the same as the BAM format). `0` is a gap.
+const IUPAC_4BIT_LETTERS: [u8; 16] = *b"-ACMGRSVTWYHKDBN";
+
+const IUPAC_4BIT_LOOKUP: [u8; 256] = {
+    // 255 marks invalid characters
+    let mut lookup = [255u8; 256];
+    let mut code = 0;
+    while code < 16 {
+        let letter = IUPAC_4BIT_LETTERS[code];
+        lookup[letter as usize] = code as u8;
+        lookup[letter.to_ascii_lowercase() as usize] = code as u8;
+        code += 1;
+    }
+    lookup[b'U' as usize] = 0b1000;
+    lookup[b'u' as usize] = 0b1000;
+    lookup[b'.' as usize] = 0;
+    lookup
+};
+
+/// Converts an IUPAC nucleotide letter (in any case, U being treated as T and
+/// `-`/`.` as gaps) to its 4-bit code, see `decode_4bit_base`.
+#[inline]
+pub fn encode_4bit_base(base: u8) -> Option<u8> {
+    match IUPAC_4BIT_LOOKUP[base as usize] {
+        255 => None,
+        code => Some(code),
+    }
+}
+
+/// Converts a 4-bit code back to its uppercase IUPAC letter: each bit is one of
+/// A (`0b0001`), C (`0b0010`), G (`0b0100`) or T (`0b1000`), e.g. `R` (A or G)
+/// is `0b0101`. Only the lowest 4 bits are considered.
+#[inline]
+pub fn decode_4bit_base(code: u8) -> u8 {
+    IUPAC_4BIT_LETTERS[(code & 0xF) as usize]
+}
+
+/// Complements a 4-bit code, which is reversing its bits: A <> T, C <> G and
+/// e.g. R (A/G) <> Y (C/T) follow from it.
+#[inline]
+pub fn complement_4bit(code: u8) -> u8 {
+    ((code & 1) << 3) | ((code & 2) << 1) | ((code & 4) >> 1) | ((code & 8) >> 3)
+}
+
+/// Whether two 4-bit codes can stand for the same base, e.g. `R` matches `A`
+/// and `N` matches anything but a gap.
+#[inline]
+pub fn matches_4bit(a: u8, b: u8) -> bool {
+    a & b != 0
+}
+
+/// Packs a sequence of IUPAC nucleotides at 2 bases per byte, the first base
+/// in the highest 4 bits. Unlike `encode_2bit`, no information is lost except
+/// for the case and U being stored as T.
+///
+/// ```
+/// use needletail::encoding::{decode_4bit, encode_4bit};
+///
+/// let packed = encode_4bit(b"ACRN-").unwrap();
+/// assert_eq!(packed, vec![0x12, 0x5F, 0x00]);
+/// assert_eq!(decode_4bit(&packed, 5), b"ACRN-");
+/// assert!(encode_4bit(b"ACX").is_err());
+/// ```
+pub fn encode_4bit(seq: &[u8]) -> Result<Vec<u8>, SequenceError> {
+    let mut packed = vec![0u8; seq.len().div_ceil(2)];
+    for (position, base) in seq.iter().enumerate() {
+        let code = encode_4bit_base(*base).ok_or(SequenceError::InvalidBase {
+            position,
+            base: *base,
+        })?;
+        packed[position / 2] |= code << (4 * (1 - position % 2));
+    }
+    Ok(packed)
+}
+
+#[inline]
+fn get_4bit(packed: &[u8], i: usize) -> u8 {
+    (packed[i / 2] >> (4 * (1 - i % 2))) & 0xF
+}
+
+/// Decodes the first `len` bases of a sequence packed with `encode_4bit`
+pub fn decode_4bit(packed: &[u8], len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| decode_4bit_base(get_4bit(packed, i)))
+        .collect()
+}
+
+/// Reverse complements the first `len` bases of a sequence packed with
+/// `encode_4bit` without decoding it
+pub fn reverse_complement_4bit(packed: &[u8], len: usize) -> Vec<u8> {
+    let mut rc = vec![0u8; len.div_ceil(2)];
+    for i in 0..len {
+        let code = complement_4bit(get_4bit(packed, len - 1 - i));
+        rc[i / 2] |= code << (4 * (1 - i % 2));
+    }
+    rc
+}
+
+/// Whether the `len` bases of two sequences packed with `encode_4bit` can be the
+/// same, taking ambiguity codes on both sides into account
+pub fn matches_4bit_seq(a: &[u8], b: &[u8], len: usize) -> bool {
+    (0..len).all(|i| matches_4bit(get_4bit(a, i), get_4bit(b, i)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sequence;
+
+    #[test]
+    fn can_roundtrip_2bit() {
+        for seq in [&b""[..], b"A", b"ACGTA", b"NNNN", b"acgtRYnnACG", b"NACGTN"] {
+            let (packed, n_runs) = encode_2bit(seq);
+            assert_eq!(packed.len(), seq.len().div_ceil(4));
+            let decoded = decode_2bit(&packed, seq.len(), &n_runs);
+            assert_eq!(decoded, seq.normalize(false).as_ref());
+        }
+        let (_, n_runs) = encode_2bit(b"NACGTNNRY");
+        assert_eq!(n_runs, vec![0..1, 5..9]);
+    }
+
+    #[test]
+    fn can_roundtrip_4bit() {
+        let seq = b"ACGTRYSWKMBDHVN-";
+        let packed = encode_4bit(seq).unwrap();
+        assert_eq!(packed.len(), 8);
+        assert_eq!(decode_4bit(&packed, seq.len()), seq);
+        assert_eq!(decode_4bit(&encode_4bit(b"acgun.").unwrap(), 6), b"ACGTN-");
+        assert_eq!(
+            encode_4bit(b"ACZ"),
+            Err(SequenceError::InvalidBase {
+                position: 2,
+                base: b'Z'
+            })
+        );
+    }
+
+    #[test]
+    fn can_complement_4bit() {
+        let seq = b"ACGTRYSWKMBDHVN-A";
+        let packed = encode_4bit(seq).unwrap();
+        let rc = reverse_complement_4bit(&packed, seq.len());
+        assert_eq!(decode_4bit(&rc, seq.len()), seq.reverse_complement());
+    }
+
+    #[test]
+    fn can_match_4bit() {
+        let a = encode_4bit(b"ARNT").unwrap();
+        let b = encode_4bit(b"AGCT").unwrap();
+        let c = encode_4bit(b"ACCT").unwrap();
+        assert!(matches_4bit_seq(&a, &b, 4));
+        assert!(!matches_4bit_seq(&a, &c, 4));
+        assert!(!matches_4bit(encode_4bit_base(b'N').unwrap(), 0));
+    }
+
+    #[test]
+    fn can_slice_two_bit_sequences() {
+        let seq = TwoBitSequence::new(b"ACGTNNACGTTT");
+        assert_eq!(seq.len(), 12);
+        assert_eq!(seq.get(3), b'T');
+        assert_eq!(seq.get(4), b'N');
+        assert_eq!(seq.slice(2..8), b"GTNNAC");
+        assert_eq!(seq.to_vec(), b"ACGTNNACGTTT");
+    }
+
+    #[test]
+    fn packed_kmers_match_bit_kmers() {
+        let raw = b"ACGTNNACGTTTGACNA";
+        let seq = TwoBitSequence::new(raw);
+        for k in 1..6 {
+            let expected: Vec<_> = raw.bit_kmers(k, false).map(|(p, k, _)| (p, k)).collect();
+            let found: Vec<_> = seq.bit_kmers(k).collect();
+            assert_eq!(expected, found);
+        }
+    }
+}
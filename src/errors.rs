@@ -1,9 +1,12 @@
-//! The errors needletail can return; only when parsing FASTA/FASTQ files
+//! The errors needletail can return: `ParseError` when parsing FASTA/FASTQ files and
+//! `SequenceError` when a sequence can't be processed by one of the sequence functions
 
-use crate::parser::Format;
+use crate::parser::{Format, Position};
+use crate::quality::QualityEncoding;
 use std::error::Error as StdError;
 use std::fmt;
 use std::io;
+use std::ops::Range;
 
 /// Represents where we were in a file when an error occurred.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -12,6 +15,23 @@ pub struct ErrorPosition {
     pub line: u64,
     /// ID of record if available
     pub id: Option<String>,
+    /// Byte offset of the start of the record within the (decompressed) file,
+    /// if known
+    pub byte: Option<u64>,
+    /// Index of the record in the file (starting with 0), if known
+    pub record: Option<u64>,
+}
+
+impl ErrorPosition {
+    /// The position of the record a reader is at
+    pub(crate) fn at(position: &Position, id: Option<String>) -> Self {
+        Self {
+            line: position.line(),
+            id,
+            byte: Some(position.byte()),
+            record: Some(position.record()),
+        }
+    }
 }
 
 impl fmt::Display for ErrorPosition {
@@ -19,12 +39,18 @@ impl fmt::Display for ErrorPosition {
         if let Some(id) = self.id.as_ref() {
             write!(f, "record '{id}' at ")?;
         }
-        write!(f, "line {}", self.line)
+        write!(f, "line {}", self.line)?;
+        if let Some(byte) = self.byte {
+            write!(f, ", byte {byte}")?;
+        }
+        Ok(())
     }
 }
 
-/// The type of error that occured during file parsing
+/// The type of error that occured during file parsing. New kinds can be
+/// added in minor versions, use `as_str` for a stable identifier.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ParseErrorKind {
     /// An error happened during file/stream input/output
     Io,
@@ -40,6 +66,65 @@ pub enum ParseErrorKind {
     UnexpectedEnd,
     /// The file appears to be empty
     EmptyFile,
+    /// The sequence contains characters outside of the expected alphabet
+    InvalidSequence,
+    /// The quality line contains characters outside of the range of the
+    /// expected quality encoding
+    InvalidQuality,
+    /// Several records have the same id when they need to be unique
+    DuplicateId,
+    /// The records of paired files (or of an interleaved file) are not mates
+    UnpairedRecords,
+    /// A line ending is a lone `\r`, only rejected in strict mode
+    InvalidLineEnding,
+    /// A record is bigger than one of the limits of the `ReaderOptions`
+    LimitExceeded,
+    /// The input stopped in the middle of a compressed stream, or a BGZF
+    /// file is missing its end-of-file block: the file is incomplete
+    Truncated,
+}
+
+impl ParseErrorKind {
+    /// A stable snake case name of the kind, e.g. for logs and metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Io => "io",
+            Self::UnknownFormat => "unknown_format",
+            Self::InvalidStart => "invalid_start",
+            Self::InvalidSeparator => "invalid_separator",
+            Self::UnequalLengths => "unequal_lengths",
+            Self::UnexpectedEnd => "unexpected_end",
+            Self::EmptyFile => "empty_file",
+            Self::InvalidSequence => "invalid_sequence",
+            Self::InvalidQuality => "invalid_quality",
+            Self::DuplicateId => "duplicate_id",
+            Self::UnpairedRecords => "unpaired_records",
+            Self::InvalidLineEnding => "invalid_line_ending",
+            Self::LimitExceeded => "limit_exceeded",
+            Self::Truncated => "truncated",
+        }
+    }
+}
+
+/// How many bytes are shown in snippets
+const SNIPPET_LEN: usize = 16;
+
+/// Formats the start of `bytes` in hex and in ASCII, with `.` for the
+/// non-printable characters, like `hexdump -C`
+fn format_snippet(bytes: &[u8]) -> String {
+    let bytes = &bytes[..bytes.len().min(SNIPPET_LEN)];
+    let hex: Vec<_> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    let ascii: String = bytes
+        .iter()
+        .map(|b| {
+            if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!("{} |{ascii}|", hex.join(" "))
 }
 
 /// The only error type that needletail returns
@@ -53,9 +138,33 @@ pub struct ParseError {
     pub position: ErrorPosition,
     /// The format of the file we were parsing
     pub format: Option<Format>,
+    /// The first bytes where the error was found, in hex and ASCII
+    pub snippet: Option<Box<str>>,
+    /// How many bytes were skipped from `position.byte` to get to the next
+    /// record, when the reader recovers from the error (see
+    /// `ReaderOptions::recover`)
+    pub skipped: u64,
 }
 
 impl ParseError {
+    /// Adds the first bytes of `bytes` to the error, as the `snippet`
+    pub fn with_snippet(mut self, bytes: &[u8]) -> Self {
+        self.snippet = Some(format_snippet(bytes).into());
+        self
+    }
+
+    /// Sets how many bytes were skipped after the error
+    pub fn with_skipped(mut self, skipped: u64) -> Self {
+        self.skipped = skipped;
+        self
+    }
+
+    /// The range of bytes skipped after the error, if any
+    pub fn skipped_range(&self) -> Option<Range<u64>> {
+        let start = self.position.byte?;
+        (self.skipped > 0).then(|| start..start + self.skipped)
+    }
+
     pub fn new_invalid_start(byte_found: u8, position: ErrorPosition, format: Format) -> Self {
         let msg = format!(
             "Expected '{}' but found '{}",
@@ -67,6 +176,8 @@ impl ParseError {
             msg,
             position,
             format: Some(format),
+            snippet: None,
+            skipped: 0,
         }
     }
 
@@ -80,6 +191,8 @@ impl ParseError {
             msg,
             position,
             format: Some(Format::Fastq),
+            snippet: None,
+            skipped: 0,
         }
     }
 
@@ -93,6 +206,8 @@ impl ParseError {
             msg,
             position: ErrorPosition::default(),
             format: Some(Format::Fastq),
+            snippet: None,
+            skipped: 0,
         }
     }
 
@@ -103,6 +218,8 @@ impl ParseError {
             msg,
             position,
             format: Some(Format::Fastq),
+            snippet: None,
+            skipped: 0,
         }
     }
 
@@ -112,6 +229,106 @@ impl ParseError {
             kind: ParseErrorKind::UnexpectedEnd,
             position,
             format: Some(format),
+            snippet: None,
+            skipped: 0,
+        }
+    }
+
+    pub fn new_invalid_sequence(invalid: &[(usize, u8)], position: ErrorPosition) -> Self {
+        // only show the first few, a file of proteins read as DNA would give
+        // an unreadable message otherwise
+        let mut msg = invalid
+            .iter()
+            .take(5)
+            .map(|(pos, byte)| format!("'{}' at {pos}", (*byte as char).escape_default()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if invalid.len() > 5 {
+            msg += &format!(" and {} more", invalid.len() - 5);
+        }
+        Self {
+            msg: format!("Invalid characters in sequence: {msg}"),
+            kind: ParseErrorKind::InvalidSequence,
+            position,
+            format: None,
+            snippet: None,
+            skipped: 0,
+        }
+    }
+
+    pub fn new_invalid_quality(
+        byte_found: u8,
+        column: usize,
+        encoding: QualityEncoding,
+        position: ErrorPosition,
+    ) -> Self {
+        let msg = format!(
+            "Invalid quality '{}' at {column} for {encoding:?} qualities, expected '{}' to '~'",
+            (byte_found as char).escape_default(),
+            encoding.min_char() as char,
+        );
+        Self {
+            msg,
+            kind: ParseErrorKind::InvalidQuality,
+            position,
+            format: Some(Format::Fastq),
+            snippet: None,
+            skipped: 0,
+        }
+    }
+
+    pub fn new_duplicate_id(first_line: u64, position: ErrorPosition) -> Self {
+        Self {
+            msg: format!("Duplicate id, first seen at line {first_line}"),
+            kind: ParseErrorKind::DuplicateId,
+            position,
+            format: None,
+            snippet: None,
+            skipped: 0,
+        }
+    }
+
+    pub fn new_unpaired_records(msg: String, position: ErrorPosition) -> Self {
+        Self {
+            msg,
+            kind: ParseErrorKind::UnpairedRecords,
+            position,
+            format: None,
+            snippet: None,
+            skipped: 0,
+        }
+    }
+
+    pub fn new_empty_sequence(position: ErrorPosition) -> Self {
+        Self {
+            msg: String::from("Empty sequence"),
+            kind: ParseErrorKind::InvalidSequence,
+            position,
+            format: None,
+            snippet: None,
+            skipped: 0,
+        }
+    }
+
+    pub fn new_invalid_line_ending(position: ErrorPosition) -> Self {
+        Self {
+            msg: String::from("Line ending made of a '\\r' without '\\n'"),
+            kind: ParseErrorKind::InvalidLineEnding,
+            position,
+            format: None,
+            snippet: None,
+            skipped: 0,
+        }
+    }
+
+    pub fn new_limit_exceeded(what: &str, limit: usize, position: ErrorPosition) -> Self {
+        Self {
+            msg: format!("{what} is longer than the limit of {limit} bytes"),
+            kind: ParseErrorKind::LimitExceeded,
+            position,
+            format: None,
+            snippet: None,
+            skipped: 0,
         }
     }
 
@@ -121,6 +338,8 @@ impl ParseError {
             kind: ParseErrorKind::EmptyFile,
             position: ErrorPosition::default(),
             format: None,
+            snippet: None,
+            skipped: 0,
         }
     }
 }
@@ -129,25 +348,47 @@ impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.kind {
             ParseErrorKind::Io => write!(f, "I/O error: {}", self.msg),
+            ParseErrorKind::Truncated => write!(f, "Truncated input: {}", self.msg),
             ParseErrorKind::UnequalLengths
             | ParseErrorKind::InvalidStart
             | ParseErrorKind::UnknownFormat
             | ParseErrorKind::EmptyFile
-            | ParseErrorKind::InvalidSeparator => write!(f, "{} ({})", self.msg, self.position),
+            | ParseErrorKind::InvalidSeparator
+            | ParseErrorKind::InvalidSequence
+            | ParseErrorKind::InvalidQuality
+            | ParseErrorKind::DuplicateId
+            | ParseErrorKind::UnpairedRecords
+            | ParseErrorKind::InvalidLineEnding
+            | ParseErrorKind::LimitExceeded => write!(f, "{} ({})", self.msg, self.position),
             ParseErrorKind::UnexpectedEnd => {
                 write!(f, "Unexpected end of input ({}).", self.position)
             }
+        }?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, " near {snippet}")?;
+        }
+        if let Some(skipped) = self.skipped_range() {
+            write!(f, ", skipped bytes {}..{}", skipped.start, skipped.end)?;
         }
+        Ok(())
     }
 }
 
 impl From<io::Error> for ParseError {
     fn from(err: io::Error) -> Self {
+        // the decompressors all report streams ending early that way
+        let kind = if err.kind() == io::ErrorKind::UnexpectedEof {
+            ParseErrorKind::Truncated
+        } else {
+            ParseErrorKind::Io
+        };
         Self {
             msg: err.to_string(),
-            kind: ParseErrorKind::Io,
+            kind,
             position: ErrorPosition::default(),
             format: None,
+            snippet: None,
+            skipped: 0,
         }
     }
 }
@@ -160,3 +401,69 @@ impl StdError for ParseError {
         None
     }
 }
+
+/// Error returned by the functions working on individual sequences/kmers when
+/// their input is not valid
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SequenceError {
+    /// A byte that is not allowed in this context, e.g. an `N` in a packed kmer
+    InvalidBase { position: usize, base: u8 },
+    /// The sequence is longer than what is supported
+    TooLong { length: usize, max: usize },
+    /// The sequence is shorter than what is supported
+    TooShort { length: usize, min: usize },
+    /// A range doesn't fit in the sequence
+    OutOfBounds {
+        start: usize,
+        end: usize,
+        length: usize,
+    },
+    /// The quality doesn't have as many scores as the sequence has bases
+    UnequalLengths { seq_len: usize, qual_len: usize },
+    /// A byte that can't be part of a record header, e.g. a newline, or a
+    /// space in an id
+    InvalidHeader { position: usize, byte: u8 },
+    /// Quality scores are needed but the record has none, e.g. it comes
+    /// from a FASTA file
+    MissingQuality,
+}
+
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidBase { position, base } => write!(
+                f,
+                "Invalid base '{}' at position {position}",
+                (*base as char).escape_default()
+            ),
+            Self::TooLong { length, max } => {
+                write!(
+                    f,
+                    "Sequence of length {length} is longer than the maximum of {max}"
+                )
+            }
+            Self::TooShort { length, min } => {
+                write!(
+                    f,
+                    "Sequence of length {length} is shorter than the minimum of {min}"
+                )
+            }
+            Self::OutOfBounds { start, end, length } => write!(
+                f,
+                "Range {start}..{end} is out of the bounds of a sequence of length {length}"
+            ),
+            Self::UnequalLengths { seq_len, qual_len } => write!(
+                f,
+                "Sequence length is {seq_len} but quality length is {qual_len}"
+            ),
+            Self::InvalidHeader { position, byte } => write!(
+                f,
+                "Invalid header character '{}' at position {position}",
+                (*byte as char).escape_default()
+            ),
+            Self::MissingQuality => write!(f, "The record has no quality scores"),
+        }
+    }
+}
+
+impl StdError for SequenceError {}
@@ -0,0 +1,452 @@
+//! Read filtering on length, ambiguous bases and quality, in the style of
+//! fastp and DADA2's `filterAndTrim`, on ids, in the style of `seqtk subseq`,
+//! and random subsampling
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::errors::ParseError;
+use crate::parser::{mate_name, SequenceRecord};
+use crate::sequence::QualitySequence;
+
+/// Why a read was rejected by a `ReadFilter`, in the order the criteria are
+/// checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FilterReason {
+    TooShort,
+    TooLong,
+    TooManyNs,
+    TooManyExpectedErrors,
+    LowMeanQuality,
+}
+
+/// Criteria reads need to meet, all optional. Quality criteria are ignored for
+/// reads without qualities.
+///
+/// ```
+/// use needletail::filter::{FilterReason, ReadFilter};
+///
+/// let filter = ReadFilter::new().min_len(4).max_n(0).max_expected_errors(1.);
+/// assert_eq!(filter.check(b"ACGT", Some(b"IIII")), Ok(()));
+/// assert_eq!(filter.check(b"ACG", Some(b"III")), Err(FilterReason::TooShort));
+/// assert_eq!(filter.check(b"ACNT", None), Err(FilterReason::TooManyNs));
+/// assert_eq!(
+///     filter.check(b"ACGT", Some(b"I+#!")),
+///     Err(FilterReason::TooManyExpectedErrors)
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadFilter {
+    pub min_len: Option<usize>,
+    pub max_len: Option<usize>,
+    /// Maximum number of `N` (or `n`)
+    pub max_n: Option<usize>,
+    /// Maximum sum of the error probabilities of the bases (`maxEE`)
+    pub max_expected_errors: Option<f64>,
+    /// Minimum mean Phred score
+    pub min_mean_quality: Option<f64>,
+}
+
+impl ReadFilter {
+    /// A filter accepting everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn min_len(mut self, min_len: usize) -> Self {
+        self.min_len = Some(min_len);
+        self
+    }
+
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    pub fn max_n(mut self, max_n: usize) -> Self {
+        self.max_n = Some(max_n);
+        self
+    }
+
+    pub fn max_expected_errors(mut self, max_expected_errors: f64) -> Self {
+        self.max_expected_errors = Some(max_expected_errors);
+        self
+    }
+
+    pub fn min_mean_quality(mut self, min_mean_quality: f64) -> Self {
+        self.min_mean_quality = Some(min_mean_quality);
+        self
+    }
+
+    /// Checks a read and its Phred+33 qualities, if any, returning the first
+    /// criterion it fails
+    pub fn check(&self, seq: &[u8], qual: Option<&[u8]>) -> Result<(), FilterReason> {
+        if self.min_len.is_some_and(|min| seq.len() < min) {
+            return Err(FilterReason::TooShort);
+        }
+        if self.max_len.is_some_and(|max| seq.len() > max) {
+            return Err(FilterReason::TooLong);
+        }
+        if let Some(max_n) = self.max_n {
+            let n = seq.iter().filter(|b| matches!(b, b'N' | b'n')).count();
+            if n > max_n {
+                return Err(FilterReason::TooManyNs);
+            }
+        }
+        let Some(qual) = qual else {
+            return Ok(());
+        };
+        let rec = (seq, qual);
+        if self
+            .max_expected_errors
+            .is_some_and(|max| rec.expected_errors() > max)
+        {
+            return Err(FilterReason::TooManyExpectedErrors);
+        }
+        if self
+            .min_mean_quality
+            .is_some_and(|min| rec.mean_quality() < min)
+        {
+            return Err(FilterReason::LowMeanQuality);
+        }
+        Ok(())
+    }
+
+    /// Checks a record, see `check`
+    pub fn check_record(&self, record: &SequenceRecord) -> Result<(), FilterReason> {
+        self.check(&record.seq(), record.qual())
+    }
+
+    /// Checks a record and counts the result in `stats`, returning whether
+    /// the record passed
+    pub fn filter_record(&self, record: &SequenceRecord, stats: &mut FilterStats) -> bool {
+        let result = self.check_record(record);
+        stats.add(result);
+        result.is_ok()
+    }
+}
+
+/// How many reads passed a `ReadFilter` and how many were rejected for each
+/// criterion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilterStats {
+    pub passed: u64,
+    pub too_short: u64,
+    pub too_long: u64,
+    pub too_many_ns: u64,
+    pub too_many_expected_errors: u64,
+    pub low_mean_quality: u64,
+}
+
+impl FilterStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts the result of `ReadFilter::check`
+    pub fn add(&mut self, result: Result<(), FilterReason>) {
+        let count = match result {
+            Ok(()) => &mut self.passed,
+            Err(FilterReason::TooShort) => &mut self.too_short,
+            Err(FilterReason::TooLong) => &mut self.too_long,
+            Err(FilterReason::TooManyNs) => &mut self.too_many_ns,
+            Err(FilterReason::TooManyExpectedErrors) => &mut self.too_many_expected_errors,
+            Err(FilterReason::LowMeanQuality) => &mut self.low_mean_quality,
+        };
+        *count += 1;
+    }
+
+    /// Number of reads rejected
+    pub fn rejected(&self) -> u64 {
+        self.too_short
+            + self.too_long
+            + self.too_many_ns
+            + self.too_many_expected_errors
+            + self.low_mean_quality
+    }
+
+    /// Number of reads checked
+    pub fn total(&self) -> u64 {
+        self.passed + self.rejected()
+    }
+}
+
+/// How the names of the records are compared to the ids of an `IdFilter`
+#[derive(Debug, Clone)]
+enum IdMatch {
+    Exact(HashSet<Vec<u8>>),
+    /// The ids are prefixes of the names
+    Prefix(HashSet<Vec<u8>>),
+    #[cfg(feature = "regex")]
+    Regex(regex::bytes::RegexSet),
+}
+
+/// Keeps (or excludes) the records whose name (the id up to the first space
+/// or tab) is in a list of ids.
+///
+/// ```
+/// use needletail::filter::IdFilter;
+///
+/// let filter = IdFilter::new(["r1", "r3"]);
+/// assert!(filter.keep(b"r1"));
+/// assert!(!filter.keep(b"r2"));
+/// let filter = IdFilter::new(["r1"]).prefix(true).exclude(true);
+/// assert!(!filter.keep(b"r1_2"));
+/// assert!(filter.keep(b"r2"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct IdFilter {
+    ids: IdMatch,
+    exclude: bool,
+}
+
+impl IdFilter {
+    /// A filter keeping the records named exactly like one of the `ids`
+    pub fn new<I, T>(ids: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        IdFilter {
+            ids: IdMatch::Exact(ids.into_iter().map(|id| id.as_ref().to_vec()).collect()),
+            exclude: false,
+        }
+    }
+
+    /// Reads the ids from a file with one id per line. Empty lines are
+    /// skipped and the lines can be FASTA/FASTQ headers: a leading `>` or `@`
+    /// and anything after the first space or tab are ignored.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        let content = fs::read(path)?;
+        let ids = content
+            .split(|b| *b == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .map(|line| match line.first() {
+                Some(b'>' | b'@') => &line[1..],
+                _ => line,
+            })
+            .filter_map(|line| line.split(|b| *b == b' ' || *b == b'\t').next())
+            .filter(|id| !id.is_empty());
+        Ok(Self::new(ids))
+    }
+
+    /// A filter keeping the records whose name matches one of the `patterns`
+    #[cfg(feature = "regex")]
+    pub fn from_regexes<I, S>(patterns: I) -> Result<Self, regex::Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Ok(IdFilter {
+            ids: IdMatch::Regex(regex::bytes::RegexSet::new(patterns)?),
+            exclude: false,
+        })
+    }
+
+    /// Whether the ids are prefixes of the names rather than whole names.
+    /// Doesn't apply to filters created with `from_regexes`.
+    pub fn prefix(mut self, prefix: bool) -> Self {
+        self.ids = match self.ids {
+            IdMatch::Exact(ids) | IdMatch::Prefix(ids) if prefix => IdMatch::Prefix(ids),
+            IdMatch::Exact(ids) | IdMatch::Prefix(ids) => IdMatch::Exact(ids),
+            #[cfg(feature = "regex")]
+            ids => ids,
+        };
+        self
+    }
+
+    /// Excludes the records matching the ids instead of keeping them
+    pub fn exclude(mut self, exclude: bool) -> Self {
+        self.exclude = exclude;
+        self
+    }
+
+    /// Whether a name matches the ids, regardless of `exclude`
+    pub fn matches(&self, name: &[u8]) -> bool {
+        match &self.ids {
+            IdMatch::Exact(ids) => ids.contains(name),
+            IdMatch::Prefix(ids) => (0..=name.len()).any(|i| ids.contains(&name[..i])),
+            #[cfg(feature = "regex")]
+            IdMatch::Regex(set) => set.is_match(name),
+        }
+    }
+
+    /// Whether a record with this name passes the filter
+    pub fn keep(&self, name: &[u8]) -> bool {
+        self.matches(name) != self.exclude
+    }
+
+    /// Whether a record passes the filter, see `keep`
+    pub fn keep_record(&self, record: &SequenceRecord) -> bool {
+        self.keep(record.name())
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// 64-bit FNV-1a hash of the seed and the name, mixed with the finalizer of
+/// splitmix64 so all the bits are usable
+fn name_hash(name: &[u8], seed: u64) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for b in seed.to_le_bytes().iter().chain(name) {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+/// Keeps a random `fraction` of the records, deciding on a hash of their name
+/// (without the `/1` or `/2` suffix of older Illumina files) and of a seed
+/// rather than with a random number generator. The same records are kept
+/// every time for a given seed, and the mates of paired-end files
+/// subsampled separately stay in sync.
+///
+/// ```
+/// use needletail::filter::Subsampler;
+///
+/// let sampler = Subsampler::new(0.5).seed(42);
+/// assert_eq!(sampler.keep(b"read1/1"), sampler.keep(b"read1/2"));
+/// assert!(Subsampler::new(1.).keep(b"read1"));
+/// assert!(!Subsampler::new(0.).keep(b"read1"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Subsampler {
+    /// Between 0 (nothing kept) and 1 (everything kept)
+    pub fraction: f64,
+    pub seed: u64,
+}
+
+impl Subsampler {
+    pub fn new(fraction: f64) -> Self {
+        Subsampler { fraction, seed: 0 }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Whether the record with this name (the id up to the first space or
+    /// tab) is kept
+    pub fn keep(&self, name: &[u8]) -> bool {
+        // the top 53 bits as a float in [0, 1)
+        let x = (name_hash(mate_name(name), self.seed) >> 11) as f64 / (1u64 << 53) as f64;
+        x < self.fraction
+    }
+
+    /// Whether a record is kept, see `keep`
+    pub fn keep_record(&self, record: &SequenceRecord) -> bool {
+        self.keep(record.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn can_check_all_criteria() {
+        let filter = ReadFilter::new();
+        assert_eq!(filter.check(b"", None), Ok(()));
+        assert_eq!(filter.check(b"NNNN", Some(b"!!!!")), Ok(()));
+
+        let filter = ReadFilter::new().max_len(4).min_mean_quality(20.);
+        assert_eq!(filter.check(b"ACGTA", None), Err(FilterReason::TooLong));
+        assert_eq!(filter.check(b"ACGT", None), Ok(()));
+        assert_eq!(filter.check(b"ACGT", Some(b"II++")), Ok(()));
+        assert_eq!(
+            filter.check(b"ACGT", Some(b"I+++")),
+            Err(FilterReason::LowMeanQuality)
+        );
+        // criteria are checked in order
+        let filter = ReadFilter::new().min_len(3).max_n(1);
+        assert_eq!(filter.check(b"NN", None), Err(FilterReason::TooShort));
+    }
+
+    #[test]
+    fn can_count_rejections() {
+        let fastq = b"@a\nACGT\n+\nIIII\n@b\nAC\n+\nII\n@c\nANNT\n+\nIIII\n@d\nACGT\n+\n!!!!\n";
+        let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+        let filter = ReadFilter::new()
+            .min_len(3)
+            .max_n(1)
+            .max_expected_errors(2.);
+        let mut stats = FilterStats::new();
+        let mut kept = Vec::new();
+        while let Some(record) = reader.next() {
+            let record = record.unwrap();
+            if filter.filter_record(&record, &mut stats) {
+                kept.push(record.id().to_vec());
+            }
+        }
+        assert_eq!(kept, vec![b"a".to_vec()]);
+        assert_eq!(stats.passed, 1);
+        assert_eq!(stats.too_short, 1);
+        assert_eq!(stats.too_many_ns, 1);
+        assert_eq!(stats.too_many_expected_errors, 1);
+        assert_eq!((stats.rejected(), stats.total()), (3, 4));
+    }
+
+    #[test]
+    fn can_filter_on_ids() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"r1\r\n\n>r3 some read\n@r4\n").unwrap();
+        let filter = IdFilter::from_file(file.path()).unwrap();
+        let fasta = b">r1 a\nA\n>r2\nC\n>r3\nG\n>r4\nT\n>r10\nA\n";
+        let kept = |filter: &IdFilter| {
+            let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+            let mut kept = Vec::new();
+            while let Some(record) = reader.next() {
+                let record = record.unwrap();
+                if filter.keep_record(&record) {
+                    kept.push(String::from_utf8(record.name().to_vec()).unwrap());
+                }
+            }
+            kept
+        };
+        assert_eq!(kept(&filter), ["r1", "r3", "r4"]);
+        assert_eq!(kept(&filter.clone().exclude(true)), ["r2", "r10"]);
+        assert_eq!(kept(&filter.prefix(true)), ["r1", "r3", "r4", "r10"]);
+        assert!(IdFilter::from_file("tests/data/missing.txt").is_err());
+    }
+
+    #[test]
+    fn subsampling_keeps_mates_in_sync() {
+        let sampler = Subsampler::new(0.25).seed(7);
+        let mut kept = 0;
+        for i in 0..10_000 {
+            let r1 = format!("read{i}/1");
+            let r2 = format!("read{i}/2");
+            let keep = sampler.keep(r1.as_bytes());
+            assert_eq!(keep, sampler.keep(r2.as_bytes()));
+            kept += usize::from(keep);
+        }
+        assert!((2300..2700).contains(&kept), "kept {kept} reads");
+        // the hash is stable across versions
+        assert_eq!(name_hash(b"read1", 0), 0x9a0c7c76ef8bf63d);
+        let other_seed = (0..100)
+            .filter(|i| {
+                let name = format!("read{i}");
+                sampler.keep(name.as_bytes()) != sampler.seed(8).keep(name.as_bytes())
+            })
+            .count();
+        assert!(other_seed > 0);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn can_filter_on_regexes() {
+        let filter = IdFilter::from_regexes([r"^r\d$", "^x"]).unwrap();
+        assert!(filter.keep(b"r1"));
+        assert!(filter.keep(b"xyz"));
+        assert!(!filter.keep(b"r10"));
+        assert!(!filter.prefix(true).exclude(true).keep(b"r1"));
+        assert!(IdFilter::from_regexes(["("]).is_err());
+    }
+}
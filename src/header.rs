@@ -0,0 +1,354 @@
+//! Parsers for the metadata that sequencers put in read headers
+use std::ops::Range;
+use std::str;
+
+use crate::parser::split_header;
+
+fn parse_num<T: str::FromStr>(field: &[u8]) -> Option<T> {
+    str::from_utf8(field).ok()?.parse().ok()
+}
+
+/// The metadata in the header of an Illumina read, either in the CASAVA 1.8+
+/// format (`instrument:run:flowcell:lane:tile:x:y[:UMI] read:filtered:control:index`)
+/// or in the older one (`instrument:lane:tile:x:y[#index][/read]`).
+/// Fields missing from the older format are `None`.
+///
+/// ```
+/// use needletail::header::IlluminaHeader;
+///
+/// let header = IlluminaHeader::parse(b"A00123:8:H5KJ2DSXY:2:1101:15401:1000 1:N:0:ACGTACGT+TTGCAAGC").unwrap();
+/// assert_eq!(header.instrument, b"A00123");
+/// assert_eq!(header.lane, 2);
+/// assert_eq!((header.x, header.y), (15401, 1000));
+/// assert_eq!(header.read, Some(1));
+/// assert_eq!(header.is_filtered, Some(false));
+/// assert_eq!(header.index, Some(&b"ACGTACGT+TTGCAAGC"[..]));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IlluminaHeader<'a> {
+    pub instrument: &'a [u8],
+    pub run: Option<u32>,
+    pub flowcell: Option<&'a [u8]>,
+    pub lane: u8,
+    pub tile: u32,
+    pub x: u32,
+    pub y: u32,
+    /// The UMI that bcl2fastq and BCL Convert can append to the read name
+    pub umi: Option<&'a [u8]>,
+    /// The read number in the pair: 1 or 2
+    pub read: Option<u8>,
+    /// Whether the read failed the chastity filter (`Y`)
+    pub is_filtered: Option<bool>,
+    /// 0 when none of the control bits are on
+    pub control: Option<u16>,
+    /// The index sequence(s) or the sample number
+    pub index: Option<&'a [u8]>,
+}
+
+impl<'a> IlluminaHeader<'a> {
+    /// Parses the full header (`SequenceRecord::id`) of a read, returning `None`
+    /// if it isn't in one of the Illumina formats
+    pub fn parse(header: &'a [u8]) -> Option<Self> {
+        let (name, desc) = split_header(header);
+        let fields: Vec<&[u8]> = name.split(|b| *b == b':').collect();
+        if fields.len() == 7 || fields.len() == 8 {
+            Self::parse_casava(&fields, desc)
+        } else {
+            Self::parse_legacy(&fields)
+        }
+    }
+
+    fn parse_casava(fields: &[&'a [u8]], desc: Option<&'a [u8]>) -> Option<Self> {
+        let mut header = IlluminaHeader {
+            instrument: fields[0],
+            run: Some(parse_num(fields[1])?),
+            flowcell: Some(fields[2]),
+            lane: parse_num(fields[3])?,
+            tile: parse_num(fields[4])?,
+            x: parse_num(fields[5])?,
+            y: parse_num(fields[6])?,
+            umi: fields.get(7).copied(),
+            read: None,
+            is_filtered: None,
+            control: None,
+            index: None,
+        };
+        // the comment can be followed by other fields added by other tools
+        if let Some(comment) = desc.map(|d| split_header(d).0) {
+            let fields: Vec<&[u8]> = comment.splitn(4, |b| *b == b':').collect();
+            if fields.len() < 3 {
+                return None;
+            }
+            header.read = Some(parse_num(fields[0])?);
+            header.is_filtered = match fields[1] {
+                b"Y" => Some(true),
+                b"N" => Some(false),
+                _ => return None,
+            };
+            header.control = Some(parse_num(fields[2])?);
+            header.index = fields.get(3).copied();
+        }
+        Some(header)
+    }
+
+    fn parse_legacy(fields: &[&'a [u8]]) -> Option<Self> {
+        let [instrument, lane, tile, x, rest] = fields else {
+            return None;
+        };
+        let (rest, read) = match rest.iter().rposition(|b| *b == b'/') {
+            Some(pos) => (&rest[..pos], Some(parse_num(&rest[pos + 1..])?)),
+            None => (*rest, None),
+        };
+        let (y, index) = match rest.iter().position(|b| *b == b'#') {
+            Some(pos) => (&rest[..pos], Some(&rest[pos + 1..])),
+            None => (rest, None),
+        };
+        Some(IlluminaHeader {
+            instrument,
+            run: None,
+            flowcell: None,
+            lane: parse_num(lane)?,
+            tile: parse_num(tile)?,
+            x: parse_num(x)?,
+            y: parse_num(y)?,
+            umi: None,
+            read,
+            is_filtered: None,
+            control: None,
+            index,
+        })
+    }
+}
+
+/// The metadata in the header of an Oxford Nanopore read written by the
+/// basecallers: a read id followed by `key=value` pairs.
+///
+/// ```
+/// use needletail::header::NanoporeHeader;
+///
+/// let header = NanoporeHeader::parse(
+///     b"6c9a1b7e-4a3f-4c3e-9a5c-2b8d1f0e7a61 runid=0a1b2c read=12 ch=245 start_time=2021-03-04T10:20:30Z barcode=barcode01",
+/// )
+/// .unwrap();
+/// assert_eq!(header.read_id, b"6c9a1b7e-4a3f-4c3e-9a5c-2b8d1f0e7a61");
+/// assert_eq!(header.run_id(), Some(&b"0a1b2c"[..]));
+/// assert_eq!(header.channel(), Some(245));
+/// assert_eq!(header.get(b"barcode"), Some(&b"barcode01"[..]));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NanoporeHeader<'a> {
+    pub read_id: &'a [u8],
+    desc: &'a [u8],
+}
+
+impl<'a> NanoporeHeader<'a> {
+    /// Parses the full header (`SequenceRecord::id`) of a read, returning `None`
+    /// if it has no `key=value` pairs
+    pub fn parse(header: &'a [u8]) -> Option<Self> {
+        let (read_id, desc) = split_header(header);
+        let header = NanoporeHeader {
+            read_id,
+            desc: desc?,
+        };
+        header.pairs().next()?;
+        Some(header)
+    }
+
+    /// Iterates over the `key=value` pairs of the header, skipping the words
+    /// without a `=`
+    pub fn pairs(&self) -> impl Iterator<Item = (&'a [u8], &'a [u8])> {
+        self.desc
+            .split(|b| *b == b' ' || *b == b'\t')
+            .filter_map(|pair| {
+                let pos = pair.iter().position(|b| *b == b'=')?;
+                Some((&pair[..pos], &pair[pos + 1..]))
+            })
+    }
+
+    /// The value of the first pair with this key
+    pub fn get(&self, key: &[u8]) -> Option<&'a [u8]> {
+        self.pairs().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// The `runid`
+    pub fn run_id(&self) -> Option<&'a [u8]> {
+        self.get(b"runid")
+    }
+
+    /// The `sampleid`
+    pub fn sample_id(&self) -> Option<&'a [u8]> {
+        self.get(b"sampleid")
+    }
+
+    /// The `flow_cell_id`
+    pub fn flow_cell_id(&self) -> Option<&'a [u8]> {
+        self.get(b"flow_cell_id")
+    }
+
+    /// The read number (`read`) in the channel
+    pub fn read_number(&self) -> Option<u64> {
+        parse_num(self.get(b"read")?)
+    }
+
+    /// The channel (`ch`) of the pore
+    pub fn channel(&self) -> Option<u32> {
+        parse_num(self.get(b"ch")?)
+    }
+
+    /// The `start_time`, as an ISO 8601 date
+    pub fn start_time(&self) -> Option<&'a [u8]> {
+        self.get(b"start_time")
+    }
+}
+
+/// The metadata in the name of a PacBio read: `movie/zmw/ccs` for CCS (HiFi)
+/// reads, optionally followed by `/fwd` or `/rev` for by-strand CCS, and
+/// `movie/zmw/start_end` for subreads.
+///
+/// ```
+/// use needletail::header::PacBioHeader;
+///
+/// let header = PacBioHeader::parse(b"m64011_190830_220126/1234/ccs").unwrap();
+/// assert_eq!(header.movie, b"m64011_190830_220126");
+/// assert_eq!(header.zmw, 1234);
+/// assert!(header.is_ccs());
+/// let header = PacBioHeader::parse(b"m54006_160504_020705/4194374/0_1000").unwrap();
+/// assert_eq!(header.subread, Some(0..1000));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PacBioHeader<'a> {
+    pub movie: &'a [u8],
+    /// The Zero Mode Waveguide (well) the read comes from
+    pub zmw: u64,
+    /// The bases of the polymerase read that the subread covers, `None` for CCS reads
+    pub subread: Option<Range<usize>>,
+    /// `fwd` or `rev` for by-strand CCS reads
+    pub strand: Option<&'a [u8]>,
+}
+
+impl<'a> PacBioHeader<'a> {
+    /// Parses the full header (`SequenceRecord::id`) of a read, returning `None`
+    /// if it isn't in the PacBio format
+    pub fn parse(header: &'a [u8]) -> Option<Self> {
+        let fields: Vec<&[u8]> = split_header(header).0.split(|b| *b == b'/').collect();
+        let (movie, zmw, kind, strand) = match fields[..] {
+            [movie, zmw, kind] => (movie, zmw, kind, None),
+            [movie, zmw, b"ccs", strand @ (b"fwd" | b"rev")] => {
+                (movie, zmw, &b"ccs"[..], Some(strand))
+            }
+            _ => return None,
+        };
+        if !movie.starts_with(b"m") {
+            return None;
+        }
+        let subread = if kind == b"ccs" {
+            None
+        } else {
+            let pos = kind.iter().position(|b| *b == b'_')?;
+            Some(parse_num(&kind[..pos])?..parse_num(&kind[pos + 1..])?)
+        };
+        Some(PacBioHeader {
+            movie,
+            zmw: parse_num(zmw)?,
+            subread,
+            strand,
+        })
+    }
+
+    /// Whether this is a CCS read rather than a subread
+    pub fn is_ccs(&self) -> bool {
+        self.subread.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_casava_headers() {
+        let header = IlluminaHeader::parse(
+            b"M01234:55:000000000-A1B2C:1:2104:9876:54321:ACGTTGCA 2:Y:18:7 extra",
+        )
+        .unwrap();
+        assert_eq!(header.run, Some(55));
+        assert_eq!(header.flowcell, Some(&b"000000000-A1B2C"[..]));
+        assert_eq!(header.tile, 2104);
+        assert_eq!(header.umi, Some(&b"ACGTTGCA"[..]));
+        assert_eq!(header.read, Some(2));
+        assert_eq!(header.is_filtered, Some(true));
+        assert_eq!(header.control, Some(18));
+        assert_eq!(header.index, Some(&b"7"[..]));
+
+        // without comment
+        let header = IlluminaHeader::parse(b"M01234:55:FC:1:2104:9876:54321").unwrap();
+        assert_eq!(header.read, None);
+        assert_eq!(header.umi, None);
+
+        assert_eq!(IlluminaHeader::parse(b"M01234:55:FC:1:2104:x:54321"), None);
+        assert_eq!(
+            IlluminaHeader::parse(b"M01234:55:FC:1:2104:9876:54321 1:X:0:1"),
+            None
+        );
+        assert_eq!(IlluminaHeader::parse(b"SRR001666.1"), None);
+    }
+
+    #[test]
+    fn can_parse_legacy_headers() {
+        let header = IlluminaHeader::parse(b"HWUSI-EAS100R:6:73:941:1973#0/1").unwrap();
+        assert_eq!(
+            header,
+            IlluminaHeader {
+                instrument: b"HWUSI-EAS100R",
+                run: None,
+                flowcell: None,
+                lane: 6,
+                tile: 73,
+                x: 941,
+                y: 1973,
+                umi: None,
+                read: Some(1),
+                is_filtered: None,
+                control: None,
+                index: Some(b"0"),
+            }
+        );
+        let header = IlluminaHeader::parse(b"HWUSI-EAS100R:6:73:941:1973").unwrap();
+        assert_eq!((header.read, header.index), (None, None));
+        assert_eq!(
+            IlluminaHeader::parse(b"HWUSI-EAS100R:6:73:941:1973/x"),
+            None
+        );
+    }
+
+    #[test]
+    fn can_parse_nanopore_headers() {
+        let header =
+            NanoporeHeader::parse(b"abcd runid=r1 sampleid=s1 read=7 ch=12 flow_cell_id=FAQ1 note")
+                .unwrap();
+        assert_eq!(header.sample_id(), Some(&b"s1"[..]));
+        assert_eq!(header.flow_cell_id(), Some(&b"FAQ1"[..]));
+        assert_eq!(header.read_number(), Some(7));
+        assert_eq!(header.start_time(), None);
+        assert_eq!(header.pairs().count(), 5);
+
+        assert_eq!(NanoporeHeader::parse(b"abcd"), None);
+        assert_eq!(NanoporeHeader::parse(b"abcd some description"), None);
+        let header = NanoporeHeader::parse(b"abcd ch=x").unwrap();
+        assert_eq!(header.channel(), None);
+    }
+
+    #[test]
+    fn can_parse_pacbio_headers() {
+        let header =
+            PacBioHeader::parse(b"m84011_220902_175841_s1/25233629/ccs/rev np=12").unwrap();
+        assert_eq!(header.zmw, 25233629);
+        assert!(header.is_ccs());
+        assert_eq!(header.strand, Some(&b"rev"[..]));
+
+        assert_eq!(PacBioHeader::parse(b"m54006/1/ccs/both"), None);
+        assert_eq!(PacBioHeader::parse(b"m54006/x/ccs"), None);
+        assert_eq!(PacBioHeader::parse(b"m54006/1/10-20"), None);
+        assert_eq!(PacBioHeader::parse(b"read/1/ccs"), None);
+    }
+}
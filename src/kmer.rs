@@ -1,5 +1,12 @@
 //! Functions for splitting sequences into fixed-width moving windows (kmers)
 //! and utilities for dealing with these kmers.
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::bitkmer::{CanonicalBitKmer, KmerBits};
+use crate::quality::{QualityScores, PHRED_OFFSET};
+use crate::sequence::complement;
 
 /// Returns true if the base is a unambiguous nucleic acid base (e.g. ACGT) and
 /// false otherwise.
@@ -9,10 +16,13 @@ fn is_good_base(chr: u8) -> bool {
 
 /// Generic moving window iterator over sequences to return k-mers
 ///
-/// Iterator returns slices to the original data.
+/// Iterator returns slices to the original data. It can also be iterated from
+/// the end of the sequence with `.rev()`/`.next_back()`.
 pub struct Kmers<'a> {
     k: u8,
     start_pos: usize,
+    /// start of the kmer after the last one the iterator will return
+    end_pos: usize,
     buffer: &'a [u8],
 }
 
@@ -22,6 +32,7 @@ impl<'a> Kmers<'a> {
         Kmers {
             k,
             start_pos: 0,
+            end_pos: (buffer.len() + 1).saturating_sub(k as usize),
             buffer,
         }
     }
@@ -31,13 +42,111 @@ impl<'a> Iterator for Kmers<'a> {
     type Item = &'a [u8];
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.start_pos + self.k as usize > self.buffer.len() {
+        if self.start_pos >= self.end_pos {
             return None;
         }
         let pos = self.start_pos;
         self.start_pos += 1;
         Some(&self.buffer[pos..pos + self.k as usize])
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end_pos.saturating_sub(self.start_pos);
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for Kmers<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.start_pos >= self.end_pos {
+            return None;
+        }
+        self.end_pos -= 1;
+        let pos = self.end_pos;
+        Some(&self.buffer[pos..pos + self.k as usize])
+    }
+}
+
+impl ExactSizeIterator for Kmers<'_> {}
+
+/// Turns a spaced seed mask like `b"110101"` into the pattern used by
+/// `SpacedKmers`: `1` marks a position that's kept (a "care" position) and
+/// anything else one that's ignored.
+pub fn spaced_seed(mask: &[u8]) -> Vec<bool> {
+    mask.iter().map(|c| *c == b'1').collect()
+}
+
+/// The spaced seed pattern of a gapped kmer: a word of `word_len` bases, `gap_len`
+/// ignored bases and another word of `word_len` bases, as used for gkm-SVM
+/// style features. Being symmetric, it can also be used for canonical kmers.
+///
+/// ```
+/// use needletail::kmer::{gapped_seed, spaced_seed};
+///
+/// assert_eq!(gapped_seed(2, 3), spaced_seed(b"1100011"));
+/// ```
+pub fn gapped_seed(word_len: usize, gap_len: usize) -> Vec<bool> {
+    let mut pattern = vec![true; 2 * word_len + gap_len];
+    pattern[word_len..word_len + gap_len].fill(false);
+    pattern
+}
+
+/// Moving window iterator returning only the "care" positions of each window,
+/// as defined by a spaced seed pattern.
+///
+/// Since the kept bases aren't contiguous, every kmer is copied into a new
+/// `Vec`. Like `Kmers`, it does not skip any base.
+pub struct SpacedKmers<'a> {
+    care: Vec<usize>,
+    span: usize,
+    start_pos: usize,
+    buffer: &'a [u8],
+}
+
+impl<'a> SpacedKmers<'a> {
+    /// Creates a new iterator; the pattern needs to start and end with a care
+    /// position.
+    ///
+    /// ```
+    /// use needletail::kmer::{spaced_seed, SpacedKmers};
+    ///
+    /// let mut kmers = SpacedKmers::new(b"ACGTA", &spaced_seed(b"101"));
+    /// assert_eq!(kmers.next(), Some(b"AG".to_vec()));
+    /// assert_eq!(kmers.next(), Some(b"CT".to_vec()));
+    /// ```
+    pub fn new(buffer: &'a [u8], pattern: &[bool]) -> Self {
+        assert!(
+            pattern.first() == Some(&true) && pattern.last() == Some(&true),
+            "Spaced seeds need to start and end with a care position"
+        );
+        SpacedKmers {
+            care: care_positions(pattern),
+            span: pattern.len(),
+            start_pos: 0,
+            buffer,
+        }
+    }
+}
+
+pub(crate) fn care_positions(pattern: &[bool]) -> Vec<usize> {
+    pattern
+        .iter()
+        .enumerate()
+        .filter_map(|(i, care)| if *care { Some(i) } else { None })
+        .collect()
+}
+
+impl Iterator for SpacedKmers<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start_pos + self.span > self.buffer.len() {
+            return None;
+        }
+        let window = &self.buffer[self.start_pos..self.start_pos + self.span];
+        self.start_pos += 1;
+        Some(self.care.iter().map(|i| window[*i]).collect())
+    }
 }
 
 /// A kmer-izer for a nucleotide acid sequences to return canonical kmers.
@@ -129,6 +238,304 @@ impl<'a> Iterator for CanonicalKmers<'a> {
     }
 }
 
+/// Moving window iterator returning only the kmers made of unambiguous bases
+/// (ACGT, in any case) along with their positions; the byte equivalent of
+/// `bitkmer::BitNuclKmer`.
+pub struct ValidKmers<'a> {
+    inner: CanonicalKmers<'a>,
+}
+
+impl<'a> ValidKmers<'a> {
+    /// ```
+    /// use needletail::kmer::ValidKmers;
+    ///
+    /// let kmers: Vec<_> = ValidKmers::new(b"ACNGTA", 2).collect();
+    /// assert_eq!(kmers, vec![(0, &b"AC"[..]), (3, b"GT"), (4, b"TA")]);
+    /// ```
+    pub fn new(buffer: &'a [u8], k: u8) -> Self {
+        ValidKmers {
+            inner: CanonicalKmers::new(buffer, &[], k),
+        }
+    }
+}
+
+impl<'a> Iterator for ValidKmers<'a> {
+    type Item = (usize, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.inner.update_position(false) {
+            return None;
+        }
+        let pos = self.inner.start_pos;
+        self.inner.start_pos += 1;
+        Some((pos, &self.inner.buffer[pos..pos + self.inner.k as usize]))
+    }
+}
+
+/// Same as `ValidKmers` on the sequence masked by `QualitySequence::quality_mask`,
+/// without allocating the masked copy: kmers containing a base with a
+/// quality below `score` (or a non-ACGT base) are skipped.
+pub struct QualityKmers<'a> {
+    k: u8,
+    score: u8,
+    start_pos: usize,
+    /// the bases in `start_pos..checked_end` are known to be good
+    checked_end: usize,
+    buffer: &'a [u8],
+    qual: &'a [u8],
+}
+
+impl<'a> QualityKmers<'a> {
+    /// ```
+    /// use needletail::kmer::QualityKmers;
+    ///
+    /// let kmers: Vec<_> = QualityKmers::new(b"ACGTAC", b"IIII#I", 2, b'5').collect();
+    /// assert_eq!(kmers, vec![(0, &b"AC"[..]), (1, b"CG"), (2, b"GT")]);
+    /// ```
+    pub fn new(buffer: &'a [u8], qual: &'a [u8], k: u8, score: u8) -> Self {
+        assert_eq!(
+            buffer.len(),
+            qual.len(),
+            "The sequence and the quality need to have the same length"
+        );
+        QualityKmers {
+            k,
+            score,
+            start_pos: 0,
+            checked_end: 0,
+            buffer,
+            qual,
+        }
+    }
+}
+
+impl<'a> Iterator for QualityKmers<'a> {
+    type Item = (usize, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let k = self.k as usize;
+        while self.checked_end < self.start_pos + k {
+            if self.checked_end >= self.buffer.len() {
+                return None;
+            }
+            let i = self.checked_end;
+            if self.qual[i] < self.score || !is_good_base(self.buffer[i]) {
+                self.start_pos = i + 1;
+            }
+            self.checked_end += 1;
+        }
+        let pos = self.start_pos;
+        self.start_pos += 1;
+        Some((pos, &self.buffer[pos..pos + k]))
+    }
+}
+
+/// Same as `CanonicalKmers` but without needing the reverse complement of the
+/// whole sequence: each kmer is compared to its reverse complement on the fly
+/// and the complemented kmer is only allocated when it's the canonical one.
+pub struct LazyCanonicalKmers<'a> {
+    inner: CanonicalKmers<'a>,
+}
+
+impl<'a> LazyCanonicalKmers<'a> {
+    /// ```
+    /// use needletail::kmer::LazyCanonicalKmers;
+    ///
+    /// let mut c_iter = LazyCanonicalKmers::new(b"ACGT", 3);
+    /// assert_eq!(c_iter.next().unwrap().1.as_ref(), b"ACG");
+    /// assert_eq!(c_iter.next().unwrap().1.as_ref(), b"ACG");
+    /// ```
+    pub fn new(buffer: &'a [u8], k: u8) -> Self {
+        LazyCanonicalKmers {
+            inner: CanonicalKmers::new(buffer, &[], k),
+        }
+    }
+}
+
+impl<'a> Iterator for LazyCanonicalKmers<'a> {
+    type Item = (usize, Cow<'a, [u8]>, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.inner.update_position(false) {
+            return None;
+        }
+        let pos = self.inner.start_pos;
+        self.inner.start_pos += 1;
+
+        let kmer = &self.inner.buffer[pos..pos + self.inner.k as usize];
+        let rc = kmer.iter().rev().map(|n| complement(*n));
+        match kmer.iter().copied().cmp(rc) {
+            Ordering::Less => Some((pos, kmer.into(), false)),
+            // same as `CanonicalKmers`, palindromes are flagged as complemented
+            Ordering::Equal => Some((pos, kmer.into(), true)),
+            Ordering::Greater => {
+                let rc = kmer
+                    .iter()
+                    .rev()
+                    .map(|n| complement(*n))
+                    .collect::<Vec<u8>>();
+                Some((pos, rc.into(), true))
+            }
+        }
+    }
+}
+
+/// Kmers yielded by the kmer iterators, which know where they are in the
+/// sequence
+pub trait KmerSpan {
+    /// Position of the kmer in the sequence
+    fn span(&self) -> Range<usize>;
+}
+
+impl KmerSpan for (usize, &[u8]) {
+    fn span(&self) -> Range<usize> {
+        self.0..self.0 + self.1.len()
+    }
+}
+
+impl KmerSpan for (usize, &[u8], bool) {
+    fn span(&self) -> Range<usize> {
+        self.0..self.0 + self.1.len()
+    }
+}
+
+impl KmerSpan for (usize, Cow<'_, [u8]>, bool) {
+    fn span(&self) -> Range<usize> {
+        self.0..self.0 + self.1.len()
+    }
+}
+
+impl<T: KmerBits> KmerSpan for (usize, (T, u8), bool) {
+    fn span(&self) -> Range<usize> {
+        self.0..self.0 + (self.1).1 as usize
+    }
+}
+
+impl KmerSpan for CanonicalBitKmer {
+    fn span(&self) -> Range<usize> {
+        self.pos..self.end()
+    }
+}
+
+/// Which kmers `QualityFilteredKmers` keeps, given a Phred score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KmerQuality {
+    /// Kmers whose bases all have at least this quality
+    Min(u8),
+    /// Kmers whose mean base quality is at least this
+    Mean(u8),
+}
+
+/// Skips the kmers of a kmer iterator that don't have a good enough quality,
+/// see `KmerQualityExt::quality_filtered`. Kmers going past the end of the
+/// qualities are skipped too.
+pub struct QualityFilteredKmers<I> {
+    inner: I,
+    /// Number of bases below the threshold (`Min`) or sum of the scores
+    /// (`Mean`) of the bases before each position
+    prefix: Vec<u32>,
+    threshold: KmerQuality,
+}
+
+impl<I> QualityFilteredKmers<I> {
+    /// `qual` is the Phred+33 quality of the sequence the kmers come from
+    pub fn new(inner: I, qual: &[u8], threshold: KmerQuality) -> Self {
+        Self::with_offset(inner, qual, PHRED_OFFSET, threshold)
+    }
+
+    /// For Phred+64 qualities for example, see `QualitySequence::quality_offset`
+    pub fn with_offset(inner: I, qual: &[u8], offset: u8, threshold: KmerQuality) -> Self {
+        let mut prefix = Vec::with_capacity(qual.len() + 1);
+        let mut total = 0u32;
+        prefix.push(total);
+        for score in QualityScores::with_offset(qual, offset) {
+            total += match threshold {
+                KmerQuality::Min(min) => u32::from(score < min),
+                KmerQuality::Mean(_) => u32::from(score),
+            };
+            prefix.push(total);
+        }
+        QualityFilteredKmers {
+            inner,
+            prefix,
+            threshold,
+        }
+    }
+
+    fn is_good(&self, span: Range<usize>) -> bool {
+        let len = span.len() as u32;
+        let Some(end) = self.prefix.get(span.end) else {
+            return false;
+        };
+        let total = end - self.prefix[span.start];
+        match self.threshold {
+            KmerQuality::Min(_) => total == 0,
+            KmerQuality::Mean(mean) => total >= u32::from(mean) * len,
+        }
+    }
+}
+
+impl<I> Iterator for QualityFilteredKmers<I>
+where
+    I: Iterator,
+    I::Item: KmerSpan,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let kmer = self.inner.next()?;
+            if self.is_good(kmer.span()) {
+                return Some(kmer);
+            }
+        }
+    }
+}
+
+/// Adds quality filtering to the kmer iterators.
+///
+/// ```
+/// use needletail::bitkmer::BitNuclKmer;
+/// use needletail::kmer::{KmerQuality, KmerQualityExt};
+///
+/// let (seq, qual) = (b"ACGTAC", b"III#II");
+/// let kmers: Vec<_> = BitNuclKmer::new(seq, 3, false)
+///     .quality_filtered(qual, KmerQuality::Min(20))
+///     .map(|(pos, _, _)| pos)
+///     .collect();
+/// assert_eq!(kmers, vec![0]);
+/// let kmers = BitNuclKmer::new(seq, 3, false).quality_filtered(qual, KmerQuality::Mean(20));
+/// assert_eq!(kmers.count(), 4);
+/// ```
+pub trait KmerQualityExt: Iterator + Sized
+where
+    Self::Item: KmerSpan,
+{
+    /// Only keeps the kmers meeting `threshold` given the Phred+33 quality of
+    /// the sequence they come from
+    fn quality_filtered(self, qual: &[u8], threshold: KmerQuality) -> QualityFilteredKmers<Self> {
+        QualityFilteredKmers::new(self, qual, threshold)
+    }
+
+    /// Same as `quality_filtered` for qualities encoded with another offset
+    /// than 33
+    fn quality_filtered_with_offset(
+        self,
+        qual: &[u8],
+        offset: u8,
+        threshold: KmerQuality,
+    ) -> QualityFilteredKmers<Self> {
+        QualityFilteredKmers::with_offset(self, qual, offset, threshold)
+    }
+}
+
+impl<I> KmerQualityExt for I
+where
+    I: Iterator,
+    I::Item: KmerSpan,
+{
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -167,6 +574,69 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_kmerize_backwards() {
+        let kmers: Vec<&[u8]> = Kmers::new(b"AGNCT", 2).rev().collect();
+        assert_eq!(kmers, vec![&b"CT"[..], b"NC", b"GN", b"AG"]);
+
+        let mut k_iter = Kmers::new(b"AGNCT", 2);
+        assert_eq!(k_iter.len(), 4);
+        assert_eq!(k_iter.next(), Some(&b"AG"[..]));
+        assert_eq!(k_iter.next_back(), Some(&b"CT"[..]));
+        assert_eq!(k_iter.len(), 2);
+        assert_eq!(k_iter.next_back(), Some(&b"NC"[..]));
+        assert_eq!(k_iter.next(), Some(&b"GN"[..]));
+        assert_eq!(k_iter.next(), None);
+        assert_eq!(k_iter.next_back(), None);
+
+        assert_eq!(Kmers::new(b"A", 2).len(), 0);
+    }
+
+    #[test]
+    fn can_kmerize_spaced_seeds() {
+        let pattern = spaced_seed(b"1101");
+        assert_eq!(pattern, vec![true, true, false, true]);
+        let kmers: Vec<Vec<u8>> = SpacedKmers::new(b"ACGTAC", &pattern).collect();
+        assert_eq!(
+            kmers,
+            vec![b"ACT".to_vec(), b"CGA".to_vec(), b"GTC".to_vec()]
+        );
+
+        assert_eq!(SpacedKmers::new(b"ACG", &pattern).next(), None);
+
+        let pattern = gapped_seed(2, 1);
+        assert_eq!(pattern, vec![true, true, false, true, true]);
+        let kmers: Vec<Vec<u8>> = SpacedKmers::new(b"ACGTAC", &pattern).collect();
+        assert_eq!(kmers, vec![b"ACTA".to_vec(), b"CGAC".to_vec()]);
+    }
+
+    #[test]
+    fn can_skip_invalid_kmers() {
+        let kmers: Vec<_> = ValidKmers::new(b"NACGTNNAcgTN", 3).collect();
+        assert_eq!(
+            kmers,
+            vec![(1, &b"ACG"[..]), (2, b"CGT"), (7, b"Acg"), (8, b"cgT")]
+        );
+        assert_eq!(ValidKmers::new(b"ACNGT", 3).next(), None);
+        assert_eq!(ValidKmers::new(b"AC", 3).next(), None);
+    }
+
+    #[test]
+    fn lazy_canonical_kmers_match_canonical_kmers() {
+        for seq in [&b"AGCTA"[..], b"AGNTA", b"ACGTTGCANNACGT", b"TTTT"] {
+            let rc_seq = seq.reverse_complement();
+            for k in 1..4 {
+                let expected: Vec<_> = CanonicalKmers::new(seq, &rc_seq, k)
+                    .map(|(pos, kmer, is_c)| (pos, kmer.to_vec(), is_c))
+                    .collect();
+                let found: Vec<_> = LazyCanonicalKmers::new(seq, k)
+                    .map(|(pos, kmer, is_c)| (pos, kmer.into_owned(), is_c))
+                    .collect();
+                assert_eq!(expected, found);
+            }
+        }
+    }
+
     #[test]
     fn can_canonicalize() {
         // test general function
@@ -224,4 +694,41 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn can_filter_kmers_on_quality() {
+        use crate::bitkmer::CanonicalBitKmers;
+
+        let (seq, qual) = (&b"ACGTNACGTA"[..], &b"I5I+II#III"[..]);
+        let min: Vec<_> = ValidKmers::new(seq, 2)
+            .quality_filtered(qual, KmerQuality::Min(20))
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(min, vec![0, 1, 7, 8]);
+        // same as QualityKmers
+        let expected: Vec<_> = QualityKmers::new(seq, qual, 2, b'5').collect();
+        let kmers: Vec<_> = ValidKmers::new(seq, 2)
+            .quality_filtered(qual, KmerQuality::Min(20))
+            .collect();
+        assert_eq!(kmers, expected);
+        let mean: Vec<_> = CanonicalBitKmers::new(seq, 2)
+            .quality_filtered(qual, KmerQuality::Mean(25))
+            .map(|k| k.pos)
+            .collect();
+        assert_eq!(mean, vec![0, 1, 2, 7, 8]);
+
+        // Phred+64
+        let phred64: Vec<_> = ValidKmers::new(seq, 2)
+            .quality_filtered_with_offset(b"hThJhhBhhh", 64, KmerQuality::Min(20))
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(phred64, min);
+
+        // the kmers past the end of the qualities are skipped
+        let short: Vec<_> = ValidKmers::new(b"ACGTACGT", 3)
+            .quality_filtered(b"III", KmerQuality::Min(20))
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(short, vec![0]);
+    }
 }
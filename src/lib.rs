@@ -43,12 +43,37 @@
 extern crate pyo3;
 
 pub mod bitkmer;
+pub mod codon_usage;
+pub mod complexity;
+pub mod count;
+pub mod dbg;
+pub mod distance;
+pub mod encoding;
+pub mod filter;
+pub mod header;
 pub mod kmer;
+pub mod melting;
+pub mod merge;
+pub mod minimizer;
+pub mod motif;
+pub mod nthash;
+pub mod orf;
 pub mod parser;
+pub mod physchem;
+pub mod pipeline;
+pub mod quality;
 pub mod sequence;
+mod simd;
+pub mod sketch;
+pub mod stats;
+pub mod strobemer;
+pub mod translate;
+pub mod trim;
 
 pub mod errors;
 
+#[cfg(feature = "parallel")]
+pub mod parallel;
 #[cfg(any(feature = "python", feature = "python_test"))]
 pub mod python;
 
@@ -0,0 +1,176 @@
+//! Melting temperature of oligonucleotides with the nearest-neighbor
+//! thermodynamics of SantaLucia (1998)
+use crate::errors::SequenceError;
+use crate::Sequence;
+
+/// Gas constant in cal/(K.mol)
+const R: f64 = 1.987;
+
+/// (ΔH in kcal/mol, ΔS in cal/(K.mol)) of every dinucleotide, indexed by the
+/// 2-bit codes of its bases (A = 0, C = 1, G = 2, T = 3), from SantaLucia's
+/// unified parameters
+const NEAREST_NEIGHBORS: [(f64, f64); 16] = [
+    (-7.9, -22.2),  // AA
+    (-8.4, -22.4),  // AC
+    (-7.8, -21.0),  // AG
+    (-7.2, -20.4),  // AT
+    (-8.5, -22.7),  // CA
+    (-8.0, -19.9),  // CC
+    (-10.6, -27.2), // CG
+    (-7.8, -21.0),  // CT
+    (-8.2, -22.2),  // GA
+    (-9.8, -24.4),  // GC
+    (-8.0, -19.9),  // GG
+    (-8.4, -22.4),  // GT
+    (-7.2, -21.3),  // TA
+    (-8.2, -22.2),  // TC
+    (-8.5, -22.7),  // TG
+    (-7.9, -22.2),  // TT
+];
+
+/// Initiation with a terminal G.C pair, counted for each end
+const INIT_GC: (f64, f64) = (0.1, -2.8);
+/// Initiation with a terminal A.T pair
+const INIT_AT: (f64, f64) = (2.3, 4.1);
+/// Entropy penalty of self-complementary sequences
+const SYMMETRY_ENTROPY: f64 = -1.4;
+
+/// The experimental conditions of `melting_temperature`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TmParams {
+    /// Monovalent cation (Na+, K+) concentration in mM, 50 by default
+    pub monovalent_mm: f64,
+    /// Mg2+ concentration in mM, 0 by default
+    pub mg_mm: f64,
+    /// dNTP concentration in mM, which bind Mg2+, 0 by default
+    pub dntp_mm: f64,
+    /// Oligonucleotide concentration in nM, 250 by default
+    pub oligo_nm: f64,
+}
+
+impl Default for TmParams {
+    fn default() -> Self {
+        TmParams {
+            monovalent_mm: 50.,
+            mg_mm: 0.,
+            dntp_mm: 0.,
+            oligo_nm: 250.,
+        }
+    }
+}
+
+impl TmParams {
+    pub fn monovalent_mm(mut self, monovalent_mm: f64) -> Self {
+        self.monovalent_mm = monovalent_mm;
+        self
+    }
+
+    pub fn mg_mm(mut self, mg_mm: f64) -> Self {
+        self.mg_mm = mg_mm;
+        self
+    }
+
+    pub fn dntp_mm(mut self, dntp_mm: f64) -> Self {
+        self.dntp_mm = dntp_mm;
+        self
+    }
+
+    pub fn oligo_nm(mut self, oligo_nm: f64) -> Self {
+        self.oligo_nm = oligo_nm;
+        self
+    }
+
+    /// Sodium-equivalent concentration in M, counting free Mg2+ as
+    /// `120 * sqrt([Mg2+] - [dNTP])` (von Ahsen et al., 2001)
+    fn sodium_equivalent(&self) -> f64 {
+        let free_mg = (self.mg_mm - self.dntp_mm).max(0.);
+        (self.monovalent_mm + 120. * free_mg.sqrt()) / 1000.
+    }
+}
+
+#[inline]
+fn base_code(position: usize, base: u8) -> Result<usize, SequenceError> {
+    match base.to_ascii_uppercase() {
+        b'A' => Ok(0),
+        b'C' => Ok(1),
+        b'G' => Ok(2),
+        b'T' => Ok(3),
+        _ => Err(SequenceError::InvalidBase { position, base }),
+    }
+}
+
+/// Melting temperature in °C of a DNA oligonucleotide (ACGT only) with its
+/// perfect complement, using the nearest-neighbor model of SantaLucia (1998)
+/// and its salt correction of the entropy.
+///
+/// ```
+/// use needletail::melting::{melting_temperature, TmParams};
+///
+/// let tm = melting_temperature(b"AGCGGATAACAATTTCACACAGGA", &TmParams::default()).unwrap();
+/// assert!((tm - 56.7).abs() < 0.1);
+/// ```
+pub fn melting_temperature(seq: &[u8], params: &TmParams) -> Result<f64, SequenceError> {
+    if seq.len() < 2 {
+        return Err(SequenceError::TooShort {
+            length: seq.len(),
+            min: 2,
+        });
+    }
+    let codes = seq
+        .iter()
+        .enumerate()
+        .map(|(i, b)| base_code(i, *b))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (mut dh, mut ds) = (0., 0.);
+    for pair in codes.windows(2) {
+        let (h, s) = NEAREST_NEIGHBORS[pair[0] * 4 + pair[1]];
+        dh += h;
+        ds += s;
+    }
+    for end in [codes[0], codes[codes.len() - 1]] {
+        // C and G are 1 and 2
+        let (h, s) = if end == 1 || end == 2 {
+            INIT_GC
+        } else {
+            INIT_AT
+        };
+        dh += h;
+        ds += s;
+    }
+
+    let self_complementary = seq.eq_ignore_ascii_case(&seq.reverse_complement());
+    let mut ct = params.oligo_nm * 1e-9;
+    if self_complementary {
+        ds += SYMMETRY_ENTROPY;
+    } else {
+        // both strands at the same concentration
+        ct /= 4.;
+    }
+    ds += 0.368 * (seq.len() - 1) as f64 * params.sodium_equivalent().ln();
+
+    Ok(dh * 1000. / (ds + R * ct.ln()) - 273.15)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tm_follows_composition_and_salt() {
+        let params = TmParams::default();
+        let at = melting_temperature(b"ATATTATAATTATATATAAT", &params).unwrap();
+        let gc = melting_temperature(b"GCGCCGCGGCGGCCGCGCGC", &params).unwrap();
+        assert!(at < gc);
+        let salty = melting_temperature(b"ATATTATAATTATATATAAT", &params.mg_mm(1.5)).unwrap();
+        assert!(at < salty);
+        assert_eq!(
+            melting_temperature(b"ACNT", &params),
+            Err(SequenceError::InvalidBase {
+                position: 2,
+                base: b'N'
+            })
+        );
+        assert!(melting_temperature(b"A", &params).is_err());
+    }
+}
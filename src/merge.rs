@@ -0,0 +1,363 @@
+//! Merging of overlapping read pairs into a single read, as done by FLASH or
+//! `vsearch --fastq_mergepairs` for amplicons, and correction of the reads
+//! of a pair where they overlap, as done by fastp
+use crate::sequence::complement;
+use crate::Sequence;
+
+/// The parameters of `merge_pairs`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergeOptions {
+    /// Minimum number of overlapping bases, 10 by default
+    pub min_overlap: usize,
+    /// Maximum number of mismatches in the overlap, 10 by default
+    pub max_diffs: usize,
+    /// Maximum fraction of the overlap that can be mismatches, 0.25 by default
+    pub max_diff_rate: f64,
+    /// Allow the second read to extend past the start of the first one, as
+    /// happens when the fragment is shorter than the reads. The overhangs,
+    /// which are adapters, are dropped. False by default.
+    pub allow_staggered: bool,
+    /// ASCII offset of the quality scores, 33 by default
+    pub phred_offset: u8,
+    /// Highest quality score given to the merged bases, 41 by default
+    pub max_quality: u8,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            min_overlap: 10,
+            max_diffs: 10,
+            max_diff_rate: 0.25,
+            allow_staggered: false,
+            phred_offset: 33,
+            max_quality: 41,
+        }
+    }
+}
+
+impl MergeOptions {
+    pub fn min_overlap(mut self, min_overlap: usize) -> Self {
+        self.min_overlap = min_overlap;
+        self
+    }
+
+    pub fn max_diffs(mut self, max_diffs: usize) -> Self {
+        self.max_diffs = max_diffs;
+        self
+    }
+
+    pub fn max_diff_rate(mut self, max_diff_rate: f64) -> Self {
+        self.max_diff_rate = max_diff_rate;
+        self
+    }
+
+    pub fn allow_staggered(mut self, allow_staggered: bool) -> Self {
+        self.allow_staggered = allow_staggered;
+        self
+    }
+
+    pub fn phred_offset(mut self, phred_offset: u8) -> Self {
+        self.phred_offset = phred_offset;
+        self
+    }
+
+    pub fn max_quality(mut self, max_quality: u8) -> Self {
+        self.max_quality = max_quality;
+        self
+    }
+}
+
+/// A read pair merged into a single read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedPair {
+    pub seq: Vec<u8>,
+    pub qual: Vec<u8>,
+    /// Number of bases where the reads overlap
+    pub overlap: usize,
+    /// Number of mismatches between the reads in the overlap
+    pub diffs: usize,
+}
+
+/// Merges the two reads of a pair, given as `(sequence, quality)`, if they
+/// overlap. `r2` is the read of the opposite strand, as found in the FASTQ
+/// file: it's reverse complemented before looking for the overlap.
+///
+/// The overlap with the lowest rate of mismatches is kept, preferring the
+/// longest one in case of ties. In the overlap, the base with the highest
+/// quality is kept: agreeing bases get the highest of both qualities and
+/// disagreeing ones the difference between the two.
+///
+/// ```
+/// use needletail::merge::{merge_pairs, MergeOptions};
+/// use needletail::Sequence;
+///
+/// let fragment = b"ACGTTGCATGCAAGTCCGATGGCATTACG";
+/// let r1 = &fragment[..20];
+/// let r2 = fragment[12..].reverse_complement();
+/// let (q1, q2) = (vec![b'I'; r1.len()], vec![b'I'; r2.len()]);
+/// let merged = merge_pairs((r1, &q1), (&r2, &q2), &MergeOptions::default().min_overlap(5)).unwrap();
+/// assert_eq!(merged.seq, fragment);
+/// assert_eq!(merged.overlap, 8);
+/// ```
+pub fn merge_pairs(
+    r1: (&[u8], &[u8]),
+    r2: (&[u8], &[u8]),
+    options: &MergeOptions,
+) -> Option<MergedPair> {
+    let (seq1, qual1) = r1;
+    let seq2 = r2.0.reverse_complement();
+    let qual2: Vec<u8> = r2.1.iter().rev().copied().collect();
+    let (offset, overlap, diffs) = find_overlap(seq1, &seq2, options)?;
+    let start = offset.max(0) as usize;
+    let end = start + overlap;
+    let mut seq = Vec::with_capacity(start + seq2.len());
+    let mut qual = Vec::with_capacity(start + seq2.len());
+    seq.extend_from_slice(&seq1[..start]);
+    qual.extend_from_slice(&qual1[..start]);
+    for i in start..end {
+        let j = (i as isize - offset) as usize;
+        let (b, q) = consensus((seq1[i], qual1[i]), (seq2[j], qual2[j]), options);
+        seq.push(b);
+        qual.push(q);
+    }
+    // whatever r2 has past the end of r1. What r1 has past the end of r2 is
+    // an adapter and is dropped.
+    let rest = (end as isize - offset) as usize;
+    seq.extend_from_slice(&seq2[rest..]);
+    qual.extend_from_slice(&qual2[rest..]);
+
+    Some(MergedPair {
+        seq,
+        qual,
+        overlap,
+        diffs,
+    })
+}
+
+/// The reads of a pair corrected by `correct_pairs`, in their original
+/// orientation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorrectedPair {
+    pub seq1: Vec<u8>,
+    pub qual1: Vec<u8>,
+    pub seq2: Vec<u8>,
+    pub qual2: Vec<u8>,
+    /// Number of bases where the reads overlap
+    pub overlap: usize,
+    /// Number of mismatches between the reads in the overlap
+    pub diffs: usize,
+    /// Number of mismatches corrected
+    pub corrected: usize,
+}
+
+/// Finds the overlap of the two reads of a pair like `merge_pairs` and
+/// corrects the mismatches in the overlap: the base with the lower quality is
+/// replaced by the other one, along with its quality. Mismatches between bases
+/// of the same quality are left as they are.
+///
+/// Returns `None` if the reads don't overlap.
+///
+/// ```
+/// use needletail::merge::{correct_pairs, MergeOptions};
+/// use needletail::Sequence;
+///
+/// let fragment = b"ACGTTGCATGCAAGTCCGATGGCATTACG";
+/// let r1 = b"ACGTTGCATGCAAGTCCGCA";
+/// let r2 = fragment[12..].reverse_complement();
+/// let (q1, q2) = (b"IIIIIIIIIIIIIIIIII##", vec![b'I'; r2.len()]);
+/// let options = MergeOptions::default().min_overlap(5);
+/// let corrected = correct_pairs((r1, q1), (&r2, &q2), &options).unwrap();
+/// assert_eq!(corrected.seq1, &fragment[..20]);
+/// assert_eq!(corrected.qual1, vec![b'I'; 20]);
+/// assert_eq!(corrected.seq2, r2);
+/// assert_eq!(corrected.corrected, 2);
+/// ```
+pub fn correct_pairs(
+    r1: (&[u8], &[u8]),
+    r2: (&[u8], &[u8]),
+    options: &MergeOptions,
+) -> Option<CorrectedPair> {
+    let (mut seq1, mut qual1) = (r1.0.to_vec(), r1.1.to_vec());
+    let (mut seq2, mut qual2) = (r2.0.to_vec(), r2.1.to_vec());
+    let rc2 = r2.0.reverse_complement();
+    let (offset, overlap, diffs) = find_overlap(&seq1, &rc2, options)?;
+    let start = offset.max(0) as usize;
+    let mut corrected = 0;
+    for i in start..start + overlap {
+        // position on r2 in its original orientation
+        let j = seq2.len() - 1 - (i as isize - offset) as usize;
+        let (a, b) = (seq1[i], complement(seq2[j]));
+        if a.eq_ignore_ascii_case(&b) || a == b'N' || b == b'N' {
+            continue;
+        }
+        if qual1[i] > qual2[j] {
+            seq2[j] = complement(a);
+            qual2[j] = qual1[i];
+        } else if qual2[j] > qual1[i] {
+            seq1[i] = b;
+            qual1[i] = qual2[j];
+        } else {
+            continue;
+        }
+        corrected += 1;
+    }
+
+    Some(CorrectedPair {
+        seq1,
+        qual1,
+        seq2,
+        qual2,
+        overlap,
+        diffs,
+        corrected,
+    })
+}
+
+/// Finds the best overlap of `seq1` and `seq2` (the reverse complemented
+/// second read) as `(offset of seq2 on seq1, overlap, diffs)`
+fn find_overlap(seq1: &[u8], seq2: &[u8], options: &MergeOptions) -> Option<(isize, usize, usize)> {
+    let (len1, len2) = (seq1.len() as isize, seq2.len() as isize);
+    let min_overlap = options.min_overlap.max(1) as isize;
+
+    // `offset` is where the reverse complemented r2 starts on r1
+    let first_offset = if options.allow_staggered {
+        min_overlap - len2
+    } else {
+        0
+    };
+    let mut best: Option<(isize, usize, usize)> = None;
+    for offset in first_offset..=len1 - min_overlap {
+        let start = offset.max(0);
+        let end = len1.min(offset + len2);
+        let overlap = (end - start) as usize;
+        if overlap < options.min_overlap {
+            continue;
+        }
+        let diffs = (start..end)
+            .filter(|i| {
+                let (a, b) = (seq1[*i as usize], seq2[(i - offset) as usize]);
+                !a.eq_ignore_ascii_case(&b) && a != b'N' && b != b'N'
+            })
+            .count();
+        if diffs > options.max_diffs || diffs as f64 > options.max_diff_rate * overlap as f64 {
+            continue;
+        }
+        let better = match best {
+            None => true,
+            // lower diffs / overlap, then longer overlaps
+            Some((_, b_overlap, b_diffs)) => {
+                let (lhs, rhs) = (diffs * b_overlap, b_diffs * overlap);
+                lhs < rhs || (lhs == rhs && overlap > b_overlap)
+            }
+        };
+        if better {
+            best = Some((offset, overlap, diffs));
+        }
+    }
+
+    best
+}
+
+/// The base and quality of a position covered by both reads
+fn consensus(a: (u8, u8), b: (u8, u8), options: &MergeOptions) -> (u8, u8) {
+    let offset = options.phred_offset;
+    let (qa, qb) = (a.1.saturating_sub(offset), b.1.saturating_sub(offset));
+    if a.0 == b'N' {
+        return b;
+    }
+    if b.0 == b'N' {
+        return a;
+    }
+    let (base, q) = if a.0.eq_ignore_ascii_case(&b.0) {
+        (a.0, qa.max(qb))
+    } else if qa >= qb {
+        (a.0, (qa - qb).max(2))
+    } else {
+        (b.0, (qb - qa).max(2))
+    };
+    (base, q.min(options.max_quality) + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAGMENT: &[u8] = b"ACGTTGCATGCAAGTCCGATGGCATTACGGATCCA";
+
+    #[test]
+    fn can_merge_with_mismatches() {
+        let r1 = &FRAGMENT[..25];
+        let mut r2 = FRAGMENT[10..].to_vec();
+        // a low quality error in r2
+        r2[5] = b'A';
+        let mut q2 = vec![b'I'; r2.len()];
+        q2[5] = b'#';
+        let r2 = r2.reverse_complement();
+        q2.reverse();
+        let q1 = vec![b'5'; r1.len()];
+        let merged = merge_pairs((r1, &q1), (&r2, &q2), &MergeOptions::default()).unwrap();
+        assert_eq!(merged.seq, FRAGMENT);
+        assert_eq!((merged.overlap, merged.diffs), (15, 1));
+        // agreeing bases get the best quality, the mismatch the difference
+        assert_eq!(merged.qual[9], b'5');
+        assert_eq!(merged.qual[11], b'I');
+        assert_eq!(merged.qual[15], b'5' - 2);
+        assert_eq!(merged.qual[30], b'I');
+
+        let strict = MergeOptions::default().max_diffs(0);
+        assert!(merge_pairs((r1, &q1), (&r2, &q2), &strict).is_none());
+    }
+
+    #[test]
+    fn can_correct_pairs() {
+        let r1 = FRAGMENT[..25].to_vec();
+        let mut r2 = FRAGMENT[10..].to_vec();
+        // errors in both reads, in the overlap or not
+        let mut q1 = vec![b'5'; 25];
+        let mut q2 = vec![b'I'; r2.len()];
+        r2[3] = b'T';
+        q2[3] = b'#';
+        r2[20] = b'A';
+        q2[20] = b'#';
+        let mut r1 = r1;
+        r1[20] = b'C';
+        q1[20] = b'#';
+        // same quality: can't tell which one is right
+        r2[8] = b'C';
+        q2[8] = b'5';
+        let (r2, q2) = (
+            r2.reverse_complement(),
+            q2.iter().rev().copied().collect::<Vec<_>>(),
+        );
+        let corrected = correct_pairs((&r1, &q1), (&r2, &q2), &MergeOptions::default()).unwrap();
+        assert_eq!(
+            (corrected.overlap, corrected.diffs, corrected.corrected),
+            (15, 3, 2)
+        );
+        assert_eq!(corrected.seq1[20], FRAGMENT[20]);
+        assert_eq!(corrected.qual1[20], b'I');
+        let seq2 = corrected.seq2.reverse_complement();
+        assert_eq!(seq2[3], FRAGMENT[13]);
+        assert_eq!(seq2[8], b'C');
+        // outside of the overlap
+        assert_eq!(seq2[20], b'A');
+        assert_eq!(corrected.qual2[corrected.qual2.len() - 4], b'5');
+
+        let far = &FRAGMENT[..10];
+        assert!(correct_pairs((far, far), (&r2, &q2), &MergeOptions::default()).is_none());
+    }
+
+    #[test]
+    fn can_merge_staggered_pairs() {
+        // the fragment is shorter than the reads: they run into the adapters
+        let r1 = [&FRAGMENT[..20], &b"AGATCGGAAG"[..]].concat();
+        let r2 = [&FRAGMENT[..20].reverse_complement(), &b"AGATCGTCGG"[..]].concat();
+        let q = vec![b'I'; 30];
+        let options = MergeOptions::default();
+        assert!(merge_pairs((&r1, &q), (&r2, &q), &options).is_none());
+        let merged = merge_pairs((&r1, &q), (&r2, &q), &options.allow_staggered(true)).unwrap();
+        assert_eq!(merged.seq, &FRAGMENT[..20]);
+        assert_eq!(merged.qual.len(), 20);
+    }
+}
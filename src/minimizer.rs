@@ -0,0 +1,362 @@
+//! Windowed minimizers: from every window of `w` consecutive kmers, only the
+//! kmer with the smallest hash is kept. This is the usual way of sampling
+//! kmers for sketching, indexing and partitioning.
+//!
+//! Different tools use slightly different winnowing semantics so the
+//! tie-breaking, the handling of sequence ends and the window size can all be
+//! configured with `MinimizerParams`.
+use crate::bitkmer::{mix_hash, BitKmer, BitNuclKmer};
+
+/// The parameters of a minimizer scheme
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimizerParams {
+    /// Kmer size, at most 32
+    pub k: u8,
+    /// Number of consecutive kmers in a window
+    pub w: usize,
+    /// Whether to use canonical kmers, so the minimizers are the same on both strands
+    pub canonical: bool,
+    /// Robust winnowing (Schleimer et al. 2003): on ties, keep the previous
+    /// minimizer if it's still in the window and otherwise pick the rightmost
+    /// of the minimal kmers. When `false`, the leftmost one is picked.
+    pub robust: bool,
+    /// Also sample the minimizers of the windows of less than `w` kmers
+    /// touching the ends of the sequence, so the first and last bases are
+    /// always covered by a minimizer.
+    pub end_minimizers: bool,
+}
+
+impl MinimizerParams {
+    /// Canonical minimizers with robust winnowing and no end minimizers
+    pub fn new(k: u8, w: usize) -> Self {
+        Self {
+            k,
+            w,
+            canonical: true,
+            robust: true,
+            end_minimizers: false,
+        }
+    }
+
+    /// Picks the window size so that the expected fraction of kmers sampled is
+    /// close to `density`, given that random minimizers have a density of
+    /// `2 / (w + 1)`.
+    ///
+    /// ```
+    /// use needletail::minimizer::MinimizerParams;
+    ///
+    /// assert_eq!(MinimizerParams::with_density(15, 0.1).w, 19);
+    /// ```
+    pub fn with_density(k: u8, density: f64) -> Self {
+        assert!(density > 0. && density <= 1.);
+        let w = (2. / density - 1.).round().max(1.) as usize;
+        Self::new(k, w)
+    }
+
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    pub fn robust(mut self, robust: bool) -> Self {
+        self.robust = robust;
+        self
+    }
+
+    pub fn end_minimizers(mut self, end_minimizers: bool) -> Self {
+        self.end_minimizers = end_minimizers;
+        self
+    }
+}
+
+/// A minimizer, as returned by the `Minimizers` iterator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Minimizer {
+    /// Position of the kmer in the sequence
+    pub pos: usize,
+    /// The (canonical, if requested) kmer
+    pub kmer: BitKmer,
+    /// The hash the kmers were compared on
+    pub hash: u64,
+    /// Whether `kmer` is the reverse complement of the sequence at `pos`
+    pub is_rc: bool,
+}
+
+/// Iterator over the minimizers of a sequence, in order of position. Each
+/// kmer is returned at most once even if it is the minimizer of several
+/// windows.
+///
+/// Windows never span a non-ACGT base: the sequence is split into runs of
+/// valid kmers which are processed independently.
+pub struct Minimizers {
+    minimizers: std::vec::IntoIter<Minimizer>,
+}
+
+impl Minimizers {
+    pub fn new(seq: &[u8], params: MinimizerParams) -> Self {
+        assert!(params.w > 0);
+        let mut minimizers = Vec::new();
+        let mut run: Vec<Minimizer> = Vec::new();
+        for (pos, kmer, is_rc) in BitNuclKmer::new(seq, params.k, params.canonical) {
+            if run.last().is_some_and(|m| m.pos + 1 != pos) {
+                winnow_into(&run, &params, &mut minimizers);
+                run.clear();
+            }
+            run.push(Minimizer {
+                pos,
+                kmer,
+                hash: mix_hash(kmer.0),
+                is_rc,
+            });
+        }
+        winnow_into(&run, &params, &mut minimizers);
+
+        Minimizers {
+            minimizers: minimizers.into_iter(),
+        }
+    }
+}
+
+/// Collects the distinct minimizers of a run of consecutive kmers
+fn winnow_into(kmers: &[Minimizer], params: &MinimizerParams, out: &mut Vec<Minimizer>) {
+    let mut previous = None;
+    winnow(kmers, params, |_, picked| {
+        // windows only move forward so the same minimizer can't come back later
+        if previous != Some(picked) {
+            out.push(kmers[picked]);
+            previous = Some(picked);
+        }
+    });
+}
+
+/// Picks the minimizer of each window of a run of consecutive kmers and calls
+/// `f` with the start of the window and the index of the minimizer in `kmers`.
+fn winnow<F: FnMut(usize, usize)>(kmers: &[Minimizer], params: &MinimizerParams, mut f: F) {
+    let n = kmers.len();
+    let w = params.w;
+    let mut windows: Vec<(usize, usize)> = Vec::new();
+    if params.end_minimizers {
+        windows.extend((1..w.min(n + 1)).map(|size| (0, size)));
+    }
+    if n >= w {
+        windows.extend((0..=n - w).map(|start| (start, start + w)));
+    }
+    if params.end_minimizers {
+        windows.extend((1..w.min(n + 1)).rev().map(|size| (n - size, n)));
+    }
+
+    let mut previous: Option<usize> = None;
+    for (start, end) in windows {
+        let min_hash = kmers[start..end].iter().map(|m| m.hash).min().unwrap();
+        let picked = match previous {
+            Some(p) if params.robust && p >= start && p < end && kmers[p].hash == min_hash => p,
+            _ => {
+                let mut candidates = (start..end).filter(|i| kmers[*i].hash == min_hash);
+                if params.robust {
+                    candidates.next_back().unwrap()
+                } else {
+                    candidates.next().unwrap()
+                }
+            }
+        };
+        f(start, picked);
+        previous = Some(picked);
+    }
+}
+
+impl Iterator for Minimizers {
+    type Item = Minimizer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.minimizers.next()
+    }
+}
+
+/// A super-kmer: a maximal run of consecutive kmers sharing the same minimizer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SuperKmer {
+    /// Start of the first kmer in the sequence
+    pub start: usize,
+    /// End (exclusive) of the last kmer in the sequence
+    pub end: usize,
+    /// The minimizer shared by all the kmers
+    pub minimizer: Minimizer,
+}
+
+impl SuperKmer {
+    /// Number of kmers in the super-kmer
+    pub fn n_kmers(&self, k: u8) -> usize {
+        self.end - self.start - k as usize + 1
+    }
+
+    /// The bases of the super-kmer in `seq`, which must be the sequence it
+    /// was computed from
+    pub fn seq<'a>(&self, seq: &'a [u8]) -> &'a [u8] {
+        &seq[self.start..self.end]
+    }
+}
+
+/// Iterator over the super-kmers of a sequence, in order of position.
+///
+/// The minimizers of the kmers of size `k` are the minimizers of size
+/// `params.k` of the windows covering each kmer, so the window size of
+/// `params` is ignored (it is always `k - params.k + 1`) and end minimizers
+/// are never used. Kmers containing non-ACGT bases are skipped.
+pub struct SuperKmers {
+    super_kmers: std::vec::IntoIter<SuperKmer>,
+}
+
+impl SuperKmers {
+    pub fn new(seq: &[u8], k: u8, params: MinimizerParams) -> Self {
+        assert!(params.k <= k);
+        let params = MinimizerParams {
+            w: (k - params.k) as usize + 1,
+            end_minimizers: false,
+            ..params
+        };
+        let mut super_kmers = Vec::new();
+        let mut run: Vec<Minimizer> = Vec::new();
+        let mut add_run = |run: &[Minimizer]| {
+            let mut current: Option<(usize, SuperKmer)> = None;
+            winnow(run, &params, |start, picked| {
+                let kmer_end = run[start].pos + k as usize;
+                match current.as_mut() {
+                    Some((p, sk)) if *p == picked => sk.end = kmer_end,
+                    _ => {
+                        if let Some((_, sk)) = current.take() {
+                            super_kmers.push(sk);
+                        }
+                        current = Some((
+                            picked,
+                            SuperKmer {
+                                start: run[start].pos,
+                                end: kmer_end,
+                                minimizer: run[picked],
+                            },
+                        ));
+                    }
+                }
+            });
+            if let Some((_, sk)) = current {
+                super_kmers.push(sk);
+            }
+        };
+        for (pos, kmer, is_rc) in BitNuclKmer::new(seq, params.k, params.canonical) {
+            if run.last().is_some_and(|m| m.pos + 1 != pos) {
+                add_run(&run);
+                run.clear();
+            }
+            run.push(Minimizer {
+                pos,
+                kmer,
+                hash: mix_hash(kmer.0),
+                is_rc,
+            });
+        }
+        add_run(&run);
+
+        SuperKmers {
+            super_kmers: super_kmers.into_iter(),
+        }
+    }
+}
+
+impl Iterator for SuperKmers {
+    type Item = SuperKmer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.super_kmers.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sequence;
+
+    const SEQ: &[u8] = b"ACGTAGCTAGCTAGGATCGATCGACTAGCATCAGCTAGCGACTAGCATTTTTTTTTT";
+
+    #[test]
+    fn every_window_contains_a_minimizer() {
+        let params = MinimizerParams::new(5, 4);
+        let positions: Vec<usize> = Minimizers::new(SEQ, params).map(|m| m.pos).collect();
+        let n_kmers = SEQ.len() - 5 + 1;
+        for start in 0..=n_kmers - 4 {
+            assert!(positions.iter().any(|p| *p >= start && *p < start + 4));
+        }
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn can_pick_ties_both_ways() {
+        // all the kmers are the same so every window is a tie
+        let seq = b"AAAAAAAAAA";
+        let params = MinimizerParams::new(3, 3);
+        let robust: Vec<usize> = Minimizers::new(seq, params).map(|m| m.pos).collect();
+        assert_eq!(robust, vec![2, 5]);
+        let leftmost: Vec<usize> = Minimizers::new(seq, params.robust(false))
+            .map(|m| m.pos)
+            .collect();
+        assert_eq!(leftmost, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn can_sample_end_minimizers() {
+        let params = MinimizerParams::new(5, 10);
+        let plain: Vec<Minimizer> = Minimizers::new(SEQ, params).collect();
+        let with_ends: Vec<Minimizer> = Minimizers::new(SEQ, params.end_minimizers(true)).collect();
+        assert_eq!(with_ends[0].pos, 0);
+        assert_eq!(with_ends.last().unwrap().pos, SEQ.len() - 5);
+        assert!(plain.iter().all(|m| with_ends.contains(m)));
+    }
+
+    #[test]
+    fn canonical_minimizers_are_strand_independent() {
+        let params = MinimizerParams::new(7, 5);
+        let fwd: Vec<_> = Minimizers::new(SEQ, params).map(|m| m.kmer).collect();
+        let rc = SEQ.reverse_complement();
+        let mut rev: Vec<_> = Minimizers::new(&rc, params.robust(false))
+            .map(|m| m.kmer)
+            .collect();
+        rev.reverse();
+        // ties can be broken differently but there are none in this sequence
+        assert_eq!(fwd, rev);
+    }
+
+    #[test]
+    fn windows_dont_span_ns() {
+        let params = MinimizerParams::new(3, 2);
+        for m in Minimizers::new(b"ACGTNACGT", params) {
+            assert!(m.pos < 2 || m.pos > 4);
+        }
+    }
+
+    #[test]
+    fn super_kmers_cover_all_kmers() {
+        let k = 11;
+        let params = MinimizerParams::new(5, 1);
+        let super_kmers: Vec<SuperKmer> = SuperKmers::new(SEQ, k, params).collect();
+        let n_kmers: usize = super_kmers.iter().map(|sk| sk.n_kmers(k)).sum();
+        assert_eq!(n_kmers, SEQ.len() - k as usize + 1);
+        for pair in super_kmers.windows(2) {
+            // consecutive super-kmers overlap by k - 1 bases
+            assert_eq!(pair[0].end - k as usize + 1, pair[1].start);
+            assert_ne!(pair[0].minimizer.pos, pair[1].minimizer.pos);
+        }
+        for sk in &super_kmers {
+            let m = sk.minimizer;
+            assert!(m.pos >= sk.start && m.pos + 5 <= sk.end);
+            assert_eq!(sk.seq(SEQ).len(), sk.end - sk.start);
+        }
+    }
+
+    #[test]
+    fn super_kmers_skip_ns() {
+        let seq = b"ACGTACGTNNACGTAC";
+        let super_kmers: Vec<SuperKmer> =
+            SuperKmers::new(seq, 4, MinimizerParams::new(2, 1)).collect();
+        assert!(super_kmers.iter().all(|sk| sk.end <= 8 || sk.start >= 10));
+        let n_kmers: usize = super_kmers.iter().map(|sk| sk.n_kmers(4)).sum();
+        assert_eq!(n_kmers, 5 + 3);
+    }
+}
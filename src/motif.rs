@@ -0,0 +1,223 @@
+//! Search for degenerate motifs (IUPAC patterns) such as primers or
+//! restriction sites on both strands of a sequence
+use crate::errors::SequenceError;
+use crate::sequence::Strand;
+
+/// The bases (A = 1, C = 2, G = 4, T = 8) an IUPAC code can stand for, 0
+/// for anything else
+#[inline]
+fn iupac_bases(code: u8) -> u8 {
+    match code.to_ascii_uppercase() {
+        b'A' => 0b0001,
+        b'C' => 0b0010,
+        b'G' => 0b0100,
+        b'T' | b'U' => 0b1000,
+        b'R' => 0b0101,
+        b'Y' => 0b1010,
+        b'S' => 0b0110,
+        b'W' => 0b1001,
+        b'K' => 0b1100,
+        b'M' => 0b0011,
+        b'B' => 0b1110,
+        b'D' => 0b1101,
+        b'H' => 0b1011,
+        b'V' => 0b0111,
+        b'N' => 0b1111,
+        _ => 0,
+    }
+}
+
+/// The complement of a set of bases: A <-> T and C <-> G
+#[inline]
+fn complement_bases(bases: u8) -> u8 {
+    (bases & 0b1000) >> 3 | (bases & 0b0100) >> 1 | (bases & 0b0010) << 1 | (bases & 0b0001) << 3
+}
+
+/// A match of a `Motif`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MotifMatch {
+    /// Start of the match on the forward strand, whatever its strand
+    pub pos: usize,
+    pub strand: Strand,
+}
+
+/// A degenerate motif of at most 64 bases, e.g. `GRYANNNNTG`.
+///
+/// A base of the sequence matches a position of the motif if they can stand
+/// for the same base, so ambiguity codes are honored in both: `R` in the
+/// motif matches A, G, R, N, ... in the sequence. Any byte that isn't a base
+/// or an IUPAC code (gaps, ...) in the sequence never matches.
+///
+/// ```
+/// use needletail::motif::{Motif, MotifMatch};
+/// use needletail::sequence::Strand;
+///
+/// let motif = Motif::new(b"GRCC").unwrap();
+/// let matches = motif.find_all(b"TTGACCAAGGTCAA");
+/// assert_eq!(matches, vec![
+///     MotifMatch { pos: 2, strand: Strand::Forward },
+///     MotifMatch { pos: 8, strand: Strand::Reverse },
+/// ]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Motif {
+    pattern: Vec<u8>,
+    /// For every set of bases of the sequence, the positions of the motif
+    /// (as bits) it matches, on each strand
+    forward: [u64; 16],
+    reverse: [u64; 16],
+    palindromic: bool,
+}
+
+impl Motif {
+    /// Creates a motif from a pattern of IUPAC codes (in either case)
+    pub fn new(pattern: &[u8]) -> Result<Self, SequenceError> {
+        if pattern.len() > 64 {
+            return Err(SequenceError::TooLong {
+                length: pattern.len(),
+                max: 64,
+            });
+        }
+        let mut bases = Vec::with_capacity(pattern.len());
+        for (position, code) in pattern.iter().enumerate() {
+            match iupac_bases(*code) {
+                0 => {
+                    return Err(SequenceError::InvalidBase {
+                        position,
+                        base: *code,
+                    })
+                }
+                b => bases.push(b),
+            }
+        }
+        let rc_bases: Vec<u8> = bases.iter().rev().map(|b| complement_bases(*b)).collect();
+
+        let table = |bases: &[u8]| {
+            let mut table = [0u64; 16];
+            for (subject, entry) in table.iter_mut().enumerate() {
+                for (i, b) in bases.iter().enumerate() {
+                    if b & subject as u8 != 0 {
+                        *entry |= 1 << i;
+                    }
+                }
+            }
+            table
+        };
+        Ok(Motif {
+            pattern: pattern.to_ascii_uppercase(),
+            forward: table(&bases),
+            reverse: table(&rc_bases),
+            palindromic: bases == rc_bases,
+        })
+    }
+
+    /// The motif, uppercased
+    pub fn pattern(&self) -> &[u8] {
+        &self.pattern
+    }
+
+    pub fn len(&self) -> usize {
+        self.pattern.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_empty()
+    }
+
+    /// Whether the motif is its own reverse complement, e.g. most restriction
+    /// sites. Matches of palindromic motifs are only reported on the forward
+    /// strand.
+    pub fn is_palindromic(&self) -> bool {
+        self.palindromic
+    }
+
+    /// Finds all the matches on both strands of `seq`, overlapping ones
+    /// included, sorted by position
+    pub fn find_all(&self, seq: &[u8]) -> Vec<MotifMatch> {
+        let mut matches = Vec::new();
+        let m = self.pattern.len();
+        if m == 0 || m > seq.len() {
+            return matches;
+        }
+        // bit-parallel (shift-and) matching of both strands at once
+        let last = 1u64 << (m - 1);
+        let (mut fwd, mut rev) = (0u64, 0u64);
+        for (i, base) in seq.iter().enumerate() {
+            let bases = iupac_bases(*base) as usize;
+            fwd = ((fwd << 1) | 1) & self.forward[bases];
+            rev = ((rev << 1) | 1) & self.reverse[bases];
+            if i + 1 < m {
+                continue;
+            }
+            let pos = i + 1 - m;
+            if fwd & last != 0 {
+                matches.push(MotifMatch {
+                    pos,
+                    strand: Strand::Forward,
+                });
+            }
+            if rev & last != 0 && !self.palindromic {
+                matches.push(MotifMatch {
+                    pos,
+                    strand: Strand::Reverse,
+                });
+            }
+        }
+        matches
+    }
+
+    /// Whether the motif occurs on either strand of `seq`
+    pub fn is_match(&self, seq: &[u8]) -> bool {
+        !self.find_all(seq).is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sequence;
+
+    #[test]
+    fn honors_ambiguity_codes() {
+        let motif = Motif::new(b"GRYANNNNTG").unwrap();
+        assert!(!motif.is_palindromic());
+        let site = b"GATAcgtaTG";
+        let seq = [&b"CCC"[..], site, b"CC", &site.reverse_complement()].concat();
+        let matches = motif.find_all(&seq);
+        assert_eq!(
+            matches,
+            vec![
+                MotifMatch {
+                    pos: 3,
+                    strand: Strand::Forward
+                },
+                MotifMatch {
+                    pos: 15,
+                    strand: Strand::Reverse
+                }
+            ]
+        );
+        // ambiguity in the sequence too, but gaps never match
+        assert!(motif.is_match(b"GNNAAAAATG"));
+        assert!(!motif.is_match(b"G-TAAAAATG"));
+        assert!(!motif.is_match(b"GCTAAAAATG"));
+    }
+
+    #[test]
+    fn palindromes_and_errors() {
+        let ecori = Motif::new(b"gaattc").unwrap();
+        assert!(ecori.is_palindromic());
+        assert_eq!(ecori.find_all(b"AGAATTCGAATTC").len(), 2);
+        assert_eq!(ecori.pattern(), b"GAATTC");
+
+        assert_eq!(
+            Motif::new(b"ACX"),
+            Err(SequenceError::InvalidBase {
+                position: 2,
+                base: b'X'
+            })
+        );
+        assert!(Motif::new(&[b'A'; 65]).is_err());
+        assert!(Motif::new(&[b'N'; 64]).unwrap().is_match(&[b'A'; 64]));
+    }
+}
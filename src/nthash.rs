@@ -0,0 +1,226 @@
+//! ntHash: a recursive (rolling) hash function for kmers that only needs the
+//! base leaving and the base entering the window to compute the next value.
+//! This allows hashing every kmer of a sequence without ever materializing the
+//! kmers themselves, e.g. to fill Bloom filters or build sketches.
+//!
+//! See Mohamadi, H. et al. (2016) "ntHash: recursive nucleotide hashing",
+//! Bioinformatics.
+
+const SEED_A: u64 = 0x3c8b_fbb3_95c6_0474;
+const SEED_C: u64 = 0x3193_c185_62a0_2b4c;
+const SEED_G: u64 = 0x2032_3ed0_8257_2324;
+const SEED_T: u64 = 0x2955_49f5_4be2_4456;
+
+const MULTI_SEED: u64 = 0x90b4_5d39_fb6d_a1fa;
+const MULTI_SHIFT: u32 = 27;
+
+/// Returns the seeds of the base and of its complement, or `None` for
+/// anything else than ACGT.
+#[inline]
+fn seeds(base: u8) -> Option<(u64, u64)> {
+    match base {
+        b'A' | b'a' => Some((SEED_A, SEED_T)),
+        b'C' | b'c' => Some((SEED_C, SEED_G)),
+        b'G' | b'g' => Some((SEED_G, SEED_C)),
+        b'T' | b't' => Some((SEED_T, SEED_A)),
+        _ => None,
+    }
+}
+
+/// Computes the forward and reverse complement hashes of a kmer from scratch.
+/// Returns `None` if the kmer contains a non-ACGT base.
+pub fn hash_kmer(kmer: &[u8]) -> Option<(u64, u64)> {
+    let k = kmer.len() as u32;
+    let mut fh = 0;
+    let mut rh = 0;
+    for (i, base) in kmer.iter().enumerate() {
+        let (f, r) = seeds(*base)?;
+        fh ^= f.rotate_left(k - 1 - i as u32);
+        rh ^= r.rotate_left(i as u32);
+    }
+    Some((fh, rh))
+}
+
+/// Derives `hashes.len() - 1` extra hash values from `hash` and stores them after it
+/// in `hashes`, as done by ntHash for Bloom filters needing several hash functions.
+pub fn multi_hash(hash: u64, k: u8, hashes: &mut [u64]) {
+    if hashes.is_empty() {
+        return;
+    }
+    hashes[0] = hash;
+    for (i, h) in hashes.iter_mut().enumerate().skip(1) {
+        let mut t = hash.wrapping_mul(i as u64 ^ (u64::from(k).wrapping_mul(MULTI_SEED)));
+        t ^= t >> MULTI_SHIFT;
+        *h = t;
+    }
+}
+
+/// Iterator returning the position and the ntHash of every kmer of a
+/// sequence, skipping the kmers containing non-ACGT bases.
+///
+/// If `canonical` is set, the hash is the minimum of the hashes of the kmer and
+/// its reverse complement so it is the same for both strands.
+pub struct NtHash<'a> {
+    buffer: &'a [u8],
+    k: u8,
+    canonical: bool,
+    /// start of the current kmer
+    pos: usize,
+    fh: u64,
+    rh: u64,
+    initialized: bool,
+}
+
+impl<'a> NtHash<'a> {
+    pub fn new(buffer: &'a [u8], k: u8, canonical: bool) -> Self {
+        assert!(k > 0);
+        NtHash {
+            buffer,
+            k,
+            canonical,
+            pos: 0,
+            fh: 0,
+            rh: 0,
+            initialized: false,
+        }
+    }
+
+    /// Finds the next kmer without invalid bases from `self.pos` and hashes it
+    fn init(&mut self) -> bool {
+        let k = self.k as usize;
+        while self.pos + k <= self.buffer.len() {
+            match self.buffer[self.pos..self.pos + k]
+                .iter()
+                .rposition(|b| seeds(*b).is_none())
+            {
+                Some(bad) => self.pos += bad + 1,
+                None => {
+                    let (fh, rh) = hash_kmer(&self.buffer[self.pos..self.pos + k]).unwrap();
+                    self.fh = fh;
+                    self.rh = rh;
+                    self.initialized = true;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Moves the window one base to the right, returning false if the new base is
+    /// invalid or if we reached the end
+    fn roll(&mut self) -> bool {
+        let k = u32::from(self.k);
+        let Some(&incoming) = self.buffer.get(self.pos + self.k as usize) else {
+            return false;
+        };
+        let Some((in_f, in_r)) = seeds(incoming) else {
+            return false;
+        };
+        let (out_f, out_r) = seeds(self.buffer[self.pos]).unwrap();
+        self.fh = self.fh.rotate_left(1) ^ out_f.rotate_left(k) ^ in_f;
+        self.rh = self.rh.rotate_right(1) ^ out_r.rotate_right(1) ^ in_r.rotate_left(k - 1);
+        self.pos += 1;
+        true
+    }
+
+    #[inline]
+    fn current(&self) -> u64 {
+        if self.canonical {
+            self.fh.min(self.rh)
+        } else {
+            self.fh
+        }
+    }
+}
+
+impl Iterator for NtHash<'_> {
+    type Item = (usize, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.initialized && self.roll() {
+            return Some((self.pos, self.current()));
+        }
+        if self.initialized {
+            // we hit an invalid base (or the end), restart after it
+            self.pos += self.k as usize + 1;
+            self.initialized = false;
+        }
+        if self.init() {
+            Some((self.pos, self.current()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Like `NtHash` but returns `N` hash values per kmer (see `multi_hash`)
+pub struct MultiNtHash<'a, const N: usize> {
+    inner: NtHash<'a>,
+}
+
+impl<'a, const N: usize> MultiNtHash<'a, N> {
+    pub fn new(buffer: &'a [u8], k: u8, canonical: bool) -> Self {
+        MultiNtHash {
+            inner: NtHash::new(buffer, k, canonical),
+        }
+    }
+}
+
+impl<const N: usize> Iterator for MultiNtHash<'_, N> {
+    type Item = (usize, [u64; N]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (pos, hash) = self.inner.next()?;
+        let mut hashes = [0; N];
+        multi_hash(hash, self.inner.k, &mut hashes);
+        Some((pos, hashes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sequence;
+
+    #[test]
+    fn rolling_matches_direct_hashing() {
+        let seq = b"ACGTAGCTAGCTAGGATCGATCGACTAGCATCAGC";
+        let k = 7;
+        let hashes: Vec<(usize, u64)> = NtHash::new(seq, k, false).collect();
+        assert_eq!(hashes.len(), seq.len() - k as usize + 1);
+        for (pos, hash) in hashes {
+            let (fh, _) = hash_kmer(&seq[pos..pos + k as usize]).unwrap();
+            assert_eq!(hash, fh);
+        }
+    }
+
+    #[test]
+    fn canonical_hashes_are_strand_independent() {
+        let seq = b"ACGTAGCTAGCTAGGATCGATCGACTAGCATCAGC";
+        let rc = seq.reverse_complement();
+        let mut fwd: Vec<u64> = NtHash::new(seq, 9, true).map(|(_, h)| h).collect();
+        let rev: Vec<u64> = NtHash::new(&rc, 9, true).map(|(_, h)| h).collect();
+        fwd.reverse();
+        assert_eq!(fwd, rev);
+    }
+
+    #[test]
+    fn skips_invalid_bases() {
+        let positions: Vec<usize> = NtHash::new(b"ACGNACGTNAC", 3, false)
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(positions, vec![0, 4, 5]);
+        assert!(NtHash::new(b"AC", 3, false).next().is_none());
+    }
+
+    #[test]
+    fn multi_hashes() {
+        let mut iter = MultiNtHash::<3>::new(b"ACGTA", 3, true);
+        let (pos, hashes) = iter.next().unwrap();
+        assert_eq!(pos, 0);
+        assert_eq!(hashes[0], NtHash::new(b"ACG", 3, true).next().unwrap().1);
+        assert_ne!(hashes[1], hashes[0]);
+        assert_ne!(hashes[2], hashes[1]);
+        assert_eq!(iter.count(), 2);
+    }
+}
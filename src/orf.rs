@@ -0,0 +1,261 @@
+//! Open reading frame (ORF) detection on the six frames of a sequence
+use crate::sequence::Strand;
+use crate::translate::{CodonTable, Frame};
+use crate::Sequence;
+
+/// The parameters of the ORF search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrfParams {
+    /// Codons an ORF can start with, `ATG` by default
+    pub start_codons: Vec<[u8; 3]>,
+    /// Minimum length of the ORF in amino acids, not counting the stop codon
+    pub min_len: usize,
+    pub table: CodonTable,
+    /// Report an ORF for every start codon before a stop codon rather than only
+    /// the longest one
+    pub nested: bool,
+    /// Report ORFs overlapping a longer ORF (in any frame)
+    pub overlapping: bool,
+    /// Report ORFs running to the end of the sequence without a stop codon
+    pub incomplete: bool,
+}
+
+impl Default for OrfParams {
+    fn default() -> Self {
+        OrfParams {
+            start_codons: vec![*b"ATG"],
+            min_len: 30,
+            table: CodonTable::STANDARD,
+            nested: false,
+            overlapping: true,
+            incomplete: false,
+        }
+    }
+}
+
+impl OrfParams {
+    pub fn start_codons(mut self, start_codons: &[&[u8; 3]]) -> Self {
+        self.start_codons = start_codons
+            .iter()
+            .map(|c| c.map(|b| b.to_ascii_uppercase()))
+            .collect();
+        self
+    }
+
+    pub fn min_len(mut self, min_len: usize) -> Self {
+        self.min_len = min_len;
+        self
+    }
+
+    pub fn table(mut self, table: CodonTable) -> Self {
+        self.table = table;
+        self
+    }
+
+    pub fn nested(mut self, nested: bool) -> Self {
+        self.nested = nested;
+        self
+    }
+
+    pub fn overlapping(mut self, overlapping: bool) -> Self {
+        self.overlapping = overlapping;
+        self
+    }
+
+    pub fn incomplete(mut self, incomplete: bool) -> Self {
+        self.incomplete = incomplete;
+        self
+    }
+}
+
+/// An open reading frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Orf {
+    /// Start of the ORF on the forward strand, whatever its strand
+    pub start: usize,
+    /// End (exclusive) of the ORF on the forward strand, including the stop codon
+    pub end: usize,
+    pub frame: Frame,
+    /// The translated ORF, without the stop codon
+    pub protein: Vec<u8>,
+    /// false if the ORF runs to the end of the sequence
+    pub has_stop: bool,
+}
+
+impl Orf {
+    pub fn strand(&self) -> Strand {
+        self.frame.strand
+    }
+
+    /// Length of the ORF in bases
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Finds the ORFs of the six frames of `seq`, sorted by start position
+///
+/// ```
+/// use needletail::orf::{find_orfs, OrfParams};
+///
+/// let seq = b"CCATGAAACCCGGGTTTTAGCC";
+/// let orfs = find_orfs(seq, &OrfParams::default().min_len(3));
+/// assert_eq!(orfs.len(), 1);
+/// assert_eq!((orfs[0].start, orfs[0].end), (2, 20));
+/// assert_eq!(orfs[0].protein, b"MKPGF");
+/// ```
+pub fn find_orfs(seq: &[u8], params: &OrfParams) -> Vec<Orf> {
+    let rc = seq.reverse_complement();
+    let mut orfs = Vec::new();
+    for frame in Frame::all() {
+        let strand_seq = match frame.strand {
+            Strand::Forward => seq,
+            Strand::Reverse => &rc[..],
+        };
+        find_frame_orfs(strand_seq, frame, params, &mut orfs);
+    }
+
+    if !params.overlapping {
+        // keep the longest ORFs first
+        orfs.sort_by(|a, b| b.len().cmp(&a.len()).then(a.start.cmp(&b.start)));
+        let mut kept: Vec<Orf> = Vec::new();
+        for orf in orfs {
+            if kept
+                .iter()
+                .all(|k| orf.end <= k.start || orf.start >= k.end)
+            {
+                kept.push(orf);
+            }
+        }
+        orfs = kept;
+    }
+    orfs.sort_by_key(|orf| (orf.start, orf.end));
+    orfs
+}
+
+fn find_frame_orfs(strand_seq: &[u8], frame: Frame, params: &OrfParams, orfs: &mut Vec<Orf>) {
+    let len = strand_seq.len();
+    let offset = frame.offset as usize;
+    if offset >= len {
+        return;
+    }
+    let codons: Vec<&[u8]> = strand_seq[offset..].chunks_exact(3).collect();
+    let protein: Vec<u8> = codons
+        .iter()
+        .map(|c| params.table.translate_codon(c))
+        .collect();
+
+    let mut add_orf = |start_codon: usize, end_codon: usize, has_stop: bool| {
+        let aa_len = end_codon - start_codon - usize::from(has_stop);
+        if aa_len < params.min_len {
+            return;
+        }
+        // coordinates on the strand
+        let start = offset + 3 * start_codon;
+        let end = offset + 3 * end_codon;
+        let (start, end) = match frame.strand {
+            Strand::Forward => (start, end),
+            Strand::Reverse => (len - end, len - start),
+        };
+        let mut orf_protein = protein[start_codon..start_codon + aa_len].to_vec();
+        // alternative start codons still code for methionine when initiating
+        if let Some(first) = orf_protein.first_mut() {
+            *first = b'M';
+        }
+        orfs.push(Orf {
+            start,
+            end,
+            frame,
+            protein: orf_protein,
+            has_stop,
+        });
+    };
+
+    let mut open: Vec<usize> = Vec::new();
+    for (i, codon) in codons.iter().enumerate() {
+        if protein[i] == b'*' {
+            for start in open.drain(..) {
+                add_orf(start, i + 1, true);
+            }
+            continue;
+        }
+        if (params.nested || open.is_empty()) && is_start(codon, &params.start_codons) {
+            open.push(i);
+        }
+    }
+    if params.incomplete {
+        for start in open {
+            add_orf(start, codons.len(), false);
+        }
+    }
+}
+
+#[inline]
+fn is_start(codon: &[u8], start_codons: &[[u8; 3]]) -> bool {
+    let codon = [
+        codon[0].to_ascii_uppercase(),
+        codon[1].to_ascii_uppercase(),
+        codon[2].to_ascii_uppercase(),
+    ];
+    start_codons.contains(&codon)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_find_orfs_on_both_strands() {
+        let fwd = b"CCATGAAACCCGGGTTTTAGCC";
+        let seq = [&fwd[..], b"TT", &fwd.reverse_complement()].concat();
+        let orfs = find_orfs(&seq, &OrfParams::default().min_len(3));
+        assert_eq!(orfs.len(), 2);
+        assert_eq!(orfs[0].strand(), Strand::Forward);
+        assert_eq!(orfs[1].strand(), Strand::Reverse);
+        assert_eq!(orfs[0].protein, orfs[1].protein);
+        assert_eq!((orfs[1].start, orfs[1].end), (26, 44));
+        assert_eq!(
+            seq[orfs[1].start..orfs[1].end].reverse_complement(),
+            &fwd[2..20]
+        );
+        assert!(find_orfs(&seq, &OrfParams::default()).is_empty());
+    }
+
+    #[test]
+    fn can_find_nested_and_incomplete_orfs() {
+        let seq = b"ATGAAAATGCCCTGAATGGGG";
+        let params = OrfParams::default().min_len(1);
+        let orfs = find_orfs(seq, &params);
+        assert_eq!(orfs.len(), 1);
+        assert_eq!(orfs[0].protein, b"MKMP");
+
+        let orfs = find_orfs(seq, &params.clone().nested(true));
+        assert_eq!(orfs.len(), 2);
+        assert_eq!(orfs[1].protein, b"MP");
+
+        let orfs = find_orfs(seq, &params.clone().incomplete(true));
+        assert_eq!(orfs.len(), 2);
+        assert_eq!(orfs[1].protein, b"MG");
+        assert!(!orfs[1].has_stop);
+        assert_eq!(orfs[1].end, seq.len());
+    }
+
+    #[test]
+    fn can_use_other_start_codons_and_drop_overlaps() {
+        let seq = b"TTGAAATAGGATGAAATGAAAGGGTAA";
+        let params = OrfParams::default().min_len(1);
+        assert_eq!(find_orfs(seq, &params).len(), 2);
+        let alternative = params.clone().start_codons(&[b"ATG", b"ttg"]);
+        let orfs = find_orfs(seq, &alternative);
+        assert_eq!(orfs.len(), 3);
+        assert_eq!(orfs[0].protein, b"MK");
+        // ATGAAATGA (frame 2) overlaps the longer ATGAAAGGGTAA (frame 0)
+        let orfs = find_orfs(seq, &params.clone().overlapping(false));
+        assert_eq!(orfs.len(), 1);
+        assert_eq!(orfs[0].protein, b"MKG");
+    }
+}
@@ -0,0 +1,262 @@
+//! Helpers to process the records of a reader on several threads with rayon.
+//! Only available with the `parallel` feature.
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+
+use rayon::prelude::*;
+
+use crate::bitkmer::{CanonicalBitKmer, CanonicalBitKmers};
+use crate::errors::ParseError;
+use crate::parser::{read_records, FastxReader, OwnedRecord};
+use crate::Sequence;
+
+/// How many records are read before being handed to the thread pool
+const BATCH_SIZE: usize = 1024;
+
+/// Reads up to `BATCH_SIZE` normalized sequences
+fn read_batch<R: FastxReader + ?Sized>(reader: &mut R) -> Result<Vec<Vec<u8>>, ParseError> {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    while batch.len() < BATCH_SIZE {
+        match reader.next() {
+            Some(record) => batch.push(record?.normalize(false).into_owned()),
+            None => break,
+        }
+    }
+    Ok(batch)
+}
+
+/// The order of the results of `parallel_for_each`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultOrder {
+    /// The results are in the order of the batches in the file
+    Ordered,
+    /// The results are in the order the batches were processed in, which
+    /// saves sorting them
+    Unordered,
+}
+
+/// Parallel processing methods for every `FastxReader`
+pub trait ParallelFastxReader: FastxReader {
+    /// Runs `f` on every valid canonical kmer of every record on the rayon
+    /// thread pool and returns the merged results.
+    ///
+    /// Each thread accumulates its results in a state created with `init`;
+    /// the states are then combined with `merge`. Records are read in batches
+    /// while the previous batch is being processed.
+    ///
+    /// ```
+    /// use needletail::parse_fastx_file;
+    /// use needletail::parallel::ParallelFastxReader;
+    ///
+    /// let mut reader = parse_fastx_file("tests/data/28S.fasta").unwrap();
+    /// let n_kmers = reader
+    ///     .process_kmers_parallel(21, || 0, |n, _kmer| *n += 1, |a, b| a + b)
+    ///     .unwrap();
+    /// assert!(n_kmers > 0);
+    /// ```
+    fn process_kmers_parallel<S, I, F, M>(
+        &mut self,
+        k: u8,
+        init: I,
+        f: F,
+        merge: M,
+    ) -> Result<S, ParseError>
+    where
+        S: Send,
+        I: Fn() -> S + Sync + Send,
+        F: Fn(&mut S, CanonicalBitKmer) + Sync + Send,
+        M: Fn(S, S) -> S + Sync + Send,
+    {
+        let mut result = init();
+        let mut batch = read_batch(self)?;
+        while !batch.is_empty() {
+            let (next_batch, batch_result) = rayon::join(
+                || read_batch(self),
+                || {
+                    batch
+                        .par_iter()
+                        .fold(&init, |mut state, seq| {
+                            for kmer in CanonicalBitKmers::new(seq, k) {
+                                f(&mut state, kmer);
+                            }
+                            state
+                        })
+                        .reduce(&init, &merge)
+                },
+            );
+            result = merge(result, batch_result);
+            batch = next_batch?;
+        }
+        Ok(result)
+    }
+
+    /// Reads batches of up to `chunk_size` records on the current thread and
+    /// runs `f` on them on the rayon thread pool, returning the result of
+    /// every batch. `f` always runs on a thread of the pool, never on the
+    /// current one.
+    ///
+    /// At most twice as many batches as there are threads are in flight: the
+    /// current thread then blocks until one of them is done, so the memory
+    /// used doesn't depend on the size of the file. Don't call it from a
+    /// thread of the pool itself (e.g. in a `par_iter`), as it could wait
+    /// for batches queued behind it. If a record can't be parsed, the batches
+    /// already read are still processed but the error is returned. If `f`
+    /// panics, the panic is resumed once all the batches are done.
+    ///
+    /// ```
+    /// use needletail::parse_fastx_file;
+    /// use needletail::parallel::{ParallelFastxReader, ResultOrder};
+    ///
+    /// let mut reader = parse_fastx_file("tests/data/PRJNA271013_head.fq").unwrap();
+    /// let lengths = reader
+    ///     .parallel_for_each(100, ResultOrder::Ordered, |batch| {
+    ///         batch.iter().map(|r| r.seq.len()).sum::<usize>()
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(lengths.len(), 20);
+    /// ```
+    fn parallel_for_each<T, F>(
+        &mut self,
+        chunk_size: usize,
+        order: ResultOrder,
+        f: F,
+    ) -> Result<Vec<T>, ParseError>
+    where
+        T: Send,
+        F: Fn(Vec<OwnedRecord>) -> T + Sync + Send,
+    {
+        assert!(chunk_size > 0, "chunk_size must be at least 1");
+        let max_in_flight = 2 * rayon::current_num_threads();
+        let (tx, rx) = mpsc::channel();
+        let mut results = Vec::new();
+        let f = &f;
+        // runs on the current thread, which only reads and waits for the pool
+        let res = rayon::in_place_scope(|scope| {
+            let mut in_flight = 0;
+            for i in 0usize.. {
+                let mut batch = Vec::with_capacity(chunk_size);
+                read_records(self, &mut batch, chunk_size, usize::MAX)?;
+                if batch.is_empty() {
+                    break;
+                }
+                let tx = tx.clone();
+                scope.spawn(move |_| {
+                    // sent even if `f` panics so the reader doesn't wait forever
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| f(batch)));
+                    // the receiver outlives the scope
+                    tx.send((i, result)).unwrap();
+                });
+                in_flight += 1;
+                // blocks until a batch is done once enough of them are queued
+                if in_flight >= max_in_flight {
+                    results.push(rx.recv().unwrap());
+                    in_flight -= 1;
+                }
+            }
+            Ok(())
+        });
+        drop(tx);
+        results.extend(rx);
+        if order == ResultOrder::Ordered {
+            results.sort_unstable_by_key(|(i, _)| *i);
+        }
+        let results: Vec<_> = results
+            .into_iter()
+            .map(|(_, result)| result.unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect();
+        res.map(|()| results)
+    }
+}
+
+impl<R: FastxReader + ?Sized> ParallelFastxReader for R {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::count::{CountingMode, KmerCounts};
+    use crate::{parse_fastx_file, parse_fastx_reader};
+
+    #[test]
+    fn matches_sequential_counting() {
+        let mut exact = KmerCounts::new(9, CountingMode::Exact);
+        let mut reader = parse_fastx_file("tests/data/28S.fasta").unwrap();
+        exact.add_reader(&mut *reader).unwrap();
+
+        let mut reader = parse_fastx_file("tests/data/28S.fasta").unwrap();
+        let counts = reader
+            .process_kmers_parallel(
+                9,
+                HashMap::new,
+                |counts, kmer| *counts.entry(kmer.kmer).or_insert(0) += 1,
+                |mut a, b| {
+                    for (kmer, count) in b {
+                        *a.entry(kmer).or_insert(0) += count;
+                    }
+                    a
+                },
+            )
+            .unwrap();
+        assert_eq!(counts.len(), exact.len());
+        for (kmer, count) in counts {
+            assert_eq!(exact.get(kmer), count);
+        }
+    }
+
+    #[test]
+    fn can_process_batches() {
+        let mut reader = parse_fastx_file("tests/data/PRJNA271013_head.fq").unwrap();
+        let ids = reader
+            .parallel_for_each(7, ResultOrder::Ordered, |batch| {
+                batch.into_iter().map(|r| r.id).collect::<Vec<_>>()
+            })
+            .unwrap();
+        assert_eq!(ids.len(), 286);
+        assert!(ids[..285].iter().all(|batch| batch.len() == 7));
+
+        let mut reader = parse_fastx_file("tests/data/PRJNA271013_head.fq").unwrap();
+        let mut sequential = Vec::new();
+        while let Some(record) = reader.next() {
+            sequential.push(record.unwrap().name().to_vec());
+        }
+        assert_eq!(ids.concat(), sequential);
+
+        let mut reader = parse_fastx_file("tests/data/PRJNA271013_head.fq").unwrap();
+        let mut sizes = reader
+            .parallel_for_each(7, ResultOrder::Unordered, |batch| batch.len())
+            .unwrap();
+        sizes.sort_unstable();
+        assert_eq!(sizes.iter().sum::<usize>(), 2000);
+        assert_eq!(sizes[0], 5);
+
+        let mut reader = parse_fastx_file("tests/data/PRJNA271013_head.fq").unwrap();
+        let on_pool = reader
+            .parallel_for_each(7, ResultOrder::Unordered, |_| {
+                rayon::current_thread_index().is_some()
+            })
+            .unwrap();
+        assert!(on_pool.into_iter().all(|b| b));
+    }
+
+    #[test]
+    #[should_panic(expected = "bad batch")]
+    fn resumes_panics() {
+        let mut reader = parse_fastx_file("tests/data/PRJNA271013_head.fq").unwrap();
+        let _ = reader.parallel_for_each(1, ResultOrder::Ordered, |batch| {
+            if batch[0].id.ends_with(b"2") {
+                panic!("bad batch");
+            }
+        });
+    }
+
+    #[test]
+    fn returns_parse_errors() {
+        let mut reader = parse_fastx_reader(&b"@a\nACGT\n+\nII\n"[..]).unwrap();
+        let res = reader.process_kmers_parallel(2, || 0, |n, _| *n += 1, |a, b| a + b);
+        assert!(res.is_err());
+        let mut reader = parse_fastx_reader(&b"@a\nACGT\n+\nII\n"[..]).unwrap();
+        let res = reader.parallel_for_each(1, ResultOrder::Ordered, |batch| batch.len());
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! Mapping of positions in BGZF files (blocked gzip, as used by `bgzip` and
+//! `samtools`) to their virtual offsets
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The compressed and uncompressed offsets of the blocks of a BGZF file,
+/// read from the block headers without decompressing anything.
+///
+/// The readers give the position of the records in the decompressed stream
+/// (`Position::byte`), `virtual_offset` converts them into BGZF virtual
+/// offsets that can be used to seek in the compressed file, like in `.fai`
+/// and `.gzi` based indices.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BgzfIndex {
+    /// (compressed offset, uncompressed offset) of the start of each block
+    blocks: Vec<(u64, u64)>,
+    uncompressed_len: u64,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid BGZF file: {msg}"),
+    )
+}
+
+impl BgzfIndex {
+    /// Reads the block headers of a BGZF file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads the block headers of a BGZF stream, seeking over the
+    /// compressed data
+    pub fn from_reader<R: Read + Seek>(mut reader: R) -> io::Result<Self> {
+        let mut index = BgzfIndex::default();
+        let mut compressed_offset = reader.stream_position()?;
+        let mut header = [0u8; 12];
+        loop {
+            match reader.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            if header[..4] != [0x1f, 0x8b, 8, 4] {
+                return Err(invalid_data("not a gzip block with extra fields"));
+            }
+            let mut extra = vec![0; u16::from_le_bytes([header[10], header[11]]) as usize];
+            reader.read_exact(&mut extra)?;
+            let block_size = find_block_size(&extra)
+                .ok_or_else(|| invalid_data("missing BC field in block header"))?;
+            // the uncompressed size is in the last 4 bytes of the block
+            let header_len = 12 + extra.len() as u64;
+            if block_size < header_len + 8 {
+                return Err(invalid_data("block too small"));
+            }
+            reader.seek(SeekFrom::Current((block_size - header_len - 4) as i64))?;
+            let mut isize = [0u8; 4];
+            reader.read_exact(&mut isize)?;
+
+            index
+                .blocks
+                .push((compressed_offset, index.uncompressed_len));
+            index.uncompressed_len += u64::from(u32::from_le_bytes(isize));
+            compressed_offset += block_size;
+        }
+        Ok(index)
+    }
+
+    /// The number of blocks, including the empty one at the end of the file
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Converts an offset in the decompressed stream into a virtual offset:
+    /// the compressed offset of its block shifted by 16 bits, plus the offset
+    /// in the decompressed block. `None` if it is past the end of the file.
+    pub fn virtual_offset(&self, uncompressed_offset: u64) -> Option<u64> {
+        if uncompressed_offset > self.uncompressed_len {
+            return None;
+        }
+        let i = self
+            .blocks
+            .partition_point(|(_, start)| *start <= uncompressed_offset)
+            .checked_sub(1)?;
+        let (compressed, start) = self.blocks[i];
+        Some((compressed << 16) | (uncompressed_offset - start))
+    }
+}
+
+/// Size of the empty block ending BGZF files
+#[cfg(feature = "flate2")]
+const EOF_BLOCK_LEN: usize = 28;
+/// Size of the header of a BGZF block, up to the `BC` subfield
+#[cfg(feature = "flate2")]
+const HEADER_LEN: usize = 18;
+
+/// Passes a gzip stream through, failing at the end if it is a BGZF file
+/// that doesn't end with the empty end-of-file block, i.e. a file that was cut
+/// exactly between two blocks, which decompresses without errors otherwise.
+#[cfg(feature = "flate2")]
+pub(crate) struct EofCheck<R> {
+    inner: R,
+    /// The first bytes of the stream, to know if it is BGZF
+    head: Vec<u8>,
+    /// The last bytes read
+    tail: Vec<u8>,
+}
+
+#[cfg(feature = "flate2")]
+impl<R: Read> EofCheck<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            head: Vec::with_capacity(HEADER_LEN),
+            tail: Vec::with_capacity(2 * EOF_BLOCK_LEN),
+        }
+    }
+
+    fn is_bgzf(&self) -> bool {
+        self.head.len() == HEADER_LEN
+            && self.head[..4] == [0x1f, 0x8b, 8, 4]
+            && find_block_size(&self.head[12..]).is_some()
+    }
+
+    /// Whether the last block is an empty BGZF block
+    fn ends_with_eof_block(&self) -> bool {
+        self.tail.len() == EOF_BLOCK_LEN
+            && self.tail[..4] == [0x1f, 0x8b, 8, 4]
+            && self.tail[EOF_BLOCK_LEN - 4..] == [0; 4]
+    }
+}
+
+#[cfg(feature = "flate2")]
+impl<R: Read> Read for EofCheck<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && self.is_bgzf() && !self.ends_with_eof_block() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "BGZF file without its end-of-file block",
+            ));
+        }
+        let missing = HEADER_LEN - self.head.len();
+        self.head.extend_from_slice(&buf[..n.min(missing)]);
+        self.tail
+            .extend_from_slice(&buf[n.saturating_sub(EOF_BLOCK_LEN)..n]);
+        let extra = self.tail.len().saturating_sub(EOF_BLOCK_LEN);
+        self.tail.drain(..extra);
+        Ok(n)
+    }
+}
+
+/// Finds the size of the block in the `BC` subfield of the gzip extra field
+fn find_block_size(mut extra: &[u8]) -> Option<u64> {
+    while extra.len() >= 4 {
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len)?;
+        if extra[..2] == *b"BC" && len == 2 {
+            return Some(u64::from(u16::from_le_bytes([data[0], data[1]])) + 1);
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+#[cfg(all(test, feature = "flate2"))]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use flate2::write::GzEncoder;
+    use flate2::{Compression, GzBuilder};
+
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    fn bgzip(chunks: &[&[u8]]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for chunk in chunks.iter().chain([&&b""[..]]) {
+            let mut encoder: GzEncoder<Vec<u8>> = GzBuilder::new()
+                .extra(b"BC\x02\x00\x00\x00".to_vec())
+                .write(Vec::new(), Compression::default());
+            encoder.write_all(chunk).unwrap();
+            let mut block = encoder.finish().unwrap();
+            let size = (block.len() - 1) as u16;
+            block[16..18].copy_from_slice(&size.to_le_bytes());
+            out.extend(block);
+        }
+        out
+    }
+
+    #[test]
+    fn can_compute_virtual_offsets() {
+        let data = bgzip(&[
+            b"@r1\nACGT\n+\nIIII\n@r2\nAC",
+            b"GT\n+\nIIII\n@r3\nA\n+\nI\n",
+        ]);
+        let index = BgzfIndex::from_reader(Cursor::new(&data)).unwrap();
+        assert_eq!(index.len(), 3);
+        let second_block = index.blocks[1].0;
+
+        let mut reader = parse_fastx_reader(&data[..]).unwrap();
+        let mut offsets = Vec::new();
+        while let Some(record) = reader.next() {
+            let record = record.unwrap();
+            offsets.push(index.virtual_offset(record.position().byte()).unwrap());
+        }
+        assert_eq!(offsets, vec![0, 16, (second_block << 16) | 10]);
+        assert_eq!(index.virtual_offset(1000), None);
+    }
+
+    #[test]
+    fn detects_truncated_files() {
+        let data = bgzip(&[b"@r1\nACGT\n+\nIIII\n", b"@r2\nAC\n+\nII\n"]);
+        let last_block = BgzfIndex::from_reader(Cursor::new(&data)).unwrap().blocks[2].0;
+        let mut reader = parse_fastx_reader(&data[..]).unwrap();
+        while let Some(record) = reader.next() {
+            assert!(record.is_ok());
+        }
+
+        // cut between two blocks
+        let mut reader = parse_fastx_reader(&data[..last_block as usize]).unwrap();
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::Truncated);
+        assert!(reader.next().is_none());
+
+        // cut in the middle of a plain gzip stream
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&b">r1\nACGT\n".repeat(100)).unwrap();
+        let data = encoder.finish().unwrap();
+        let mut reader = parse_fastx_reader(&data[..data.len() - 4]).unwrap();
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::Truncated);
+        assert_eq!(e.kind.as_str(), "truncated");
+    }
+
+    #[test]
+    fn rejects_plain_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b">r1\nACGT\n").unwrap();
+        let data = encoder.finish().unwrap();
+        assert!(BgzfIndex::from_reader(Cursor::new(&data)).is_err());
+        assert!(BgzfIndex::from_reader(Cursor::new(b"")).unwrap().is_empty());
+    }
+}
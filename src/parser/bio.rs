@@ -0,0 +1,137 @@
+//! Conversions between the records of needletail and the ones of rust-bio
+//! (`bio::io::fasta::Record` and `bio::io::fastq::Record`).
+//!
+//! rust-bio stores ids and descriptions as strings: invalid UTF-8 is
+//! replaced when converting to its records.
+use ::bio::io::{fasta, fastq};
+
+use crate::errors::SequenceError;
+use crate::parser::owned::OwnedRecord;
+use crate::parser::record::SequenceRecord;
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Fails if the record has no qualities, i.e. comes from a FASTA file
+fn fastq_record(
+    id: &[u8],
+    desc: Option<&[u8]>,
+    seq: &[u8],
+    qual: Option<&[u8]>,
+) -> Result<fastq::Record, SequenceError> {
+    let qual = qual.ok_or(SequenceError::MissingQuality)?;
+    Ok(fastq::Record::with_attrs(
+        &lossy(id),
+        desc.map(lossy).as_deref(),
+        seq,
+        qual,
+    ))
+}
+
+impl From<&OwnedRecord> for fasta::Record {
+    fn from(record: &OwnedRecord) -> Self {
+        fasta::Record::with_attrs(
+            &lossy(&record.id),
+            record.desc.as_deref().map(lossy).as_deref(),
+            &record.seq,
+        )
+    }
+}
+
+impl TryFrom<&OwnedRecord> for fastq::Record {
+    type Error = SequenceError;
+
+    fn try_from(record: &OwnedRecord) -> Result<Self, Self::Error> {
+        fastq_record(
+            &record.id,
+            record.desc.as_deref(),
+            &record.seq,
+            record.qual.as_deref(),
+        )
+    }
+}
+
+impl From<&SequenceRecord<'_>> for fasta::Record {
+    fn from(record: &SequenceRecord<'_>) -> Self {
+        fasta::Record::with_attrs(
+            &lossy(record.name()),
+            record.description().map(lossy).as_deref(),
+            &record.seq(),
+        )
+    }
+}
+
+impl TryFrom<&SequenceRecord<'_>> for fastq::Record {
+    type Error = SequenceError;
+
+    fn try_from(record: &SequenceRecord<'_>) -> Result<Self, Self::Error> {
+        fastq_record(
+            record.name(),
+            record.description(),
+            &record.seq(),
+            record.qual(),
+        )
+    }
+}
+
+impl From<&fasta::Record> for OwnedRecord {
+    fn from(record: &fasta::Record) -> Self {
+        OwnedRecord {
+            id: record.id().as_bytes().to_vec(),
+            desc: record.desc().map(|d| d.as_bytes().to_vec()),
+            seq: record.seq().to_vec(),
+            qual: None,
+            ..OwnedRecord::default()
+        }
+    }
+}
+
+impl From<&fastq::Record> for OwnedRecord {
+    fn from(record: &fastq::Record) -> Self {
+        OwnedRecord {
+            id: record.id().as_bytes().to_vec(),
+            desc: record.desc().map(|d| d.as_bytes().to_vec()),
+            seq: record.seq().to_vec(),
+            qual: Some(record.qual().to_vec()),
+            ..OwnedRecord::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn can_convert_fasta_records() {
+        let mut reader = parse_fastx_reader(&b">r1 some read\nACGT\nAC\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let bio_record = fasta::Record::from(&record);
+        assert_eq!(bio_record.id(), "r1");
+        assert_eq!(bio_record.desc(), Some("some read"));
+        assert_eq!(bio_record.seq(), b"ACGTAC");
+        assert_eq!(
+            fastq::Record::try_from(&record).unwrap_err(),
+            SequenceError::MissingQuality
+        );
+
+        let owned = OwnedRecord::from(&record);
+        assert_eq!(OwnedRecord::from(&fasta::Record::from(&owned)), owned);
+    }
+
+    #[test]
+    fn can_convert_fastq_records() {
+        let mut reader = parse_fastx_reader(&b"@r1\nACGT\n+\nII#I\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let bio_record = fastq::Record::try_from(&record).unwrap();
+        assert_eq!(bio_record.id(), "r1");
+        assert_eq!(bio_record.desc(), None);
+        assert_eq!(bio_record.qual(), b"II#I");
+
+        let owned = OwnedRecord::from(&record);
+        let bio_record = fastq::Record::try_from(&owned).unwrap();
+        assert_eq!(OwnedRecord::from(&bio_record), owned);
+    }
+}
@@ -0,0 +1,156 @@
+//! Counting the records and bases of files without parsing the records
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use memchr::memchr;
+
+use crate::errors::ParseError;
+use crate::parser::decompress;
+
+/// Size of the blocks the files are read in
+const BLOCK_SIZE: usize = 1 << 20;
+
+/// Goes through the lines of a file a block at a time, only looking at
+/// where they start and how long they are
+struct LineCounter {
+    fastq: bool,
+    records: u64,
+    bases: u64,
+    /// Complete lines seen so far
+    lines: u64,
+    /// Whether the next byte starts a line
+    line_start: bool,
+    /// Whether the current line is a sequence line
+    in_seq: bool,
+}
+
+impl LineCounter {
+    fn new(first_byte: u8) -> Result<Self, ParseError> {
+        if first_byte != b'>' && first_byte != b'@' {
+            return Err(ParseError::new_unknown_format(first_byte));
+        }
+        Ok(LineCounter {
+            fastq: first_byte == b'@',
+            records: 0,
+            bases: 0,
+            lines: 0,
+            line_start: true,
+            in_seq: false,
+        })
+    }
+
+    fn feed(&mut self, block: &[u8]) {
+        let mut start = 0;
+        while start < block.len() {
+            let end = memchr(b'\n', &block[start..]).map(|p| start + p);
+            let line = &block[start..end.unwrap_or(block.len())];
+            if self.line_start && !line.is_empty() {
+                let (is_header, is_seq) = if self.fastq {
+                    (self.lines.is_multiple_of(4), self.lines % 4 == 1)
+                } else {
+                    (line[0] == b'>', line[0] != b'>')
+                };
+                self.records += u64::from(is_header);
+                self.in_seq = is_seq;
+            }
+            if self.in_seq {
+                self.bases += (line.len() - bytecount::count(line, b'\r')) as u64;
+            }
+            match end {
+                Some(end) => {
+                    self.lines += 1;
+                    self.line_start = true;
+                    // empty lines are not sequence lines
+                    self.in_seq = self.fastq && self.lines % 4 == 1;
+                    start = end + 1;
+                }
+                None => {
+                    self.line_start = false;
+                    start = block.len();
+                }
+            }
+        }
+    }
+}
+
+/// Counts the records and bases of a FASTA/FASTQ file (compressed or not)
+fn count<P: AsRef<Path>>(path: P) -> Result<LineCounter, ParseError> {
+    let (mut reader, first_byte) = decompress(File::open(&path)?)?;
+    let mut counter = LineCounter::new(first_byte)?;
+    let mut block = vec![0; BLOCK_SIZE];
+    loop {
+        let n = match reader.read(&mut block) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        counter.feed(&block[..n]);
+    }
+    Ok(counter)
+}
+
+/// Counts the records of a FASTA/FASTQ file (compressed or not) much faster
+/// than parsing it, by only looking at the line starts. The records are not
+/// validated and FASTQ records are expected to be on 4 lines.
+///
+/// ```
+/// use needletail::parser::count_records;
+///
+/// assert_eq!(count_records("tests/data/test.fa").unwrap(), 2);
+/// ```
+pub fn count_records<P: AsRef<Path>>(path: P) -> Result<u64, ParseError> {
+    Ok(count(path)?.records)
+}
+
+/// Counts the bases of all the records of a FASTA/FASTQ file (compressed or
+/// not), see `count_records`
+///
+/// ```
+/// use needletail::parser::count_bases;
+///
+/// assert_eq!(count_bases("tests/data/test.fa").unwrap(), 14);
+/// ```
+pub fn count_bases<P: AsRef<Path>>(path: P) -> Result<u64, ParseError> {
+    Ok(count(path)?.bases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_fastx_file;
+
+    fn count_blocks(data: &[u8], block_size: usize) -> (u64, u64) {
+        let mut counter = LineCounter::new(data[0]).unwrap();
+        for block in data.chunks(block_size) {
+            counter.feed(block);
+        }
+        (counter.records, counter.bases)
+    }
+
+    #[test]
+    fn can_count_across_blocks() {
+        let fasta = b">a desc\nACGT\r\nAC\r\n\n>b\n\n>c\nA";
+        let fastq = b"@a\nACGT\n+\nIIII\n@b\r\nAC\r\n+\r\nII\r\n\n";
+        for block_size in [1, 2, 3, 7, 100] {
+            assert_eq!(count_blocks(fasta, block_size), (3, 7));
+            assert_eq!(count_blocks(fastq, block_size), (2, 6));
+        }
+    }
+
+    #[test]
+    fn counts_like_the_parser() {
+        for path in ["tests/data/PRJNA271013_head.fq", "tests/data/test.fa"] {
+            let mut reader = parse_fastx_file(path).unwrap();
+            let (mut records, mut bases) = (0, 0);
+            while let Some(record) = reader.next() {
+                records += 1;
+                bases += record.unwrap().num_bases() as u64;
+            }
+            assert_eq!(count_records(path).unwrap(), records);
+            assert_eq!(count_bases(path).unwrap(), bases);
+        }
+        assert!(count_records("tests/data/missing.fa").is_err());
+    }
+}
@@ -1,12 +1,13 @@
 //! The vast majority of the code is taken from https://github.com/markschl/seq_io/blob/master/src/fasta.rs
 
 use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::options::ReaderOptions;
 use crate::parser::record::SequenceRecord;
 use crate::parser::utils::{
-    fill_buf, find_line_ending, grow_to, trim_cr, FastxReader, Format, LineEnding, Position,
-    BUFSIZE,
+    fill_buf, find_line_ending, grow_within_limits, trim_cr, FastxReader, Format, LineEnding,
+    Position, SeenIds, BUFSIZE,
 };
-use memchr::{memchr2, Memchr};
+use memchr::{memchr, memchr2, Memchr};
 use std::borrow::Cow;
 use std::fs::File;
 use std::io;
@@ -22,6 +23,12 @@ pub struct BufferPosition {
     /// the last byte of the last sequence line (including line terminator if present).
     /// Therefore, the length of this Vec should never be 0.
     pub(crate) seq_pos: Vec<usize>,
+    /// Whether the record has `;` comment lines, only looked for with the
+    /// `legacy_fasta` option
+    pub(crate) has_comments: bool,
+    /// The sequence lines of a record with comments, without the comment
+    /// lines, returned by `raw_seq` instead of the buffer
+    pub(crate) uncommented: Vec<u8>,
 }
 
 impl BufferPosition {
@@ -34,6 +41,7 @@ impl BufferPosition {
     fn reset(&mut self, start: usize) {
         self.seq_pos.clear();
         self.start = start;
+        self.has_comments = false;
     }
 
     #[inline]
@@ -52,8 +60,10 @@ impl BufferPosition {
     }
 
     #[inline]
-    pub(crate) fn raw_seq<'a>(&self, buffer: &'a [u8]) -> &'a [u8] {
-        if self.seq_pos.len() > 1 {
+    pub(crate) fn raw_seq<'a>(&'a self, buffer: &'a [u8]) -> &'a [u8] {
+        if self.has_comments {
+            &self.uncommented
+        } else if self.seq_pos.len() > 1 {
             let start = *self.seq_pos.first().unwrap() + 1;
             let end = *self.seq_pos.last().unwrap();
             trim_cr(&buffer[start..end])
@@ -62,16 +72,25 @@ impl BufferPosition {
         }
     }
 
+    /// Copies the sequence lines that aren't `;` comments to `uncommented`
+    fn remove_comments(&mut self, buffer: &[u8]) {
+        self.uncommented.clear();
+        let start = *self.seq_pos.first().unwrap() + 1;
+        let end = *self.seq_pos.last().unwrap();
+        let lines = trim_cr(&buffer[start..end])
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.starts_with(b";"));
+        for line in lines {
+            if !self.uncommented.is_empty() {
+                self.uncommented.push(b'\n');
+            }
+            self.uncommented.extend_from_slice(line);
+        }
+    }
+
     #[inline]
-    pub(crate) fn seq<'a>(&self, buffer: &'a [u8]) -> Cow<'a, [u8]> {
-        // TODO: make that DRY
-        let seq = if self.seq_pos.len() > 1 {
-            let start = *self.seq_pos.first().unwrap() + 1;
-            let end = *self.seq_pos.last().unwrap();
-            trim_cr(&buffer[start..end])
-        } else {
-            b""
-        };
+    pub(crate) fn seq<'a>(&'a self, buffer: &'a [u8]) -> Cow<'a, [u8]> {
+        let seq = self.raw_seq(buffer);
 
         // first part is a fast check to see if we need to do any allocations
         let mut i;
@@ -118,6 +137,8 @@ pub struct Reader<R: io::Read> {
     position: Position,
     finished: bool,
     line_ending: Option<LineEnding>,
+    options: ReaderOptions,
+    seen_ids: SeenIds,
 }
 
 impl<R> Reader<R>
@@ -151,13 +172,24 @@ where
             buf_pos: BufferPosition {
                 start: 0,
                 seq_pos: Vec::with_capacity(1),
+                has_comments: false,
+                uncommented: Vec::new(),
             },
             position: Position::new(0, 0),
             search_pos: 0,
             finished: false,
             line_ending: None,
+            options: ReaderOptions::default(),
+            seen_ids: SeenIds::default(),
         }
     }
+
+    /// Sets the options of the reader, see [`ReaderOptions`](struct.ReaderOptions.html)
+    #[inline]
+    pub fn with_options(mut self, options: ReaderOptions) -> Self {
+        self.options = options;
+        self
+    }
 }
 
 impl Reader<File> {
@@ -191,6 +223,7 @@ where
     fn next_pos(&mut self) {
         self.position.line += self.buf_pos.seq_pos.len() as u64;
         self.position.byte += (self.search_pos - self.buf_pos.start) as u64;
+        self.position.record += 1;
         self.buf_pos.reset(self.search_pos);
     }
 
@@ -230,10 +263,14 @@ where
             }
 
             self.buf_pos.seq_pos.push(pos);
-            if self.get_buf()[next_line_start] == b'>' {
-                // complete record was found
-                self.search_pos = next_line_start;
-                return true;
+            match self.get_buf()[next_line_start] {
+                b'>' => {
+                    // complete record was found
+                    self.search_pos = next_line_start;
+                    return true;
+                }
+                b';' if self.options.legacy_fasta => self.buf_pos.has_comments = true,
+                _ => {}
             }
         }
 
@@ -251,14 +288,14 @@ where
         loop {
             if self.buf_pos.start == 0 {
                 // first record -> buffer too small
-                self.grow();
+                self.grow()?;
             } else {
                 // not the first record -> buffer may be big enough
                 self.make_room();
             }
 
             // fill up remaining buffer
-            fill_buf(&mut self.buf_reader)?;
+            fill_buf(&mut self.buf_reader).inspect_err(|_| self.finished = true)?;
 
             if self.find() {
                 return Ok(true);
@@ -267,11 +304,13 @@ where
     }
 
     /// Grow internal buffer as needed
-    fn grow(&mut self) {
+    fn grow(&mut self) -> Result<(), ParseError> {
         let cap = self.buf_reader.capacity();
-        let new_size = grow_to(cap);
+        let new_size = grow_within_limits(self.get_buf(), cap, &self.options, &self.position)
+            .inspect_err(|_| self.finished = true)?;
         let additional = new_size - cap;
         self.buf_reader.reserve(additional);
+        Ok(())
     }
 
     /// Move incomplete bytes to start of buffer
@@ -287,8 +326,23 @@ where
     }
 }
 
+/// The length and number of the blank and `;` comment lines at the start of
+/// `buf`, skipped in old-style FASTA files
+fn leading_lines(buf: &[u8]) -> (usize, u64) {
+    let (mut start, mut lines) = (0, 0);
+    while let Some(end) = memchr(b'\n', &buf[start..]) {
+        let line = &buf[start..start + end];
+        if !(trim_cr(line).is_empty() || line.starts_with(b";")) {
+            break;
+        }
+        start += end + 1;
+        lines += 1;
+    }
+    (start, lines)
+}
+
 impl<R: io::Read + Send> FastxReader for Reader<R> {
-    fn next(&mut self) -> Option<Result<SequenceRecord, ParseError>> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
         if self.finished {
             return None;
         }
@@ -303,24 +357,39 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
                     }
                 }
                 Err(e) => {
+                    self.finished = true;
                     return Some(Err(e.into()));
                 }
             };
 
-            if self.get_buf()[0] == b'>' {
-                self.position.line = 1;
-                self.position.byte = 0;
-                self.buf_pos.start = 0;
-                self.search_pos = 1;
+            let (start, lines) = if self.options.legacy_fasta {
+                leading_lines(self.get_buf())
             } else {
+                (0, 0)
+            };
+            if start == self.get_buf().len() {
+                // only comments
+                self.finished = true;
+                return None;
+            }
+            if self.get_buf()[start] == b'>' {
+                self.position.line = lines + 1;
+                self.position.byte = start as u64;
+                self.buf_pos.start = start;
+                self.search_pos = start + 1;
+            } else {
+                // the position of the first line that isn't skipped
+                let position = ErrorPosition {
+                    line: lines + 1,
+                    byte: Some(start as u64),
+                    ..ErrorPosition::at(&self.position, None)
+                };
                 return Some(Err(ParseError::new_invalid_start(
-                    self.get_buf()[0],
-                    ErrorPosition {
-                        line: self.position.line,
-                        id: None,
-                    },
+                    self.get_buf()[start],
+                    position,
                     Format::Fasta,
-                )));
+                )
+                .with_snippet(&self.get_buf()[start..])));
             }
         }
 
@@ -347,23 +416,40 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
 
         if self.buf_pos.seq_pos.is_empty() {
             return Some(Err(ParseError::new_unexpected_end(
-                ErrorPosition {
-                    line: self.position.line,
-                    id: None,
-                },
+                ErrorPosition::at(&self.position, None),
                 Format::Fasta,
             )));
         }
+        // the last record goes up to the end of the file
+        let end = if self.finished {
+            self.get_buf().len()
+        } else {
+            self.search_pos
+        };
+        self.position.read_record((end - self.buf_pos.start) as u64);
 
         if self.line_ending.is_none() {
             self.line_ending = self.buf_pos.find_line_ending(self.get_buf());
         }
-        Some(Ok(SequenceRecord::new_fasta(
+        if self.options.strict {
+            let header = self.buf_pos.id(self.buf_reader.buffer());
+            if let Err(e) = self.seen_ids.insert(header, &self.position) {
+                return Some(Err(e));
+            }
+        }
+        if self.buf_pos.has_comments {
+            self.buf_pos.remove_comments(self.buf_reader.buffer());
+        }
+        let record = SequenceRecord::new_fasta(
             self.get_buf(),
             &self.buf_pos,
             &self.position,
             self.line_ending,
-        )))
+        );
+        if let Err(e) = record.check(&self.options) {
+            return Some(Err(e));
+        }
+        Some(Ok(record))
     }
 
     fn position(&self) -> &Position {
@@ -381,6 +467,8 @@ mod tests {
 
     use super::*;
     use crate::errors::ParseErrorKind;
+    use crate::sequence::Alphabet;
+    use crate::Sequence;
 
     fn seq(s: &[u8]) -> Cursor<&[u8]> {
         Cursor::new(s)
@@ -405,6 +493,18 @@ mod tests {
         assert!(reader.next().is_none());
     }
 
+    #[test]
+    fn test_records() {
+        let mut reader = Reader::new(seq(b">test\nACGT\n>test2\nTG\nCA\n"));
+        let records: Vec<_> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].seq, b"TGCA");
+
+        let mut reader = Reader::new(seq(b">test\nACGT\n>test2\nTG\nCA\n"));
+        let sizes: Vec<_> = reader.batches(1).map(|b| b.unwrap().len()).collect();
+        assert_eq!(sizes, vec![1, 1]);
+    }
+
     #[test]
     fn test_wrapped_fasta() {
         let mut reader = Reader::new(seq(b">test\nACGT\nACGT\n>test2\nTGCA\nTG"));
@@ -480,4 +580,119 @@ mod tests {
         assert_eq!(rec.id(), b"shine");
         assert_eq!(rec.raw_seq(), b"AGGAGGU");
     }
+
+    #[test]
+    fn test_legacy_fasta_option() {
+        let data = b"; old file\n\n>a\n;a comment\nAC\nGT\n\n>b\nAC\n;\n";
+        let mut reader = Reader::new(seq(data));
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind,
+            ParseErrorKind::InvalidStart
+        );
+
+        let options = ReaderOptions::new().legacy_fasta(true).strict(true);
+        let mut reader = Reader::new(seq(data)).with_options(options);
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.id(), b"a");
+        assert_eq!(record.start_line_number(), 3);
+        assert_eq!(record.seq(), &b"ACGT"[..]);
+        assert_eq!(record.num_bases(), 4);
+        let mut out = Vec::new();
+        record.write(&mut out, None).unwrap();
+        assert_eq!(out, b">a\nACGT\n");
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.seq(), &b"AC"[..]);
+        assert_eq!(record.raw_seq(), b"AC");
+        assert!(reader.next().is_none());
+
+        let data = b">a\nAC\n;note\nGT\n>b\r\n;note\r\nAC\r\nGT\r\n";
+        let mut reader =
+            Reader::new(seq(data)).with_options(ReaderOptions::new().legacy_fasta(true));
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.raw_seq(), b"AC\nGT");
+        assert_eq!(&record.normalize(false)[..], b"ACGT");
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(record.raw_seq(), b"AC\r\nGT");
+        assert_eq!(&record.normalize(false)[..], b"ACGT");
+
+        let mut reader = Reader::new(seq(b";only comments\n"))
+            .with_options(ReaderOptions::new().legacy_fasta(true));
+        assert!(reader.next().is_none());
+
+        let mut reader =
+            Reader::new(seq(b"; c\nACGT\n")).with_options(ReaderOptions::new().legacy_fasta(true));
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::InvalidStart);
+        assert!(e.msg.ends_with("found 'A"));
+        assert_eq!(e.position.line, 2);
+        assert_eq!(e.position.byte, Some(4));
+    }
+
+    #[test]
+    fn test_strict_option() {
+        let read_all = |data: &'static [u8]| {
+            let mut reader = Reader::new(seq(data)).with_options(ReaderOptions::new().strict(true));
+            let mut errors = Vec::new();
+            while let Some(record) = reader.next() {
+                if let Err(e) = record {
+                    errors.push((e.kind, e.position.line));
+                }
+            }
+            errors
+        };
+        assert!(read_all(b">a\nACGT\n>b desc\nAC\r\nGT\r\n").is_empty());
+        assert_eq!(
+            read_all(b">a\nACGT\n>b\nA\n>a\nC\n"),
+            vec![(ParseErrorKind::DuplicateId, 5)]
+        );
+        assert_eq!(
+            read_all(b">a\n\n>b\nAC!T\n"),
+            vec![
+                (ParseErrorKind::InvalidSequence, 1),
+                (ParseErrorKind::InvalidSequence, 3)
+            ]
+        );
+        assert_eq!(
+            read_all(b">a\nAC\rGT\r\n>b\rdesc\nACGT\n"),
+            vec![
+                (ParseErrorKind::InvalidLineEnding, 1),
+                (ParseErrorKind::InvalidLineEnding, 3)
+            ]
+        );
+        // the alphabet can be changed
+        let options = ReaderOptions::new()
+            .strict(true)
+            .validate(Alphabet::Protein);
+        let mut reader = Reader::new(seq(b">p\nMKV*\n")).with_options(options);
+        assert!(reader.next().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let mut data = b">a\nACGT\n>long\n".to_vec();
+        data.extend(vec![b'A'; 1000]);
+        let options = ReaderOptions::new().max_buffer_size(64);
+        let mut reader = Reader::with_capacity(seq(&data), 16).with_options(options);
+        assert!(reader.next().unwrap().is_ok());
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::LimitExceeded);
+        assert_eq!(e.msg, "Record is longer than the limit of 64 bytes");
+        assert!(reader.next().is_none());
+
+        // complete records are checked too
+        let options = ReaderOptions::new().max_record_len(20).max_header_len(4);
+        let mut reader = Reader::new(seq(&data)).with_options(options);
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind,
+            ParseErrorKind::LimitExceeded
+        );
+
+        let mut data = b">".to_vec();
+        data.extend(vec![b'x'; 1000]);
+        let options = ReaderOptions::new().max_header_len(100);
+        let mut reader = Reader::with_capacity(seq(&data), 16).with_options(options);
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.msg, "Header is longer than the limit of 100 bytes");
+    }
 }
@@ -1,15 +1,19 @@
 //! The vast majority of the code is taken from https://github.com/markschl/seq_io/blob/master/src/fastq.rs
 
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
 
-use crate::errors::{ErrorPosition, ParseError};
+use crate::errors::{ErrorPosition, ParseError, ParseErrorKind};
+use crate::parser::options::{MissingQuality, ReaderOptions};
 use crate::parser::record::SequenceRecord;
 use crate::parser::utils::{
-    fill_buf, find_line_ending, grow_to, trim_cr, FastxReader, Format, LineEnding, Position,
-    BUFSIZE,
+    fill_buf, find_line_ending, grow_within_limits, trim_cr, FastxReader, Format, LineEnding,
+    Position, SeenIds, BUFSIZE,
 };
+use crate::quality::{EncodingGuess, QualityConverter, QualityEncoding};
+use crate::simd::range_prefix_len;
 use memchr::memchr;
 
 /// Represents the position of a record within a buffer
@@ -20,6 +24,23 @@ pub struct BufferPosition {
     pub(crate) seq: usize,
     pub(crate) sep: usize,
     pub(crate) qual: usize,
+    /// Whether the sequence or the qualities are on several lines, only
+    /// possible with the `multiline_fastq` option
+    pub(crate) wrapped: bool,
+}
+
+/// Length of `s` without its line endings
+#[inline]
+fn unwrapped_len(s: &[u8]) -> usize {
+    s.len() - bytecount::count(s, b'\n') - bytecount::count(s, b'\r')
+}
+
+/// `s` without its line endings
+fn unwrap_lines(s: &[u8]) -> Vec<u8> {
+    s.iter()
+        .filter(|b| !matches!(b, b'\n' | b'\r'))
+        .copied()
+        .collect()
 }
 
 impl BufferPosition {
@@ -43,14 +64,64 @@ impl BufferPosition {
         trim_cr(&buffer[self.seq..self.sep - 1])
     }
 
+    /// The sequence without line endings if it is wrapped
+    #[inline]
+    pub(crate) fn unwrapped_seq<'a>(&'a self, buffer: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.wrapped {
+            unwrap_lines(self.seq(buffer)).into()
+        } else {
+            self.seq(buffer).into()
+        }
+    }
+
     #[inline]
     pub(crate) fn qual<'a>(&'a self, buffer: &'a [u8]) -> &'a [u8] {
         trim_cr(&buffer[self.qual..self.end])
     }
 
+    /// The qualities without line endings if they are wrapped
+    #[inline]
+    pub(crate) fn unwrapped_qual<'a>(&'a self, buffer: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.wrapped {
+            unwrap_lines(self.qual(buffer)).into()
+        } else {
+            self.qual(buffer).into()
+        }
+    }
+
+    #[inline]
+    pub(crate) fn num_quals(&self, buffer: &[u8]) -> usize {
+        if self.wrapped {
+            unwrapped_len(self.qual(buffer))
+        } else {
+            self.qual(buffer).len()
+        }
+    }
+
+    /// The number of lines of the record
+    #[inline]
+    pub(crate) fn lines(&self, buffer: &[u8]) -> u64 {
+        if self.wrapped {
+            bytecount::count(&buffer[self.start..self.end], b'\n') as u64 + 1
+        } else {
+            4
+        }
+    }
+
+    /// Whether the quality line is empty or `*` while the sequence isn't
+    #[inline]
+    pub(crate) fn is_qual_missing(&self, buffer: &[u8]) -> bool {
+        let qual = self.qual(buffer);
+        (qual.is_empty() || qual == b"*") && qual.len() != self.num_bases(buffer)
+    }
+
     #[inline]
     pub(crate) fn num_bases<'a>(&'a self, buffer: &'a [u8]) -> usize {
-        self.seq(buffer).len()
+        if self.wrapped {
+            unwrapped_len(self.seq(buffer))
+        } else {
+            self.seq(buffer).len()
+        }
     }
 
     #[inline]
@@ -83,6 +154,13 @@ pub struct Reader<R: io::Read> {
     position: Position,
     finished: bool,
     line_ending: Option<LineEnding>,
+    options: ReaderOptions,
+    /// Set if the qualities need to be converted to Phred+33
+    qual_converter: Option<QualityConverter>,
+    converted_qual: Vec<u8>,
+    /// Whether the quality encoding still needs to be detected
+    detect_encoding: bool,
+    seen_ids: SeenIds,
 }
 
 impl<R> Reader<R>
@@ -116,8 +194,68 @@ where
             position: Position::new(1, 0),
             finished: false,
             line_ending: None,
+            options: ReaderOptions::default(),
+            qual_converter: None,
+            converted_qual: Vec::new(),
+            detect_encoding: false,
+            seen_ids: SeenIds::default(),
         }
     }
+
+    /// Sets the options of the reader, see [`ReaderOptions`](struct.ReaderOptions.html)
+    #[inline]
+    pub fn with_options(mut self, options: ReaderOptions) -> Self {
+        self.set_quality_encoding(options.quality_encoding);
+        self.detect_encoding = options.detect_quality_encoding;
+        self.options = options;
+        self
+    }
+
+    /// The encoding of the qualities of the file, as given in the options or
+    /// detected. The records always have Phred+33 qualities.
+    pub fn quality_encoding(&self) -> QualityEncoding {
+        self.qual_converter
+            .as_ref()
+            .map_or(QualityEncoding::Phred33, |c| c.from())
+    }
+
+    fn set_quality_encoding(&mut self, encoding: QualityEncoding) {
+        self.qual_converter = quality_converter(encoding);
+    }
+
+    /// Guesses the quality encoding from the records in the buffer, which
+    /// only works if they are 4 lines long
+    fn detect_quality_encoding(&mut self) {
+        let buf = self.get_buf();
+        // the last line might be incomplete
+        let n_lines = buf.iter().filter(|b| **b == b'\n').count();
+        let quals = buf.split(|b| *b == b'\n').take(n_lines).skip(3).step_by(4);
+        if let Some(encoding) = guess_quality_encoding(quals) {
+            self.set_quality_encoding(encoding);
+        }
+        self.detect_encoding = false;
+    }
+}
+
+/// Converts qualities in `encoding` to Phred+33, if needed
+fn quality_converter(encoding: QualityEncoding) -> Option<QualityConverter> {
+    match encoding {
+        QualityEncoding::Phred33 => None,
+        encoding => Some(QualityConverter::new(encoding, QualityEncoding::Phred33)),
+    }
+}
+
+/// Guesses the quality encoding from the range of the characters of the
+/// quality lines, if there are any
+fn guess_quality_encoding<'a>(quals: impl Iterator<Item = &'a [u8]>) -> Option<QualityEncoding> {
+    let (mut min, mut max) = (u8::MAX, u8::MIN);
+    for line in quals {
+        for q in trim_cr(line) {
+            min = min.min(*q);
+            max = max.max(*q);
+        }
+    }
+    (min <= max).then(|| EncodingGuess::from_range(min, max).encoding)
 }
 
 impl Reader<File> {
@@ -153,6 +291,9 @@ where
     /// meaning that the last record may be incomplete.
     /// Updates `self.search_pos`.
     fn find(&mut self) -> Result<bool, ParseError> {
+        if self.options.multiline_fastq {
+            return self.find_multiline();
+        }
         self.buf_pos.seq = if let Some(p) = self.find_line(self.buf_pos.start) {
             p
         } else {
@@ -190,6 +331,9 @@ where
     // re-searching positions that were already found.
     // The resulting position may still be incomplete (-> false).
     fn find_incomplete(&mut self) -> Result<bool, ParseError> {
+        if self.options.multiline_fastq {
+            return self.find_multiline();
+        }
         if self.search_pos == SearchPosition::Id {
             self.buf_pos.seq = if let Some(p) = self.find_line(self.buf_pos.start) {
                 p
@@ -233,6 +377,68 @@ where
         Ok(true)
     }
 
+    /// Same as `find` for records whose sequence and qualities can be wrapped:
+    /// the sequence goes up to the `+` line and the qualities up to the line
+    /// where there are as many qualities as bases. Always searches from the
+    /// start of the record.
+    fn find_multiline(&mut self) -> Result<bool, ParseError> {
+        let buf = self.buf_reader.buffer();
+        let find_line = |from: usize| memchr(b'\n', &buf[from..]).map(|p| from + p + 1);
+
+        let Some(seq) = find_line(self.buf_pos.start) else {
+            self.search_pos = SearchPosition::Id;
+            return Ok(false);
+        };
+        self.buf_pos.seq = seq;
+
+        // sequence lines can't start with `@` either, so we don't read the
+        // next records if the `+` line is missing
+        let (mut sep, mut seq_lines, mut num_bases) = (seq, 0, 0);
+        while !matches!(buf.get(sep), Some(b'+' | b'@')) {
+            let Some(next) = find_line(sep) else {
+                self.search_pos = SearchPosition::Sequence;
+                return Ok(false);
+            };
+            num_bases += trim_cr(&buf[sep..next - 1]).len();
+            seq_lines += 1;
+            sep = next;
+        }
+        self.buf_pos.sep = sep;
+        if buf[sep] != b'+' {
+            return self.validate().map(|_| true);
+        }
+
+        let Some(qual) = find_line(sep) else {
+            self.search_pos = SearchPosition::Separator;
+            return Ok(false);
+        };
+        self.buf_pos.qual = qual;
+
+        let tolerate_missing = self.options.missing_quality != MissingQuality::Error;
+        let (mut end, mut qual_lines, mut num_quals) = (qual, 0, 0);
+        while qual_lines == 0 || num_quals < num_bases {
+            let Some(next) = find_line(end) else {
+                self.buf_pos.wrapped = seq_lines > 1 || qual_lines > 0;
+                self.search_pos = SearchPosition::Quality;
+                return Ok(false);
+            };
+            let line = trim_cr(&buf[end..next - 1]);
+            num_quals += line.len();
+            qual_lines += 1;
+            end = next;
+            if tolerate_missing && qual_lines == 1 && (line.is_empty() || line == b"*") {
+                break;
+            }
+        }
+        self.buf_pos.end = end - 1;
+        self.buf_pos.wrapped = seq_lines > 1 || qual_lines > 1;
+        self.search_pos = SearchPosition::Id;
+
+        self.validate()?;
+
+        Ok(true)
+    }
+
     /// Verify that the record is valid:
     /// - starts with @
     /// - separator line starts with -
@@ -245,22 +451,23 @@ where
                 start_byte,
                 self.get_error_pos(0, false),
                 Format::Fastq,
-            ));
+            )
+            .with_snippet(&self.get_buf()[self.buf_pos.start..]));
         }
 
         let sep_byte = self.get_buf()[self.buf_pos.sep];
         if sep_byte != b'+' {
             self.finished = true;
-            return Err(ParseError::new_invalid_separator(
-                sep_byte,
-                self.get_error_pos(2, true),
-            ));
+            return Err(
+                ParseError::new_invalid_separator(sep_byte, self.get_error_pos(2, true))
+                    .with_snippet(&self.get_buf()[self.buf_pos.sep..]),
+            );
         }
 
         let buf = self.get_buf();
         // We assume we only have ASCII in sequence and quality
-        let seq_len = self.buf_pos.seq(buf).len();
-        let qual_len = self.buf_pos.qual(buf).len();
+        let seq_len = self.buf_pos.num_bases(buf);
+        let qual_len = self.buf_pos.num_quals(buf);
 
         // TODO: we don't do that every time because it's a ~90% performance penalty.
         // TODO: mention it on the README
@@ -273,7 +480,9 @@ where
         //     .filter(|c| *c >= &b'!' && *c <= &b'~')
         //     .count();
 
-        if seq_len != qual_len {
+        let tolerated = self.options.missing_quality != MissingQuality::Error
+            && self.buf_pos.is_qual_missing(buf);
+        if seq_len != qual_len && !tolerated {
             self.finished = true;
             return Err(ParseError::new_unequal_length(
                 seq_len,
@@ -298,7 +507,7 @@ where
         };
         ErrorPosition {
             line: self.position.line() + line_offset,
-            id,
+            ..ErrorPosition::at(&self.position, id)
         }
     }
 
@@ -318,13 +527,13 @@ where
 
             if self.buf_pos.start == 0 {
                 // first record already incomplete -> buffer too small
-                self.grow();
+                self.grow()?;
             } else {
                 // not the first record -> buffer may be big enough but we need to make some space
                 self.make_room();
             }
 
-            fill_buf(&mut self.buf_reader)?;
+            fill_buf(&mut self.buf_reader).inspect_err(|_| self.finished = true)?;
 
             if self.find_incomplete()? {
                 return Ok(true);
@@ -357,11 +566,109 @@ where
 
     // Grow the internal buffer. Used if the original buffer is not big
     // enough for a record
-    fn grow(&mut self) {
+    fn grow(&mut self) -> Result<(), ParseError> {
         let cap = self.buf_reader.capacity();
-        let new_size = grow_to(cap);
+        let new_size = grow_within_limits(self.get_buf(), cap, &self.options, &self.position)
+            .inspect_err(|_| self.finished = true)?;
         let additional = new_size - cap;
         self.buf_reader.reserve(additional);
+        Ok(())
+    }
+
+    /// Returns the error as is, or skips to the next record if the `recover`
+    /// option is set and the error is about an invalid record
+    fn recover(&mut self, error: ParseError) -> ParseError {
+        let invalid_record = matches!(
+            error.kind,
+            ParseErrorKind::InvalidStart
+                | ParseErrorKind::InvalidSeparator
+                | ParseErrorKind::UnequalLengths
+        );
+        if !self.options.recover || !invalid_record {
+            return error;
+        }
+        self.finished = false;
+        match self.skip_to_next_record() {
+            Ok(skipped) => error.with_skipped(skipped),
+            Err(e) => {
+                self.finished = true;
+                e
+            }
+        }
+    }
+
+    /// Skips lines from the start of the current record until one starting
+    /// with `@` whose record has a `+` separator and as many qualities as
+    /// bases. Returns the number of bytes skipped.
+    fn skip_to_next_record(&mut self) -> Result<u64, ParseError> {
+        let from = self.position.byte;
+        let mut pos = self.buf_pos.start;
+        'lines: loop {
+            let next = loop {
+                if let Some(next) = self.find_line(pos) {
+                    break next;
+                }
+                if !self.refill(&mut pos)? {
+                    // no more records, skip the rest
+                    self.position.byte += (self.get_buf().len() - pos) as u64;
+                    pos = self.get_buf().len();
+                    self.finished = true;
+                    break 'lines;
+                }
+            };
+            self.position.byte += (next - pos) as u64;
+            self.position.line += 1;
+            pos = next;
+            loop {
+                match self.looks_like_record(pos) {
+                    Some(true) => break 'lines,
+                    Some(false) => continue 'lines,
+                    // let the parser report the incomplete record at the end
+                    None if !self.refill(&mut pos)? => break 'lines,
+                    None => {}
+                }
+            }
+        }
+        self.buf_pos = BufferPosition {
+            start: pos,
+            ..Default::default()
+        };
+        self.search_pos = SearchPosition::Id;
+        self.position.consumed = self.position.byte;
+        Ok(self.position.byte - from)
+    }
+
+    /// Whether a valid record starts at `pos`, `None` if the buffer ends
+    /// before the end of the record
+    fn looks_like_record(&self, pos: usize) -> Option<bool> {
+        if *self.get_buf().get(pos)? != b'@' {
+            return Some(false);
+        }
+        let seq = self.find_line(pos)?;
+        let sep = self.find_line(seq)?;
+        let qual = self.find_line(sep)?;
+        let end = self.find_line(qual)?;
+        let buf = self.get_buf();
+        let seq_len = trim_cr(&buf[seq..sep - 1]).len();
+        let qual_len = trim_cr(&buf[qual..end - 1]).len();
+        Some(buf[sep] == b'+' && seq_len == qual_len)
+    }
+
+    /// Moves the bytes from `pos` to the start of the buffer, growing it if
+    /// needed, and fills it. Returns false at EOF.
+    fn refill(&mut self, pos: &mut usize) -> Result<bool, ParseError> {
+        if self.get_buf().len() < self.buf_reader.capacity() {
+            return Ok(false);
+        }
+        if *pos == 0 {
+            self.grow()?;
+        } else {
+            self.buf_reader.consume(*pos);
+            self.buf_reader.make_room();
+            *pos = 0;
+        }
+        fill_buf(&mut self.buf_reader).inspect_err(|_| self.finished = true)?;
+        Ok(true)
     }
 
     // Consume bytes from records we've seen and move incomplete bytes to start of buffer
@@ -385,7 +692,7 @@ where
 }
 
 impl<R: io::Read + Send> FastxReader for Reader<R> {
-    fn next(&mut self) -> Option<Result<SequenceRecord, ParseError>> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
         // No more records to read
         if self.finished {
             return None;
@@ -402,15 +709,21 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
                     }
                 }
                 Err(e) => {
+                    self.finished = true;
                     return Some(Err(e.into()));
                 }
             };
         }
 
+        if self.detect_encoding && !self.options.multiline_fastq {
+            self.detect_quality_encoding();
+        }
+
         // If we already did look at a record, let's setup for the next one
         if !self.buf_pos.is_new() {
             self.position.byte += self.buf_pos.len();
-            self.position.line += 4;
+            self.position.line += self.buf_pos.lines(self.buf_reader.buffer());
+            self.position.record += 1;
             self.buf_pos.start = self.buf_pos.end + 1;
         }
 
@@ -418,7 +731,7 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
         let complete = match self.find() {
             Ok(f) => f,
             Err(e) => {
-                return Some(Err(e));
+                return Some(Err(self.recover(e)));
             }
         };
 
@@ -428,7 +741,7 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
             let got_record = match self.next_complete() {
                 Ok(f) => f,
                 Err(e) => {
-                    return Some(Err(e));
+                    return Some(Err(self.recover(e)));
                 }
             };
 
@@ -436,16 +749,68 @@ impl<R: io::Read + Send> FastxReader for Reader<R> {
                 return None;
             }
         }
+        let end = (self.buf_pos.end + 1).min(self.get_buf().len());
+        self.position.read_record((end - self.buf_pos.start) as u64);
         if self.line_ending.is_none() {
             self.line_ending = self.buf_pos.find_line_ending(self.get_buf());
         }
+        let buf = self.buf_reader.buffer();
+        let missing_qual = self.options.missing_quality != MissingQuality::Error
+            && self.buf_pos.is_qual_missing(buf);
+        let qual = self.buf_pos.unwrapped_qual(buf);
+        if self.detect_encoding {
+            // the quality lines of wrapped records can only be found by
+            // parsing them, so only the first record is used
+            if let Some(encoding) = guess_quality_encoding(std::iter::once(&qual[..])) {
+                self.qual_converter = quality_converter(encoding);
+            }
+            self.detect_encoding = false;
+        }
+        if self.options.validate_quality && !missing_qual {
+            let encoding = self.quality_encoding();
+            let column = range_prefix_len(&qual, encoding.min_char(), b'~');
+            if column < qual.len() {
+                let byte = qual[column];
+                return Some(Err(ParseError::new_invalid_quality(
+                    byte,
+                    column,
+                    encoding,
+                    self.get_error_pos(3, true),
+                )));
+            }
+        }
+        if missing_qual {
+            if let MissingQuality::Fill(q) = self.options.missing_quality {
+                self.converted_qual.clear();
+                self.converted_qual.resize(self.buf_pos.num_bases(buf), q);
+            }
+        } else if let Some(converter) = &self.qual_converter {
+            converter.convert_into(&qual, &mut self.converted_qual);
+        } else if self.buf_pos.wrapped {
+            self.converted_qual = qual.into_owned();
+        }
+        if self.options.strict {
+            let header = self.buf_pos.id(self.buf_reader.buffer());
+            if let Err(e) = self.seen_ids.insert(header, &self.position) {
+                return Some(Err(e));
+            }
+        }
         // We got one!
-        Some(Ok(SequenceRecord::new_fastq(
+        let mut record = SequenceRecord::new_fastq(
             self.get_buf(),
             &self.buf_pos,
             &self.position,
             self.line_ending,
-        )))
+        );
+        if missing_qual && self.options.missing_quality == MissingQuality::Omit {
+            record = record.without_qual();
+        } else if missing_qual || self.qual_converter.is_some() || self.buf_pos.wrapped {
+            record = record.with_qual(&self.converted_qual);
+        }
+        if let Err(e) = record.check(&self.options) {
+            return Some(Err(e));
+        }
+        Some(Ok(record))
     }
 
     fn position(&self) -> &Position {
@@ -626,4 +991,244 @@ mod test {
         // It errors when it tries to validate the separator line that needs to start with `+`
         assert_eq!(e.kind, ParseErrorKind::InvalidSeparator);
     }
+
+    #[test]
+    fn test_records() {
+        let data = b"@a\nACGT\n+\nIIII\n@b\nACGT\n+\nII\n";
+        let mut reader = Reader::new(seq(data));
+        let records: Vec<_> = reader.records().collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].as_ref().unwrap().qual.as_deref(),
+            Some(&b"IIII"[..])
+        );
+        assert!(records[1].is_err());
+    }
+
+    #[test]
+    fn test_validate_option() {
+        use crate::parser::ReaderOptions;
+        use crate::sequence::Alphabet;
+
+        let data = b"@a\nACGU\n+\nIIII\n@b\nACGT\n+\nIIII\n";
+        let mut reader = Reader::new(seq(data));
+        assert!(reader.next().unwrap().is_ok());
+
+        let options = ReaderOptions::new().validate(Alphabet::Dna);
+        let mut reader = Reader::new(seq(data)).with_options(options);
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::InvalidSequence);
+        assert_eq!(e.position.id.as_deref(), Some("a"));
+        assert_eq!(e.position.line, 1);
+        // the error doesn't stop the reader
+        assert_eq!(reader.next().unwrap().unwrap().id(), b"b");
+    }
+
+    #[test]
+    fn test_quality_encoding_option() {
+        use crate::parser::ReaderOptions;
+        use crate::quality::QualityEncoding;
+
+        let data = b"@a\nACGT\n+\nhhB@\n@b\nAC\n+\n^h\n";
+        let options = ReaderOptions::new().quality_encoding(QualityEncoding::Phred64);
+        let mut reader = Reader::new(seq(data)).with_options(options);
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(rec.qual().unwrap(), b"II#!");
+        let mut out = Vec::new();
+        rec.write(&mut out, None).unwrap();
+        assert_eq!(out, b"@a\nACGT\n+\nII#!\n");
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(rec.qual().unwrap(), b"?I");
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_missing_quality_option() {
+        use crate::parser::{MissingQuality, ReaderOptions};
+
+        let data = b"@a\nACGT\n+\n*\n@b\nACG\n+\n\n@c\nA\n+\n*\n";
+        let mut reader = Reader::new(seq(data));
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::UnequalLengths);
+
+        let options = ReaderOptions::new().missing_quality(MissingQuality::Omit);
+        let mut reader = Reader::new(seq(data)).with_options(options.clone());
+        assert_eq!(reader.next().unwrap().unwrap().qual(), None);
+        assert_eq!(reader.next().unwrap().unwrap().qual(), None);
+        // a valid quality of a single base
+        assert_eq!(reader.next().unwrap().unwrap().qual(), Some(&b"*"[..]));
+        assert!(reader.next().is_none());
+
+        let options = options.missing_quality(MissingQuality::Fill(b'?'));
+        let mut reader = Reader::new(seq(data)).with_options(options);
+        assert_eq!(reader.next().unwrap().unwrap().qual(), Some(&b"????"[..]));
+        assert_eq!(reader.next().unwrap().unwrap().qual(), Some(&b"???"[..]));
+
+        // other length mismatches are still errors
+        let options = ReaderOptions::new().missing_quality(MissingQuality::Omit);
+        let mut reader = Reader::new(seq(b"@a\nACGT\n+\nII\n")).with_options(options);
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_strict_option() {
+        use crate::parser::ReaderOptions;
+
+        let data = b"@a\nACGT\n+\nIIII\n@b/1\nAC\n+\nII\n@a extra\nA\n+\nI\n";
+        let mut reader = Reader::new(seq(data)).with_options(ReaderOptions::new().strict(true));
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::DuplicateId);
+        assert_eq!(
+            e.to_string(),
+            "Duplicate id, first seen at line 1 (record 'a' at line 9, byte 28)"
+        );
+    }
+
+    #[test]
+    fn test_size_limits() {
+        use crate::parser::ReaderOptions;
+
+        let long = vec![b'A'; 200];
+        let data = [
+            &b"@a\nACGT\n+\nIIII\n@b\n"[..],
+            &long,
+            b"\n+\n",
+            &long,
+            b"\n",
+        ]
+        .concat();
+        let options = ReaderOptions::new().max_record_len(100);
+        let mut reader = Reader::with_capacity(seq(&data), 32).with_options(options);
+        assert!(reader.next().unwrap().is_ok());
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::LimitExceeded);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_multiline_option() {
+        use crate::parser::ReaderOptions;
+
+        let data = b"@a\nACGT\nAC\n+\n@III\nII\n@b\nAC\n+\nII\n@c\nAC\r\nG\r\n+\r\nI\r\n@I\r\n";
+        let mut reader = Reader::new(seq(data));
+        assert!(reader.next().unwrap().is_err());
+
+        for capacity in [4, 1024] {
+            let options = ReaderOptions::new().multiline_fastq(true);
+            let mut reader = Reader::with_capacity(seq(data), capacity).with_options(options);
+            let record = reader.next().unwrap().unwrap();
+            assert_eq!(record.seq(), &b"ACGTAC"[..]);
+            assert_eq!(record.qual(), Some(&b"@IIIII"[..]));
+            assert_eq!(record.num_bases(), 6);
+            let mut out = Vec::new();
+            record.write(&mut out, None).unwrap();
+            assert_eq!(out, b"@a\nACGTAC\n+\n@IIIII\n");
+            let record = reader.next().unwrap().unwrap();
+            assert_eq!(record.id(), b"b");
+            assert_eq!(record.start_line_number(), 7);
+            assert_eq!(record.qual(), Some(&b"II"[..]));
+            let record = reader.next().unwrap().unwrap();
+            assert_eq!(record.start_line_number(), 11);
+            assert_eq!(record.seq(), &b"ACG"[..]);
+            assert_eq!(record.qual(), Some(&b"I@I"[..]));
+            assert!(reader.next().is_none());
+        }
+
+        // the separator line is still required
+        let options = ReaderOptions::new().multiline_fastq(true);
+        let mut reader = Reader::new(seq(b"@a\nAC\n@b\nAC\n+\nII\n")).with_options(options);
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::InvalidSeparator);
+    }
+
+    #[test]
+    fn test_recover_option() {
+        use crate::parser::ReaderOptions;
+
+        let data =
+            b"@a\nACGT\n+\nIIII\n@b\nACGT\n-\nIIII\nGARBAGE\n@c\nAC\n+\nII\n@d\nACG\n+\nII\n@e\nA\n+\nI";
+        let mut reader = Reader::new(seq(data));
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+
+        for capacity in [8, 1024] {
+            let options = ReaderOptions::new().recover(true);
+            let mut reader = Reader::with_capacity(seq(data), capacity).with_options(options);
+            assert_eq!(reader.next().unwrap().unwrap().id(), b"a");
+            let e = reader.next().unwrap().unwrap_err();
+            assert_eq!(e.kind, ParseErrorKind::InvalidSeparator);
+            assert_eq!(e.skipped_range(), Some(15..38));
+            let record = reader.next().unwrap().unwrap();
+            assert_eq!(record.id(), b"c");
+            assert_eq!(record.start_line_number(), 10);
+            let e = reader.next().unwrap().unwrap_err();
+            assert_eq!(e.kind, ParseErrorKind::UnequalLengths);
+            assert!(e.to_string().ends_with(", skipped bytes 49..61"));
+            assert_eq!(reader.next().unwrap().unwrap().id(), b"e");
+            assert!(reader.next().is_none());
+        }
+
+        // nothing to recover from at the end
+        let options = ReaderOptions::new().recover(true);
+        let mut reader = Reader::new(seq(b"@a\nAC\n+\nI\nGARBAGE")).with_options(options);
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().skipped_range(),
+            Some(0..17)
+        );
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_validate_quality_option() {
+        use crate::parser::ReaderOptions;
+        use crate::quality::QualityEncoding;
+
+        let data = b"@r1 desc\nACGT\n+\nII I\n@r2\nACGT\n+\nIIII\n";
+        let mut reader = Reader::new(seq(data));
+        assert!(reader.next().unwrap().is_ok());
+
+        let options = ReaderOptions::new().validate_quality(true);
+        let mut reader = Reader::new(seq(data)).with_options(options.clone());
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::InvalidQuality);
+        assert_eq!(e.position.id.as_deref(), Some("r1"));
+        assert_eq!(e.position.line, 4);
+        assert!(e.to_string().contains("' ' at 2"));
+        assert_eq!(reader.next().unwrap().unwrap().id(), b"r2");
+
+        // the range depends on the encoding
+        let options = options.quality_encoding(QualityEncoding::Phred64);
+        let mut reader = Reader::new(seq(b"@a\nAC\n+\nh5\n")).with_options(options);
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::InvalidQuality);
+    }
+
+    #[test]
+    fn test_detect_quality_encoding_option() {
+        use crate::parser::ReaderOptions;
+        use crate::quality::QualityEncoding;
+
+        let options = ReaderOptions::new().detect_quality_encoding(true);
+        // Solexa qualities go below Phred+64's `@`
+        let data = b"@a\nACGT\n+\nhh;@\n@b\nAC\n+\nhh\n";
+        let mut reader = Reader::new(seq(data)).with_options(options.clone());
+        assert_eq!(reader.next().unwrap().unwrap().qual().unwrap(), b"II\"$");
+        assert_eq!(reader.quality_encoding(), QualityEncoding::Solexa);
+        assert_eq!(reader.next().unwrap().unwrap().qual().unwrap(), b"II");
+
+        let data = b"@a\nACGT\n+\nIII#\r\n";
+        let mut reader = Reader::new(seq(data)).with_options(options.clone());
+        assert_eq!(reader.next().unwrap().unwrap().qual().unwrap(), b"III#");
+        assert_eq!(reader.quality_encoding(), QualityEncoding::Phred33);
+
+        // the `+` line isn't taken for qualities when the records are wrapped
+        let data = b"@a\nACGT\nAC\n+\nhhhhhh\n@b\nAC\n+\nhh\n";
+        let mut reader = Reader::new(seq(data)).with_options(options.multiline_fastq(true));
+        assert_eq!(reader.next().unwrap().unwrap().qual().unwrap(), b"IIIIII");
+        assert_eq!(reader.quality_encoding(), QualityEncoding::Phred64);
+        assert_eq!(reader.next().unwrap().unwrap().qual().unwrap(), b"II");
+    }
 }
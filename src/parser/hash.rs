@@ -0,0 +1,135 @@
+//! Stable hashes of the content of records, e.g. as deduplication or cache
+//! keys
+use crate::parser::owned::OwnedRecord;
+use crate::parser::record::SequenceRecord;
+
+/// Version of the algorithm of `content_hash`, changed whenever the hash of
+/// a record changes, so stored hashes can be invalidated.
+///
+/// Version 1 is the 128-bit FNV-1a hash of the uppercased sequence, followed
+/// by a `0xff` byte and the id if included, then by a `0xfe` byte and the
+/// (Phred+33) qualities if included.
+pub const CONTENT_HASH_VERSION: u32 = 1;
+
+const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// What is hashed by `content_hash` on top of the sequence
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct HashOptions {
+    /// Include the id (the header up to the first space or tab)
+    pub id: bool,
+    /// Include the quality scores, if any
+    pub qual: bool,
+}
+
+impl HashOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn id(mut self, id: bool) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn qual(mut self, qual: bool) -> Self {
+        self.qual = qual;
+        self
+    }
+}
+
+struct Fnv(u128);
+
+impl Fnv {
+    #[inline]
+    fn write(&mut self, bytes: impl IntoIterator<Item = u8>) {
+        for b in bytes {
+            self.0 ^= u128::from(b);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// Hashes the parts of a record, see `CONTENT_HASH_VERSION` for the
+/// algorithm. The hash doesn't depend on the platform nor on how the record
+/// was written (line wrapping, line endings, case of the bases). Use the low
+/// 64 bits (`as u64`) if a 64-bit hash is enough.
+pub fn content_hash(id: &[u8], seq: &[u8], qual: Option<&[u8]>, options: HashOptions) -> u128 {
+    let mut hasher = Fnv(FNV_OFFSET);
+    hasher.write(seq.iter().map(u8::to_ascii_uppercase));
+    if options.id {
+        hasher.write([0xff]);
+        hasher.write(id.iter().copied());
+    }
+    if let Some(qual) = qual.filter(|_| options.qual) {
+        hasher.write([0xfe]);
+        hasher.write(qual.iter().copied());
+    }
+    hasher.0
+}
+
+impl SequenceRecord<'_> {
+    /// A hash of the sequence and optionally of the id and qualities that
+    /// stays the same across versions of needletail for a given
+    /// `CONTENT_HASH_VERSION`, see `content_hash`
+    ///
+    /// ```
+    /// use needletail::parse_fastx_reader;
+    /// use needletail::parser::HashOptions;
+    ///
+    /// let mut reader = parse_fastx_reader(&b">a\nACGT\n>b\nAC\ngt\n"[..]).unwrap();
+    /// let a = reader.next().unwrap().unwrap().content_hash(HashOptions::new());
+    /// let b = reader.next().unwrap().unwrap().content_hash(HashOptions::new());
+    /// assert_eq!(a, b);
+    /// ```
+    pub fn content_hash(&self, options: HashOptions) -> u128 {
+        content_hash(self.name(), &self.seq(), self.qual(), options)
+    }
+}
+
+impl OwnedRecord {
+    /// Same as `SequenceRecord::content_hash`
+    pub fn content_hash(&self, options: HashOptions) -> u128 {
+        content_hash(&self.id, &self.seq, self.qual.as_deref(), options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn hashes_are_stable() {
+        let options = HashOptions::new();
+        // changing this value means bumping CONTENT_HASH_VERSION
+        assert_eq!(
+            content_hash(b"r1", b"ACGT", None, options),
+            0x6883e1b7f5757277b806e92e16119b2e
+        );
+        assert_eq!(
+            content_hash(b"r1", b"ACGT", None, options),
+            content_hash(b"r2", b"acgt", Some(b"IIII"), options)
+        );
+        let with_id = options.id(true);
+        assert_ne!(
+            content_hash(b"r1", b"ACGT", None, with_id),
+            content_hash(b"r2", b"ACGT", None, with_id)
+        );
+        let with_qual = options.qual(true);
+        assert_ne!(
+            content_hash(b"r1", b"ACGT", Some(b"IIII"), with_qual),
+            content_hash(b"r1", b"ACGT", Some(b"IIIJ"), with_qual)
+        );
+    }
+
+    #[test]
+    fn records_and_owned_records_agree() {
+        let options = HashOptions::new().id(true).qual(true);
+        let mut reader = parse_fastx_reader(&b"@r1 desc\nACGT\n+\nIIII\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let owned = OwnedRecord::from(&record);
+        assert_eq!(record.content_hash(options), owned.content_hash(options));
+    }
+}
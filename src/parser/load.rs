@@ -0,0 +1,124 @@
+//! Loading whole (small) files in memory
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::owned::OwnedRecord;
+use crate::parser::parse_fastx_file;
+
+/// How many records to read before guessing how many there are in the file
+const SAMPLE_RECORDS: usize = 16;
+
+/// Guesses how many records are left in a `file_size` bytes file from the
+/// offset of the `SAMPLE_RECORDS + 1`th record. The guess is too low for
+/// compressed files but still saves most of the reallocations.
+fn remaining_records(file_size: u64, offset: u64) -> usize {
+    if offset == 0 {
+        return 0;
+    }
+    let per_record = offset / SAMPLE_RECORDS as u64;
+    (file_size.saturating_sub(offset) / per_record.max(1)) as usize
+}
+
+/// Reads all the records of a FASTA/FASTQ file (compressed or not) in memory.
+/// Meant for small files like references: use `parse_fastx_file` to go
+/// through big files one record at a time.
+///
+/// ```
+/// use needletail::parser::read_all;
+///
+/// let records = read_all("tests/data/test.fa").unwrap();
+/// assert_eq!(records.len(), 2);
+/// assert_eq!(records[0].id, b"test");
+/// ```
+pub fn read_all<P: AsRef<Path>>(path: P) -> Result<Vec<OwnedRecord>, ParseError> {
+    let file_size = fs::metadata(&path)?.len();
+    let mut reader = parse_fastx_file(&path)?;
+    let mut records = Vec::with_capacity(SAMPLE_RECORDS);
+    while let Some(record) = reader.next() {
+        let record = record?;
+        if records.len() == SAMPLE_RECORDS {
+            records.reserve(remaining_records(file_size, record.start_byte_offset()));
+        }
+        records.push(OwnedRecord::from(&record));
+    }
+    Ok(records)
+}
+
+/// Same as `read_all` but returns the records by id (the header up to the
+/// first space or tab), failing with a `DuplicateId` error if several
+/// records have the same id.
+///
+/// ```
+/// use needletail::parser::read_map;
+///
+/// let records = read_map("tests/data/test.fa").unwrap();
+/// assert_eq!(records[&b"test2"[..]].seq, b"TAGC");
+/// ```
+pub fn read_map<P: AsRef<Path>>(path: P) -> Result<HashMap<Vec<u8>, OwnedRecord>, ParseError> {
+    let file_size = fs::metadata(&path)?.len();
+    let mut reader = parse_fastx_file(&path)?;
+    let mut records = HashMap::with_capacity(SAMPLE_RECORDS);
+    // only used to report duplicates
+    let mut lines = HashMap::with_capacity(SAMPLE_RECORDS);
+    while let Some(record) = reader.next() {
+        let record = record?;
+        if records.len() == SAMPLE_RECORDS {
+            let remaining = remaining_records(file_size, record.start_byte_offset());
+            records.reserve(remaining);
+            lines.reserve(remaining);
+        }
+        let line = record.start_line_number();
+        let record = OwnedRecord::from(&record);
+        match lines.entry(record.id.clone()) {
+            Entry::Occupied(first) => {
+                let position = ErrorPosition {
+                    line,
+                    id: Some(String::from_utf8_lossy(&record.id).into()),
+                    ..Default::default()
+                };
+                return Err(ParseError::new_duplicate_id(*first.get(), position));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(line);
+            }
+        }
+        records.insert(record.id.clone(), record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::errors::ParseErrorKind;
+
+    #[test]
+    fn can_load_files() {
+        let records = read_all("tests/data/PRJNA271013_head.fq").unwrap();
+        assert_eq!(records.len(), 2000);
+        assert!(records.iter().all(|r| r.qual.is_some()));
+        let map = read_map("tests/data/PRJNA271013_head.fq").unwrap();
+        assert_eq!(map.len(), 2000);
+        assert_eq!(map[&records[42].id], records[42]);
+        assert!(read_all("tests/data/missing.fa").is_err());
+    }
+
+    #[test]
+    fn reports_duplicate_ids() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b">a\nACGT\n>b first\nAC\n>c\nA\n>b second\nGT\n")
+            .unwrap();
+        assert_eq!(read_all(file.path()).unwrap().len(), 4);
+        let err = read_map(file.path()).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::DuplicateId);
+        assert_eq!(
+            err.to_string(),
+            "Duplicate id, first seen at line 3 (record 'b' at line 7)"
+        );
+    }
+}
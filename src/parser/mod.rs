@@ -1,7 +1,7 @@
 //! Handles all the FASTA/FASTQ parsing
 use std::fs::File;
 use std::io::{stdin, Cursor, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[cfg(feature = "bzip2")]
 use bzip2::read::BzDecoder;
@@ -16,13 +16,41 @@ use crate::errors::ParseError;
 pub use crate::parser::fasta::Reader as FastaReader;
 pub use crate::parser::fastq::Reader as FastqReader;
 
+mod bgzf;
+#[cfg(feature = "bio")]
+mod bio;
+mod count;
+mod hash;
+mod load;
+#[cfg(feature = "noodles")]
+mod noodles;
+mod options;
+mod owned;
+mod pair;
 mod record;
+#[cfg(feature = "serde")]
+pub mod serialize;
+mod spawned;
 mod utils;
 
 mod fasta;
 mod fastq;
 
-pub use crate::parser::utils::FastxReader;
+pub use crate::parser::bgzf::BgzfIndex;
+#[cfg(feature = "flate2")]
+use crate::parser::bgzf::EofCheck;
+pub use crate::parser::count::{count_bases, count_records};
+pub use crate::parser::hash::{content_hash, HashOptions, CONTENT_HASH_VERSION};
+pub use crate::parser::load::{read_all, read_map};
+pub use crate::parser::options::{MissingQuality, ReaderOptions, WriterOptions};
+pub(crate) use crate::parser::owned::split_header;
+pub use crate::parser::owned::{OwnedRecord, RecordBuilder, RecordView};
+pub(crate) use crate::parser::pair::mate_name;
+pub use crate::parser::pair::{FastxPairReader, InterleavedPairs};
+pub use crate::parser::spawned::SpawnedReader;
+#[cfg(feature = "parallel")]
+pub(crate) use crate::parser::utils::read_records;
+pub use crate::parser::utils::{Batches, FastxReader, Records};
 
 // Magic bytes for each compression format
 #[cfg(feature = "flate2")]
@@ -37,10 +65,14 @@ const ZST_MAGIC: [u8; 2] = [0x28, 0xB5];
 fn get_fastx_reader<'a, R: 'a + io::Read + Send>(
     reader: R,
     first_byte: u8,
+    options: ReaderOptions,
 ) -> Result<Box<dyn FastxReader + 'a>, ParseError> {
     match first_byte {
-        b'>' => Ok(Box::new(FastaReader::new(reader))),
-        b'@' => Ok(Box::new(FastqReader::new(reader))),
+        b'>' => Ok(Box::new(FastaReader::new(reader).with_options(options))),
+        b';' | b'\n' | b'\r' if options.legacy_fasta => {
+            Ok(Box::new(FastaReader::new(reader).with_options(options)))
+        }
+        b'@' => Ok(Box::new(FastqReader::new(reader).with_options(options))),
         _ => Err(ParseError::new_unknown_format(first_byte)),
     }
 }
@@ -83,8 +115,42 @@ fn get_fastx_reader<'a, R: 'a + io::Read + Send>(
 /// [zstd]: https://facebook.github.io/zstd/
 ///
 pub fn parse_fastx_reader<'a, R: 'a + io::Read + Send>(
-    mut reader: R,
+    reader: R,
 ) -> Result<Box<dyn FastxReader + 'a>, ParseError> {
+    parse_fastx_reader_with_options(reader, ReaderOptions::default())
+}
+
+/// Same as [`parse_fastx_reader`](fn.parse_fastx_reader.html) but with
+/// [`ReaderOptions`](struct.ReaderOptions.html) changing what the readers accept.
+///
+/// # Examples
+///
+/// ```
+/// use needletail::errors::ParseErrorKind;
+/// use needletail::parser::{parse_fastx_reader_with_options, ReaderOptions};
+/// use needletail::sequence::Alphabet;
+///
+/// let reader = ">read1\nACGT\n>read2\nAC*T".as_bytes();
+/// let options = ReaderOptions::new().validate(Alphabet::Dna);
+/// let mut fastx_reader = parse_fastx_reader_with_options(reader, options).unwrap();
+/// assert!(fastx_reader.next().unwrap().is_ok());
+/// let err = fastx_reader.next().unwrap().unwrap_err();
+/// assert_eq!(err.kind, ParseErrorKind::InvalidSequence);
+/// assert_eq!(err.to_string(), "Invalid characters in sequence: '*' at 2 (record 'read2' at line 3, byte 12)");
+/// ```
+pub fn parse_fastx_reader_with_options<'a, R: 'a + io::Read + Send>(
+    reader: R,
+    options: ReaderOptions,
+) -> Result<Box<dyn FastxReader + 'a>, ParseError> {
+    let (reader, first_byte) = decompress(reader)?;
+    get_fastx_reader(reader, first_byte, options)
+}
+
+/// Detects the compression of `reader` and returns the decompressed stream
+/// with its first byte
+pub(crate) fn decompress<'a, R: 'a + io::Read + Send>(
+    mut reader: R,
+) -> Result<(Box<dyn io::Read + Send + 'a>, u8), ParseError> {
     let mut first_two_bytes = [0; 2];
     reader
         .read_exact(&mut first_two_bytes)
@@ -95,7 +161,7 @@ pub fn parse_fastx_reader<'a, R: 'a + io::Read + Send>(
     match first_two_bytes {
         #[cfg(feature = "flate2")]
         GZ_MAGIC => {
-            let mut gz_reader = MultiGzDecoder::new(new_reader);
+            let mut gz_reader = MultiGzDecoder::new(EofCheck::new(new_reader));
             let mut first = [0; 1];
             gz_reader
                 .read_exact(&mut first)
@@ -104,7 +170,7 @@ pub fn parse_fastx_reader<'a, R: 'a + io::Read + Send>(
                     _ => e.into(),
                 })?;
             let r = Cursor::new(first).chain(gz_reader);
-            get_fastx_reader(r, first[0])
+            Ok((Box::new(r), first[0]))
         }
         #[cfg(feature = "bzip2")]
         BZ_MAGIC => {
@@ -117,7 +183,7 @@ pub fn parse_fastx_reader<'a, R: 'a + io::Read + Send>(
                     _ => e.into(),
                 })?;
             let r = Cursor::new(first).chain(bz_reader);
-            get_fastx_reader(r, first[0])
+            Ok((Box::new(r), first[0]))
         }
         #[cfg(feature = "xz2")]
         XZ_MAGIC => {
@@ -130,7 +196,7 @@ pub fn parse_fastx_reader<'a, R: 'a + io::Read + Send>(
                     _ => e.into(),
                 })?;
             let r = Cursor::new(first).chain(xz_reader);
-            get_fastx_reader(r, first[0])
+            Ok((Box::new(r), first[0]))
         }
         #[cfg(feature = "zstd")]
         ZST_MAGIC => {
@@ -143,9 +209,9 @@ pub fn parse_fastx_reader<'a, R: 'a + io::Read + Send>(
                     _ => e.into(),
                 })?;
             let r = Cursor::new(first).chain(zst_reader);
-            get_fastx_reader(r, first[0])
+            Ok((Box::new(r), first[0]))
         }
-        _ => get_fastx_reader(new_reader, first_two_bytes[0]),
+        _ => Ok((Box::new(new_reader), first_two_bytes[0])),
     }
 }
 
@@ -159,17 +225,57 @@ pub fn parse_fastx_stdin() -> Result<Box<dyn FastxReader>, ParseError> {
 /// The main entry point of needletail if you're reading from a file.
 /// Shortcut to calling `parse_fastx_reader` with a file
 pub fn parse_fastx_file<P: AsRef<Path>>(path: P) -> Result<Box<dyn FastxReader>, ParseError> {
-    parse_fastx_reader(File::open(&path)?)
+    parse_fastx_file_with_options(path, ReaderOptions::default())
+}
+
+/// Same as [`parse_fastx_file`](fn.parse_fastx_file.html) but with
+/// [`ReaderOptions`](struct.ReaderOptions.html) changing what the readers accept.
+pub fn parse_fastx_file_with_options<P: AsRef<Path>>(
+    path: P,
+    options: ReaderOptions,
+) -> Result<Box<dyn FastxReader>, ParseError> {
+    let inner = parse_fastx_reader_with_options(File::open(&path)?, options.clone())?;
+    Ok(Box::new(FileReader {
+        path: path.as_ref().to_path_buf(),
+        options,
+        inner,
+    }))
+}
+
+/// A reader of a file, which can be opened again by `try_clone`
+struct FileReader {
+    path: PathBuf,
+    options: ReaderOptions,
+    inner: Box<dyn FastxReader>,
+}
+
+impl FastxReader for FileReader {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        self.inner.next()
+    }
+
+    fn position(&self) -> &Position {
+        self.inner.position()
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        self.inner.line_ending()
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn FastxReader>, ParseError> {
+        parse_fastx_file_with_options(&self.path, self.options.clone())
+    }
 }
 
 pub use record::{mask_header_tabs, mask_header_utf8, write_fasta, write_fastq, SequenceRecord};
 use std::io;
-pub use utils::{Format, LineEnding};
+pub use utils::{Format, LineEnding, Position};
 
 #[cfg(test)]
 mod test {
     use crate::errors::ParseErrorKind;
     use crate::parse_fastx_reader;
+    use crate::parser::{parse_fastx_reader_with_options, FastxReader, ReaderOptions};
     #[cfg(feature = "bzip2")]
     use bzip2::{read::BzEncoder, Compression as BzCompression};
     #[cfg(feature = "flate2")]
@@ -179,6 +285,92 @@ mod test {
     #[cfg(feature = "zstd")]
     use zstd::stream::write::Encoder as ZstdEncoder;
 
+    #[test]
+    fn test_errors_locate_the_problem() {
+        let data = b"@r1\nACGT\n+\nIIII\n@r2\nAC\n-\nII\n";
+        let mut reader = parse_fastx_reader(&data[..]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind.as_str(), "invalid_separator");
+        assert_eq!(e.position.line, 7);
+        assert_eq!(e.position.byte, Some(16));
+        assert_eq!(e.position.record, Some(1));
+        assert_eq!(e.position.id.as_deref(), Some("r2"));
+        assert_eq!(e.snippet.as_deref(), Some("2d 0a 49 49 0a |-.II.|"));
+        assert_eq!(
+            e.to_string(),
+            "Expected '+' separator but found '- (record 'r2' at line 7, byte 16) near 2d 0a 49 49 0a |-.II.|"
+        );
+    }
+
+    #[test]
+    fn test_try_clone() {
+        let mut reader = crate::parse_fastx_file("tests/data/test.fa").unwrap();
+        assert_eq!(reader.next().unwrap().unwrap().id(), b"test");
+        let mut clone = reader.try_clone().unwrap();
+        assert_eq!(reader.next().unwrap().unwrap().id(), b"test2");
+        assert_eq!(clone.next().unwrap().unwrap().id(), b"test");
+        assert!(reader.next().is_none());
+        assert_eq!(clone.next().unwrap().unwrap().id(), b"test2");
+
+        let reader = parse_fastx_reader(&b">a\nACGT\n"[..]).unwrap();
+        assert_eq!(reader.try_clone().err().unwrap().kind, ParseErrorKind::Io);
+    }
+
+    #[test]
+    fn test_batches() {
+        let mut reader = crate::parse_fastx_file("tests/data/PRJNA271013_head.fq").unwrap();
+        let batches: Vec<_> = reader.batches(300).map(|b| b.unwrap()).collect();
+        assert_eq!(batches.len(), 7);
+        assert_eq!(batches[6].len(), 200);
+        assert_eq!(batches.concat().len(), 2000);
+
+        let data = b"@a\nACGT\n+\nIIII\n@b\nACGT\n+\nII\n@c\nA\n+\nI\n";
+        let mut reader = parse_fastx_reader(&data[..]).unwrap();
+        let mut batches = reader.batches(2);
+        assert!(batches.next().unwrap().is_err());
+        assert!(batches.next().is_none());
+
+        let mut reader = parse_fastx_reader(&b">a\nACGT\n>b\nAC\n"[..]).unwrap();
+        let sizes: Vec<_> = reader
+            .batches_by_bytes(0)
+            .map(|b| b.unwrap().len())
+            .collect();
+        assert_eq!(sizes, vec![1, 1]);
+    }
+
+    #[test]
+    fn test_progress() {
+        for data in [
+            &b">r1\nACGT\n>r2\nAC\nGT\n"[..],
+            b">r1\nACGT\n>r2\nAC\nGT",
+            b"@r1\nACGT\n+\nIIII\n@r2\nAC\n+\nII\n",
+            b"@r1\nACGT\n+\nIIII\n@r2\nAC\n+\nII",
+        ] {
+            let mut reader = parse_fastx_reader(data).unwrap();
+            assert_eq!(reader.position().records_emitted(), 0);
+            reader.next().unwrap().unwrap();
+            let position = reader.position().clone();
+            assert_eq!(position.records_emitted(), 1);
+            assert_eq!(position.line(), 1);
+            let consumed = position.bytes_consumed() as usize;
+            assert_eq!(data[consumed..consumed + 3], [data[0], b'r', b'2']);
+            reader.next().unwrap().unwrap();
+            assert_eq!(reader.position().records_emitted(), 2);
+            assert_eq!(reader.position().bytes_consumed(), data.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_legacy_fasta_can_start_with_comments() {
+        let data = b";comment\n>r1\nACGT\n";
+        let e = parse_fastx_reader(&data[..]).err().unwrap();
+        assert_eq!(e.kind, ParseErrorKind::UnknownFormat);
+        let options = ReaderOptions::new().legacy_fasta(true);
+        let mut reader = parse_fastx_reader_with_options(&data[..], options).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap().id(), b"r1");
+    }
+
     #[test]
     fn test_empty_file_raises_parser_error_of_same_kind() {
         let reader = "".as_bytes();
@@ -0,0 +1,141 @@
+//! Conversions between the records of needletail and the ones of noodles
+//! (`noodles_fasta::Record` and `noodles_fastq::Record`).
+//!
+//! noodles FASTQ records always have a description: an empty one is `None`
+//! in needletail.
+use noodles_fasta::record::{Definition as FastaDefinition, Sequence};
+use noodles_fastq::record::Definition as FastqDefinition;
+
+use crate::errors::SequenceError;
+use crate::parser::owned::OwnedRecord;
+use crate::parser::record::SequenceRecord;
+
+fn fasta_record(id: &[u8], desc: Option<&[u8]>, seq: Vec<u8>) -> noodles_fasta::Record {
+    let definition = FastaDefinition::new(id.to_vec(), desc.map(|d| d.to_vec().into()));
+    noodles_fasta::Record::new(definition, Sequence::from(seq))
+}
+
+/// Fails if the record has no qualities, i.e. comes from a FASTA file
+fn fastq_record(
+    id: &[u8],
+    desc: Option<&[u8]>,
+    seq: Vec<u8>,
+    qual: Option<&[u8]>,
+) -> Result<noodles_fastq::Record, SequenceError> {
+    let qual = qual.ok_or(SequenceError::MissingQuality)?;
+    let definition = FastqDefinition::new(id.to_vec(), desc.unwrap_or_default().to_vec());
+    Ok(noodles_fastq::Record::new(definition, seq, qual.to_vec()))
+}
+
+impl From<&OwnedRecord> for noodles_fasta::Record {
+    fn from(record: &OwnedRecord) -> Self {
+        fasta_record(&record.id, record.desc.as_deref(), record.seq.clone())
+    }
+}
+
+impl TryFrom<&OwnedRecord> for noodles_fastq::Record {
+    type Error = SequenceError;
+
+    fn try_from(record: &OwnedRecord) -> Result<Self, Self::Error> {
+        fastq_record(
+            &record.id,
+            record.desc.as_deref(),
+            record.seq.clone(),
+            record.qual.as_deref(),
+        )
+    }
+}
+
+impl From<&SequenceRecord<'_>> for noodles_fasta::Record {
+    fn from(record: &SequenceRecord<'_>) -> Self {
+        fasta_record(
+            record.name(),
+            record.description(),
+            record.seq().into_owned(),
+        )
+    }
+}
+
+impl TryFrom<&SequenceRecord<'_>> for noodles_fastq::Record {
+    type Error = SequenceError;
+
+    fn try_from(record: &SequenceRecord<'_>) -> Result<Self, Self::Error> {
+        fastq_record(
+            record.name(),
+            record.description(),
+            record.seq().into_owned(),
+            record.qual(),
+        )
+    }
+}
+
+impl From<&noodles_fasta::Record> for OwnedRecord {
+    fn from(record: &noodles_fasta::Record) -> Self {
+        OwnedRecord {
+            id: record.name().to_vec(),
+            desc: record.description().map(|d| d.to_vec()),
+            seq: record.sequence().as_ref().to_vec(),
+            qual: None,
+            ..OwnedRecord::default()
+        }
+    }
+}
+
+impl From<&noodles_fastq::Record> for OwnedRecord {
+    fn from(record: &noodles_fastq::Record) -> Self {
+        let desc = record.description();
+        OwnedRecord {
+            id: record.name().to_vec(),
+            desc: (!desc.is_empty()).then(|| desc.to_vec()),
+            seq: record.sequence().to_vec(),
+            qual: Some(record.quality_scores().to_vec()),
+            ..OwnedRecord::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn can_convert_fasta_records() {
+        let mut reader = parse_fastx_reader(&b">r1 some read\nACGT\nAC\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let noodles_record = noodles_fasta::Record::from(&record);
+        assert_eq!(noodles_record.name(), b"r1");
+        assert_eq!(
+            noodles_record.description().map(|d| d.to_vec()),
+            Some(b"some read".to_vec())
+        );
+        assert_eq!(noodles_record.sequence().as_ref(), b"ACGTAC");
+        assert_eq!(
+            noodles_fastq::Record::try_from(&record).unwrap_err(),
+            SequenceError::MissingQuality
+        );
+
+        let owned = OwnedRecord::from(&record);
+        let noodles_record = noodles_fasta::Record::from(&owned);
+        assert_eq!(OwnedRecord::from(&noodles_record), owned);
+    }
+
+    #[test]
+    fn can_convert_fastq_records() {
+        let mut reader = parse_fastx_reader(&b"@r1\nACGT\n+\nII#I\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        let noodles_record = noodles_fastq::Record::try_from(&record).unwrap();
+        assert_eq!(noodles_record.name(), "r1");
+        assert!(noodles_record.description().is_empty());
+        assert_eq!(noodles_record.quality_scores(), b"II#I");
+
+        let owned = OwnedRecord::from(&record);
+        let noodles_record = noodles_fastq::Record::try_from(&owned).unwrap();
+        assert_eq!(OwnedRecord::from(&noodles_record), owned);
+
+        let owned = OwnedRecord::new(b"r2 desc", b"AC".to_vec(), Some(b"II".to_vec()));
+        let noodles_record = noodles_fastq::Record::try_from(&owned).unwrap();
+        assert_eq!(noodles_record.description(), "desc");
+        assert_eq!(OwnedRecord::from(&noodles_record), owned);
+    }
+}
@@ -0,0 +1,167 @@
+//! Options changing what the FASTA/FASTQ readers accept and how records are
+//! written
+use crate::parser::utils::LineEnding;
+use crate::quality::{QualityBins, QualityEncoding, QualityTransform};
+use crate::sequence::Alphabet;
+
+/// What the FASTQ reader does with records without qualities, i.e. whose
+/// quality line is empty or `*` (as in SAM) while their sequence isn't
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MissingQuality {
+    /// Return a parse error, as for any quality line of the wrong length
+    #[default]
+    Error,
+    /// Return records whose `qual()` is `None`
+    Omit,
+    /// Return records with this (Phred+33) quality for every base
+    Fill(u8),
+}
+
+/// Options of the FASTA and FASTQ readers, see
+/// [`parse_fastx_reader_with_options`](fn.parse_fastx_reader_with_options.html).
+///
+/// The defaults parse files the same way as the readers without options.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReaderOptions {
+    /// Reject records whose sequence isn't made of the given alphabet, see
+    /// `needletail::sequence::validate`
+    pub validate: Option<Alphabet>,
+    /// Encoding of the qualities of the FASTQ files read. Qualities in other
+    /// encodings than Phred+33 are converted so records always have Phred+33
+    /// qualities.
+    pub quality_encoding: QualityEncoding,
+    /// Guess the encoding of the qualities (e.g. the Solexa scores of old
+    /// files) from the first records instead of using `quality_encoding`, see
+    /// `needletail::quality::EncodingGuess::from_range`. With `multiline_fastq`,
+    /// only the first record is used.
+    pub detect_quality_encoding: bool,
+    pub missing_quality: MissingQuality,
+    /// Reject FASTQ records with quality characters outside of the range of
+    /// their encoding. This is off by default as it slows parsing down.
+    pub validate_quality: bool,
+    /// Reject files that are not perfectly clean: records with an id already
+    /// seen, with an empty sequence, with characters outside of the `validate`
+    /// alphabet (DNA if not set) or with line endings made of only `\r`.
+    /// Remembering the ids takes memory for big files.
+    pub strict: bool,
+    /// Reject records whose header is longer than this many bytes
+    pub max_header_len: Option<usize>,
+    /// Reject records longer than this many bytes, counting all their lines
+    pub max_record_len: Option<usize>,
+    /// Maximum size of the buffer of the reader: records that don't fit in it
+    /// are rejected instead of growing it further. The buffer starts at
+    /// 64 KiB by default.
+    pub max_buffer_size: Option<usize>,
+    /// After an invalid FASTQ record, skip to the next line starting with `@`
+    /// that looks like the start of a record and keep reading instead of
+    /// stopping. The error of the invalid record has the skipped byte range.
+    /// FASTA files have nothing to recover from as records start at any line
+    /// starting with `>`.
+    pub recover: bool,
+    /// Accept old-style FASTA files: lines starting with `;` are comments,
+    /// left out of the sequences, and the file can start with comments and
+    /// blank lines. Blank lines between records are always accepted.
+    pub legacy_fasta: bool,
+    /// Accept FASTQ records whose sequence and qualities are wrapped on
+    /// several lines: the qualities end on the line where there are as many
+    /// as bases. Slower than the default 4 lines per record parsing.
+    pub multiline_fastq: bool,
+}
+
+impl ReaderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn validate(mut self, alphabet: Alphabet) -> Self {
+        self.validate = Some(alphabet);
+        self
+    }
+
+    pub fn quality_encoding(mut self, encoding: QualityEncoding) -> Self {
+        self.quality_encoding = encoding;
+        self
+    }
+
+    pub fn detect_quality_encoding(mut self, detect: bool) -> Self {
+        self.detect_quality_encoding = detect;
+        self
+    }
+
+    pub fn missing_quality(mut self, missing_quality: MissingQuality) -> Self {
+        self.missing_quality = missing_quality;
+        self
+    }
+
+    pub fn validate_quality(mut self, validate: bool) -> Self {
+        self.validate_quality = validate;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn max_header_len(mut self, max: usize) -> Self {
+        self.max_header_len = Some(max);
+        self
+    }
+
+    pub fn max_record_len(mut self, max: usize) -> Self {
+        self.max_record_len = Some(max);
+        self
+    }
+
+    pub fn max_buffer_size(mut self, max: usize) -> Self {
+        self.max_buffer_size = Some(max);
+        self
+    }
+
+    pub fn recover(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
+    pub fn legacy_fasta(mut self, legacy: bool) -> Self {
+        self.legacy_fasta = legacy;
+        self
+    }
+
+    pub fn multiline_fastq(mut self, multiline: bool) -> Self {
+        self.multiline_fastq = multiline;
+        self
+    }
+}
+
+/// Options to write records with, see `SequenceRecord::write_with_options`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriterOptions {
+    /// Line ending to use instead of the one of the record
+    pub line_ending: Option<LineEnding>,
+    /// Transform the qualities of FASTQ records, before binning them
+    pub quality_transform: Option<QualityTransform>,
+    /// Bin the qualities of FASTQ records
+    pub quality_bins: Option<QualityBins>,
+}
+
+impl WriterOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = Some(line_ending);
+        self
+    }
+
+    pub fn quality_transform(mut self, transform: QualityTransform) -> Self {
+        self.quality_transform = Some(transform);
+        self
+    }
+
+    pub fn quality_bins(mut self, bins: QualityBins) -> Self {
+        self.quality_bins = Some(bins);
+        self
+    }
+}
@@ -0,0 +1,541 @@
+//! A record owning its data
+use std::fmt;
+use std::io::Write;
+use std::ops::Range;
+
+use crate::errors::{ParseError, SequenceError};
+use crate::parser::fasta::BufferPosition as FastaBufferPosition;
+use crate::parser::fastq::BufferPosition as FastqBufferPosition;
+use crate::parser::record::{fmt_record, preview, write_fasta, write_fastq, SequenceRecord};
+use crate::parser::utils::{Format, LineEnding, Position};
+use crate::sequence::{check_range, QualitySequence};
+use crate::Sequence;
+
+/// A FASTA or FASTQ record owning its data, unlike `SequenceRecord` which
+/// borrows the buffer of the reader. It can be kept around after reading
+/// the next records and sent to other threads.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::OwnedRecord;
+///
+/// let mut reader = parse_fastx_reader(&b">r1 some read\nACGT\nAC\n>r2\nT\n"[..]).unwrap();
+/// let mut records = Vec::new();
+/// while let Some(record) = reader.next() {
+///     records.push(OwnedRecord::from(&record.unwrap()));
+/// }
+/// assert_eq!(records[0].id, b"r1");
+/// assert_eq!(records[0].desc.as_deref(), Some(&b"some read"[..]));
+/// assert_eq!(records[0].seq, b"ACGTAC");
+/// assert_eq!(records[1].qual, None);
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct OwnedRecord {
+    /// The header up to the first space or tab
+    pub id: Vec<u8>,
+    /// The byte separating the id from the description in the header: a
+    /// space (the default) or a tab
+    pub sep: u8,
+    /// The rest of the header, if any
+    pub desc: Option<Vec<u8>>,
+    /// The sequence, without line endings
+    pub seq: Vec<u8>,
+    /// The quality scores, `None` for FASTA records
+    pub qual: Option<Vec<u8>>,
+}
+
+impl OwnedRecord {
+    /// Creates a record from its full header (id and description)
+    pub fn new(header: &[u8], seq: Vec<u8>, qual: Option<Vec<u8>>) -> Self {
+        let (id, desc) = split_header(header);
+        OwnedRecord {
+            id: id.to_vec(),
+            sep: header.get(id.len()).copied().unwrap_or(b' '),
+            desc: desc.map(|d| d.to_vec()),
+            seq,
+            qual,
+        }
+    }
+
+    /// Returns a builder to create a record field by field, see
+    /// `RecordBuilder`
+    pub fn builder() -> RecordBuilder {
+        RecordBuilder::default()
+    }
+
+    /// Replaces the id, which can't contain whitespace
+    pub fn set_id(&mut self, id: &[u8]) -> Result<(), SequenceError> {
+        check_header(id, true)?;
+        self.id = id.to_vec();
+        Ok(())
+    }
+
+    /// Replaces the description, which can't contain line endings
+    pub fn set_desc(&mut self, desc: Option<&[u8]>) -> Result<(), SequenceError> {
+        if let Some(desc) = desc {
+            check_header(desc, false)?;
+        }
+        self.desc = desc.map(|d| d.to_vec());
+        Ok(())
+    }
+
+    /// Adds `text` at the end of the description, separated by a space from
+    /// what was already there
+    ///
+    /// ```
+    /// use needletail::parser::OwnedRecord;
+    ///
+    /// let mut record = OwnedRecord::new(b"r1", b"ACGT".to_vec(), None);
+    /// record.append_desc(b"length=4").unwrap();
+    /// record.append_desc(b"gc=0.5").unwrap();
+    /// assert_eq!(record.header(), b"r1 length=4 gc=0.5");
+    /// ```
+    pub fn append_desc(&mut self, text: &[u8]) -> Result<(), SequenceError> {
+        check_header(text, false)?;
+        match &mut self.desc {
+            Some(desc) => {
+                desc.push(b' ');
+                desc.extend_from_slice(text);
+            }
+            None => self.desc = Some(text.to_vec()),
+        }
+        Ok(())
+    }
+
+    /// Replaces the sequence and the quality, which need to have the same
+    /// length
+    pub fn set_sequence(
+        &mut self,
+        seq: Vec<u8>,
+        qual: Option<Vec<u8>>,
+    ) -> Result<(), SequenceError> {
+        check_lengths(&seq, qual.as_deref())?;
+        self.seq = seq;
+        self.qual = qual;
+        Ok(())
+    }
+
+    /// Replaces the quality, which needs to be as long as the sequence
+    pub fn set_qual(&mut self, qual: Option<Vec<u8>>) -> Result<(), SequenceError> {
+        check_lengths(&self.seq, qual.as_deref())?;
+        self.qual = qual;
+        Ok(())
+    }
+
+    /// Only keeps the bases (and quality scores) in `range`
+    ///
+    /// ```
+    /// use needletail::parser::OwnedRecord;
+    ///
+    /// let mut record = OwnedRecord::new(b"r1", b"ACGTAC".to_vec(), Some(b"#IIII#".to_vec()));
+    /// record.trim(1..5).unwrap();
+    /// assert_eq!(record.seq, b"CGTA");
+    /// assert_eq!(record.qual.as_deref(), Some(&b"IIII"[..]));
+    /// assert!(record.trim(2..8).is_err());
+    /// ```
+    pub fn trim(&mut self, range: Range<usize>) -> Result<(), SequenceError> {
+        check_range(&range, self.seq.len())?;
+        self.seq.truncate(range.end);
+        self.seq.drain(..range.start);
+        if let Some(qual) = &mut self.qual {
+            qual.truncate(range.end);
+            qual.drain(..range.start);
+        }
+        Ok(())
+    }
+
+    /// `Fastq` if the record has qualities, `Fasta` otherwise
+    pub fn format(&self) -> Format {
+        if self.qual.is_some() {
+            Format::Fastq
+        } else {
+            Format::Fasta
+        }
+    }
+
+    /// The full header: the id and the description separated by `sep`. It
+    /// is the header of the file unless several spaces or tabs separated
+    /// them or the header ended with whitespace.
+    ///
+    /// ```
+    /// use needletail::parser::OwnedRecord;
+    ///
+    /// let record = OwnedRecord::new(b"r1\tsome read", b"ACGT".to_vec(), None);
+    /// assert_eq!(record.header(), b"r1\tsome read");
+    /// ```
+    pub fn header(&self) -> Vec<u8> {
+        match &self.desc {
+            Some(desc) => [&self.id[..], &[self.sep], desc].concat(),
+            None => self.id.clone(),
+        }
+    }
+
+    /// Copies the record in the same layout as the readers use so it can be
+    /// used where a `SequenceRecord` is expected, see `RecordView`
+    pub fn view(&self) -> RecordView {
+        let header = self.header();
+        let mut buffer = Vec::with_capacity(header.len() + 2 * self.seq.len() + 6);
+        match &self.qual {
+            Some(qual) => write_fastq(
+                &header,
+                &self.seq,
+                Some(qual),
+                &mut buffer,
+                LineEnding::Unix,
+            ),
+            None => write_fasta(&header, &self.seq, &mut buffer, LineEnding::Unix),
+        }
+        .expect("writing to a Vec doesn't fail");
+        let header_end = header.len() + 1;
+        let buf_pos = match &self.qual {
+            Some(qual) => {
+                let seq = header_end + 1;
+                let sep = seq + self.seq.len() + 1;
+                ViewPosition::Fastq(FastqBufferPosition {
+                    start: 0,
+                    end: sep + 2 + qual.len(),
+                    seq,
+                    sep,
+                    qual: sep + 2,
+                    wrapped: false,
+                })
+            }
+            None => ViewPosition::Fasta(FastaBufferPosition {
+                start: 0,
+                seq_pos: vec![header_end, buffer.len() - 1],
+                has_comments: false,
+                uncommented: Vec::new(),
+            }),
+        };
+        RecordView {
+            buffer,
+            buf_pos,
+            position: Position::new(0, 0),
+        }
+    }
+
+    /// Writes the record as FASTQ if it has qualities and FASTA otherwise
+    pub fn write(&self, writer: &mut dyn Write, line_ending: LineEnding) -> Result<(), ParseError> {
+        let header = self.header();
+        match &self.qual {
+            Some(qual) => write_fastq(&header, &self.seq, Some(qual), writer, line_ending),
+            None => write_fasta(&header, &self.seq, writer, line_ending),
+        }
+    }
+}
+
+impl Default for OwnedRecord {
+    fn default() -> Self {
+        OwnedRecord {
+            id: Vec::new(),
+            sep: b' ',
+            desc: None,
+            seq: Vec::new(),
+            qual: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ViewPosition {
+    Fasta(FastaBufferPosition),
+    Fastq(FastqBufferPosition),
+}
+
+/// A copy of an `OwnedRecord` laid out like in the buffer of a reader, to
+/// borrow it as a `SequenceRecord`, e.g. to pass it to functions written for
+/// the records of readers. Its position is the start of a file.
+///
+/// ```
+/// use needletail::parser::{OwnedRecord, SequenceRecord};
+///
+/// fn gc(record: &SequenceRecord) -> usize {
+///     record.seq().iter().filter(|b| matches!(b, b'G' | b'C')).count()
+/// }
+///
+/// let owned = OwnedRecord::new(b"r1 some read", b"ACGT".to_vec(), Some(b"IIII".to_vec()));
+/// let view = owned.view();
+/// let record = view.record();
+/// assert_eq!(gc(&record), 2);
+/// assert_eq!(record.id(), b"r1 some read");
+/// assert_eq!(OwnedRecord::from(&record), owned);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecordView {
+    buffer: Vec<u8>,
+    buf_pos: ViewPosition,
+    position: Position,
+}
+
+impl RecordView {
+    pub fn record(&self) -> SequenceRecord<'_> {
+        match &self.buf_pos {
+            ViewPosition::Fasta(bp) => {
+                SequenceRecord::new_fasta(&self.buffer, bp, &self.position, None)
+            }
+            ViewPosition::Fastq(bp) => {
+                SequenceRecord::new_fastq(&self.buffer, bp, &self.position, None)
+            }
+        }
+    }
+}
+
+/// Builds an `OwnedRecord`, checking that the header and the quality are
+/// valid. Records without qualities are FASTA records.
+///
+/// ```
+/// use needletail::parser::{Format, SequenceRecord};
+///
+/// let record = SequenceRecord::builder()
+///     .id("x")
+///     .desc("a read")
+///     .seq(b"ACGT")
+///     .qual(b"IIII")
+///     .build()
+///     .unwrap();
+/// assert_eq!(record.format(), Format::Fastq);
+/// assert_eq!(record.header(), b"x a read");
+/// assert!(SequenceRecord::builder().id("x").seq(b"ACGT").qual(b"I").build().is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordBuilder {
+    id: Vec<u8>,
+    desc: Option<Vec<u8>>,
+    seq: Vec<u8>,
+    qual: Option<Vec<u8>>,
+}
+
+impl RecordBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn id(mut self, id: impl AsRef<[u8]>) -> Self {
+        self.id = id.as_ref().to_vec();
+        self
+    }
+
+    pub fn desc(mut self, desc: impl AsRef<[u8]>) -> Self {
+        self.desc = Some(desc.as_ref().to_vec());
+        self
+    }
+
+    pub fn seq(mut self, seq: impl AsRef<[u8]>) -> Self {
+        self.seq = seq.as_ref().to_vec();
+        self
+    }
+
+    pub fn qual(mut self, qual: impl AsRef<[u8]>) -> Self {
+        self.qual = Some(qual.as_ref().to_vec());
+        self
+    }
+
+    pub fn build(self) -> Result<OwnedRecord, SequenceError> {
+        check_header(&self.id, true)?;
+        if let Some(desc) = &self.desc {
+            check_header(desc, false)?;
+        }
+        check_lengths(&self.seq, self.qual.as_deref())?;
+        Ok(OwnedRecord {
+            id: self.id,
+            desc: self.desc,
+            seq: self.seq,
+            qual: self.qual,
+            ..OwnedRecord::default()
+        })
+    }
+}
+
+fn check_header(text: &[u8], is_id: bool) -> Result<(), SequenceError> {
+    let invalid = |b: &u8| matches!(b, b'\n' | b'\r') || (is_id && matches!(b, b' ' | b'\t'));
+    match text.iter().position(invalid) {
+        Some(position) => Err(SequenceError::InvalidHeader {
+            position,
+            byte: text[position],
+        }),
+        None => Ok(()),
+    }
+}
+
+fn check_lengths(seq: &[u8], qual: Option<&[u8]>) -> Result<(), SequenceError> {
+    match qual {
+        Some(qual) if qual.len() != seq.len() => Err(SequenceError::UnequalLengths {
+            seq_len: seq.len(),
+            qual_len: qual.len(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Splits a header at its first space or tab. The whitespace separating the
+/// two parts is skipped and a description made only of whitespace is `None`.
+pub(crate) fn split_header(header: &[u8]) -> (&[u8], Option<&[u8]>) {
+    let is_space = |b: &u8| *b == b' ' || *b == b'\t';
+    let Some(pos) = header.iter().position(is_space) else {
+        return (header, None);
+    };
+    let desc = header[pos..]
+        .iter()
+        .position(|b| !is_space(b))
+        .map(|start| &header[pos + start..]);
+    (&header[..pos], desc)
+}
+
+/// Same as the `Display` of `SequenceRecord`
+impl fmt::Display for OwnedRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_record(f, &self.header(), &self.seq, self.qual.as_deref())
+    }
+}
+
+impl fmt::Debug for OwnedRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OwnedRecord")
+            .field("id", &String::from_utf8_lossy(&self.id))
+            .field("desc", &self.desc.as_deref().map(String::from_utf8_lossy))
+            .field("len", &self.seq.len())
+            .field("seq", &preview(&self.seq))
+            .field("qual", &self.qual.as_deref().map(preview))
+            .finish()
+    }
+}
+
+impl<'a> From<&SequenceRecord<'a>> for OwnedRecord {
+    fn from(record: &SequenceRecord<'a>) -> Self {
+        OwnedRecord::new(
+            record.id(),
+            record.seq().into_owned(),
+            record.qual().map(|q| q.to_vec()),
+        )
+    }
+}
+
+impl<'a> Sequence<'a> for OwnedRecord {
+    fn sequence(&'a self) -> &'a [u8] {
+        &self.seq
+    }
+}
+
+/// Records without qualities have an empty quality
+impl<'a> QualitySequence<'a> for OwnedRecord {
+    fn quality(&'a self) -> &'a [u8] {
+        self.qual.as_deref().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn can_format_records() {
+        let record = RecordBuilder::new()
+            .id("r1")
+            .desc("some read")
+            .seq(b"ACGT".repeat(10))
+            .build()
+            .unwrap();
+        assert_eq!(
+            record.to_string(),
+            "r1 some read (40 bp): ACGTACGTACGTACGTACGTACGTACGTAC..."
+        );
+        assert_eq!(
+            format!("{record:?}"),
+            "OwnedRecord { id: \"r1\", desc: Some(\"some read\"), len: 40, \
+             seq: \"ACGTACGTACGTACGTACGTACGTACGTAC...\", qual: None }"
+        );
+
+        let mut reader = parse_fastx_reader(&b"@r1\nACGT\n+\nII#I\n"[..]).unwrap();
+        let record = reader.next().unwrap().unwrap();
+        assert_eq!(
+            format!("{record:?}"),
+            "SequenceRecord { id: \"r1\", len: 4, seq: \"ACGT\", qual: Some(\"II#I\"), \
+             position: Position { line: 1, byte: 0, record: 0, consumed: 16, emitted: 1 } }"
+        );
+        assert_eq!(record.to_string(), OwnedRecord::from(&record).to_string());
+    }
+
+    #[test]
+    fn can_convert_records() {
+        let fastq = b"@r1\tdesc\nACGT\n+\nII#I\n";
+        let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+        let record = OwnedRecord::from(&reader.next().unwrap().unwrap());
+        assert_eq!(record.format(), Format::Fastq);
+        assert_eq!(record.id, b"r1");
+        assert_eq!(record.sep, b'\t');
+        assert_eq!(record.header(), b"r1\tdesc");
+        assert_eq!(record.quality_mask(b'5').as_ref(), b"ACNT");
+
+        let mut out = Vec::new();
+        record.write(&mut out, LineEnding::Unix).unwrap();
+        assert_eq!(out, fastq);
+
+        // and back to a `SequenceRecord`
+        let view = record.view();
+        let view_record = view.record();
+        assert_eq!(view_record.id(), b"r1\tdesc");
+        assert_eq!(view_record.qual(), Some(&b"II#I"[..]));
+        assert_eq!(OwnedRecord::from(&view_record), record);
+        for (header, seq) in [(&b"r1 desc"[..], &b"ACGT"[..]), (b"r2", b"")] {
+            let record = OwnedRecord::new(header, seq.to_vec(), None);
+            let view = record.view();
+            assert_eq!(view.record().num_bases(), record.seq.len());
+            assert_eq!(OwnedRecord::from(&view.record()), record);
+        }
+
+        // records can be sent to other threads
+        let handle = std::thread::spawn(move || record.seq.len());
+        assert_eq!(handle.join().unwrap(), 4);
+    }
+
+    #[test]
+    fn can_edit_records() {
+        let mut record = OwnedRecord::new(b"r1 old", b"ACGT".to_vec(), Some(b"IIII".to_vec()));
+        record.set_id(b"r2").unwrap();
+        assert_eq!(
+            record.set_id(b"r 3"),
+            Err(SequenceError::InvalidHeader {
+                position: 1,
+                byte: b' '
+            })
+        );
+        record.set_desc(None).unwrap();
+        assert!(record.set_desc(Some(b"two\nlines")).is_err());
+        assert_eq!(record.header(), b"r2");
+
+        assert_eq!(
+            record.set_qual(Some(b"II".to_vec())),
+            Err(SequenceError::UnequalLengths {
+                seq_len: 4,
+                qual_len: 2
+            })
+        );
+        assert!(record.set_sequence(b"AC".to_vec(), None).is_ok());
+        assert_eq!(record.format(), Format::Fasta);
+        record.set_qual(Some(b"#I".to_vec())).unwrap();
+        record.trim(1..2).unwrap();
+        assert_eq!(
+            (&record.seq[..], record.qual.as_deref()),
+            (&b"C"[..], Some(&b"I"[..]))
+        );
+    }
+
+    #[test]
+    fn can_build_records() {
+        let record = OwnedRecord::builder()
+            .id(b"r1")
+            .seq("ACGT")
+            .build()
+            .unwrap();
+        assert_eq!(record.format(), Format::Fasta);
+        assert_eq!(record.desc, None);
+        assert!(RecordBuilder::new().id("r 1").build().is_err());
+        assert!(RecordBuilder::new().desc("a\nb").build().is_err());
+        // empty records are fine
+        assert_eq!(
+            RecordBuilder::new().build().unwrap(),
+            OwnedRecord::default()
+        );
+    }
+}
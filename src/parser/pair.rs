@@ -0,0 +1,294 @@
+//! Reading paired-end files in sync
+use std::collections::VecDeque;
+use std::path::Path;
+
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::owned::OwnedRecord;
+use crate::parser::parse_fastx_file;
+use crate::parser::record::SequenceRecord;
+use crate::parser::utils::FastxReader;
+
+/// The name of a record without the `/1` or `/2` suffix of older Illumina
+/// files, which is the same for both mates
+pub(crate) fn mate_name(name: &[u8]) -> &[u8] {
+    match name {
+        [rest @ .., b'/', b'1' | b'2'] => rest,
+        _ => name,
+    }
+}
+
+fn record_position(record: &SequenceRecord) -> ErrorPosition {
+    ErrorPosition::at(
+        record.position(),
+        Some(String::from_utf8_lossy(record.id()).into()),
+    )
+}
+
+/// Checks that two records are mates given their names, returning an
+/// `UnpairedRecords` error at `position` otherwise
+fn check_mates(name1: &[u8], name2: &[u8], position: ErrorPosition) -> Result<(), ParseError> {
+    if mate_name(name1) == mate_name(name2) {
+        return Ok(());
+    }
+    Err(ParseError::new_unpaired_records(
+        format!(
+            "Mates have different names: '{}' and '{}'",
+            String::from_utf8_lossy(name1),
+            String::from_utf8_lossy(name2),
+        ),
+        position,
+    ))
+}
+
+/// Reads two files of paired-end reads together, returning the mates
+/// side by side. The files can be compressed differently.
+///
+/// By default it checks that the names of the mates are the same, once
+/// `/1` and `/2` suffixes are removed, and that both files have the same
+/// number of records. Both errors are `UnpairedRecords` errors.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::FastxPairReader;
+///
+/// let r1 = parse_fastx_reader(&b"@a/1\nACGT\n+\nIIII\n@b/1\nGG\n+\nII\n"[..]).unwrap();
+/// let r2 = parse_fastx_reader(&b"@a/2\nTTGC\n+\nIIII\n@c/2\nCC\n+\nII\n"[..]).unwrap();
+/// let mut reader = FastxPairReader::new(r1, r2);
+/// let (rec1, rec2) = reader.next().unwrap().unwrap();
+/// assert_eq!((&rec1.seq()[..], &rec2.seq()[..]), (&b"ACGT"[..], &b"TTGC"[..]));
+/// assert!(reader.next().unwrap().is_err());
+/// ```
+pub struct FastxPairReader<'a> {
+    r1: Box<dyn FastxReader + 'a>,
+    r2: Box<dyn FastxReader + 'a>,
+    check_names: bool,
+}
+
+impl<'a> FastxPairReader<'a> {
+    pub fn new(r1: Box<dyn FastxReader + 'a>, r2: Box<dyn FastxReader + 'a>) -> Self {
+        FastxPairReader {
+            r1,
+            r2,
+            check_names: true,
+        }
+    }
+
+    /// Opens both files with `parse_fastx_file`
+    pub fn from_paths<P: AsRef<Path>, Q: AsRef<Path>>(r1: P, r2: Q) -> Result<Self, ParseError> {
+        Ok(Self::new(parse_fastx_file(r1)?, parse_fastx_file(r2)?))
+    }
+
+    /// Whether to check that the mates have the same name, `true` by default
+    pub fn check_names(mut self, check_names: bool) -> Self {
+        self.check_names = check_names;
+        self
+    }
+
+    /// Returns the next pair of mates, see `FastxReader::next`.
+    /// The records borrow the buffers of the readers so this can't be an `Iterator`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<(SequenceRecord<'_>, SequenceRecord<'_>), ParseError>> {
+        let missing_mate = |file: &str, record: &SequenceRecord| {
+            ParseError::new_unpaired_records(
+                format!("The {file} file has fewer records"),
+                record_position(record),
+            )
+        };
+        match (self.r1.next(), self.r2.next()) {
+            (None, None) => None,
+            (Some(Err(e)), _) | (_, Some(Err(e))) => Some(Err(e)),
+            (Some(Ok(r1)), None) => Some(Err(missing_mate("second", &r1))),
+            (None, Some(Ok(r2))) => Some(Err(missing_mate("first", &r2))),
+            (Some(Ok(r1)), Some(Ok(r2))) => {
+                if self.check_names {
+                    if let Err(e) = check_mates(r1.name(), r2.name(), record_position(&r2)) {
+                        return Some(Err(e));
+                    }
+                }
+                Some(Ok((r1, r2)))
+            }
+        }
+    }
+}
+
+/// How many pairs `InterleavedPairs::new` looks at to decide whether the
+/// input is interleaved
+const DETECTION_PAIRS: usize = 4;
+
+/// An `Iterator` over the pairs of mates of a reader of interleaved reads
+/// (`r1`, `r2` of the first pair, `r1`, `r2` of the second pair...), as
+/// `OwnedRecord`s since the reader can only hold one record at a time.
+///
+/// The names of the mates are checked like in `FastxPairReader` and an
+/// `UnpairedRecords` error is returned if they differ or if the last record
+/// has no mate.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::InterleavedPairs;
+///
+/// let fastq = b"@a/1\nACGT\n+\nIIII\n@a/2\nTTGC\n+\nIIII\n@b/1\nGG\n+\nII\n@b/2\nCC\n+\nII\n";
+/// let pairs = InterleavedPairs::new(parse_fastx_reader(&fastq[..]).unwrap()).unwrap();
+/// let pairs: Vec<_> = pairs.map(|p| p.unwrap()).collect();
+/// assert_eq!(pairs.len(), 2);
+/// assert_eq!(pairs[1].1.seq, b"CC");
+///
+/// // not interleaved
+/// let fasta = b">a\nA\n>b\nC\n>c\nG\n>d\nT\n";
+/// assert!(InterleavedPairs::new(parse_fastx_reader(&fasta[..]).unwrap()).is_err());
+/// ```
+pub struct InterleavedPairs<'a> {
+    reader: Box<dyn FastxReader + 'a>,
+    /// The records read during the detection, with their line
+    buffer: VecDeque<(OwnedRecord, u64)>,
+}
+
+impl<'a> InterleavedPairs<'a> {
+    /// Wraps a reader after checking that its first pairs are mates, failing
+    /// with an `UnpairedRecords` error explaining why the input doesn't look
+    /// interleaved otherwise
+    pub fn new(mut reader: Box<dyn FastxReader + 'a>) -> Result<Self, ParseError> {
+        let mut buffer = VecDeque::with_capacity(2 * DETECTION_PAIRS);
+        while buffer.len() < 2 * DETECTION_PAIRS {
+            match reader.next() {
+                Some(record) => {
+                    let record = record?;
+                    buffer.push_back((OwnedRecord::from(&record), record.start_line_number()));
+                }
+                None => break,
+            }
+        }
+        for pair in buffer.make_contiguous().chunks(2) {
+            if let [(r1, _), (r2, line)] = pair {
+                check_mates(&r1.id, &r2.id, owned_position(r2, *line)).map_err(|mut e| {
+                    e.msg = format!("The input doesn't look interleaved: {}", e.msg);
+                    e
+                })?;
+            }
+        }
+        Ok(InterleavedPairs { reader, buffer })
+    }
+
+    fn next_record(&mut self) -> Option<Result<(OwnedRecord, u64), ParseError>> {
+        if let Some(record) = self.buffer.pop_front() {
+            return Some(Ok(record));
+        }
+        self.reader
+            .next()
+            .map(|record| record.map(|r| (OwnedRecord::from(&r), r.start_line_number())))
+    }
+}
+
+fn owned_position(record: &OwnedRecord, line: u64) -> ErrorPosition {
+    ErrorPosition {
+        line,
+        id: Some(String::from_utf8_lossy(&record.header()).into()),
+        ..Default::default()
+    }
+}
+
+impl Iterator for InterleavedPairs<'_> {
+    type Item = Result<(OwnedRecord, OwnedRecord), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (r1, line) = match self.next_record()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+        let (r2, line) = match self.next_record() {
+            Some(Ok(record)) => record,
+            Some(Err(e)) => return Some(Err(e)),
+            None => {
+                return Some(Err(ParseError::new_unpaired_records(
+                    String::from("The last record has no mate"),
+                    owned_position(&r1, line),
+                )))
+            }
+        };
+        if let Err(e) = check_mates(&r1.id, &r2.id, owned_position(&r2, line)) {
+            return Some(Err(e));
+        }
+        Some(Ok((r1, r2)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ParseErrorKind;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn checks_mates() {
+        let r1 = parse_fastx_reader(&b">a\nA\n>b\nC\n"[..]).unwrap();
+        let r2 = parse_fastx_reader(&b">a\nG\n"[..]).unwrap();
+        let mut reader = FastxPairReader::new(r1, r2);
+        assert!(reader.next().unwrap().is_ok());
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::UnpairedRecords);
+        assert_eq!(e.msg, "The second file has fewer records");
+
+        let r1 = parse_fastx_reader(&b">a 1:N:0:1\nA\n"[..]).unwrap();
+        let r2 = parse_fastx_reader(&b">b 2:N:0:1\nG\n"[..]).unwrap();
+        let mut reader = FastxPairReader::new(r1, r2);
+        let e = reader.next().unwrap().unwrap_err();
+        assert_eq!(e.msg, "Mates have different names: 'a' and 'b'");
+
+        let r1 = parse_fastx_reader(&b">a\nA\n"[..]).unwrap();
+        let r2 = parse_fastx_reader(&b">b\nG\n"[..]).unwrap();
+        let mut reader = FastxPairReader::new(r1, r2).check_names(false);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().is_none());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn can_read_differently_compressed_files() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut f1 = tempfile::NamedTempFile::new().unwrap();
+        f1.write_all(b"@a/1\nACGT\n+\nIIII\n").unwrap();
+        let mut f2 = tempfile::NamedTempFile::new().unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"@a/2\nTTTT\n+\nIIII\n").unwrap();
+        f2.write_all(&encoder.finish().unwrap()).unwrap();
+
+        let mut reader = FastxPairReader::from_paths(f1.path(), f2.path()).unwrap();
+        let (r1, r2) = reader.next().unwrap().unwrap();
+        assert_eq!((r1.id(), r2.id()), (&b"a/1"[..], &b"a/2"[..]));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn can_read_interleaved_pairs() {
+        let mut fasta = Vec::new();
+        for i in 0..6 {
+            fasta.extend_from_slice(format!(">r{i} 1:N:0:1\nA\n>r{i} 2:N:0:1\nC\n").as_bytes());
+        }
+        let pairs = InterleavedPairs::new(parse_fastx_reader(&fasta[..]).unwrap()).unwrap();
+        assert_eq!(pairs.filter(|p| p.is_ok()).count(), 6);
+
+        // the detection only looks at the first pairs
+        fasta.extend_from_slice(b">r6\nA\n>r7\nC\n>r8\nG\n");
+        let pairs: Vec<_> = InterleavedPairs::new(parse_fastx_reader(&fasta[..]).unwrap())
+            .unwrap()
+            .collect();
+        assert_eq!(pairs.len(), 8);
+        let e = pairs[6].as_ref().unwrap_err();
+        assert_eq!(e.msg, "Mates have different names: 'r6' and 'r7'");
+        assert_eq!(e.position.line, 27);
+        let e = pairs[7].as_ref().unwrap_err();
+        assert_eq!(e.msg, "The last record has no mate");
+
+        let e = InterleavedPairs::new(parse_fastx_reader(&b">a\nA\n>b\nA\n"[..]).unwrap())
+            .err()
+            .unwrap();
+        assert_eq!(e.kind, ParseErrorKind::UnpairedRecords);
+        assert_eq!(
+            e.to_string(),
+            "The input doesn't look interleaved: Mates have different names: 'a' and 'b' (record 'b' at line 3)"
+        );
+    }
+}
@@ -1,14 +1,31 @@
 use std::borrow::Cow;
+use std::fmt;
 use std::io::Write;
+use std::ops::Range;
 
 use memchr::memchr;
 
-use crate::errors::ParseError;
+use crate::errors::{ErrorPosition, ParseError, SequenceError};
+use crate::parser::bgzf::BgzfIndex;
 use crate::parser::fasta::BufferPosition as FastaBufferPosition;
 use crate::parser::fastq::BufferPosition as FastqBufferPosition;
+use crate::parser::options::{ReaderOptions, WriterOptions};
+use crate::parser::owned::{split_header, OwnedRecord, RecordBuilder};
 use crate::parser::utils::{Format, LineEnding, Position};
+use crate::quality::QualityBins;
+use crate::sequence::{check_range, validate, Alphabet, QualitySequence, Strand};
 use crate::Sequence;
 
+/// Where the qualities of a FASTQ record come from
+#[derive(Debug, Clone, Copy)]
+enum Qualities<'a> {
+    Buffer,
+    /// e.g. converted to Phred+33 or filled in by the reader
+    Replaced(&'a [u8]),
+    /// The record had no quality line, see `MissingQuality`
+    Missing,
+}
+
 #[derive(Debug, Clone)]
 enum BufferPositionKind<'a> {
     Fasta(&'a FastaBufferPosition),
@@ -16,12 +33,13 @@ enum BufferPositionKind<'a> {
 }
 
 /// A FASTA or FASTQ record
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct SequenceRecord<'a> {
     buffer: &'a [u8],
     buf_pos: BufferPositionKind<'a>,
     position: &'a Position,
     line_ending: LineEnding,
+    qual: Qualities<'a>,
 }
 
 impl<'a> SequenceRecord<'a> {
@@ -36,6 +54,7 @@ impl<'a> SequenceRecord<'a> {
             position,
             buf_pos: BufferPositionKind::Fasta(buf_pos),
             line_ending: line_ending.unwrap_or(LineEnding::Unix),
+            qual: Qualities::Buffer,
         }
     }
 
@@ -50,9 +69,22 @@ impl<'a> SequenceRecord<'a> {
             position,
             buf_pos: BufferPositionKind::Fastq(buf_pos),
             line_ending: line_ending.unwrap_or(LineEnding::Unix),
+            qual: Qualities::Buffer,
         }
     }
 
+    /// Replaces the quality line of a FASTQ record
+    pub(crate) fn with_qual(mut self, qual: &'a [u8]) -> Self {
+        self.qual = Qualities::Replaced(qual);
+        self
+    }
+
+    /// Marks a FASTQ record as not having qualities
+    pub(crate) fn without_qual(mut self) -> Self {
+        self.qual = Qualities::Missing;
+        self
+    }
+
     /// Returns the format of the record
     #[inline]
     pub fn format(&self) -> Format {
@@ -71,8 +103,31 @@ impl<'a> SequenceRecord<'a> {
         }
     }
 
+    /// Returns the name of the record: its id up to the first space or tab
+    ///
+    /// ```
+    /// use needletail::parse_fastx_reader;
+    ///
+    /// let mut reader = parse_fastx_reader(&b">r1  some\tread\nACGT\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// assert_eq!(record.id(), b"r1  some\tread");
+    /// assert_eq!(record.name(), b"r1");
+    /// assert_eq!(record.description(), Some(&b"some\tread"[..]));
+    /// ```
+    #[inline]
+    pub fn name(&self) -> &[u8] {
+        split_header(self.id()).0
+    }
+
+    /// Returns the rest of the id after the name and the whitespace following
+    /// it, if there is anything left
+    #[inline]
+    pub fn description(&self) -> Option<&[u8]> {
+        split_header(self.id()).1
+    }
+
     /// Returns the raw sequence of the record. Only matters for FASTA since it can contain
-    /// newlines.
+    /// newlines. The `;` comment lines read with the `legacy_fasta` option are left out.
     #[inline]
     pub fn raw_seq(&self) -> &[u8] {
         match self.buf_pos {
@@ -81,22 +136,27 @@ impl<'a> SequenceRecord<'a> {
         }
     }
 
-    /// Returns the cleaned up sequence of the record. For FASTQ it is the same as `raw_seq` but
-    /// for FASTA it is `raw_seq` minus all the `\r\n`
-    pub fn seq(&self) -> Cow<[u8]> {
+    /// Returns the cleaned up sequence of the record: `raw_seq` minus all the `\r\n`. For FASTQ
+    /// it is the same as `raw_seq` unless the record is wrapped (see `multiline_fastq`)
+    pub fn seq(&self) -> Cow<'_, [u8]> {
         match self.buf_pos {
             BufferPositionKind::Fasta(bp) => bp.seq(self.buffer),
-            BufferPositionKind::Fastq(bp) => bp.seq(self.buffer).into(),
+            BufferPositionKind::Fastq(bp) => bp.unwrapped_seq(self.buffer),
         }
     }
 
     /// Returns the quality line if there is one.
-    /// Always `None` for FASTA and `Some` for FASTQ, even if the quality line is empty.
+    /// Always `None` for FASTA and `Some` for FASTQ, even if the quality line is empty,
+    /// unless the reader was set to omit missing qualities (see `MissingQuality`).
     #[inline]
     pub fn qual(&self) -> Option<&[u8]> {
         match self.buf_pos {
             BufferPositionKind::Fasta(_) => None,
-            BufferPositionKind::Fastq(bp) => Some(bp.qual(self.buffer)),
+            BufferPositionKind::Fastq(bp) => match self.qual {
+                Qualities::Buffer => Some(bp.qual(self.buffer)),
+                Qualities::Replaced(qual) => Some(qual),
+                Qualities::Missing => None,
+            },
         }
     }
 
@@ -128,11 +188,147 @@ impl<'a> SequenceRecord<'a> {
         self.position
     }
 
+    /// Return the byte offset of the start of the record (its header line) in the
+    /// (decompressed) file
+    pub fn start_byte_offset(&self) -> u64 {
+        self.position.byte
+    }
+
+    /// Return the BGZF virtual offset of the start of the record (its header
+    /// line), given the index of the compressed file. `None` if the record is
+    /// not in the file.
+    pub fn virtual_offset(&self, index: &BgzfIndex) -> Option<u64> {
+        index.virtual_offset(self.position.byte)
+    }
+
+    /// Returns a builder to create an `OwnedRecord` field by field, e.g. in
+    /// tests, see `RecordBuilder`
+    pub fn builder() -> RecordBuilder {
+        RecordBuilder::new()
+    }
+
+    /// Copies the record into an `OwnedRecord`
+    pub fn to_owned_record(&self) -> OwnedRecord {
+        OwnedRecord::from(self)
+    }
+
     /// Which line ending is this record using?
     pub fn line_ending(&self) -> LineEnding {
         self.line_ending
     }
 
+    /// Returns the bases in `start..end` and their quality scores, if any.
+    /// The range is clamped to the length of the sequence.
+    ///
+    /// ```
+    /// use needletail::parse_fastx_reader;
+    ///
+    /// let mut reader = parse_fastx_reader(&b"@r\nACGTACGT\n+\n01234567\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// let (seq, qual) = record.crop(2, 5);
+    /// assert_eq!(&seq[..], b"GTA");
+    /// assert_eq!(qual, Some(&b"234"[..]));
+    /// ```
+    pub fn crop(&self, start: usize, end: usize) -> (Cow<'_, [u8]>, Option<&[u8]>) {
+        let seq = self.seq();
+        let end = end.min(seq.len());
+        let start = start.min(end);
+        let qual = self.qual().map(|q| &q[start..end]);
+        let seq = match seq {
+            Cow::Borrowed(s) => Cow::Borrowed(&s[start..end]),
+            Cow::Owned(s) => Cow::Owned(s[start..end].to_vec()),
+        };
+        (seq, qual)
+    }
+
+    /// Removes the first `n` bases (and quality scores)
+    pub fn head_crop(&self, n: usize) -> (Cow<'_, [u8]>, Option<&[u8]>) {
+        self.crop(n, usize::MAX)
+    }
+
+    /// Removes the last `n` bases (and quality scores)
+    pub fn tail_crop(&self, n: usize) -> (Cow<'_, [u8]>, Option<&[u8]>) {
+        self.crop(0, self.num_bases().saturating_sub(n))
+    }
+
+    /// Returns the bases in `range` and their quality scores, if any, both
+    /// reversed (and the bases complemented) if `strand` is `Strand::Reverse`.
+    /// See `Sequence::subseq`.
+    #[allow(clippy::type_complexity)]
+    pub fn subseq(
+        &self,
+        range: Range<usize>,
+        strand: Strand,
+    ) -> Result<(Cow<'_, [u8]>, Option<Cow<'_, [u8]>>), SequenceError> {
+        check_range(&range, self.num_bases())?;
+        let (seq, qual) = self.crop(range.start, range.end);
+        Ok(match strand {
+            Strand::Forward => (seq, qual.map(Cow::Borrowed)),
+            Strand::Reverse => (
+                seq.reverse_complement().into(),
+                qual.map(|q| q.iter().rev().copied().collect()),
+            ),
+        })
+    }
+
+    /// Whether the record has at least `min` bases, e.g. to filter out
+    /// reads that are too short after trimming
+    #[inline]
+    pub fn has_min_length(&self, min: usize) -> bool {
+        self.num_bases() >= min
+    }
+
+    /// Whether the record has at most `max` bases
+    #[inline]
+    pub fn has_max_length(&self, max: usize) -> bool {
+        self.num_bases() <= max
+    }
+
+    /// Runs the per-record checks enabled in the reader options
+    pub(crate) fn check(&self, options: &ReaderOptions) -> Result<(), ParseError> {
+        if let Some(max) = options.max_header_len.filter(|max| self.id().len() > *max) {
+            return Err(ParseError::new_limit_exceeded(
+                "Header",
+                max,
+                self.error_position(),
+            ));
+        }
+        if let Some(max) = options.max_record_len.filter(|max| self.all().len() > *max) {
+            return Err(ParseError::new_limit_exceeded(
+                "Record",
+                max,
+                self.error_position(),
+            ));
+        }
+        if options.strict {
+            if self.num_bases() == 0 {
+                return Err(ParseError::new_empty_sequence(self.error_position()));
+            }
+            let all = self.all();
+            if memchr::memchr_iter(b'\r', all).any(|i| i + 1 < all.len() && all[i + 1] != b'\n') {
+                return Err(ParseError::new_invalid_line_ending(self.error_position()));
+            }
+        }
+        let alphabet = options
+            .validate
+            .or(options.strict.then_some(Alphabet::default()));
+        if let Some(alphabet) = alphabet {
+            if let Err(invalid) = validate(&self.seq(), alphabet) {
+                return Err(ParseError::new_invalid_sequence(
+                    &invalid,
+                    self.error_position(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Where the record starts, to report errors found after parsing it
+    pub(crate) fn error_position(&self) -> ErrorPosition {
+        let id = self.id().split(|b| *b == b' ').next().unwrap_or_default();
+        ErrorPosition::at(self.position, Some(String::from_utf8_lossy(id).into()))
+    }
+
     /// Write record back to a `Write` instance. By default it will use the original line ending but
     /// you can force it to use another one.
     pub fn write(
@@ -141,6 +337,13 @@ impl<'a> SequenceRecord<'a> {
         forced_line_ending: Option<LineEnding>,
     ) -> Result<(), ParseError> {
         match self.buf_pos {
+            // comments are not written back
+            BufferPositionKind::Fasta(bp) if bp.has_comments => write_fasta(
+                self.id(),
+                &self.seq(),
+                writer,
+                forced_line_ending.unwrap_or(self.line_ending),
+            ),
             BufferPositionKind::Fasta(_) => write_fasta(
                 self.id(),
                 self.raw_seq(),
@@ -149,13 +352,109 @@ impl<'a> SequenceRecord<'a> {
             ),
             BufferPositionKind::Fastq(_) => write_fastq(
                 self.id(),
-                self.raw_seq(),
+                &self.seq(),
                 self.qual(),
                 writer,
                 forced_line_ending.unwrap_or(self.line_ending),
             ),
         }
     }
+
+    /// Returns the quality line binned with `bins`, `None` for FASTA
+    pub fn binned_qual(&self, bins: &QualityBins) -> Option<Cow<'_, [u8]>> {
+        self.qual().map(|q| bins.bin(q))
+    }
+
+    /// Write record to a `Write` instance, transformed according to `options`.
+    ///
+    /// ```
+    /// use needletail::parse_fastx_reader;
+    /// use needletail::parser::WriterOptions;
+    /// use needletail::quality::{QualityBins, QualityTransform};
+    ///
+    /// let mut reader = parse_fastx_reader(&b"@a\nACGT\n+\nJI5#\n"[..]).unwrap();
+    /// let record = reader.next().unwrap().unwrap();
+    /// let mut out = Vec::new();
+    /// let options = WriterOptions::new().quality_bins(QualityBins::illumina());
+    /// record.write_with_options(&mut out, &options).unwrap();
+    /// assert_eq!(out, b"@a\nACGT\n+\nII7'\n");
+    ///
+    /// let mut out = Vec::new();
+    /// let options = WriterOptions::new().quality_transform(QualityTransform::cap(30));
+    /// record.write_with_options(&mut out, &options).unwrap();
+    /// assert_eq!(out, b"@a\nACGT\n+\n??5#\n");
+    /// ```
+    pub fn write_with_options(
+        &self,
+        writer: &mut dyn Write,
+        options: &WriterOptions,
+    ) -> Result<(), ParseError> {
+        let line_ending = options.line_ending.unwrap_or(self.line_ending);
+        let Some(qual) = self.qual() else {
+            return self.write(writer, Some(line_ending));
+        };
+        let mut qual = Cow::Borrowed(qual);
+        if let Some(transform) = &options.quality_transform {
+            transform.apply_in_place(qual.to_mut());
+        }
+        if let Some(bins) = &options.quality_bins {
+            bins.bin_in_place(qual.to_mut());
+        }
+        write_fastq(self.id(), &self.seq(), Some(&qual), writer, line_ending)
+    }
+}
+
+/// How many bases and qualities `Display` and `Debug` show
+const PREVIEW_LEN: usize = 30;
+
+/// The start of `bytes` as a string, followed by `...` if it is longer
+pub(crate) fn preview(bytes: &[u8]) -> String {
+    let mut preview = String::from_utf8_lossy(&bytes[..bytes.len().min(PREVIEW_LEN)]).into_owned();
+    if bytes.len() > PREVIEW_LEN {
+        preview.push_str("...");
+    }
+    preview
+}
+
+/// Writes a record on one line: header, length, mean quality (for FASTQ)
+/// and start of the sequence
+pub(crate) fn fmt_record(
+    f: &mut fmt::Formatter,
+    header: &[u8],
+    seq: &[u8],
+    qual: Option<&[u8]>,
+) -> fmt::Result {
+    write!(f, "{} ({} bp", String::from_utf8_lossy(header), seq.len())?;
+    if let Some(qual) = qual {
+        write!(f, ", mean quality {:.1}", (seq, qual).mean_quality())?;
+    }
+    write!(f, "): {}", preview(seq))
+}
+
+/// ```
+/// use needletail::parse_fastx_reader;
+///
+/// let mut reader = parse_fastx_reader(&b"@r1 desc\nACGT\n+\nII#I\n"[..]).unwrap();
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!(record.to_string(), "r1 desc (4 bp, mean quality 30.5): ACGT");
+/// ```
+impl fmt::Display for SequenceRecord<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_record(f, self.id(), &self.seq(), self.qual())
+    }
+}
+
+impl fmt::Debug for SequenceRecord<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seq = self.seq();
+        f.debug_struct("SequenceRecord")
+            .field("id", &String::from_utf8_lossy(self.id()))
+            .field("len", &seq.len())
+            .field("seq", &preview(&seq))
+            .field("qual", &self.qual().map(preview))
+            .field("position", self.position)
+            .finish()
+    }
 }
 
 impl<'a> Sequence<'a> for SequenceRecord<'a> {
@@ -231,6 +530,7 @@ mod test {
     use std::io::Cursor;
 
     use crate::parse_fastx_reader;
+    use crate::sequence::Strand;
     fn seq(s: &[u8]) -> Cursor<&[u8]> {
         Cursor::new(s)
     }
@@ -247,6 +547,45 @@ mod test {
         assert_eq!(rec.start_line_number(), 5);
     }
 
+    #[test]
+    fn test_name_description() {
+        let mut reader = parse_fastx_reader(seq(b">r1\nA\n>r2 \nA\n>r3\t\tx y \r\nA\n")).unwrap();
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!((rec.name(), rec.description()), (&b"r1"[..], None));
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!((rec.name(), rec.description()), (&b"r2"[..], None));
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(rec.name(), b"r3");
+        assert_eq!(rec.description(), Some(&b"x y "[..]));
+    }
+
+    #[test]
+    fn test_crop() {
+        let mut reader = parse_fastx_reader(seq(b">wrapped\nACGT\nACGT\n")).unwrap();
+        let rec = reader.next().unwrap().unwrap();
+        assert_eq!(&rec.crop(2, 6).0[..], b"GTAC");
+        assert_eq!(&rec.crop(6, 20).0[..], b"GT");
+        assert!(rec.crop(10, 20).0.is_empty());
+        assert_eq!(&rec.head_crop(3).0[..], b"TACGT");
+        assert_eq!(&rec.tail_crop(3).0[..], b"ACGTA");
+        assert_eq!(rec.tail_crop(3).1, None);
+        assert!(rec.has_min_length(8) && !rec.has_min_length(9));
+        assert!(rec.has_max_length(8) && !rec.has_max_length(7));
+    }
+
+    #[test]
+    fn test_subseq() {
+        let mut reader = parse_fastx_reader(seq(b"@r\nAACGT\n+\n01234\n")).unwrap();
+        let rec = reader.next().unwrap().unwrap();
+        let (s, q) = rec.subseq(1..4, Strand::Reverse).unwrap();
+        assert_eq!(&s[..], b"CGT");
+        assert_eq!(q.as_deref(), Some(&b"321"[..]));
+        let (s, q) = rec.subseq(0..2, Strand::Forward).unwrap();
+        assert_eq!(&s[..], b"AA");
+        assert_eq!(q.as_deref(), Some(&b"01"[..]));
+        assert!(rec.subseq(3..6, Strand::Forward).is_err());
+    }
+
     #[test]
     fn test_position() {
         let mut reader = parse_fastx_reader(seq(
@@ -259,6 +598,7 @@ mod test {
 
         let rec = reader.next().unwrap().unwrap();
         assert_eq!(rec.position().byte(), 19);
+        assert_eq!(rec.start_byte_offset(), 19);
 
         let rec = reader.next().unwrap().unwrap();
         assert_eq!(rec.position().byte(), 40);
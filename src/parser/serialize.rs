@@ -0,0 +1,279 @@
+//! Serde support for `OwnedRecord`, behind the `serde` feature.
+//!
+//! Records are serialized as a struct with the fields `id`, `desc`, `seq`
+//! and `qual`. By default the fields are strings, which is what most text
+//! formats like JSON expect; use `as_bytes` to serialize them as bytes
+//! instead, or `as_bytes_rle` to also run-length encode the qualities in a
+//! `qual_rle` field (see `quality::encode_quality_rle`), which is much
+//! smaller for long reads with constant qualities. All the representations
+//! can be deserialized.
+use std::fmt;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{self, SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+
+use crate::parser::owned::OwnedRecord;
+use crate::quality::{decode_quality_rle, encode_quality_rle};
+
+struct Field<'a> {
+    data: &'a [u8],
+    as_bytes: bool,
+}
+
+impl Serialize for Field<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.as_bytes {
+            return serializer.serialize_bytes(self.data);
+        }
+        match std::str::from_utf8(self.data) {
+            Ok(text) => serializer.serialize_str(text),
+            Err(e) => Err(ser::Error::custom(format!(
+                "invalid UTF-8 at byte {}, serialize the record as bytes",
+                e.valid_up_to()
+            ))),
+        }
+    }
+}
+
+/// `rle` run-length encodes the qualities in a `qual_rle` field, only with
+/// `as_bytes` since the encoded qualities are not text
+fn serialize_record<S: Serializer>(
+    record: &OwnedRecord,
+    serializer: S,
+    as_bytes: bool,
+    rle: bool,
+) -> Result<S::Ok, S::Error> {
+    let field = |data| Field { data, as_bytes };
+    let mut state = serializer.serialize_struct("OwnedRecord", 4)?;
+    state.serialize_field("id", &field(&record.id))?;
+    state.serialize_field("desc", &record.desc.as_deref().map(field))?;
+    state.serialize_field("seq", &field(&record.seq))?;
+    if rle {
+        let encoded = record.qual.as_deref().map(encode_quality_rle);
+        state.serialize_field("qual_rle", &encoded.as_deref().map(field))?;
+    } else {
+        state.serialize_field("qual", &record.qual.as_deref().map(field))?;
+    }
+    state.end()
+}
+
+/// The fields are serialized as strings, failing if they are not valid UTF-8
+///
+/// ```
+/// use needletail::parser::OwnedRecord;
+///
+/// let record = OwnedRecord::new(b"r1", b"ACGT".to_vec(), Some(b"IIII".to_vec()));
+/// let json = serde_json::to_string(&record).unwrap();
+/// assert_eq!(json, r#"{"id":"r1","desc":null,"seq":"ACGT","qual":"IIII"}"#);
+/// assert_eq!(serde_json::from_str::<OwnedRecord>(&json).unwrap(), record);
+/// ```
+impl Serialize for OwnedRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_record(self, serializer, false, false)
+    }
+}
+
+/// A field given either as a string or as bytes
+struct Bytes(Vec<u8>);
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string or bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Bytes, E> {
+        Ok(Bytes(v.as_bytes().to_vec()))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Bytes, E> {
+        Ok(Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Bytes, E> {
+        Ok(Bytes(v))
+    }
+
+    // formats without a bytes type, like JSON, write them as arrays
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Bytes, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(Bytes(bytes))
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "OwnedRecord")]
+struct RawRecord {
+    id: Bytes,
+    #[serde(default)]
+    desc: Option<Bytes>,
+    seq: Bytes,
+    #[serde(default)]
+    qual: Option<Bytes>,
+    #[serde(default)]
+    qual_rle: Option<Bytes>,
+}
+
+/// The fields can be strings or bytes. The record is checked like in
+/// `RecordBuilder::build`.
+impl<'de> Deserialize<'de> for OwnedRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawRecord::deserialize(deserializer)?;
+        let mut builder = OwnedRecord::builder().id(raw.id.0).seq(raw.seq.0);
+        if let Some(desc) = raw.desc {
+            builder = builder.desc(desc.0);
+        }
+        let qual = match (raw.qual, raw.qual_rle) {
+            (Some(_), Some(_)) => return Err(de::Error::custom("both qual and qual_rle are set")),
+            (Some(qual), None) => Some(qual.0),
+            (None, Some(encoded)) => Some(
+                decode_quality_rle(&encoded.0)
+                    .ok_or_else(|| de::Error::custom("invalid run-length encoded qualities"))?,
+            ),
+            (None, None) => None,
+        };
+        if let Some(qual) = qual {
+            builder = builder.qual(qual);
+        }
+        builder.build().map_err(de::Error::custom)
+    }
+}
+
+/// Serializes the fields of an `OwnedRecord` as bytes, which works for records
+/// that are not valid UTF-8 and is more compact in binary formats.
+/// To use with `#[serde(with = "needletail::parser::serialize::as_bytes")]`.
+pub mod as_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::parser::owned::OwnedRecord;
+
+    pub fn serialize<S: Serializer>(
+        record: &OwnedRecord,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize_record(record, serializer, true, false)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<OwnedRecord, D::Error> {
+        OwnedRecord::deserialize(deserializer)
+    }
+}
+
+/// Same as `as_bytes` with the qualities run-length encoded in a `qual_rle`
+/// field. To use with
+/// `#[serde(with = "needletail::parser::serialize::as_bytes_rle")]`.
+pub mod as_bytes_rle {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::parser::owned::OwnedRecord;
+
+    pub fn serialize<S: Serializer>(
+        record: &OwnedRecord,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize_record(record, serializer, true, true)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<OwnedRecord, D::Error> {
+        OwnedRecord::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapper {
+        #[serde(with = "as_bytes")]
+        record: OwnedRecord,
+    }
+
+    #[test]
+    fn can_serialize_as_strings() {
+        let record = OwnedRecord::new(b"r1 a read", b"ACGT".to_vec(), None);
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            json,
+            r#"{"id":"r1","desc":"a read","seq":"ACGT","qual":null}"#
+        );
+        assert_eq!(serde_json::from_str::<OwnedRecord>(&json).unwrap(), record);
+
+        // desc and qual can be left out
+        let record: OwnedRecord = serde_json::from_str(r#"{"id":"r2","seq":"AC"}"#).unwrap();
+        assert_eq!(record, OwnedRecord::new(b"r2", b"AC".to_vec(), None));
+
+        let invalid = OwnedRecord::new(b"r1", vec![0xff], None);
+        assert!(serde_json::to_string(&invalid).is_err());
+    }
+
+    #[test]
+    fn can_serialize_as_bytes() {
+        let record = OwnedRecord::new(b"r1", vec![b'A', 0xff], Some(b"I#".to_vec()));
+        let wrapper = Wrapper { record };
+        let json = serde_json::to_string(&wrapper).unwrap();
+        assert_eq!(
+            json,
+            r#"{"record":{"id":[114,49],"desc":null,"seq":[65,255],"qual":[73,35]}}"#
+        );
+        assert_eq!(serde_json::from_str::<Wrapper>(&json).unwrap(), wrapper);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct RleWrapper {
+        #[serde(with = "as_bytes_rle")]
+        record: OwnedRecord,
+    }
+
+    #[test]
+    fn can_run_length_encode_qualities() {
+        let qual = [vec![b'I'; 1000], b"##".to_vec()].concat();
+        let record = OwnedRecord::new(b"r1", vec![b'A'; 1002], Some(qual));
+        let wrapper = RleWrapper { record };
+        let json = serde_json::to_string(&wrapper).unwrap();
+        assert!(json.contains(r#""qual_rle":[73,232,7,35,2]"#), "{}", json);
+        let decoded = serde_json::from_str::<RleWrapper>(&json).unwrap();
+        assert_eq!(decoded, wrapper);
+        // the other representations read the encoded qualities too
+        assert_eq!(
+            serde_json::from_str::<OwnedRecord>(&json[10..json.len() - 1]).unwrap(),
+            wrapper.record
+        );
+
+        let fasta = RleWrapper {
+            record: OwnedRecord::new(b"r2", b"AC".to_vec(), None),
+        };
+        let json = serde_json::to_string(&fasta).unwrap();
+        assert_eq!(serde_json::from_str::<RleWrapper>(&json).unwrap(), fasta);
+
+        let invalid = r#"{"id":"r1","seq":"AC","qual_rle":[73]}"#;
+        assert!(serde_json::from_str::<OwnedRecord>(invalid).is_err());
+        let both = r#"{"id":"r1","seq":"AC","qual":"II","qual_rle":[73,2]}"#;
+        assert!(serde_json::from_str::<OwnedRecord>(both).is_err());
+    }
+
+    #[test]
+    fn deserialization_checks_records() {
+        assert!(serde_json::from_str::<OwnedRecord>(r#"{"id":"r 1","seq":"AC"}"#).is_err());
+        let err = serde_json::from_str::<OwnedRecord>(r#"{"id":"r1","seq":"AC","qual":"I"}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("length"), "{}", err);
+    }
+}
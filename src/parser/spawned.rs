@@ -0,0 +1,153 @@
+//! Parsing on a background thread
+use std::panic;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::vec;
+
+use crate::errors::ParseError;
+use crate::parser::owned::OwnedRecord;
+use crate::parser::parse_fastx_file;
+use crate::parser::utils::{read_records, FastxReader};
+
+/// How many records are sent to the consumer at a time
+const BATCH_SIZE: usize = 256;
+
+/// An `Iterator` over the records of a reader that is read (and decompressed)
+/// on its own thread, so the parsing happens while the records are being
+/// processed.
+///
+/// The records are sent in batches of `BATCH_SIZE` over a channel holding
+/// up to `capacity` batches: the thread waits once the channel is full. It
+/// stops after the first error, or once the `SpawnedReader` is dropped. If
+/// the thread panics, the panic is resumed in the thread calling `next`.
+///
+/// ```
+/// use needletail::parser::SpawnedReader;
+///
+/// let reader = SpawnedReader::from_path("tests/data/test.fa", 4).unwrap();
+/// let ids: Vec<_> = reader.map(|r| r.unwrap().id).collect();
+/// assert_eq!(ids, vec![b"test".to_vec(), b"test2".to_vec()]);
+/// ```
+pub struct SpawnedReader {
+    rx: Receiver<Result<Vec<OwnedRecord>, ParseError>>,
+    batch: vec::IntoIter<OwnedRecord>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SpawnedReader {
+    /// Moves `reader` to a new thread, `capacity` being the number of
+    /// batches that can be waiting to be consumed
+    pub fn new(mut reader: Box<dyn FastxReader>, capacity: usize) -> Self {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        let handle = thread::spawn(move || loop {
+            let mut batch = Vec::with_capacity(BATCH_SIZE);
+            let error = read_records(&mut reader, &mut batch, BATCH_SIZE, usize::MAX).err();
+            let done = error.is_some() || batch.len() < BATCH_SIZE;
+            // the receiver was dropped if sending fails
+            if !batch.is_empty() && tx.send(Ok(batch)).is_err() {
+                return;
+            }
+            if let Some(e) = error {
+                let _ = tx.send(Err(e));
+            }
+            if done {
+                return;
+            }
+        });
+        SpawnedReader {
+            rx,
+            batch: Vec::new().into_iter(),
+            handle: Some(handle),
+        }
+    }
+
+    /// Opens a FASTA/FASTQ file (compressed or not) and reads it on a new
+    /// thread, see `new`
+    pub fn from_path<P: AsRef<Path>>(path: P, capacity: usize) -> Result<Self, ParseError> {
+        Ok(Self::new(parse_fastx_file(path)?, capacity))
+    }
+}
+
+impl Iterator for SpawnedReader {
+    type Item = Result<OwnedRecord, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.batch.next() {
+                return Some(Ok(record));
+            }
+            match self.rx.recv() {
+                Ok(Ok(batch)) => self.batch = batch.into_iter(),
+                Ok(Err(e)) => return Some(Err(e)),
+                // the sender is dropped once the thread is done, which is
+                // only the end of the file if it didn't panic
+                Err(_) => {
+                    if let Err(panic) = self.handle.take()?.join() {
+                        panic::resume_unwind(panic);
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+    use crate::parser::{LineEnding, Position, SequenceRecord};
+
+    #[test]
+    fn reads_like_the_parser() {
+        let path = "tests/data/PRJNA271013_head.fq";
+        let records: Vec<_> = SpawnedReader::from_path(path, 1)
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let mut reader = parse_fastx_file(path).unwrap();
+        let expected: Vec<_> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 2000);
+        assert_eq!(records, expected);
+
+        // the thread stops once the reader is dropped
+        let mut reader = SpawnedReader::from_path(path, 1).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        drop(reader);
+        assert!(SpawnedReader::from_path("tests/data/missing.fa", 1).is_err());
+    }
+
+    struct PanickingReader(Position);
+
+    impl FastxReader for PanickingReader {
+        fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+            panic!("parser bug");
+        }
+
+        fn position(&self) -> &Position {
+            &self.0
+        }
+
+        fn line_ending(&self) -> Option<LineEnding> {
+            None
+        }
+    }
+
+    #[test]
+    fn resumes_panics() {
+        let mut reader = SpawnedReader::new(Box::new(PanickingReader(Position::new(0, 0))), 1);
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| reader.next()));
+        assert!(res.is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn returns_parse_errors() {
+        let fastq = &b"@a\nACGT\n+\nIIII\n@b\nACGT\n+\nII\n"[..];
+        let mut reader = SpawnedReader::new(parse_fastx_reader(fastq).unwrap(), 1);
+        assert_eq!(reader.next().unwrap().unwrap().id, b"a");
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+}
@@ -1,8 +1,11 @@
+use std::collections::HashMap;
 use std::io;
 
 use memchr::memchr;
 
-use crate::errors::ParseError;
+use crate::errors::{ErrorPosition, ParseError};
+use crate::parser::options::ReaderOptions;
+use crate::parser::owned::{split_header, OwnedRecord};
 use crate::parser::record::SequenceRecord;
 
 pub(crate) const BUFSIZE: usize = 64 * 1024;
@@ -29,6 +32,37 @@ pub(crate) fn grow_to(current_size: usize) -> usize {
     }
 }
 
+/// Returns the capacity the buffer needs to grow to when the incomplete
+/// record at the start of `buf` doesn't fit in it, or a `LimitExceeded` error
+/// if the record or the buffer is bigger than allowed by `options`.
+pub(crate) fn grow_within_limits(
+    buf: &[u8],
+    capacity: usize,
+    options: &ReaderOptions,
+    position: &Position,
+) -> Result<usize, ParseError> {
+    let position = ErrorPosition::at(position, None);
+    if let Some(max) = options.max_header_len {
+        let header_len = memchr(b'\n', buf).map_or(buf.len(), |pos| trim_cr(&buf[..pos]).len());
+        // without the start character
+        if header_len.saturating_sub(1) > max {
+            return Err(ParseError::new_limit_exceeded("Header", max, position));
+        }
+    }
+    if let Some(max) = options.max_record_len {
+        if buf.len() > max {
+            return Err(ParseError::new_limit_exceeded("Record", max, position));
+        }
+    }
+    match options.max_buffer_size {
+        Some(max) if capacity >= max => {
+            Err(ParseError::new_limit_exceeded("Record", max, position))
+        }
+        Some(max) => Ok(grow_to(capacity).min(max)),
+        None => Ok(grow_to(capacity)),
+    }
+}
+
 /// Makes sure the buffer is full after this call (unless EOF reached)
 /// code adapted from `io::Read::read_exact`
 pub(crate) fn fill_buf<R>(reader: &mut buffer_redux::BufReader<R>) -> io::Result<usize>
@@ -48,16 +82,34 @@ where
     Ok(num_read)
 }
 
-/// Holds line number and byte offset of our current state in a parser
+/// Holds line number and byte offset of our current state in a parser: where
+/// the last record read starts, and how far the reader went
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Position {
     pub(crate) line: u64,
     pub(crate) byte: u64,
+    pub(crate) record: u64,
+    pub(crate) consumed: u64,
+    pub(crate) emitted: u64,
 }
 
 impl Position {
     pub fn new(line: u64, byte: u64) -> Self {
-        Self { line, byte }
+        Self {
+            line,
+            byte,
+            record: 0,
+            consumed: 0,
+            emitted: 0,
+        }
+    }
+
+    /// Sets the progress once the record at this position has been read,
+    /// `len` being its size with its last line ending
+    #[inline]
+    pub(crate) fn read_record(&mut self, len: u64) {
+        self.consumed = self.byte + len;
+        self.emitted = self.record + 1;
     }
 
     /// Line number (starting with 1)
@@ -69,6 +121,41 @@ impl Position {
     pub fn byte(&self) -> u64 {
         self.byte
     }
+
+    /// Index of the record in the file (starting with 0)
+    pub fn record(&self) -> u64 {
+        self.record
+    }
+
+    /// Bytes (of the decompressed stream) read up to the end of the last
+    /// record, i.e. where the reading could be resumed from
+    pub fn bytes_consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    /// Number of records read so far, including the ones returned as errors
+    /// by the checks of the `ReaderOptions`
+    pub fn records_emitted(&self) -> u64 {
+        self.emitted
+    }
+}
+
+/// The ids seen by a reader in strict mode, with the line of their record
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SeenIds(HashMap<Vec<u8>, u64>);
+
+impl SeenIds {
+    /// Remembers the id of the record at `position`, failing if it was
+    /// already seen
+    pub(crate) fn insert(&mut self, header: &[u8], position: &Position) -> Result<(), ParseError> {
+        let id = split_header(header).0;
+        if let Some(first_line) = self.0.get(id) {
+            let position = ErrorPosition::at(position, Some(String::from_utf8_lossy(id).into()));
+            return Err(ParseError::new_duplicate_id(*first_line, position));
+        }
+        self.0.insert(id.to_vec(), position.line);
+        Ok(())
+    }
 }
 
 /// FASTA or FASTQ?
@@ -115,16 +202,186 @@ pub fn find_line_ending(bytes: &[u8]) -> Option<LineEnding> {
     }
     None
 }
+/// Reads records into `batch` until it holds `max_records` records or about
+/// `max_bytes` bytes of ids, descriptions, sequences and qualities. At least
+/// one record is read if the reader isn't done, whatever `max_bytes` is.
+/// On errors, `batch` keeps the records read before.
+pub(crate) fn read_records<R: FastxReader + ?Sized>(
+    reader: &mut R,
+    batch: &mut Vec<OwnedRecord>,
+    max_records: usize,
+    max_bytes: usize,
+) -> Result<(), ParseError> {
+    let mut bytes = 0;
+    while batch.len() < max_records && (batch.is_empty() || bytes < max_bytes) {
+        let record = match reader.next() {
+            Some(record) => OwnedRecord::from(&record?),
+            None => break,
+        };
+        bytes += record.id.len()
+            + record.desc.as_ref().map_or(0, Vec::len)
+            + record.seq.len()
+            + record.qual.as_ref().map_or(0, Vec::len);
+        batch.push(record);
+    }
+    Ok(())
+}
+
 /// The main trait, iterator-like, that the FASTA and FASTQ readers implement
 pub trait FastxReader: Send {
     /// Gets the next record in the stream.
     /// This imitates the Iterator API but does not support any iterator functions.
     /// This returns None once we reached the EOF.
-    fn next(&mut self) -> Option<Result<SequenceRecord, ParseError>>;
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>>;
     /// Returns the current line/byte in the stream we are reading from
     fn position(&self) -> &Position;
     /// Returns whether the current stream uses Windows or Unix style line endings
     /// It is `None` only before calling `next`, once `next` has been called it will always
     /// return a line ending.
     fn line_ending(&self) -> Option<LineEnding>;
+    /// Returns a new reader going through the same input from the start, with
+    /// its own buffer, e.g. to read a file twice. Only the readers created by
+    /// `parse_fastx_file` support it, other inputs can only be read once.
+    fn try_clone(&self) -> Result<Box<dyn FastxReader>, ParseError> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Only the readers of files can be cloned",
+        )
+        .into())
+    }
+
+    /// Returns an `Iterator` over copies of the records, see `Records`
+    fn records(&mut self) -> Records<'_, Self>
+    where
+        Self: Sized,
+    {
+        Records { reader: self }
+    }
+
+    /// Returns an `Iterator` over batches of up to `n` records, see `Batches`
+    fn batches(&mut self, n: usize) -> Batches<'_, Self>
+    where
+        Self: Sized,
+    {
+        assert!(n > 0, "batches need to hold at least 1 record");
+        Batches {
+            reader: self,
+            max_records: n,
+            max_bytes: usize::MAX,
+            done: false,
+        }
+    }
+
+    /// Returns an `Iterator` over batches of records holding about
+    /// `max_bytes` bytes of ids, sequences and qualities each: records are
+    /// added until the batch reaches `max_bytes`, so a batch always holds at
+    /// least one record, even if `max_bytes` is 0. See `Batches`.
+    fn batches_by_bytes(&mut self, max_bytes: usize) -> Batches<'_, Self>
+    where
+        Self: Sized,
+    {
+        Batches {
+            reader: self,
+            max_records: usize::MAX,
+            max_bytes,
+            done: false,
+        }
+    }
+}
+
+impl<R: FastxReader + ?Sized> FastxReader for Box<R> {
+    fn next(&mut self) -> Option<Result<SequenceRecord<'_>, ParseError>> {
+        (**self).next()
+    }
+
+    fn position(&self) -> &Position {
+        (**self).position()
+    }
+
+    fn line_ending(&self) -> Option<LineEnding> {
+        (**self).line_ending()
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn FastxReader>, ParseError> {
+        (**self).try_clone()
+    }
+}
+
+/// An `Iterator` over the records of a reader as `OwnedRecord`s, so all the
+/// iterator adapters can be used.
+///
+/// Every record is copied: when performance matters, prefer calling `next`
+/// on the reader directly, which returns records borrowing its buffer.
+///
+/// ```
+/// use needletail::{parse_fastx_reader, FastxReader};
+///
+/// let fasta = b">a\nACGT\n>b\nAC\n>c\nACGTAC\n";
+/// let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+/// let long: Vec<_> = reader
+///     .records()
+///     .map(|r| r.unwrap())
+///     .filter(|r| r.seq.len() >= 4)
+///     .map(|r| r.id)
+///     .collect();
+/// assert_eq!(long, vec![b"a".to_vec(), b"c".to_vec()]);
+/// ```
+pub struct Records<'a, R: FastxReader + ?Sized> {
+    reader: &'a mut R,
+}
+
+impl<R: FastxReader + ?Sized> Iterator for Records<'_, R> {
+    type Item = Result<OwnedRecord, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader
+            .next()
+            .map(|record| record.map(|r| OwnedRecord::from(&r)))
+    }
+}
+
+/// An `Iterator` over batches of records as `Vec<OwnedRecord>`, e.g. to send
+/// them to other threads. All the batches are full except for the last one.
+///
+/// If a record can't be parsed, the error is returned instead of the batch
+/// it was part of and the iteration stops.
+///
+/// ```
+/// use needletail::{parse_fastx_reader, FastxReader};
+///
+/// let fasta = b">a\nACGT\n>b\nAC\n>c\nACGTAC\n";
+/// let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+/// let sizes: Vec<_> = reader.batches(2).map(|b| b.unwrap().len()).collect();
+/// assert_eq!(sizes, vec![2, 1]);
+///
+/// let mut reader = parse_fastx_reader(&fasta[..]).unwrap();
+/// let sizes: Vec<_> = reader.batches_by_bytes(6).map(|b| b.unwrap().len()).collect();
+/// assert_eq!(sizes, vec![2, 1]);
+/// ```
+pub struct Batches<'a, R: FastxReader + ?Sized> {
+    reader: &'a mut R,
+    max_records: usize,
+    max_bytes: usize,
+    done: bool,
+}
+
+impl<R: FastxReader + ?Sized> Iterator for Batches<'_, R> {
+    type Item = Result<Vec<OwnedRecord>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut batch = Vec::new();
+        if let Err(e) = read_records(self.reader, &mut batch, self.max_records, self.max_bytes) {
+            self.done = true;
+            return Some(Err(e));
+        }
+        if batch.is_empty() {
+            self.done = true;
+            None
+        } else {
+            Some(Ok(batch))
+        }
+    }
 }
@@ -0,0 +1,143 @@
+//! Physico-chemical properties of sequences: molecular weight and
+//! isoelectric point
+use crate::errors::SequenceError;
+use crate::sequence::Alphabet;
+
+/// Average mass of water in Da, lost at every bond
+const WATER: f64 = 18.0153;
+
+/// Average mass in Da of a nucleotide monophosphate or amino acid,
+/// case-insensitive
+fn monomer_weight(residue: u8, alphabet: Alphabet) -> Option<f64> {
+    Some(match (alphabet, residue.to_ascii_uppercase()) {
+        (Alphabet::Dna, b'A') => 331.2218,
+        (Alphabet::Dna, b'C') => 307.1971,
+        (Alphabet::Dna, b'G') => 347.2212,
+        (Alphabet::Dna, b'T') => 322.2085,
+        (Alphabet::Rna, b'A') => 347.2212,
+        (Alphabet::Rna, b'C') => 323.1965,
+        (Alphabet::Rna, b'G') => 363.2206,
+        (Alphabet::Rna, b'U') => 324.1813,
+        (Alphabet::Protein, b'A') => 89.0932,
+        (Alphabet::Protein, b'R') => 174.201,
+        (Alphabet::Protein, b'N') => 132.1179,
+        (Alphabet::Protein, b'D') => 133.1027,
+        (Alphabet::Protein, b'C') => 121.1582,
+        (Alphabet::Protein, b'E') => 147.1293,
+        (Alphabet::Protein, b'Q') => 146.1445,
+        (Alphabet::Protein, b'G') => 75.0666,
+        (Alphabet::Protein, b'H') => 155.1546,
+        (Alphabet::Protein, b'I') => 131.1729,
+        (Alphabet::Protein, b'L') => 131.1729,
+        (Alphabet::Protein, b'K') => 146.1876,
+        (Alphabet::Protein, b'M') => 149.2113,
+        (Alphabet::Protein, b'F') => 165.1891,
+        (Alphabet::Protein, b'P') => 115.1305,
+        (Alphabet::Protein, b'S') => 105.0926,
+        (Alphabet::Protein, b'T') => 119.1192,
+        (Alphabet::Protein, b'W') => 204.2252,
+        (Alphabet::Protein, b'Y') => 181.1885,
+        (Alphabet::Protein, b'V') => 117.1463,
+        (Alphabet::Protein, b'U') => 168.0532,
+        (Alphabet::Protein, b'O') => 255.3131,
+        _ => return None,
+    })
+}
+
+/// Average molecular weight in Da of a single-stranded DNA or RNA sequence
+/// (with a 5' monophosphate) or of a protein. Only unambiguous residues are
+/// supported; a trailing stop (`*`) is ignored for proteins.
+///
+/// ```
+/// use needletail::physchem::molecular_weight;
+/// use needletail::sequence::Alphabet;
+///
+/// let mw = molecular_weight(b"AGC", Alphabet::Dna).unwrap();
+/// assert!((mw - 949.61).abs() < 0.01);
+/// ```
+pub fn molecular_weight(seq: &[u8], alphabet: Alphabet) -> Result<f64, SequenceError> {
+    let seq = match (alphabet, seq.split_last()) {
+        (Alphabet::Protein, Some((b'*', rest))) => rest,
+        _ => seq,
+    };
+    let mut weight = 0.;
+    for (position, residue) in seq.iter().enumerate() {
+        weight += monomer_weight(*residue, alphabet).ok_or(SequenceError::InvalidBase {
+            position,
+            base: *residue,
+        })?;
+    }
+    Ok(weight - seq.len().saturating_sub(1) as f64 * WATER)
+}
+
+/// pKa of the charged groups, from EMBOSS
+const PK_N_TERM: f64 = 8.6;
+const PK_C_TERM: f64 = 3.6;
+/// (residue, pKa) of the positively charged side chains
+const PK_POSITIVE: [(u8, f64); 3] = [(b'K', 10.8), (b'R', 12.5), (b'H', 6.5)];
+/// (residue, pKa) of the negatively charged side chains
+const PK_NEGATIVE: [(u8, f64); 4] = [(b'D', 3.9), (b'E', 4.1), (b'C', 8.5), (b'Y', 10.1)];
+
+/// Net charge of a protein at the given pH
+pub fn charge_at_ph(protein: &[u8], ph: f64) -> f64 {
+    let positive = |pk: f64| 1. / (1. + 10f64.powf(ph - pk));
+    let negative = |pk: f64| -1. / (1. + 10f64.powf(pk - ph));
+    let mut charge = positive(PK_N_TERM) + negative(PK_C_TERM);
+    for residue in protein {
+        let residue = residue.to_ascii_uppercase();
+        if let Some((_, pk)) = PK_POSITIVE.iter().find(|(r, _)| *r == residue) {
+            charge += positive(*pk);
+        } else if let Some((_, pk)) = PK_NEGATIVE.iter().find(|(r, _)| *r == residue) {
+            charge += negative(*pk);
+        }
+    }
+    charge
+}
+
+/// Estimates the isoelectric point of a protein, the pH at which its net
+/// charge is 0, by bisection of `charge_at_ph` with the EMBOSS pKa values.
+///
+/// ```
+/// use needletail::physchem::isoelectric_point;
+///
+/// assert!(isoelectric_point(b"DDEEGG") < 4.);
+/// assert!(isoelectric_point(b"KKRRGG") > 11.);
+/// ```
+pub fn isoelectric_point(protein: &[u8]) -> f64 {
+    let (mut low, mut high) = (0., 14.);
+    while high - low > 1e-4 {
+        let mid = (low + high) / 2.;
+        if charge_at_ph(protein, mid) > 0. {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_of_each_alphabet() {
+        let dna = molecular_weight(b"at", Alphabet::Dna).unwrap();
+        assert!((dna - (331.2218 + 322.2085 - WATER)).abs() < 1e-9);
+        let rna = molecular_weight(b"AU", Alphabet::Rna).unwrap();
+        assert!(rna > dna);
+        assert!(molecular_weight(b"AT", Alphabet::Rna).is_err());
+        let protein = molecular_weight(b"GG*", Alphabet::Protein).unwrap();
+        assert!((protein - (2. * 75.0666 - WATER)).abs() < 1e-9);
+        assert_eq!(molecular_weight(b"", Alphabet::Protein), Ok(0.));
+    }
+
+    #[test]
+    fn isoelectric_point_is_neutral() {
+        let protein = b"MKWVTFISLLFLFSSAYSRGVFRRDTHKSEIAHRFKDLGEENFKALVLIAFAQYLQQCPF";
+        let pi = isoelectric_point(protein);
+        assert!(charge_at_ph(protein, pi).abs() < 1e-3);
+        assert!(charge_at_ph(protein, pi - 1.) > 0.);
+        assert!(charge_at_ph(protein, pi + 1.) < 0.);
+    }
+}
@@ -0,0 +1,233 @@
+//! Chains of record processing steps (filters and transforms) between a
+//! reader and a writer, counting how many records go through each step.
+use std::io::Write;
+
+use crate::errors::ParseError;
+use crate::filter::{IdFilter, ReadFilter, Subsampler};
+use crate::parser::{FastxReader, LineEnding, OwnedRecord};
+use crate::quality::PHRED_OFFSET;
+use crate::sequence::QualitySequence;
+
+enum Step<'a> {
+    Filter(Box<dyn FnMut(&OwnedRecord) -> bool + 'a>),
+    Map(Box<dyn FnMut(&mut OwnedRecord) + 'a>),
+}
+
+/// How many records entered and left a step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepStats {
+    pub name: String,
+    pub input: u64,
+    pub output: u64,
+}
+
+/// The counts of a pipeline run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    /// Records read
+    pub read: u64,
+    /// Records that made it through all the steps
+    pub written: u64,
+    /// The counts of each step, in order
+    pub steps: Vec<StepStats>,
+}
+
+/// Records read from a reader go through the steps in order, as
+/// `OwnedRecord`s, and the ones that are not filtered out are written or
+/// passed to a function.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::parser::LineEnding;
+/// use needletail::pipeline::Pipeline;
+///
+/// let fastq = b"@r1\nACGTAC\n+\nIIIII#\n@r2\nAC\n+\nII\n@r3\nNNNNAC\n+\nIIIIII\n";
+/// let reader = parse_fastx_reader(&fastq[..]).unwrap();
+/// let mut out = Vec::new();
+/// let stats = Pipeline::new(reader)
+///     .trim_quality(20)
+///     .min_length(4)
+///     .filter("no N", |r| !r.seq.contains(&b'N'))
+///     .map("rename", |r| r.id.extend_from_slice(b"_ok"))
+///     .write(&mut out, LineEnding::Unix)
+///     .unwrap();
+/// assert_eq!(out, b"@r1_ok\nACGTA\n+\nIIIII\n");
+/// assert_eq!((stats.read, stats.written), (3, 1));
+/// assert_eq!(stats.steps[1].name, "min length 4");
+/// assert_eq!((stats.steps[1].input, stats.steps[1].output), (3, 2));
+/// ```
+pub struct Pipeline<'a> {
+    reader: Box<dyn FastxReader + 'a>,
+    steps: Vec<Step<'a>>,
+    stats: PipelineStats,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(reader: Box<dyn FastxReader + 'a>) -> Self {
+        Pipeline {
+            reader,
+            steps: Vec::new(),
+            stats: PipelineStats::default(),
+        }
+    }
+
+    fn add_step(mut self, name: &str, step: Step<'a>) -> Self {
+        self.steps.push(step);
+        self.stats.steps.push(StepStats {
+            name: name.to_string(),
+            input: 0,
+            output: 0,
+        });
+        self
+    }
+
+    /// Only keeps the records for which `f` returns `true`
+    pub fn filter(self, name: &str, f: impl FnMut(&OwnedRecord) -> bool + 'a) -> Self {
+        self.add_step(name, Step::Filter(Box::new(f)))
+    }
+
+    /// Modifies the records with `f`
+    pub fn map(self, name: &str, f: impl FnMut(&mut OwnedRecord) + 'a) -> Self {
+        self.add_step(name, Step::Map(Box::new(f)))
+    }
+
+    /// Only keeps the records passing a `ReadFilter`
+    pub fn read_filter(self, filter: ReadFilter) -> Self {
+        self.filter("read filter", move |r| {
+            filter.check(&r.seq, r.qual.as_deref()).is_ok()
+        })
+    }
+
+    /// Only keeps (or excludes) the records whose id is in an `IdFilter`
+    pub fn ids(self, filter: IdFilter) -> Self {
+        self.filter("ids", move |r| filter.keep(&r.id))
+    }
+
+    /// Only keeps a random fraction of the records, see `Subsampler`
+    pub fn subsample(self, sampler: Subsampler) -> Self {
+        self.filter(&format!("subsample {}", sampler.fraction), move |r| {
+            sampler.keep(&r.id)
+        })
+    }
+
+    /// Only keeps the records with at least `min` bases
+    pub fn min_length(self, min: usize) -> Self {
+        self.filter(&format!("min length {min}"), move |r| r.seq.len() >= min)
+    }
+
+    /// Trims the ends of the FASTQ records with Phred scores below `cutoff` with
+    /// the modified Mott algorithm, see `QualitySequence::trim_quality_mott`
+    pub fn trim_quality(self, cutoff: u8) -> Self {
+        self.map(&format!("quality trim {cutoff}"), move |r| {
+            if r.qual.is_some() {
+                let range = r.trim_quality_mott(cutoff);
+                r.trim(range).expect("the range is in the sequence");
+            }
+        })
+    }
+
+    /// Replaces the bases of FASTQ records with Phred scores below `score` by
+    /// `N`, see `QualitySequence::quality_mask`
+    pub fn quality_mask(self, score: u8) -> Self {
+        let cutoff = score.saturating_add(PHRED_OFFSET);
+        self.map(&format!("quality mask {score}"), move |r| {
+            if r.qual.is_some() {
+                r.seq = r.quality_mask(cutoff).into_owned();
+            }
+        })
+    }
+
+    /// Runs the pipeline, calling `f` on every record making it through
+    /// all the steps
+    pub fn for_each(
+        mut self,
+        mut f: impl FnMut(OwnedRecord) -> Result<(), ParseError>,
+    ) -> Result<PipelineStats, ParseError> {
+        'records: while let Some(record) = self.reader.next() {
+            let mut record = OwnedRecord::from(&record?);
+            self.stats.read += 1;
+            for (step, stats) in self.steps.iter_mut().zip(&mut self.stats.steps) {
+                stats.input += 1;
+                match step {
+                    Step::Filter(f) => {
+                        if !f(&record) {
+                            continue 'records;
+                        }
+                    }
+                    Step::Map(f) => f(&mut record),
+                }
+                stats.output += 1;
+            }
+            self.stats.written += 1;
+            f(record)?;
+        }
+        Ok(self.stats)
+    }
+
+    /// Runs the pipeline, writing the records making it through all the
+    /// steps as FASTQ if they have qualities and as FASTA otherwise
+    pub fn write(
+        self,
+        writer: &mut dyn Write,
+        line_ending: LineEnding,
+    ) -> Result<PipelineStats, ParseError> {
+        self.for_each(|record| record.write(writer, line_ending))
+    }
+
+    /// Runs the pipeline, returning the records making it through all the
+    /// steps
+    pub fn collect(self) -> Result<(Vec<OwnedRecord>, PipelineStats), ParseError> {
+        let mut records = Vec::new();
+        let stats = self.for_each(|record| {
+            records.push(record);
+            Ok(())
+        })?;
+        Ok((records, stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn can_run_pipelines() {
+        let fasta = b">a\nACGTNNAC\n>b\nAC\n>c\nAAAAAAAA\n";
+        let filter = ReadFilter::new().max_n(1);
+        let (records, stats) = Pipeline::new(parse_fastx_reader(&fasta[..]).unwrap())
+            .read_filter(filter)
+            .quality_mask(30)
+            .trim_quality(30)
+            .collect()
+            .unwrap();
+        // FASTA records are not trimmed nor masked
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].seq, b"AAAAAAAA");
+        assert_eq!(stats.steps.len(), 3);
+        assert_eq!((stats.steps[0].input, stats.steps[0].output), (3, 2));
+        assert_eq!((stats.steps[2].input, stats.steps[2].output), (2, 2));
+
+        let fastq = b"@a\nACGT\n+\nI#I#\n";
+        let (records, stats) = Pipeline::new(parse_fastx_reader(&fastq[..]).unwrap())
+            .quality_mask(30)
+            .collect()
+            .unwrap();
+        assert_eq!(records[0].seq, b"ANGN");
+        assert_eq!(stats.written, 1);
+
+        let (records, _) = Pipeline::new(parse_fastx_reader(&fasta[..]).unwrap())
+            .ids(IdFilter::new(["b"]).exclude(true))
+            .collect()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].id, b"c");
+    }
+
+    #[test]
+    fn stops_on_errors() {
+        let reader = parse_fastx_reader(&b"@a\nACGT\n+\nIIII\n"[..]).unwrap();
+        let res = Pipeline::new(reader).for_each(|_| Err(ParseError::new_empty_file()));
+        assert!(res.is_err());
+    }
+}
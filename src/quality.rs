@@ -0,0 +1,701 @@
+//! Quality score encodings and conversions
+use std::borrow::Cow;
+
+use crate::errors::ParseError;
+use crate::parser::FastxReader;
+
+/// How quality scores are stored as ASCII characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum QualityEncoding {
+    /// Phred scores + 33 (Sanger, Illumina 1.8+, ...)
+    #[default]
+    Phred33,
+    /// Phred scores + 64 (Illumina 1.3 to 1.7)
+    Phred64,
+    /// Solexa scores (which can be negative) + 64 (Solexa, Illumina < 1.3)
+    Solexa,
+}
+
+impl QualityEncoding {
+    /// The ASCII value of a score of 0
+    pub fn offset(&self) -> u8 {
+        match self {
+            QualityEncoding::Phred33 => 33,
+            QualityEncoding::Phred64 | QualityEncoding::Solexa => 64,
+        }
+    }
+
+    /// The lowest ASCII value a valid quality score can have
+    pub fn min_char(&self) -> u8 {
+        match self {
+            QualityEncoding::Phred33 => 33,
+            QualityEncoding::Phred64 => 64,
+            // Solexa scores go down to -5
+            QualityEncoding::Solexa => 59,
+        }
+    }
+}
+
+/// Offset of the Phred+33 qualities used everywhere else in the crate
+pub const PHRED_OFFSET: u8 = 33;
+
+/// `10^(-i/10)` for i in 0..10
+const TENTHS: [f64; 10] = [
+    1.,
+    0.794_328_234_724_281_5,
+    0.630_957_344_480_193_2,
+    0.501_187_233_627_272_2,
+    0.398_107_170_553_497_3,
+    0.316_227_766_016_837_94,
+    0.251_188_643_150_958,
+    0.199_526_231_496_888,
+    0.158_489_319_246_111_35,
+    0.125_892_541_179_416_72,
+];
+
+/// The error probability of every Phred score
+static ERROR_PROBABILITIES: [f32; 256] = {
+    let mut table = [0f32; 256];
+    let mut power = 1f64;
+    let mut i = 0;
+    while i < 256 {
+        table[i] = (TENTHS[i % 10] * power) as f32;
+        if i % 10 == 9 {
+            power /= 10.;
+        }
+        i += 1;
+    }
+    table
+};
+
+/// Probability that a base call with the given Phred score is wrong. This is
+/// a table lookup.
+#[inline]
+pub fn error_probability(score: u8) -> f32 {
+    ERROR_PROBABILITIES[score as usize]
+}
+
+/// Iterator over the Phred scores of a quality string, Phred+33 encoded
+/// unless given another offset. Characters below the offset give 0.
+#[derive(Debug, Clone)]
+pub struct QualityScores<'a> {
+    qual: std::slice::Iter<'a, u8>,
+    offset: u8,
+}
+
+impl<'a> QualityScores<'a> {
+    pub fn new(qual: &'a [u8]) -> Self {
+        Self::with_offset(qual, PHRED_OFFSET)
+    }
+
+    /// For Phred+64 qualities for example
+    pub fn with_offset(qual: &'a [u8], offset: u8) -> Self {
+        QualityScores {
+            qual: qual.iter(),
+            offset,
+        }
+    }
+}
+
+impl Iterator for QualityScores<'_> {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.qual.next().map(|q| q.saturating_sub(self.offset))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.qual.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for QualityScores<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.qual.next_back().map(|q| q.saturating_sub(self.offset))
+    }
+}
+
+impl ExactSizeIterator for QualityScores<'_> {}
+
+/// Iterator over the error probabilities of the bases of a quality string,
+/// see `QualitySequence::error_probabilities`.
+#[derive(Debug, Clone)]
+pub struct ErrorProbabilities<'a> {
+    scores: QualityScores<'a>,
+}
+
+impl<'a> ErrorProbabilities<'a> {
+    pub fn new(qual: &'a [u8]) -> Self {
+        Self::with_offset(qual, PHRED_OFFSET)
+    }
+
+    pub fn with_offset(qual: &'a [u8], offset: u8) -> Self {
+        ErrorProbabilities {
+            scores: QualityScores::with_offset(qual, offset),
+        }
+    }
+}
+
+impl Iterator for ErrorProbabilities<'_> {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.scores.next().map(error_probability)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.scores.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for ErrorProbabilities<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.scores.next_back().map(error_probability)
+    }
+}
+
+impl ExactSizeIterator for ErrorProbabilities<'_> {}
+
+/// A binning of Phred+33 quality scores into a few levels, which makes
+/// quality strings much more compressible at little cost for downstream
+/// analyses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityBins {
+    table: [u8; 256],
+}
+
+impl QualityBins {
+    /// Creates a binning from `(lowest score of the bin, score given to the
+    /// bin)` pairs sorted by lowest score. Each bin goes up to the next one
+    /// and scores below the first bin are left untouched.
+    ///
+    /// ```
+    /// use needletail::quality::QualityBins;
+    ///
+    /// // everything is either Q10 or Q30
+    /// let bins = QualityBins::new(&[(0, 10), (20, 30)]);
+    /// assert_eq!(&bins.bin(b"#5I")[..], b"+??");
+    /// ```
+    pub fn new(bins: &[(u8, u8)]) -> Self {
+        assert!(
+            bins.windows(2).all(|w| w[0].0 < w[1].0),
+            "The bins need to be sorted by lowest score"
+        );
+        let mut table = [0u8; 256];
+        for (c, binned) in table.iter_mut().enumerate() {
+            let c = c as u8;
+            *binned = match c.checked_sub(PHRED_OFFSET) {
+                Some(score) => match bins.iter().rev().find(|(low, _)| *low <= score) {
+                    Some((_, value)) => value.saturating_add(PHRED_OFFSET).min(126),
+                    None => c,
+                },
+                None => c,
+            };
+        }
+        QualityBins { table }
+    }
+
+    /// The 8 levels binning of Illumina's sequencers (NovaSeq, HiSeq X, ...):
+    /// scores 2-9 become 6, 10-19 15, 20-24 22, 25-29 27, 30-34 33, 35-39 37
+    /// and 40+ 40. Scores of 0 and 1 (no calls) are kept.
+    pub fn illumina() -> Self {
+        QualityBins::new(&[
+            (2, 6),
+            (10, 15),
+            (20, 22),
+            (25, 27),
+            (30, 33),
+            (35, 37),
+            (40, 40),
+        ])
+    }
+
+    /// Bins a quality character
+    #[inline]
+    pub fn bin_char(&self, q: u8) -> u8 {
+        self.table[q as usize]
+    }
+
+    /// Bins a quality string, only allocating if it changes
+    pub fn bin<'a>(&self, qual: &'a [u8]) -> Cow<'a, [u8]> {
+        match qual.iter().position(|q| self.bin_char(*q) != *q) {
+            Some(first) => {
+                let mut binned = qual.to_vec();
+                self.bin_in_place(&mut binned[first..]);
+                binned.into()
+            }
+            None => qual.into(),
+        }
+    }
+
+    /// Bins a quality string in place
+    pub fn bin_in_place(&self, qual: &mut [u8]) {
+        for q in qual {
+            *q = self.bin_char(*q);
+        }
+    }
+}
+
+/// A transformation of Phred+33 quality scores: capping, recalibration or
+/// setting a constant, to make datasets from different instruments
+/// consistent. Transformations can be chained with `then`.
+///
+/// ```
+/// use needletail::quality::QualityTransform;
+///
+/// let cap = QualityTransform::cap(40);
+/// assert_eq!(&cap.apply(b"IJK5")[..], b"III5");
+/// let recalibrated = QualityTransform::linear(0.5, 10.).then(&cap);
+/// assert_eq!(&recalibrated.apply(b"!5~")[..], b"+5I");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityTransform {
+    table: [u8; 256],
+}
+
+impl QualityTransform {
+    /// Creates a transform from a function of the Phred scores. Characters
+    /// below `!` are left untouched and the results are capped at `~`.
+    pub fn from_fn(f: impl Fn(u8) -> u8) -> Self {
+        let mut table = [0u8; 256];
+        for (c, transformed) in table.iter_mut().enumerate() {
+            let c = c as u8;
+            *transformed = match c.checked_sub(PHRED_OFFSET) {
+                Some(score) => f(score).min(b'~' - PHRED_OFFSET) + PHRED_OFFSET,
+                None => c,
+            };
+        }
+        QualityTransform { table }
+    }
+
+    /// Scores above `max` become `max`
+    pub fn cap(max: u8) -> Self {
+        Self::from_fn(|score| score.min(max))
+    }
+
+    /// Every score becomes `score`
+    pub fn constant(score: u8) -> Self {
+        Self::from_fn(|_| score)
+    }
+
+    /// Score `i` becomes `table[i]`; scores past the end of the table become
+    /// its last value
+    pub fn recalibrate(table: &[u8]) -> Self {
+        assert!(!table.is_empty(), "The recalibration table can't be empty");
+        Self::from_fn(|score| table[usize::from(score).min(table.len() - 1)])
+    }
+
+    /// Score `q` becomes `slope * q + intercept`, rounded and clamped to the
+    /// valid scores
+    pub fn linear(slope: f64, intercept: f64) -> Self {
+        Self::from_fn(|score| {
+            (slope * f64::from(score) + intercept)
+                .round()
+                .clamp(0., f64::from(u8::MAX)) as u8
+        })
+    }
+
+    /// Applies this transform and then `other`
+    pub fn then(&self, other: &QualityTransform) -> Self {
+        let mut table = self.table;
+        for q in table.iter_mut() {
+            *q = other.apply_char(*q);
+        }
+        QualityTransform { table }
+    }
+
+    /// Transforms a quality character
+    #[inline]
+    pub fn apply_char(&self, q: u8) -> u8 {
+        self.table[q as usize]
+    }
+
+    /// Transforms a quality string, only allocating if it changes
+    pub fn apply<'a>(&self, qual: &'a [u8]) -> Cow<'a, [u8]> {
+        match qual.iter().position(|q| self.apply_char(*q) != *q) {
+            Some(first) => {
+                let mut transformed = qual.to_vec();
+                self.apply_in_place(&mut transformed[first..]);
+                transformed.into()
+            }
+            None => qual.into(),
+        }
+    }
+
+    /// Transforms a quality string in place
+    pub fn apply_in_place(&self, qual: &mut [u8]) {
+        for q in qual {
+            *q = self.apply_char(*q);
+        }
+    }
+}
+
+/// Run-length encodes a quality string: each run of the same quality is
+/// stored as the quality byte followed by the length of the run as a LEB128
+/// varint. Long reads with constant qualities (e.g. some PacBio and Nanopore
+/// data) shrink to a few bytes. Records can be serialized that way with
+/// `parser::serialize::as_bytes_rle` (`serde` feature).
+///
+/// ```
+/// use needletail::quality::{decode_quality_rle, encode_quality_rle};
+///
+/// let qual = [vec![b'I'; 200], b"##".to_vec()].concat();
+/// let encoded = encode_quality_rle(&qual);
+/// assert_eq!(encoded, vec![b'I', 0xc8, 0x01, b'#', 2]);
+/// assert_eq!(decode_quality_rle(&encoded), Some(qual));
+/// ```
+pub fn encode_quality_rle(qual: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for run in qual.chunk_by(|a, b| a == b) {
+        encoded.push(run[0]);
+        let mut len = run.len();
+        loop {
+            let byte = (len & 0x7f) as u8;
+            len >>= 7;
+            if len == 0 {
+                encoded.push(byte);
+                break;
+            }
+            encoded.push(byte | 0x80);
+        }
+    }
+    encoded
+}
+
+/// Decodes a quality string encoded by `encode_quality_rle` into `out`
+/// (cleared first). Returns `false` if `encoded` is truncated or invalid.
+pub fn decode_quality_rle_into(encoded: &[u8], out: &mut Vec<u8>) -> bool {
+    out.clear();
+    let mut bytes = encoded.iter();
+    while let Some(q) = bytes.next() {
+        let mut len = 0usize;
+        let mut shift = 0;
+        loop {
+            let Some(byte) = bytes.next() else {
+                return false;
+            };
+            if shift >= usize::BITS {
+                return false;
+            }
+            len |= usize::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        out.resize(out.len() + len, *q);
+    }
+    true
+}
+
+/// Decodes a quality string encoded by `encode_quality_rle`, `None` if
+/// `encoded` is truncated or invalid
+pub fn decode_quality_rle(encoded: &[u8]) -> Option<Vec<u8>> {
+    let mut qual = Vec::new();
+    decode_quality_rle_into(encoded, &mut qual).then_some(qual)
+}
+
+/// Converts a Solexa score to the closest Phred score
+fn solexa_to_phred(score: i32) -> i32 {
+    (10. * (10f64.powf(f64::from(score) / 10.) + 1.).log10()).round() as i32
+}
+
+/// Converts a Phred score to the closest Solexa score, Phred 0 being -5
+fn phred_to_solexa(score: i32) -> i32 {
+    if score <= 0 {
+        return -5;
+    }
+    ((10. * (10f64.powf(f64::from(score) / 10.) - 1.).log10()).round() as i32).max(-5)
+}
+
+/// Converts quality strings from one encoding to another with a lookup table
+/// of all the 256 possible characters.
+///
+/// Scores below the minimum of the source encoding (and Solexa scores below
+/// -5) are clamped to it, and the converted scores are clamped to the
+/// printable characters (`~` at most).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityConverter {
+    from: QualityEncoding,
+    to: QualityEncoding,
+    table: [u8; 256],
+}
+
+impl QualityConverter {
+    pub fn new(from: QualityEncoding, to: QualityEncoding) -> Self {
+        let mut table = [0u8; 256];
+        for (c, converted) in table.iter_mut().enumerate() {
+            let c = (c as u8).max(from.min_char());
+            let score = i32::from(c) - i32::from(from.offset());
+            let score = match (from, to) {
+                (QualityEncoding::Solexa, QualityEncoding::Solexa) => score,
+                (QualityEncoding::Solexa, _) => solexa_to_phred(score),
+                (_, QualityEncoding::Solexa) => phred_to_solexa(score),
+                _ => score,
+            };
+            let c = (score + i32::from(to.offset())).clamp(i32::from(to.min_char()), 126);
+            *converted = c as u8;
+        }
+        QualityConverter { from, to, table }
+    }
+
+    pub fn from(&self) -> QualityEncoding {
+        self.from
+    }
+
+    pub fn to(&self) -> QualityEncoding {
+        self.to
+    }
+
+    /// Converts a quality character
+    #[inline]
+    pub fn convert_char(&self, q: u8) -> u8 {
+        self.table[q as usize]
+    }
+
+    /// Converts a quality string, borrowing it if the encodings are the same
+    pub fn convert<'a>(&self, qual: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.from == self.to {
+            return qual.into();
+        }
+        qual.iter()
+            .map(|q| self.convert_char(*q))
+            .collect::<Vec<u8>>()
+            .into()
+    }
+
+    /// Converts a quality string into `out`, clearing it first
+    pub fn convert_into(&self, qual: &[u8], out: &mut Vec<u8>) {
+        out.clear();
+        out.extend(qual.iter().map(|q| self.convert_char(*q)));
+    }
+
+    /// Converts a quality string in place
+    pub fn convert_in_place(&self, qual: &mut [u8]) {
+        for q in qual {
+            *q = self.convert_char(*q);
+        }
+    }
+}
+
+/// Converts a quality string from one encoding to another, see
+/// `QualityConverter`. Use a `QualityConverter` directly to convert many
+/// quality strings.
+///
+/// To have the readers convert the qualities of a whole file to Phred+33, see
+/// `ReaderOptions::quality_encoding`.
+///
+/// ```
+/// use needletail::quality::{convert_quality, QualityEncoding};
+///
+/// let qual = convert_quality(b"hhB@", QualityEncoding::Phred64, QualityEncoding::Phred33);
+/// assert_eq!(&qual[..], b"II#!");
+/// // Solexa -5 is Phred 1
+/// let qual = convert_quality(b";h", QualityEncoding::Solexa, QualityEncoding::Phred33);
+/// assert_eq!(&qual[..], b"\"I");
+/// ```
+pub fn convert_quality(qual: &[u8], from: QualityEncoding, to: QualityEncoding) -> Cow<'_, [u8]> {
+    QualityConverter::new(from, to).convert(qual)
+}
+
+/// The result of `detect_quality_encoding`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodingGuess {
+    pub encoding: QualityEncoding,
+    /// How sure the guess is, from 0 (the qualities are invalid in any
+    /// encoding) to 1 (no other encoding is possible)
+    pub confidence: f64,
+    /// Lowest quality character seen
+    pub min: u8,
+    /// Highest quality character seen
+    pub max: u8,
+}
+
+impl EncodingGuess {
+    /// Guesses the encoding from the range of quality characters seen.
+    ///
+    /// Phred+33 is certain if anything is below `;` (-5 in Solexa). Above
+    /// that, characters past `J` (Q41 in Phred+33, the highest that
+    /// Illumina 1.8+ outputs) point to the +64 encodings, told apart by
+    /// the negative Solexa scores. Otherwise it's likely high-quality
+    /// Phred+33 data.
+    pub fn from_range(min: u8, max: u8) -> Self {
+        let (encoding, confidence) = match (min, max) {
+            (0..=32, _) => (QualityEncoding::Phred33, 0.),
+            (33..=58, _) => (QualityEncoding::Phred33, 1.),
+            (59..=63, 75..) => (QualityEncoding::Solexa, 0.9),
+            (64.., 75..) => (QualityEncoding::Phred64, 0.9),
+            _ => (QualityEncoding::Phred33, 0.6),
+        };
+        EncodingGuess {
+            encoding,
+            confidence,
+            min,
+            max,
+        }
+    }
+}
+
+/// Guesses the quality encoding from the first `max_records` records of a
+/// reader, see `EncodingGuess::from_range`. Returns `None` if no qualities
+/// were seen, e.g. for FASTA files.
+///
+/// ```
+/// use needletail::parse_fastx_reader;
+/// use needletail::quality::{detect_quality_encoding, QualityEncoding};
+///
+/// let fastq = b"@a\nACGT\n+\nhhgB\n@b\nACGT\n+\nhh^a\n";
+/// let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+/// let guess = detect_quality_encoding(reader.as_mut(), 1000).unwrap().unwrap();
+/// assert_eq!(guess.encoding, QualityEncoding::Phred64);
+/// assert_eq!((guess.min, guess.max), (b'B', b'h'));
+/// ```
+pub fn detect_quality_encoding(
+    reader: &mut dyn FastxReader,
+    max_records: usize,
+) -> Result<Option<EncodingGuess>, ParseError> {
+    let (mut min, mut max) = (u8::MAX, u8::MIN);
+    let mut n_records = 0;
+    while n_records < max_records {
+        let Some(record) = reader.next() else {
+            break;
+        };
+        if let Some(qual) = record?.qual() {
+            for q in qual {
+                min = min.min(*q);
+                max = max.max(*q);
+            }
+        }
+        n_records += 1;
+    }
+    if min > max {
+        return Ok(None);
+    }
+    Ok(Some(EncodingGuess::from_range(min, max)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    #[test]
+    fn error_probabilities_match_the_formula() {
+        for score in 0..=255u8 {
+            let expected = 10f64.powf(-f64::from(score) / 10.);
+            let found = f64::from(error_probability(score));
+            assert!((found - expected).abs() <= expected * 1e-6, "Q{score}");
+        }
+    }
+
+    #[test]
+    fn can_iterate_over_scores() {
+        let scores: Vec<u8> = QualityScores::new(b"!+5I ").collect();
+        assert_eq!(scores, vec![0, 10, 20, 40, 0]);
+        assert_eq!(QualityScores::new(b"!I").next_back(), Some(40));
+        let probs: Vec<f32> = ErrorProbabilities::new(b"!+5").collect();
+        assert_eq!(probs.len(), 3);
+        assert!((probs[0] - 1.).abs() < 1e-6);
+        assert!((probs[1] - 0.1).abs() < 1e-6);
+        assert!((probs[2] - 0.01).abs() < 1e-6);
+    }
+
+    #[test]
+    fn can_bin_qualities() {
+        let bins = QualityBins::illumina();
+        assert_eq!(&bins.bin(b"!\"#*+4569>?DIJ~")[..], b"!\"''00777<BFIII");
+        assert!(matches!(bins.bin(b"'07<BI"), Cow::Borrowed(_)));
+        let mut qual = b"IJ".to_vec();
+        bins.bin_in_place(&mut qual);
+        assert_eq!(qual, b"II");
+    }
+
+    #[test]
+    fn can_transform_qualities() {
+        assert_eq!(&QualityTransform::constant(30).apply(b"#5I")[..], b"???");
+        assert!(matches!(
+            QualityTransform::cap(41).apply(b"!IJ"),
+            Cow::Borrowed(_)
+        ));
+        let recalibrate = QualityTransform::recalibrate(&[0, 0, 5, 10]);
+        assert_eq!(&recalibrate.apply(b"!\"#$I")[..], b"!!&++");
+        // results are kept printable
+        assert_eq!(&QualityTransform::linear(2., 0.).apply(b"I~")[..], b"q~");
+        assert_eq!(&QualityTransform::linear(1., -50.).apply(b"I")[..], b"!");
+        let mut qual = b"JJ".to_vec();
+        QualityTransform::cap(40)
+            .then(&QualityTransform::linear(1., -10.))
+            .apply_in_place(&mut qual);
+        assert_eq!(qual, b"??");
+    }
+
+    #[test]
+    fn can_run_length_encode_qualities() {
+        assert!(encode_quality_rle(b"").is_empty());
+        assert_eq!(decode_quality_rle(b""), Some(Vec::new()));
+        let qual = b"IIII5+++I";
+        let encoded = encode_quality_rle(qual);
+        assert_eq!(encoded, b"I\x045\x01+\x03I\x01");
+        assert_eq!(decode_quality_rle(&encoded).unwrap(), qual);
+        let long = vec![b'?'; 100_000];
+        assert_eq!(encode_quality_rle(&long).len(), 4);
+        assert_eq!(
+            decode_quality_rle(&encode_quality_rle(&long)).unwrap(),
+            long
+        );
+        // truncated
+        assert_eq!(decode_quality_rle(b"I"), None);
+        assert_eq!(decode_quality_rle(b"I\x80"), None);
+    }
+
+    #[test]
+    fn can_guess_encodings() {
+        let guess = |min: u8, max: u8| EncodingGuess::from_range(min, max);
+        assert_eq!(guess(b'#', b'J').encoding, QualityEncoding::Phred33);
+        assert_eq!(guess(b'#', b'J').confidence, 1.);
+        assert_eq!(guess(b';', b'h').encoding, QualityEncoding::Solexa);
+        assert_eq!(guess(b'@', b'h').encoding, QualityEncoding::Phred64);
+        // could be either, but Phred+33 is much more common nowadays
+        assert_eq!(guess(b'@', b'I').encoding, QualityEncoding::Phred33);
+        assert!(guess(b'@', b'I').confidence < 1.);
+        assert_eq!(guess(b' ', b'I').confidence, 0.);
+    }
+
+    #[test]
+    fn detection_needs_qualities() {
+        let mut reader = parse_fastx_reader(&b">a\nACGT\n"[..]).unwrap();
+        assert_eq!(detect_quality_encoding(reader.as_mut(), 10).unwrap(), None);
+        let mut reader = parse_fastx_reader(&b"@a\nACGT\n+\nII\n"[..]).unwrap();
+        assert!(detect_quality_encoding(reader.as_mut(), 10).is_err());
+    }
+
+    #[test]
+    fn can_convert_qualities() {
+        use QualityEncoding::*;
+        // Solexa and Phred are the same above ~Q10
+        assert_eq!(&convert_quality(b"h", Solexa, Phred33)[..], b"I");
+        assert_eq!(&convert_quality(b"J", Phred33, Solexa)[..], b"i");
+        // negative Solexa scores are clamped
+        assert_eq!(&convert_quality(b"@;5", Solexa, Phred64)[..], b"CAA");
+        assert_eq!(&convert_quality(b"!\"", Phred33, Solexa)[..], b";;");
+        // out of range characters are clamped too
+        assert_eq!(&convert_quality(b"#~", Phred64, Phred33)[..], b"!_");
+        assert_eq!(&convert_quality(b"~", Phred33, Phred64)[..], b"~");
+        assert!(matches!(
+            convert_quality(b"II", Phred33, Phred33),
+            Cow::Borrowed(_)
+        ));
+
+        let converter = QualityConverter::new(Phred33, Phred64);
+        let mut qual = b"!I".to_vec();
+        converter.convert_in_place(&mut qual);
+        assert_eq!(qual, b"@h");
+        let mut out = Vec::new();
+        QualityConverter::new(Phred64, Phred33).convert_into(&qual, &mut out);
+        assert_eq!(out, b"!I");
+    }
+}
@@ -1,10 +1,178 @@
 //! Generic functions for working with (primarily nucleic acid) sequences
 use std::borrow::Cow;
+use std::ops::Range;
 
 use memchr::memchr2;
 
-use crate::bitkmer::BitNuclKmer;
-use crate::kmer::{CanonicalKmers, Kmers};
+use crate::bitkmer::{BitNuclKmer, CanonicalBitKmers, LongBitKmerSeq, SpacedBitKmers};
+use crate::complexity::{dust, kmer_entropy, DustParams};
+use crate::errors::SequenceError;
+use crate::kmer::{
+    gapped_seed, CanonicalKmers, Kmers, LazyCanonicalKmers, QualityKmers, SpacedKmers, ValidKmers,
+};
+use crate::melting::{melting_temperature, TmParams};
+use crate::minimizer::{MinimizerParams, Minimizers, SuperKmers};
+use crate::nthash::NtHash;
+use crate::quality::{ErrorProbabilities, QualityScores, PHRED_OFFSET};
+use crate::simd::{self, acgt_prefix_len};
+use crate::stats::BaseCounts;
+use crate::strobemer::{StrobemerParams, Strobemers};
+use crate::translate::{translate, CodonTable, Frame, PartialCodon, SixFrameTranslations};
+use crate::trim::trim_scores_mott;
+
+/// Which strand of a sequence something (a kmer, a match, etc) is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+impl Strand {
+    pub fn is_reverse(self) -> bool {
+        self == Strand::Reverse
+    }
+
+    /// The other strand
+    pub fn flip(self) -> Self {
+        match self {
+            Strand::Forward => Strand::Reverse,
+            Strand::Reverse => Strand::Forward,
+        }
+    }
+}
+
+/// The kind of sequence a normalized sequence should be
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Alphabet {
+    /// DNA: uridines are converted to thymines
+    #[default]
+    Dna,
+    /// RNA: thymines are converted to uridines
+    Rna,
+    /// Amino acids, see `normalize_protein`
+    Protein,
+}
+
+/// Whether `residue` is an uppercase amino acid code: the 20 standard ones,
+/// selenocysteine (U), pyrrolysine (O), the ambiguity codes B, Z, J and X,
+/// stops (*) and gaps (-)
+#[inline]
+fn is_protein_residue(residue: u8) -> bool {
+    residue.is_ascii_uppercase() || matches!(residue, b'*' | b'-')
+}
+
+/// Whether `base` (uppercase) is a nucleotide of `alphabet`, an IUPAC
+/// ambiguity code or a gap
+#[inline]
+fn is_valid_base(base: u8, alphabet: Alphabet) -> bool {
+    match (base, alphabet) {
+        (_, Alphabet::Protein) => is_protein_residue(base),
+        (b'A' | b'C' | b'G' | b'N' | b'-', _) => true,
+        (b'R' | b'Y' | b'S' | b'W' | b'K' | b'M' | b'B' | b'D' | b'H' | b'V', _) => true,
+        (b'T', Alphabet::Dna) | (b'U', Alphabet::Rna) => true,
+        _ => false,
+    }
+}
+
+/// Checks that every byte of `seq` belongs to `alphabet` (in either case),
+/// returning the position and value of all the ones that don't.
+///
+/// Nucleotide alphabets accept N, the IUPAC ambiguity codes and `-` gaps on
+/// top of ACGT/ACGU; see `validate_protein` for proteins. Unlike `normalize`,
+/// nothing is converted: whitespace, `.` gaps and U in DNA are all invalid.
+///
+/// ```
+/// use needletail::sequence::{validate, Alphabet};
+///
+/// assert!(validate(b"ACGTRYn-", Alphabet::Dna).is_ok());
+/// assert_eq!(validate(b"ACGU X", Alphabet::Dna), Err(vec![(3, b'U'), (4, b' '), (5, b'X')]));
+/// assert!(validate(b"ACGU", Alphabet::Rna).is_ok());
+/// ```
+pub fn validate(seq: &[u8], alphabet: Alphabet) -> Result<(), Vec<(usize, u8)>> {
+    let mut invalid = Vec::new();
+    let mut i = 0;
+    while i < seq.len() {
+        // skips the runs of ACGT with SIMD, only checking the other bytes one
+        // at a time
+        if alphabet == Alphabet::Dna {
+            i += acgt_prefix_len(&seq[i..]);
+            if i == seq.len() {
+                break;
+            }
+        }
+        if !is_valid_base(seq[i].to_ascii_uppercase(), alphabet) {
+            invalid.push((i, seq[i]));
+        }
+        i += 1;
+    }
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid)
+    }
+}
+
+/// Transform an amino acid sequence into its "normalized" form.
+///
+/// The normalized form is:
+///  - strip out any whitespace or line endings
+///  - lowercase residues are uppercased
+///  - `*` (stop) is kept and `.` and `~` are converted to `-` gaps
+///  - everything else is considered a X
+///
+/// ```
+/// use needletail::sequence::normalize_protein;
+///
+/// assert_eq!(normalize_protein(b"mkv.L*\n"), Some(b"MKV-L*".to_vec()));
+/// assert_eq!(normalize_protein(b"MKV#"), Some(b"MKVX".to_vec()));
+/// assert_eq!(normalize_protein(b"MKV"), None);
+/// ```
+pub fn normalize_protein(seq: &[u8]) -> Option<Vec<u8>> {
+    let mut buf: Vec<u8> = Vec::with_capacity(seq.len());
+    let mut changed: bool = false;
+
+    for n in seq {
+        let new_char = match *n {
+            c if is_protein_residue(c) => c,
+            c if c.is_ascii_lowercase() => c.to_ascii_uppercase(),
+            b'.' | b'~' => b'-',
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                changed = true;
+                continue;
+            }
+            _ => b'X',
+        };
+        changed = changed || new_char != *n;
+        buf.push(new_char);
+    }
+    if changed {
+        Some(buf)
+    } else {
+        None
+    }
+}
+
+/// Checks that every byte of `seq` is an amino acid code (in either case), a
+/// stop or a gap, returning the first one that isn't.
+///
+/// ```
+/// use needletail::sequence::validate_protein;
+///
+/// assert!(validate_protein(b"MKV-L*").is_ok());
+/// assert!(validate_protein(b"MK1").is_err());
+/// ```
+pub fn validate_protein(seq: &[u8]) -> Result<(), SequenceError> {
+    match seq
+        .iter()
+        .position(|r| !is_protein_residue(r.to_ascii_uppercase()))
+    {
+        Some(position) => Err(SequenceError::InvalidBase {
+            position,
+            base: seq[position],
+        }),
+        None => Ok(()),
+    }
+}
 
 /// Transform a nucleic acid sequence into its "normalized" form.
 ///
@@ -17,17 +185,36 @@ use crate::kmer::{CanonicalKmers, Kmers};
 ///  - IUPAC bases may be converted to N's depending on the parameter passed in
 ///  - everything else is considered a N
 pub fn normalize(seq: &[u8], allow_iupac: bool) -> Option<Vec<u8>> {
+    normalize_alphabet(seq, allow_iupac, Alphabet::Dna)
+}
+
+/// Same as `normalize` but T and U are converted to the base of `alphabet`,
+/// e.g. `Alphabet::Rna` gives AGCUN sequences. `Alphabet::Protein` uses
+/// `normalize_protein` instead and ignores `allow_iupac`.
+///
+/// ```
+/// use needletail::sequence::{normalize_alphabet, Alphabet};
+///
+/// assert_eq!(normalize_alphabet(b"ACGt", false, Alphabet::Rna), Some(b"ACGU".to_vec()));
+/// assert_eq!(normalize_alphabet(b"ACGU", false, Alphabet::Rna), None);
+/// ```
+pub fn normalize_alphabet(seq: &[u8], allow_iupac: bool, alphabet: Alphabet) -> Option<Vec<u8>> {
+    let thymine = match alphabet {
+        Alphabet::Dna => b'T',
+        Alphabet::Rna => b'U',
+        Alphabet::Protein => return normalize_protein(seq),
+    };
     let mut buf: Vec<u8> = Vec::with_capacity(seq.len());
     let mut changed: bool = false;
 
     for n in seq {
         let (new_char, char_changed) = match (*n, allow_iupac) {
-            c @ (b'A' | b'C' | b'G' | b'T' | b'N' | b'-', _) => (c.0, false),
+            c @ (b'A' | b'C' | b'G' | b'N' | b'-', _) => (c.0, false),
             (b'a', _) => (b'A', true),
             (b'c', _) => (b'C', true),
             (b'g', _) => (b'G', true),
-            // normalize uridine to thymine
-            (b't' | b'u' | b'U', _) => (b'T', true),
+            // normalize uridine to thymine (or the reverse for RNA)
+            (b't' | b'T' | b'u' | b'U', _) => (thymine, *n != thymine),
             // normalize gaps
             (b'.' | b'~', _) => (b'-', true),
             // logic for IUPAC bases (a little messy)
@@ -61,12 +248,147 @@ pub fn normalize(seq: &[u8], allow_iupac: bool) -> Option<Vec<u8>> {
     }
 }
 
-/// Returns the complementary base for a given IUPAC base code.
+/// A normalized sequence along with the intervals that were soft-masked
+/// (lowercase) in the original sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaskedSequence {
+    /// The normalized (uppercase) sequence
+    pub seq: Vec<u8>,
+    /// The lowercase intervals, in coordinates of the normalized sequence
+    pub masked: Vec<Range<usize>>,
+}
+
+impl MaskedSequence {
+    /// Whether the base at `pos` was soft-masked
+    pub fn is_masked(&self, pos: usize) -> bool {
+        let i = self.masked.partition_point(|r| r.end <= pos);
+        self.masked.get(i).is_some_and(|r| r.contains(&pos))
+    }
+
+    /// The normalized sequence with the masked intervals lowercased again
+    pub fn to_soft_masked(&self) -> Vec<u8> {
+        let mut seq = self.seq.clone();
+        for range in &self.masked {
+            seq[range.clone()].make_ascii_lowercase();
+        }
+        seq
+    }
+}
+
+/// Normalizes the sequence like `normalize` but also returns which parts of
+/// it were soft-masked (lowercase letters), e.g. repeats masked by
+/// RepeatMasker, so the masking can be honored or reapplied downstream.
+///
+/// ```
+/// use needletail::sequence::normalize_with_mask;
+///
+/// let masked = normalize_with_mask(b"ACgu\nnaCT", false);
+/// assert_eq!(masked.seq, b"ACGTNACT");
+/// assert_eq!(masked.masked, vec![2..6]);
+/// assert_eq!(masked.to_soft_masked(), b"ACgtnaCT");
+/// ```
+pub fn normalize_with_mask(seq: &[u8], allow_iupac: bool) -> MaskedSequence {
+    let normalized = normalize(seq, allow_iupac).unwrap_or_else(|| seq.to_vec());
+    // normalize only drops whitespace so the positions of the other
+    // characters match once it's skipped
+    let bases = seq
+        .iter()
+        .filter(|b| !matches!(b, b' ' | b'\t' | b'\r' | b'\n'));
+    MaskedSequence {
+        seq: normalized,
+        masked: lowercase_intervals(bases),
+    }
+}
+
+/// Uppercases the sequence without any other change, returning the
+/// soft-masked (lowercase) intervals alongside it.
+///
+/// ```
+/// use needletail::sequence::to_uppercase_preserving_mask;
+///
+/// let masked = to_uppercase_preserving_mask(b"ACgtnN");
+/// assert_eq!(masked.seq, b"ACGTNN");
+/// assert_eq!(masked.masked, vec![2..5]);
+/// ```
+pub fn to_uppercase_preserving_mask(seq: &[u8]) -> MaskedSequence {
+    MaskedSequence {
+        seq: seq.to_ascii_uppercase(),
+        masked: lowercase_intervals(seq.iter()),
+    }
+}
+
+/// The runs of lowercase letters of a sequence
+pub fn lowercase_intervals<'a>(seq: impl IntoIterator<Item = &'a u8>) -> Vec<Range<usize>> {
+    let mut masked: Vec<Range<usize>> = Vec::new();
+    for (pos, base) in seq.into_iter().enumerate() {
+        if !base.is_ascii_lowercase() {
+            continue;
+        }
+        match masked.last_mut() {
+            Some(last) if last.end == pos => last.end += 1,
+            _ => masked.push(pos..pos + 1),
+        }
+    }
+    masked
+}
+
+/// Lowercases the bases in `ranges`, e.g. to apply the intervals found by
+/// RepeatMasker or `complexity::dust`. Ranges are clamped to the sequence.
+///
+/// ```
+/// use needletail::sequence::soft_mask;
+///
+/// let mut seq = b"ACGTACGT".to_vec();
+/// soft_mask(&mut seq, &[1..3, 6..10]);
+/// assert_eq!(seq, b"AcgTACgt");
+/// ```
+pub fn soft_mask(seq: &mut [u8], ranges: &[Range<usize>]) {
+    for range in ranges {
+        let end = range.end.min(seq.len());
+        seq[range.start.min(end)..end].make_ascii_lowercase();
+    }
+}
+
+/// Replaces the bases in `ranges` by `N`. Ranges are clamped to the sequence.
 ///
+/// ```
+/// use needletail::sequence::hard_mask;
+///
+/// let mut seq = b"ACGTACGT".to_vec();
+/// hard_mask(&mut seq, &[1..3]);
+/// assert_eq!(seq, b"ANNTACGT");
+/// ```
+pub fn hard_mask(seq: &mut [u8], ranges: &[Range<usize>]) {
+    for range in ranges {
+        let end = range.end.min(seq.len());
+        seq[range.start.min(end)..end].fill(b'N');
+    }
+}
+
+/// Returns the complementary base for a given IUPAC base code, keeping its
+/// case. Ambiguity codes are complemented to the code of the complementary
+/// bases (e.g. R (A/G) to Y (C/T)) and N and gaps are their own complement.
+///
+/// Anything else is returned as is, see `try_complement` to catch them.
 /// Does not work for RNA sequences (maybe we should raise an error or something?)
 #[inline]
 pub fn complement(n: u8) -> u8 {
-    match n {
+    try_complement(n).unwrap_or(n)
+}
+
+/// Returns the complementary base for a given IUPAC base code or `None` if
+/// `n` isn't a nucleotide, IUPAC code or gap.
+///
+/// ```
+/// use needletail::sequence::try_complement;
+///
+/// assert_eq!(try_complement(b'R'), Some(b'Y'));
+/// assert_eq!(try_complement(b'd'), Some(b'h'));
+/// assert_eq!(try_complement(b'X'), None);
+/// ```
+#[inline]
+pub const fn try_complement(n: u8) -> Option<u8> {
+    Some(match n {
         b'a' => b't',
         b'A' => b'T',
         b'c' => b'g',
@@ -97,17 +419,94 @@ pub fn complement(n: u8) -> u8 {
         b'H' => b'D',
         b'S' => b'S',
         b'W' => b'W',
+        b'n' => b'n',
+        b'N' => b'N',
+
+        // gaps
+        b'-' => b'-',
+        b'.' => b'.',
 
-        // anything else just pass through
         // 'u' | 'U' => panic!("Does not support complements of U"),
-        x => x,
+        _ => return None,
+    })
+}
+
+/// Reverse complements `seq`, returning an error on the first byte that isn't
+/// a nucleotide, IUPAC code or gap rather than passing it through like
+/// `reverse_complement`.
+///
+/// ```
+/// use needletail::sequence::reverse_complement_strict;
+///
+/// assert_eq!(reverse_complement_strict(b"ACRn").unwrap(), b"nYGT");
+/// assert!(reverse_complement_strict(b"AC*G").is_err());
+/// ```
+pub fn reverse_complement_strict(seq: &[u8]) -> Result<Vec<u8>, SequenceError> {
+    seq.iter()
+        .enumerate()
+        .rev()
+        .map(|(position, base)| {
+            try_complement(*base).ok_or(SequenceError::InvalidBase {
+                position,
+                base: *base,
+            })
+        })
+        .collect()
+}
+
+/// Writes the reverse complement of `seq` into `out`, replacing its contents
+/// but reusing its allocation.
+///
+/// ```
+/// use needletail::sequence::reverse_complement_into;
+///
+/// let mut buf = Vec::new();
+/// reverse_complement_into(b"AACG", &mut buf);
+/// assert_eq!(buf, b"CGTT");
+/// ```
+pub fn reverse_complement_into(seq: &[u8], out: &mut Vec<u8>) {
+    out.clear();
+    out.resize(seq.len(), 0);
+    simd::reverse_complement(seq, out);
+}
+
+/// Reverse complements `seq` in place.
+///
+/// ```
+/// use needletail::sequence::reverse_complement_in_place;
+///
+/// let mut seq = b"AACG".to_vec();
+/// reverse_complement_in_place(&mut seq);
+/// assert_eq!(seq, b"CGTT");
+/// ```
+pub fn reverse_complement_in_place(seq: &mut [u8]) {
+    let len = seq.len();
+    for i in 0..len / 2 {
+        let (front, back) = (complement(seq[i]), complement(seq[len - 1 - i]));
+        seq[i] = back;
+        seq[len - 1 - i] = front;
+    }
+    if len % 2 == 1 {
+        seq[len / 2] = complement(seq[len / 2]);
+    }
+}
+
+/// Checks that `range` is a valid range of a sequence of length `length`
+pub(crate) fn check_range(range: &Range<usize>, length: usize) -> Result<(), SequenceError> {
+    if range.start > range.end || range.end > length {
+        return Err(SequenceError::OutOfBounds {
+            start: range.start,
+            end: range.end,
+            length,
+        });
     }
+    Ok(())
 }
 
 /// Taking in a sequence string, return the canonical form of the sequence
 /// (e.g. the lexigraphically lowest of either the original sequence or its
 /// reverse complement)
-pub fn canonical(seq: &[u8]) -> Cow<[u8]> {
+pub fn canonical(seq: &[u8]) -> Cow<'_, [u8]> {
     let mut buf: Vec<u8> = Vec::with_capacity(seq.len());
     // enough just keeps our comparisons from happening after they need to
     let mut enough = false;
@@ -136,7 +535,7 @@ pub fn canonical(seq: &[u8]) -> Cow<[u8]> {
 /// Find the lexigraphically smallest substring of `seq` of length `length`
 ///
 /// There's probably a faster algorithm for this somewhere...
-pub fn minimizer(seq: &[u8], length: usize) -> Cow<[u8]> {
+pub fn minimizer(seq: &[u8], length: usize) -> Cow<'_, [u8]> {
     let reverse_complement: Vec<u8> = seq.iter().rev().map(|n| complement(*n)).collect();
     let mut minmer = Cow::Borrowed(&seq[..length]);
 
@@ -151,6 +550,166 @@ pub fn minimizer(seq: &[u8], length: usize) -> Cow<[u8]> {
     minmer
 }
 
+/// A homopolymer-compressed sequence, where every run of identical bases is
+/// collapsed into a single base, along with the runs' positions in the
+/// original sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HomopolymerCompressed {
+    /// The compressed sequence
+    pub seq: Vec<u8>,
+    /// The start of each run in the original sequence, followed by the length
+    /// of the original sequence
+    offsets: Vec<usize>,
+}
+
+impl HomopolymerCompressed {
+    /// Compresses the runs of identical (case-sensitive) bytes of `seq`
+    pub fn new(seq: &[u8]) -> Self {
+        let mut compressed = Vec::new();
+        let mut offsets = Vec::new();
+        for (i, base) in seq.iter().enumerate() {
+            if compressed.last() != Some(base) {
+                compressed.push(*base);
+                offsets.push(i);
+            }
+        }
+        offsets.push(seq.len());
+        HomopolymerCompressed {
+            seq: compressed,
+            offsets,
+        }
+    }
+
+    /// Length of the compressed sequence
+    pub fn len(&self) -> usize {
+        self.seq.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seq.is_empty()
+    }
+
+    /// Length of the original sequence
+    pub fn original_len(&self) -> usize {
+        self.offsets[self.offsets.len() - 1]
+    }
+
+    /// Length of the run compressed into the base at `pos`
+    pub fn run_length(&self, pos: usize) -> usize {
+        self.offsets[pos + 1] - self.offsets[pos]
+    }
+
+    /// The lengths of all the runs, in order
+    pub fn run_lengths(&self) -> impl Iterator<Item = usize> + '_ {
+        self.offsets.windows(2).map(|w| w[1] - w[0])
+    }
+
+    /// The range of the original sequence covered by the compressed bases
+    /// `start..end`, e.g. to map a kmer or a minimizer back
+    pub fn original_range(&self, start: usize, end: usize) -> Range<usize> {
+        self.offsets[start]..self.offsets[end]
+    }
+
+    /// Position of the compressed base covering `pos` in the original sequence
+    pub fn compressed_pos(&self, pos: usize) -> usize {
+        assert!(pos < self.original_len());
+        self.offsets.partition_point(|o| *o <= pos) - 1
+    }
+
+    /// Expands the compressed sequence back into the original one
+    pub fn decompress(&self) -> Vec<u8> {
+        let mut seq = Vec::with_capacity(self.original_len());
+        for (base, len) in self.seq.iter().zip(self.run_lengths()) {
+            seq.extend(std::iter::repeat_n(*base, len));
+        }
+        seq
+    }
+}
+
+/// Whether `c` is an alignment gap: `-`, or `.` and `~` as used by some
+/// formats (e.g. Stockholm for insertions)
+#[inline]
+pub fn is_gap(c: u8) -> bool {
+    matches!(c, b'-' | b'.' | b'~')
+}
+
+/// Removes the gaps (see `is_gap`) of an aligned sequence, only allocating
+/// if there are any
+pub fn ungap(seq: &[u8]) -> Cow<'_, [u8]> {
+    if seq.iter().any(|c| is_gap(*c)) {
+        seq.iter()
+            .copied()
+            .filter(|c| !is_gap(*c))
+            .collect::<Vec<u8>>()
+            .into()
+    } else {
+        seq.into()
+    }
+}
+
+/// Maps positions between an aligned sequence (one row of a multiple
+/// alignment) and the same sequence without gaps.
+///
+/// ```
+/// use needletail::sequence::GapMap;
+///
+/// let map = GapMap::new(b"AC--GT.A");
+/// assert_eq!(map.to_aligned(2), 4);
+/// assert_eq!(map.to_ungapped(4), Some(2));
+/// assert_eq!(map.to_ungapped(3), None);
+/// assert_eq!(map.ungapped_range(1..7), 1..4);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GapMap {
+    /// The alignment column of every base
+    columns: Vec<usize>,
+    aligned_len: usize,
+}
+
+impl GapMap {
+    pub fn new(aligned: &[u8]) -> Self {
+        let columns = aligned
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !is_gap(**c))
+            .map(|(i, _)| i)
+            .collect();
+        GapMap {
+            columns,
+            aligned_len: aligned.len(),
+        }
+    }
+
+    /// Length of the aligned sequence, gaps included
+    pub fn aligned_len(&self) -> usize {
+        self.aligned_len
+    }
+
+    /// Length of the sequence without gaps
+    pub fn ungapped_len(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Alignment column of the base at `pos` in the ungapped sequence
+    pub fn to_aligned(&self, pos: usize) -> usize {
+        self.columns[pos]
+    }
+
+    /// Position in the ungapped sequence of the base in column `column`, or
+    /// `None` if the column is a gap
+    pub fn to_ungapped(&self, column: usize) -> Option<usize> {
+        self.columns.binary_search(&column).ok()
+    }
+
+    /// The bases of the ungapped sequence found in the alignment columns
+    /// `columns`, e.g. to map an alignment region back to a sequence
+    pub fn ungapped_range(&self, columns: Range<usize>) -> Range<usize> {
+        let start = self.columns.partition_point(|c| *c < columns.start);
+        let end = self.columns.partition_point(|c| *c < columns.end);
+        start..end.max(start)
+    }
+}
+
 /// A generic FASTX record that also abstracts over several logical operations
 /// that can be performed on nucleic acid sequences.
 pub trait Sequence<'a> {
@@ -200,11 +759,176 @@ pub trait Sequence<'a> {
     /// assert_eq!(b"AACC".reverse_complement(), b"GGTT");
     /// ```
     fn reverse_complement(&'a self) -> Vec<u8> {
-        self.sequence()
-            .iter()
-            .rev()
-            .map(|n| complement(*n))
-            .collect()
+        let mut rc = Vec::with_capacity(self.sequence().len());
+        reverse_complement_into(self.sequence(), &mut rc);
+        rc
+    }
+
+    /// Returns the reverse complement of the sequence or an error if it
+    /// contains anything else than nucleotides, IUPAC codes and gaps.
+    /// See `needletail::sequence::reverse_complement_strict`.
+    fn reverse_complement_strict(&'a self) -> Result<Vec<u8>, SequenceError> {
+        reverse_complement_strict(self.sequence())
+    }
+
+    /// Writes the reverse complement of the sequence into `out`, reusing its
+    /// allocation rather than creating a new `Vec` for every sequence.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    ///
+    /// let mut rc = Vec::new();
+    /// for seq in [&b"AACC"[..], b"GAT"] {
+    ///     seq.reverse_complement_into(&mut rc);
+    /// }
+    /// assert_eq!(rc, b"ATC");
+    /// ```
+    fn reverse_complement_into(&'a self, out: &mut Vec<u8>) {
+        reverse_complement_into(self.sequence(), out)
+    }
+
+    /// Returns the bases in `range` of the forward strand, reverse
+    /// complemented if `strand` is `Strand::Reverse`, e.g. to extract a
+    /// feature from its GFF coordinates (converted to a 0-based range).
+    ///
+    /// ```
+    /// use needletail::sequence::Strand;
+    /// use needletail::Sequence;
+    ///
+    /// let seq = b"AACCGGTT";
+    /// assert_eq!(seq.subseq(1..4, Strand::Forward).unwrap().as_ref(), b"ACC");
+    /// assert_eq!(seq.subseq(1..4, Strand::Reverse).unwrap().as_ref(), b"GGT");
+    /// assert!(seq.subseq(4..10, Strand::Forward).is_err());
+    /// ```
+    fn subseq(
+        &'a self,
+        range: Range<usize>,
+        strand: Strand,
+    ) -> Result<Cow<'a, [u8]>, SequenceError> {
+        let seq = self.sequence();
+        check_range(&range, seq.len())?;
+        Ok(match strand {
+            Strand::Forward => seq[range].into(),
+            Strand::Reverse => seq[range].reverse_complement().into(),
+        })
+    }
+
+    /// Removes the alignment gaps (`-`, `.` and `~`) from the sequence, e.g.
+    /// before extracting kmers from a row of a multiple alignment.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    ///
+    /// assert_eq!(b"AC--GT.A".ungap().as_ref(), b"ACGTA");
+    /// ```
+    fn ungap(&'a self) -> Cow<'a, [u8]> {
+        ungap(self.sequence())
+    }
+
+    /// Returns a copy of the sequence with the bases in `ranges` lowercased.
+    /// See `needletail::sequence::soft_mask`.
+    fn soft_masked(&'a self, ranges: &[Range<usize>]) -> Vec<u8> {
+        let mut seq = self.sequence().to_vec();
+        soft_mask(&mut seq, ranges);
+        seq
+    }
+
+    /// Returns a copy of the sequence with the bases in `ranges` replaced by
+    /// `N`. See `needletail::sequence::hard_mask`.
+    fn hard_masked(&'a self, ranges: &[Range<usize>]) -> Vec<u8> {
+        let mut seq = self.sequence().to_vec();
+        hard_mask(&mut seq, ranges);
+        seq
+    }
+
+    /// Uppercases the sequence, returning the soft-masked intervals alongside.
+    /// See `needletail::sequence::to_uppercase_preserving_mask`.
+    fn to_uppercase_preserving_mask(&'a self) -> MaskedSequence {
+        to_uppercase_preserving_mask(self.sequence())
+    }
+
+    /// Melting temperature in °C of the sequence as a DNA oligonucleotide.
+    /// See `needletail::melting::melting_temperature`.
+    ///
+    /// ```
+    /// use needletail::melting::TmParams;
+    /// use needletail::Sequence;
+    ///
+    /// let tm = b"GTAAAACGACGGCCAGT".melting_temperature(&TmParams::default()).unwrap();
+    /// assert!(tm > 50. && tm < 60.);
+    /// ```
+    fn melting_temperature(&'a self, params: &TmParams) -> Result<f64, SequenceError> {
+        melting_temperature(self.sequence(), params)
+    }
+
+    /// Counts the A, C, G, T, N, other IUPAC codes, gaps and anything else in
+    /// the sequence in one pass.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    ///
+    /// let counts = b"ACGTNNRY".composition();
+    /// assert_eq!((counts.a, counts.n, counts.iupac), (1, 2, 2));
+    /// ```
+    fn composition(&'a self) -> BaseCounts {
+        BaseCounts::new(self.sequence())
+    }
+
+    /// Fraction of G and C among the unambiguous bases (see `BaseCounts::gc_content`)
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    ///
+    /// assert_eq!(b"ACGTNN".gc_content(), 0.5);
+    /// ```
+    fn gc_content(&'a self) -> f64 {
+        self.composition().gc_content()
+    }
+
+    /// `(A - T) / (A + T)`, 0 if there are no A or T
+    fn at_skew(&'a self) -> f64 {
+        self.composition().at_skew()
+    }
+
+    /// `(G - C) / (G + C)`, 0 if there are no G or C
+    fn gc_skew(&'a self) -> f64 {
+        self.composition().gc_skew()
+    }
+
+    /// Normalized Shannon entropy of the kmers of size `k` of the sequence,
+    /// from 0 for homopolymers to 1 when every kmer is different. See
+    /// `needletail::complexity::kmer_entropy`.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    ///
+    /// assert_eq!(b"AAAAAAAAAA".complexity(3), 0.);
+    /// assert!(b"ACGGTCAGTTGACCATGATC".complexity(3) > 0.9);
+    /// ```
+    fn complexity(&'a self, k: u8) -> f64 {
+        kmer_entropy(self.sequence(), k)
+    }
+
+    /// The low-complexity intervals of the sequence found by DUST, which can be
+    /// used to filter or soft-mask simple repeats.
+    /// See `needletail::complexity::dust`.
+    fn low_complexity_intervals(&'a self, params: DustParams) -> Vec<Range<usize>> {
+        dust(self.sequence(), params)
+    }
+
+    /// Collapses every run of identical bases into a single base, keeping the
+    /// run lengths to map positions back to the original sequence.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    ///
+    /// let hpc = b"AAACGGGGT".homopolymer_compress();
+    /// assert_eq!(hpc.seq, b"ACGT");
+    /// assert_eq!(hpc.run_length(2), 4);
+    /// assert_eq!(hpc.original_range(1, 3), 3..8);
+    /// ```
+    fn homopolymer_compress(&'a self) -> HomopolymerCompressed {
+        HomopolymerCompressed::new(self.sequence())
     }
 
     /// [Nucleic Acids] Normalizes the sequence. See documentation for
@@ -231,6 +955,30 @@ pub trait Sequence<'a> {
         }
     }
 
+    /// [Nucleic Acids] Normalizes the sequence to the given alphabet. See
+    /// documentation for `needletail::sequence::normalize_alphabet`.
+    ///
+    /// ```
+    /// use needletail::sequence::Alphabet;
+    /// use needletail::Sequence;
+    ///
+    /// assert_eq!(b"ACGT".normalize_alphabet(false, Alphabet::Rna).as_ref(), b"ACGU");
+    /// ```
+    fn normalize_alphabet(&'a self, iupac: bool, alphabet: Alphabet) -> Cow<'a, [u8]> {
+        if let Some(s) = normalize_alphabet(self.sequence(), iupac, alphabet) {
+            s.into()
+        } else {
+            self.sequence().into()
+        }
+    }
+
+    /// [Nucleic Acids] Normalizes the sequence, keeping track of the
+    /// soft-masked (lowercase) intervals. See documentation for
+    /// `needletail::sequence::normalize_with_mask`.
+    fn normalize_with_mask(&'a self, iupac: bool) -> MaskedSequence {
+        normalize_with_mask(self.sequence(), iupac)
+    }
+
     /// [Nucleic Acids] Returns an iterator over the sequence that skips
     /// non-ACGT bases and returns a tuple containing (position, the
     /// canonicalized kmer, if the sequence is the complement of the original).
@@ -238,6 +986,21 @@ pub trait Sequence<'a> {
         CanonicalKmers::new(self.sequence(), reverse_complement, k)
     }
 
+    /// [Nucleic Acids] Same as `canonical_kmers` but doesn't require the
+    /// reverse complement of the sequence: kmers are complemented on the fly.
+    /// Kmers from the original sequence are borrowed while complemented ones
+    /// are allocated.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    ///
+    /// let kmers: Vec<_> = b"ACGT".lazy_canonical_kmers(2).map(|(_, k, _)| k).collect();
+    /// assert_eq!(kmers, vec![&b"AC"[..], b"CG", b"AC"]);
+    /// ```
+    fn lazy_canonical_kmers(&'a self, k: u8) -> LazyCanonicalKmers<'a> {
+        LazyCanonicalKmers::new(self.sequence(), k)
+    }
+
     /// Returns an iterator that returns a sliding window of k-sized
     /// sequences (k-mers). Does not skip whitespace or correct bases in the
     /// original sequence so `.normalize` or `.strip_returns` may be
@@ -246,10 +1009,150 @@ pub trait Sequence<'a> {
         Kmers::new(self.sequence(), k)
     }
 
+    /// Returns an iterator over the kmers that only contain ACGT bases (in any
+    /// case) along with their positions, e.g. skipping any kmer with an `N`.
+    fn valid_kmers(&'a self, k: u8) -> ValidKmers<'a> {
+        ValidKmers::new(self.sequence(), k)
+    }
+
     /// Return an iterator that returns valid kmers in 4-bit form
     fn bit_kmers(&'a self, k: u8, canonical: bool) -> BitNuclKmer<'a> {
         BitNuclKmer::new(self.sequence(), k, canonical)
     }
+
+    /// Return an iterator over the valid canonical kmers in 2-bit form along
+    /// with their position and the strand they were taken from.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    /// use needletail::sequence::Strand;
+    ///
+    /// let kmer = b"TTA".canonical_bit_kmers(3).next().unwrap();
+    /// assert_eq!(kmer.to_bytes(), b"TAA");
+    /// assert_eq!(kmer.strand, Strand::Reverse);
+    /// assert_eq!(kmer.original(), (0b111100, 3));
+    /// ```
+    fn canonical_bit_kmers(&'a self, k: u8) -> CanonicalBitKmers<'a> {
+        CanonicalBitKmers::new(self.sequence(), k)
+    }
+
+    /// Same as `bit_kmers` but the kmers are packed in a `u128` so k can go up
+    /// to 64
+    fn long_bit_kmers(&'a self, k: u8, canonical: bool) -> BitNuclKmer<'a, LongBitKmerSeq> {
+        BitNuclKmer::new_long(self.sequence(), k, canonical)
+    }
+
+    /// Return an iterator over the windowed minimizers of the sequence (see
+    /// `MinimizerParams` for the winnowing options).
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    /// use needletail::minimizer::MinimizerParams;
+    ///
+    /// let params = MinimizerParams::new(5, 4).end_minimizers(true);
+    /// let minimizers: Vec<_> = b"ACGTAGCTAGCTAGGATCGA".minimizers(params).collect();
+    /// assert_eq!(minimizers[0].pos, 0);
+    /// ```
+    fn minimizers(&'a self, params: MinimizerParams) -> Minimizers {
+        Minimizers::new(self.sequence(), params)
+    }
+
+    /// Return an iterator over the super-kmers of size `k` of the sequence,
+    /// using minimizers of size `params.k` (see `SuperKmers`).
+    fn super_kmers(&'a self, k: u8, params: MinimizerParams) -> SuperKmers {
+        SuperKmers::new(self.sequence(), k, params)
+    }
+
+    /// Translates the sequence to a protein in the given frame, ignoring a
+    /// trailing partial codon (see `translate::translate` for more control).
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    /// use needletail::translate::{CodonTable, Frame};
+    ///
+    /// let protein = b"ATGGCCTGA".translate(CodonTable::STANDARD, Frame::forward(0));
+    /// assert_eq!(protein, b"MA*");
+    /// ```
+    fn translate(&'a self, table: CodonTable, frame: Frame) -> Vec<u8> {
+        translate(self.sequence(), table, frame, PartialCodon::Drop)
+    }
+
+    /// Returns the translations of the six frames of the sequence, computed
+    /// lazily (see `translate::SixFrameTranslations`).
+    fn six_frame_translations(&'a self, table: CodonTable) -> SixFrameTranslations<'a> {
+        SixFrameTranslations::new(self.sequence(), table)
+    }
+
+    /// Return an iterator over the ntHash rolling hashes of every kmer
+    /// (skipping the ones with non-ACGT bases) along with their positions.
+    fn nthash(&'a self, k: u8, canonical: bool) -> NtHash<'a> {
+        NtHash::new(self.sequence(), k, canonical)
+    }
+
+    /// Returns an iterator over the bases at the care positions (`true`) of each
+    /// window of the spaced seed `pattern`. Like `.kmers`, it does not skip
+    /// any base. `kmer::spaced_seed` converts masks like `b"110101"` into a pattern.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    /// use needletail::kmer::spaced_seed;
+    ///
+    /// let pattern = spaced_seed(b"1101");
+    /// let kmers: Vec<Vec<u8>> = b"ACGTA".spaced_kmers(&pattern).collect();
+    /// assert_eq!(kmers, vec![b"ACT".to_vec(), b"CGA".to_vec()]);
+    /// ```
+    fn spaced_kmers(&'a self, pattern: &[bool]) -> SpacedKmers<'a> {
+        SpacedKmers::new(self.sequence(), pattern)
+    }
+
+    /// Return an iterator that returns the care positions of each window of
+    /// the spaced seed `pattern` in 2-bit form, skipping windows with non-ACGT
+    /// bases in care positions.
+    fn spaced_bit_kmers(&'a self, pattern: &[bool], canonical: bool) -> SpacedBitKmers<'a> {
+        SpacedBitKmers::new(self.sequence(), pattern, canonical)
+    }
+
+    /// Returns an iterator over the gapped kmers of the sequence: `word_len`
+    /// bases, a gap of `gap_len` bases that are dropped and `word_len` more
+    /// bases. See `spaced_kmers`.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    ///
+    /// let kmers: Vec<Vec<u8>> = b"ACGTA".gapped_kmers(2, 1).collect();
+    /// assert_eq!(kmers, vec![b"ACTA".to_vec()]);
+    /// ```
+    fn gapped_kmers(&'a self, word_len: usize, gap_len: usize) -> SpacedKmers<'a> {
+        SpacedKmers::new(self.sequence(), &gapped_seed(word_len, gap_len))
+    }
+
+    /// Returns an iterator over the gapped kmers of the sequence in 2-bit form,
+    /// skipping the ones with non-ACGT bases outside of the gap. See
+    /// `spaced_bit_kmers`.
+    fn gapped_bit_kmers(
+        &'a self,
+        word_len: usize,
+        gap_len: usize,
+        canonical: bool,
+    ) -> SpacedBitKmers<'a> {
+        SpacedBitKmers::new(self.sequence(), &gapped_seed(word_len, gap_len), canonical)
+    }
+
+    /// Return an iterator over the strobemers of the sequence. See the
+    /// `strobemer` module for what the parameters mean.
+    ///
+    /// ```
+    /// use needletail::Sequence;
+    /// use needletail::strobemer::StrobemerParams;
+    ///
+    /// let seq = b"ACGTAGCTAGCTAGGATCGATCGA";
+    /// for strobemer in seq.strobemers(StrobemerParams::new(2, 5, 2, 6)) {
+    ///     assert_eq!(strobemer.strobe_positions().len(), 2);
+    /// }
+    /// ```
+    fn strobemers(&'a self, params: StrobemerParams) -> Strobemers {
+        Strobemers::new(self.sequence(), params)
+    }
 }
 
 impl<'a> Sequence<'a> for &'a [u8] {
@@ -270,6 +1173,37 @@ impl<'a> Sequence<'a> for Cow<'a, [u8]> {
     }
 }
 
+/// How `QualitySequence::quality_mask_with` masks the low-quality bases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MaskWith {
+    /// Replace them with this byte
+    Byte(u8),
+    /// Lowercase them (soft-masking)
+    Lowercase,
+}
+
+impl Default for MaskWith {
+    /// Replace them with `N`s
+    fn default() -> Self {
+        MaskWith::Byte(b'N')
+    }
+}
+
+impl MaskWith {
+    #[inline]
+    fn mask(&self, base: u8) -> u8 {
+        match self {
+            MaskWith::Byte(b) => *b,
+            MaskWith::Lowercase => base.to_ascii_lowercase(),
+        }
+    }
+}
+
+/// Converts a Phred+33 quality cutoff to an encoding starting at `offset`
+fn raw_cutoff(score: u8, offset: u8) -> u8 {
+    score.saturating_sub(PHRED_OFFSET).saturating_add(offset)
+}
+
 /// [⚠️Unstable] A trait to wrap over sequence data that has associated
 /// quality information.
 ///
@@ -277,23 +1211,142 @@ impl<'a> Sequence<'a> for Cow<'a, [u8]> {
 /// have _optional_ quality information (like `SequenceRecord`) because the
 /// return trait requires a slice from an immutable reference and
 /// `SequenceRecords` can't return that without modifying themselves.
+///
+/// The qualities are Phred+33 encoded unless `quality_offset` says otherwise.
+/// The quality cutoffs are Phred+33 characters (e.g. `b'5'` for Q20) for the
+/// masking methods and `quality_kmers`, whatever the encoding of the
+/// qualities, and plain Phred scores for `trim_quality_mott`.
 pub trait QualitySequence<'a>: Sequence<'a> {
     fn quality(&'a self) -> &'a [u8];
 
+    /// The ASCII value of a quality score of 0, 33 by default
+    fn quality_offset(&self) -> u8 {
+        PHRED_OFFSET
+    }
+
     /// Given a `SeqRecord` and a quality cutoff, mask out low-quality bases with
-    /// `N` characters.
+    /// `N` characters. The sequence is only copied if a base needs masking.
+    ///
+    /// `score` is a Phred+33 character, e.g. `b'5'` for Q20: values below 33
+    /// are all Q0.
     fn quality_mask(&'a self, score: u8) -> Cow<'a, [u8]> {
-        let qual = self.quality();
-        // could maybe speed this up by doing a copy of base and then
-        // iterating though qual and masking?
-        let seq: Vec<u8> = self
-            .sequence()
-            .iter()
-            .zip(qual.iter())
-            .map(|(base, qual)| if *qual < score { b'N' } else { *base })
-            .collect();
+        self.quality_mask_with(score, MaskWith::default())
+    }
+
+    /// Same as `quality_mask` but writes the masked sequence into `out`
+    /// (cleared first), to reuse its allocation across sequences.
+    fn quality_mask_into(&'a self, score: u8, out: &mut Vec<u8>) {
+        self.quality_mask_with_into(score, MaskWith::default(), out);
+    }
+
+    /// Same as `quality_mask` but masks the low-quality bases as given by
+    /// `mask`, e.g. by soft-masking them.
+    ///
+    /// ```
+    /// use needletail::sequence::{MaskWith, QualitySequence};
+    ///
+    /// let rec = (&b"ACGT"[..], &b"I#I#"[..]);
+    /// assert_eq!(&rec.quality_mask_with(b'5', MaskWith::Lowercase)[..], b"AcGt");
+    /// assert_eq!(&rec.quality_mask_with(b'5', MaskWith::Byte(b'-'))[..], b"A-G-");
+    /// ```
+    fn quality_mask_with(&'a self, score: u8, mask: MaskWith) -> Cow<'a, [u8]> {
+        let cutoff = raw_cutoff(score, self.quality_offset());
+        if self.quality().iter().all(|q| *q >= cutoff) {
+            return self.sequence().into();
+        }
+        let mut seq = Vec::new();
+        self.quality_mask_with_into(score, mask, &mut seq);
         seq.into()
     }
+
+    /// Same as `quality_mask_with` but writes the masked sequence into `out`
+    /// (cleared first).
+    fn quality_mask_with_into(&'a self, score: u8, mask: MaskWith, out: &mut Vec<u8>) {
+        out.clear();
+        out.extend_from_slice(self.sequence());
+        let score = raw_cutoff(score, self.quality_offset());
+        for (base, qual) in out.iter_mut().zip(self.quality()) {
+            if *qual < score {
+                *base = mask.mask(*base);
+            }
+        }
+    }
+
+    /// Returns an iterator over the kmers that would be returned by `valid_kmers`
+    /// on `quality_mask(score)`, without making the masked copy. `score` is a
+    /// Phred+33 character, as for `quality_mask`.
+    fn quality_kmers(&'a self, k: u8, score: u8) -> QualityKmers<'a> {
+        QualityKmers::new(
+            self.sequence(),
+            self.quality(),
+            k,
+            raw_cutoff(score, self.quality_offset()),
+        )
+    }
+
+    /// Returns an iterator over the Phred scores of the bases, the quality
+    /// being Phred+33 encoded. Use `.map(f32::from)` to get floats.
+    ///
+    /// ```
+    /// use needletail::sequence::QualitySequence;
+    ///
+    /// let rec = (&b"ACGT"[..], &b"I5+!"[..]);
+    /// assert_eq!(rec.quality_scores().collect::<Vec<_>>(), vec![40, 20, 10, 0]);
+    /// ```
+    fn quality_scores(&'a self) -> QualityScores<'a> {
+        QualityScores::with_offset(self.quality(), self.quality_offset())
+    }
+
+    /// Returns an iterator over the probabilities of each base being wrong
+    fn error_probabilities(&'a self) -> ErrorProbabilities<'a> {
+        ErrorProbabilities::with_offset(self.quality(), self.quality_offset())
+    }
+
+    /// Returns the probabilities of each base being wrong, see
+    /// `error_probabilities` for the iterator version.
+    ///
+    /// ```
+    /// use needletail::sequence::QualitySequence;
+    ///
+    /// let rec = (&b"AC"[..], &b"+5"[..]);
+    /// assert_eq!(rec.as_error_probs(), vec![0.1, 0.01]);
+    /// ```
+    fn as_error_probs(&'a self) -> Vec<f32> {
+        self.error_probabilities().collect()
+    }
+
+    /// Returns the mean of the Phred scores of the bases, 0 if there are none
+    fn mean_quality(&'a self) -> f64 {
+        let qual = self.quality();
+        if qual.is_empty() {
+            return 0.;
+        }
+        let total: u64 = self.quality_scores().map(u64::from).sum();
+        total as f64 / qual.len() as f64
+    }
+
+    /// Returns the number of errors expected in the sequence, i.e. the sum of
+    /// the error probabilities of its bases. This is what the `maxEE` filters
+    /// of USEARCH and DADA2 are based on.
+    ///
+    /// ```
+    /// use needletail::sequence::QualitySequence;
+    ///
+    /// // Q10 and Q20
+    /// let rec = (&b"AC"[..], &b"+5"[..]);
+    /// assert!((rec.expected_errors() - 0.11).abs() < 1e-6);
+    /// assert_eq!(rec.mean_quality(), 15.);
+    /// ```
+    fn expected_errors(&'a self) -> f64 {
+        self.error_probabilities().map(f64::from).sum()
+    }
+
+    /// Returns the part of the sequence to keep according to the modified
+    /// Mott trimming algorithm, see `needletail::trim::trim_quality_mott`.
+    /// `cutoff` is a plain Phred score, e.g. 20 for Q20.
+    fn trim_quality_mott(&'a self, cutoff: u8) -> Range<usize> {
+        trim_scores_mott(self.quality_scores(), cutoff)
+    }
 }
 
 impl<'a> Sequence<'a> for (&'a [u8], &'a [u8]) {
@@ -308,6 +1361,32 @@ impl<'a> QualitySequence<'a> for (&'a [u8], &'a [u8]) {
     }
 }
 
+impl<'a> Sequence<'a> for (&'a [u8], &'a [u8], u8) {
+    fn sequence(&'a self) -> &'a [u8] {
+        self.0
+    }
+}
+
+/// A sequence, its quality and the offset of the quality, e.g. 64 for
+/// Phred+64 qualities.
+///
+/// ```
+/// use needletail::sequence::QualitySequence;
+///
+/// let rec = (&b"ACGT"[..], &b"hT#J"[..], 64);
+/// assert_eq!(rec.quality_scores().collect::<Vec<_>>(), vec![40, 20, 0, 10]);
+/// assert_eq!(&rec.quality_mask(b'5')[..], b"ACNN");
+/// ```
+impl<'a> QualitySequence<'a> for (&'a [u8], &'a [u8], u8) {
+    fn quality(&'a self) -> &'a [u8] {
+        self.1
+    }
+
+    fn quality_offset(&self) -> u8 {
+        self.2
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -343,6 +1422,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_normalize_to_rna() {
+        assert_eq!(
+            normalize_alphabet(b"ACGTtuU\nn", false, Alphabet::Rna),
+            Some(b"ACGUUUUN".to_vec())
+        );
+        assert_eq!(normalize_alphabet(b"ACGUN", true, Alphabet::Rna), None);
+        assert_eq!(
+            normalize_alphabet(b"ACGU", true, Alphabet::Dna),
+            Some(b"ACGT".to_vec())
+        );
+    }
+
+    #[test]
+    fn can_normalize_proteins() {
+        assert_eq!(
+            normalize_protein(b"acdef ghik\r\nlmnpq~rstvwy*"),
+            Some(b"ACDEFGHIKLMNPQ-RSTVWY*".to_vec())
+        );
+        assert_eq!(normalize_protein(b"MK?1"), Some(b"MKXX".to_vec()));
+        assert_eq!(
+            normalize_alphabet(b"mk", true, Alphabet::Protein),
+            Some(b"MK".to_vec())
+        );
+        assert_eq!(
+            validate_protein(b"mkv\n"),
+            Err(SequenceError::InvalidBase {
+                position: 3,
+                base: b'\n'
+            })
+        );
+    }
+
+    #[test]
+    fn can_validate() {
+        assert!(validate(b"", Alphabet::Dna).is_ok());
+        assert!(validate(b"acgtnACGTN", Alphabet::Dna).is_ok());
+        assert_eq!(validate(b"ACGT", Alphabet::Rna), Err(vec![(3, b'T')]));
+        assert!(validate(b"MKVLA*", Alphabet::Protein).is_ok());
+        assert_eq!(validate(b"MK.", Alphabet::Protein), Err(vec![(2, b'.')]));
+    }
+
+    #[test]
+    fn normalize_keeps_the_mask() {
+        let masked = normalize_with_mask(b"acGT\r\nTTaa NN\ttt", true);
+        assert_eq!(masked.seq, b"ACGTTTAANNTT");
+        assert_eq!(masked.masked, vec![0..2, 6..8, 10..12]);
+        assert!(masked.is_masked(7));
+        assert!(!masked.is_masked(8));
+        assert!(!masked.is_masked(12));
+        assert_eq!(masked.to_soft_masked(), b"acGTTTaaNNtt");
+
+        let unmasked = normalize_with_mask(b"ACGT", false);
+        assert!(unmasked.masked.is_empty());
+    }
+
     #[test]
     fn test_complement() {
         assert_eq!(complement(b'a'), b't');
@@ -351,6 +1486,36 @@ mod tests {
         assert_eq!(complement(b'n'), b'n');
     }
 
+    #[test]
+    fn complement_handles_iupac() {
+        let codes = b"ACGTRYKMBVDHSWNacgtrykmbvdhswn-.";
+        for code in codes {
+            let c = try_complement(*code).unwrap();
+            assert_eq!(c.is_ascii_lowercase(), code.is_ascii_lowercase());
+            assert_eq!(complement(c), *code);
+        }
+        assert_eq!(complement(b'X'), b'X');
+        assert_eq!(try_complement(b'X'), None);
+        assert_eq!(
+            reverse_complement_strict(b"ACGX"),
+            Err(SequenceError::InvalidBase {
+                position: 3,
+                base: b'X'
+            })
+        );
+    }
+
+    #[test]
+    fn can_reverse_complement_in_place() {
+        for seq in [&b""[..], b"A", b"ACg", b"AACGTN", b"RYKMbvdhsw"] {
+            let mut buf = seq.to_vec();
+            reverse_complement_in_place(&mut buf);
+            assert_eq!(buf, seq.reverse_complement());
+            reverse_complement_in_place(&mut buf);
+            assert_eq!(buf, seq);
+        }
+    }
+
     #[test]
     fn can_canonicalize() {
         assert_eq!(canonical(b"A").as_ref(), b"A");
@@ -366,10 +1531,105 @@ mod tests {
         assert_eq!(&minmer[..], b"AAA");
     }
 
+    #[test]
+    fn can_homopolymer_compress() {
+        let hpc = HomopolymerCompressed::new(b"TTAAACCCCAT");
+        assert_eq!(hpc.seq, b"TACAT");
+        assert_eq!(hpc.run_lengths().collect::<Vec<_>>(), vec![2, 3, 4, 1, 1]);
+        assert_eq!(hpc.original_len(), 11);
+        assert_eq!(hpc.compressed_pos(0), 0);
+        assert_eq!(hpc.compressed_pos(4), 1);
+        assert_eq!(hpc.compressed_pos(10), 4);
+        assert_eq!(hpc.decompress(), b"TTAAACCCCAT");
+
+        let empty = HomopolymerCompressed::new(b"");
+        assert!(empty.is_empty());
+        assert_eq!(empty.original_len(), 0);
+    }
+
+    #[test]
+    fn can_map_gaps() {
+        assert!(matches!(ungap(b"ACGT"), Cow::Borrowed(_)));
+        assert_eq!(ungap(b"-A~C.").as_ref(), b"AC");
+
+        let map = GapMap::new(b"--AC-G--");
+        assert_eq!((map.aligned_len(), map.ungapped_len()), (8, 3));
+        assert_eq!(map.to_aligned(0), 2);
+        assert_eq!(map.to_aligned(2), 5);
+        assert_eq!(map.to_ungapped(0), None);
+        assert_eq!(map.to_ungapped(3), Some(1));
+        assert_eq!(map.ungapped_range(0..2), 0..0);
+        assert_eq!(map.ungapped_range(3..8), 1..3);
+        assert_eq!(map.ungapped_range(6..8), 3..3);
+    }
+
+    #[test]
+    fn can_mask_and_unmask() {
+        let seq = b"ACGTTTTTTTTTTACGT";
+        let masked = seq.soft_masked(&[4..8, 8..13]);
+        assert_eq!(masked, b"ACGTtttttttttACGT");
+        let unmasked = masked.to_uppercase_preserving_mask();
+        assert_eq!(unmasked.seq, seq);
+        assert_eq!(unmasked.masked, vec![4..13]);
+        assert_eq!(unmasked.to_soft_masked(), masked);
+        assert_eq!(seq.hard_masked(&unmasked.masked), b"ACGTNNNNNNNNNACGT");
+        // out of bounds ranges are ignored
+        assert_eq!(seq.hard_masked(&[20..30, 40..50]), seq);
+    }
+
+    #[test]
+    fn test_quality_offset() {
+        let phred33 = (&b"ACGTAC"[..], &b"I5+#II"[..]);
+        let phred64 = (&b"ACGTAC"[..], &b"hTJBhh"[..], 64);
+        assert_eq!(phred33.quality_mask(b'5'), phred64.quality_mask(b'5'));
+        assert_eq!(phred33.mean_quality(), phred64.mean_quality());
+        assert_eq!(phred33.expected_errors(), phred64.expected_errors());
+        assert_eq!(phred33.trim_quality_mott(20), phred64.trim_quality_mott(20));
+        let kmers: Vec<_> = phred64.quality_kmers(2, b'5').collect();
+        assert_eq!(kmers, phred33.quality_kmers(2, b'5').collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_quality_metrics() {
+        let rec = (&b"ACGT"[..], &b"IIII"[..]);
+        assert_eq!(rec.mean_quality(), 40.);
+        assert!((rec.expected_errors() - 0.0004).abs() < 1e-6);
+        let rec = (&b"AC"[..], &b"!I"[..]);
+        assert_eq!(rec.mean_quality(), 20.);
+        assert!((rec.expected_errors() - 1.0001).abs() < 1e-6);
+        let rec = (&b""[..], &b""[..]);
+        assert_eq!(rec.mean_quality(), 0.);
+        assert_eq!(rec.expected_errors(), 0.);
+    }
+
     #[test]
     fn test_quality_mask() {
         let seq_rec = (&b"AGCT"[..], &b"AAA0"[..]);
         let filtered_rec = seq_rec.quality_mask(b'5');
         assert_eq!(&filtered_rec[..], &b"AGCN"[..]);
+        assert!(matches!(seq_rec.quality_mask(b'0'), Cow::Borrowed(_)));
+
+        let mut out = b"TTTTTTTT".to_vec();
+        seq_rec.quality_mask_into(b'B', &mut out);
+        assert_eq!(out, b"NNNN");
+        seq_rec.quality_mask_with_into(b'5', MaskWith::Lowercase, &mut out);
+        assert_eq!(out, b"AGCt");
+        // nothing to soft-mask
+        let masked = seq_rec.quality_mask_with(b'0', MaskWith::Lowercase);
+        assert!(matches!(masked, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn quality_kmers_match_masked_kmers() {
+        let seq_rec = (&b"ACGTNACGTACGTTAC"[..], &b"IIIIIII#IIIIII#I"[..]);
+        let masked = seq_rec.quality_mask(b'5');
+        for k in 1..6 {
+            let expected: Vec<_> = masked.valid_kmers(k).map(|(p, _)| p).collect();
+            let kmers: Vec<_> = seq_rec.quality_kmers(k, b'5').collect();
+            assert_eq!(kmers.iter().map(|(p, _)| *p).collect::<Vec<_>>(), expected);
+            for (pos, kmer) in kmers {
+                assert_eq!(kmer, &seq_rec.0[pos..pos + k as usize]);
+            }
+        }
     }
 }
@@ -0,0 +1,490 @@
+//! Vectorized versions of the per-base loops that dominate kmer extraction,
+//! reverse complementing and the validation of the records.
+//!
+//! Every function picks the best implementation available at runtime (AVX2
+//! or SSE2/SSSE3 on x86_64, NEON on aarch64) and falls back to a scalar loop
+//! for the rest of the input and on other architectures.
+use crate::sequence::{complement, try_complement};
+
+/// The code given to anything that isn't ACGT by `encode_2bit_bases`
+pub(crate) const INVALID_2BIT: u8 = 4;
+
+/// Writes the 2-bit code of every base of `src` (A=0, C=1, G=2, T=3, in either
+/// case) in `dst` and `INVALID_2BIT` for anything else.
+///
+/// The codes are computed as `((b >> 1) & 3) ^ ((b >> 2) & 1)`, which maps the
+/// ASCII values of ACGT to their codes without a lookup.
+pub(crate) fn encode_2bit_bases(src: &[u8], dst: &mut [u8]) {
+    assert_eq!(src.len(), dst.len());
+    #[cfg(target_arch = "x86_64")]
+    let done = if is_x86_feature_detected!("avx2") {
+        unsafe { x86::encode_2bit_avx2(src, dst) }
+    } else {
+        unsafe { x86::encode_2bit_sse2(src, dst) }
+    };
+    #[cfg(target_arch = "aarch64")]
+    let done = unsafe { neon::encode_2bit(src, dst) };
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let done = 0;
+    encode_2bit_scalar(&src[done..], &mut dst[done..]);
+}
+
+/// Returns the length of the longest prefix of `src` made only of ACGT (in
+/// either case), which is the whole of most sequences
+pub(crate) fn acgt_prefix_len(src: &[u8]) -> usize {
+    #[cfg(target_arch = "x86_64")]
+    let done = if is_x86_feature_detected!("avx2") {
+        unsafe { x86::acgt_prefix_avx2(src) }
+    } else {
+        unsafe { x86::acgt_prefix_sse2(src) }
+    };
+    #[cfg(target_arch = "aarch64")]
+    let done = unsafe { neon::acgt_prefix(src) };
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let done = 0;
+    done + src[done..].iter().take_while(|b| is_acgt(**b)).count()
+}
+
+/// Returns the length of the longest prefix of `src` made only of bytes
+/// between `min` and `max` (inclusive), e.g. to validate quality scores
+pub(crate) fn range_prefix_len(src: &[u8], min: u8, max: u8) -> usize {
+    #[cfg(target_arch = "x86_64")]
+    let done = if is_x86_feature_detected!("avx2") {
+        unsafe { x86::range_prefix_avx2(src, min, max) }
+    } else {
+        unsafe { x86::range_prefix_sse2(src, min, max) }
+    };
+    #[cfg(target_arch = "aarch64")]
+    let done = unsafe { neon::range_prefix(src, min, max) };
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let done = 0;
+    done + src[done..]
+        .iter()
+        .take_while(|b| (min..=max).contains(*b))
+        .count()
+}
+
+/// The complement of the letters by their lowest 5 bits, the case being kept
+/// as is. `@`, `[` and the letters without a complement map to themselves.
+const COMPLEMENT_LOW: [u8; 32] = {
+    let mut table = [0; 32];
+    let mut i = 0;
+    while i < 32 {
+        table[i] = match try_complement(0x40 | i as u8) {
+            Some(c) => c & 0x1f,
+            None => i as u8,
+        };
+        i += 1;
+    }
+    table
+};
+
+/// Writes the reverse complement of `src` in `dst`, which needs to be as
+/// long, with the same result as `sequence::complement` on every byte.
+///
+/// Bytes from `0x40` to `0x7f` (letters) keep their 3 top bits and the other
+/// 5 are looked up in `COMPLEMENT_LOW` with a byte shuffle; all the other
+/// bytes are left as is.
+pub(crate) fn reverse_complement(src: &[u8], dst: &mut [u8]) {
+    assert_eq!(src.len(), dst.len());
+    #[cfg(target_arch = "x86_64")]
+    let done = if is_x86_feature_detected!("avx2") {
+        unsafe { x86::reverse_complement_avx2(src, dst) }
+    } else if is_x86_feature_detected!("ssse3") {
+        unsafe { x86::reverse_complement_ssse3(src, dst) }
+    } else {
+        0
+    };
+    #[cfg(target_arch = "aarch64")]
+    let done = unsafe { neon::reverse_complement(src, dst) };
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let done = 0;
+    let rest = src.len() - done;
+    for (d, s) in dst[..rest].iter_mut().zip(src[done..].iter().rev()) {
+        *d = complement(*s);
+    }
+}
+
+#[inline]
+fn is_acgt(base: u8) -> bool {
+    matches!(base | 0x20, b'a' | b'c' | b'g' | b't')
+}
+
+#[inline]
+fn encode_2bit_scalar(src: &[u8], dst: &mut [u8]) {
+    for (s, d) in src.iter().zip(dst.iter_mut()) {
+        *d = if is_acgt(*s) {
+            ((s >> 1) & 3) ^ ((s >> 2) & 1)
+        } else {
+            INVALID_2BIT
+        };
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::{COMPLEMENT_LOW, INVALID_2BIT};
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn valid_sse2(v: __m128i) -> __m128i {
+        let lower = _mm_or_si128(v, _mm_set1_epi8(0x20));
+        let a = _mm_cmpeq_epi8(lower, _mm_set1_epi8(b'a' as i8));
+        let c = _mm_cmpeq_epi8(lower, _mm_set1_epi8(b'c' as i8));
+        let g = _mm_cmpeq_epi8(lower, _mm_set1_epi8(b'g' as i8));
+        let t = _mm_cmpeq_epi8(lower, _mm_set1_epi8(b't' as i8));
+        _mm_or_si128(_mm_or_si128(a, c), _mm_or_si128(g, t))
+    }
+
+    /// Returns how many bases were encoded (a multiple of 16)
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn encode_2bit_sse2(src: &[u8], dst: &mut [u8]) -> usize {
+        let mut i = 0;
+        while i + 16 <= src.len() {
+            let v = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+            let valid = valid_sse2(v);
+            // there are no 8-bit shifts but the masks drop the bits coming
+            // from the neighbouring byte
+            let high = _mm_and_si128(_mm_srli_epi16(v, 1), _mm_set1_epi8(3));
+            let low = _mm_and_si128(_mm_srli_epi16(v, 2), _mm_set1_epi8(1));
+            let code = _mm_xor_si128(high, low);
+            let out = _mm_or_si128(
+                _mm_and_si128(valid, code),
+                _mm_andnot_si128(valid, _mm_set1_epi8(INVALID_2BIT as i8)),
+            );
+            _mm_storeu_si128(dst.as_mut_ptr().add(i) as *mut __m128i, out);
+            i += 16;
+        }
+        i
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn valid_avx2(v: __m256i) -> __m256i {
+        let lower = _mm256_or_si256(v, _mm256_set1_epi8(0x20));
+        let a = _mm256_cmpeq_epi8(lower, _mm256_set1_epi8(b'a' as i8));
+        let c = _mm256_cmpeq_epi8(lower, _mm256_set1_epi8(b'c' as i8));
+        let g = _mm256_cmpeq_epi8(lower, _mm256_set1_epi8(b'g' as i8));
+        let t = _mm256_cmpeq_epi8(lower, _mm256_set1_epi8(b't' as i8));
+        _mm256_or_si256(_mm256_or_si256(a, c), _mm256_or_si256(g, t))
+    }
+
+    /// Same as `encode_2bit_sse2` 32 bases at a time
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn encode_2bit_avx2(src: &[u8], dst: &mut [u8]) -> usize {
+        let mut i = 0;
+        while i + 32 <= src.len() {
+            let v = _mm256_loadu_si256(src.as_ptr().add(i) as *const __m256i);
+            let valid = valid_avx2(v);
+            let high = _mm256_and_si256(_mm256_srli_epi16(v, 1), _mm256_set1_epi8(3));
+            let low = _mm256_and_si256(_mm256_srli_epi16(v, 2), _mm256_set1_epi8(1));
+            let code = _mm256_xor_si256(high, low);
+            let out = _mm256_blendv_epi8(_mm256_set1_epi8(INVALID_2BIT as i8), code, valid);
+            _mm256_storeu_si256(dst.as_mut_ptr().add(i) as *mut __m256i, out);
+            i += 32;
+        }
+        i + encode_2bit_sse2(&src[i..], &mut dst[i..])
+    }
+
+    /// Returns the position of the first byte whose bit is not set in
+    /// `mask` in the block starting at `i`, if any
+    #[inline]
+    fn first_unset(i: usize, mask: u32, full: u32) -> Option<usize> {
+        (mask != full).then(|| i + (!mask).trailing_zeros() as usize)
+    }
+
+    /// Returns how many bytes at the start of `src` are known to be ACGT:
+    /// all the 16 bytes blocks before the first invalid byte and the valid
+    /// bytes of its block
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn acgt_prefix_sse2(src: &[u8]) -> usize {
+        let mut i = 0;
+        while i + 16 <= src.len() {
+            let v = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+            let mask = _mm_movemask_epi8(valid_sse2(v)) as u32;
+            if let Some(end) = first_unset(i, mask, 0xffff) {
+                return end;
+            }
+            i += 16;
+        }
+        i
+    }
+
+    /// Same as `acgt_prefix_sse2` 32 bytes at a time
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn acgt_prefix_avx2(src: &[u8]) -> usize {
+        let mut i = 0;
+        while i + 32 <= src.len() {
+            let v = _mm256_loadu_si256(src.as_ptr().add(i) as *const __m256i);
+            let mask = _mm256_movemask_epi8(valid_avx2(v)) as u32;
+            if let Some(end) = first_unset(i, mask, u32::MAX) {
+                return end;
+            }
+            i += 32;
+        }
+        i + acgt_prefix_sse2(&src[i..])
+    }
+
+    /// Same as `acgt_prefix_sse2` for bytes between `min` and `max`: a byte
+    /// is in the range if clamping it to the range doesn't change it
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn range_prefix_sse2(src: &[u8], min: u8, max: u8) -> usize {
+        let (min, max) = (_mm_set1_epi8(min as i8), _mm_set1_epi8(max as i8));
+        let mut i = 0;
+        while i + 16 <= src.len() {
+            let v = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+            let clamped = _mm_min_epu8(_mm_max_epu8(v, min), max);
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(clamped, v)) as u32;
+            if let Some(end) = first_unset(i, mask, 0xffff) {
+                return end;
+            }
+            i += 16;
+        }
+        i
+    }
+
+    /// Same as `range_prefix_sse2` 32 bytes at a time
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn range_prefix_avx2(src: &[u8], min: u8, max: u8) -> usize {
+        let (lo, hi) = (_mm256_set1_epi8(min as i8), _mm256_set1_epi8(max as i8));
+        let mut i = 0;
+        while i + 32 <= src.len() {
+            let v = _mm256_loadu_si256(src.as_ptr().add(i) as *const __m256i);
+            let clamped = _mm256_min_epu8(_mm256_max_epu8(v, lo), hi);
+            let mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(clamped, v)) as u32;
+            if let Some(end) = first_unset(i, mask, u32::MAX) {
+                return end;
+            }
+            i += 32;
+        }
+        i + range_prefix_sse2(&src[i..], min, max)
+    }
+
+    #[target_feature(enable = "ssse3")]
+    unsafe fn complement_ssse3(v: __m128i) -> __m128i {
+        let low_table = _mm_loadu_si128(COMPLEMENT_LOW.as_ptr() as *const __m128i);
+        let high_table = _mm_loadu_si128(COMPLEMENT_LOW.as_ptr().add(16) as *const __m128i);
+        // the shuffle only looks at 4 bits: the 5th one picks the table
+        let index = _mm_and_si128(v, _mm_set1_epi8(0x0f));
+        let bit4 = _mm_set1_epi8(0x10);
+        let high = _mm_cmpeq_epi8(_mm_and_si128(v, bit4), bit4);
+        let low5 = _mm_or_si128(
+            _mm_andnot_si128(high, _mm_shuffle_epi8(low_table, index)),
+            _mm_and_si128(high, _mm_shuffle_epi8(high_table, index)),
+        );
+        let complement = _mm_or_si128(_mm_and_si128(v, _mm_set1_epi8(0xe0u8 as i8)), low5);
+        let top2 = _mm_and_si128(v, _mm_set1_epi8(0xc0u8 as i8));
+        let letters = _mm_cmpeq_epi8(top2, _mm_set1_epi8(0x40));
+        _mm_or_si128(
+            _mm_and_si128(letters, complement),
+            _mm_andnot_si128(letters, v),
+        )
+    }
+
+    /// Returns how many bytes at the start of `src` were reverse complemented
+    /// at the end of `dst` (a multiple of 16)
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn reverse_complement_ssse3(src: &[u8], dst: &mut [u8]) -> usize {
+        let reverse = _mm_setr_epi8(15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0);
+        let len = src.len();
+        let mut i = 0;
+        while i + 16 <= len {
+            let v = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+            let out = _mm_shuffle_epi8(complement_ssse3(v), reverse);
+            _mm_storeu_si128(dst.as_mut_ptr().add(len - i - 16) as *mut __m128i, out);
+            i += 16;
+        }
+        i
+    }
+
+    /// Same as `reverse_complement_ssse3` 32 bytes at a time
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn reverse_complement_avx2(src: &[u8], dst: &mut [u8]) -> usize {
+        let low_table =
+            _mm256_broadcastsi128_si256(_mm_loadu_si128(COMPLEMENT_LOW.as_ptr() as *const __m128i));
+        let high_table = _mm256_broadcastsi128_si256(_mm_loadu_si128(
+            COMPLEMENT_LOW.as_ptr().add(16) as *const __m128i,
+        ));
+        // the shuffles only work within 128-bit lanes, which are swapped after
+        let reverse = _mm256_setr_epi8(
+            15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 15, 14, 13, 12, 11, 10, 9, 8, 7,
+            6, 5, 4, 3, 2, 1, 0,
+        );
+        let len = src.len();
+        let mut i = 0;
+        while i + 32 <= len {
+            let v = _mm256_loadu_si256(src.as_ptr().add(i) as *const __m256i);
+            let index = _mm256_and_si256(v, _mm256_set1_epi8(0x0f));
+            let bit4 = _mm256_set1_epi8(0x10);
+            let high = _mm256_cmpeq_epi8(_mm256_and_si256(v, bit4), bit4);
+            let low5 = _mm256_blendv_epi8(
+                _mm256_shuffle_epi8(low_table, index),
+                _mm256_shuffle_epi8(high_table, index),
+                high,
+            );
+            let complement =
+                _mm256_or_si256(_mm256_and_si256(v, _mm256_set1_epi8(0xe0u8 as i8)), low5);
+            let top2 = _mm256_and_si256(v, _mm256_set1_epi8(0xc0u8 as i8));
+            let letters = _mm256_cmpeq_epi8(top2, _mm256_set1_epi8(0x40));
+            let out = _mm256_blendv_epi8(v, complement, letters);
+            let out = _mm256_permute4x64_epi64(_mm256_shuffle_epi8(out, reverse), 0x4e);
+            _mm256_storeu_si256(dst.as_mut_ptr().add(len - i - 32) as *mut __m256i, out);
+            i += 32;
+        }
+        i + reverse_complement_ssse3(&src[i..], &mut dst[..len - i])
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::{COMPLEMENT_LOW, INVALID_2BIT};
+
+    #[inline]
+    unsafe fn valid(v: uint8x16_t) -> uint8x16_t {
+        let lower = vorrq_u8(v, vdupq_n_u8(0x20));
+        let a = vceqq_u8(lower, vdupq_n_u8(b'a'));
+        let c = vceqq_u8(lower, vdupq_n_u8(b'c'));
+        let g = vceqq_u8(lower, vdupq_n_u8(b'g'));
+        let t = vceqq_u8(lower, vdupq_n_u8(b't'));
+        vorrq_u8(vorrq_u8(a, c), vorrq_u8(g, t))
+    }
+
+    /// Returns how many bases were encoded (a multiple of 16)
+    pub(super) unsafe fn encode_2bit(src: &[u8], dst: &mut [u8]) -> usize {
+        let mut i = 0;
+        while i + 16 <= src.len() {
+            let v = vld1q_u8(src.as_ptr().add(i));
+            let high = vandq_u8(vshrq_n_u8(v, 1), vdupq_n_u8(3));
+            let low = vandq_u8(vshrq_n_u8(v, 2), vdupq_n_u8(1));
+            let code = veorq_u8(high, low);
+            let out = vbslq_u8(valid(v), code, vdupq_n_u8(INVALID_2BIT));
+            vst1q_u8(dst.as_mut_ptr().add(i), out);
+            i += 16;
+        }
+        i
+    }
+
+    /// Returns how many bytes at the start of `src` are known to be ACGT:
+    /// the 16 bytes blocks before the first one with an invalid byte
+    pub(super) unsafe fn acgt_prefix(src: &[u8]) -> usize {
+        let mut i = 0;
+        while i + 16 <= src.len() {
+            let v = vld1q_u8(src.as_ptr().add(i));
+            if vminvq_u8(valid(v)) != 0xff {
+                break;
+            }
+            i += 16;
+        }
+        i
+    }
+
+    /// Same as `acgt_prefix` for bytes between `min` and `max`
+    pub(super) unsafe fn range_prefix(src: &[u8], min: u8, max: u8) -> usize {
+        let mut i = 0;
+        while i + 16 <= src.len() {
+            let v = vld1q_u8(src.as_ptr().add(i));
+            let in_range = vandq_u8(vcgeq_u8(v, vdupq_n_u8(min)), vcleq_u8(v, vdupq_n_u8(max)));
+            if vminvq_u8(in_range) != 0xff {
+                break;
+            }
+            i += 16;
+        }
+        i
+    }
+
+    /// Returns how many bytes at the start of `src` were reverse complemented
+    /// at the end of `dst` (a multiple of 16)
+    pub(super) unsafe fn reverse_complement(src: &[u8], dst: &mut [u8]) -> usize {
+        let table = uint8x16x2_t(
+            vld1q_u8(COMPLEMENT_LOW.as_ptr()),
+            vld1q_u8(COMPLEMENT_LOW.as_ptr().add(16)),
+        );
+        let len = src.len();
+        let mut i = 0;
+        while i + 16 <= len {
+            let v = vld1q_u8(src.as_ptr().add(i));
+            let low5 = vqtbl2q_u8(table, vandq_u8(v, vdupq_n_u8(0x1f)));
+            let complement = vorrq_u8(vandq_u8(v, vdupq_n_u8(0xe0)), low5);
+            let letters = vceqq_u8(vandq_u8(v, vdupq_n_u8(0xc0)), vdupq_n_u8(0x40));
+            let out = vrev64q_u8(vbslq_u8(letters, complement, v));
+            vst1q_u8(dst.as_mut_ptr().add(len - i - 16), vextq_u8(out, out, 8));
+            i += 16;
+        }
+        i
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_bytes() -> Vec<u8> {
+        // every byte value, repeated enough to go through all the code paths
+        (0..=255u8).cycle().take(256 * 3 + 7).collect()
+    }
+
+    #[test]
+    fn encodes_like_the_lookup() {
+        let all = all_bytes();
+        // lengths hitting the 32, 16 and 1 byte loops in different combinations
+        for len in [0, 7, 16, 23, 48, all.len()] {
+            let src = &all[all.len() - len..];
+            let mut dst = vec![0; len];
+            encode_2bit_bases(src, &mut dst);
+            for (s, d) in src.iter().zip(dst) {
+                let expected = match s {
+                    b'A' | b'a' => 0,
+                    b'C' | b'c' => 1,
+                    b'G' | b'g' => 2,
+                    b'T' | b't' => 3,
+                    _ => INVALID_2BIT,
+                };
+                assert_eq!(d, expected, "byte {s}");
+            }
+        }
+    }
+
+    #[test]
+    fn reverse_complements_like_the_lookup() {
+        let all = all_bytes();
+        for len in [0, 7, 16, 23, 48, 100, all.len()] {
+            let src = &all[all.len() - len..];
+            let mut dst = vec![0; len];
+            reverse_complement(src, &mut dst);
+            let expected: Vec<u8> = src.iter().rev().map(|b| complement(*b)).collect();
+            assert_eq!(dst, expected, "length {len}");
+        }
+    }
+
+    #[test]
+    fn finds_the_first_invalid_byte() {
+        let acgt: Vec<u8> = b"ACGTacgt".iter().copied().cycle().take(100).collect();
+        for len in [0, 7, 16, 23, 48, 100] {
+            let seq = &acgt[..len];
+            assert_eq!(acgt_prefix_len(seq), len);
+            assert_eq!(range_prefix_len(seq, b'A', b't'), len);
+            for pos in 0..len {
+                for b in [b'N', b'n', b'\n', 0, 0xff] {
+                    let mut invalid = seq.to_vec();
+                    invalid[pos] = b;
+                    assert_eq!(acgt_prefix_len(&invalid), pos);
+                    let in_range = (b'A'..=b't').contains(&b);
+                    let expected = if in_range { len } else { pos };
+                    assert_eq!(range_prefix_len(&invalid, b'A', b't'), expected);
+                }
+            }
+        }
+        let all = &all_bytes()[37..];
+        let expected = all.iter().position(|b| !is_acgt(*b)).unwrap();
+        assert_eq!(acgt_prefix_len(all), expected);
+        for (min, max) in [(0, 255), (b'!', b'~'), (b'@', b'J'), (10, 9)] {
+            let expected = all
+                .iter()
+                .position(|b| !(min..=max).contains(b))
+                .unwrap_or(all.len());
+            assert_eq!(range_prefix_len(all, min, max), expected);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Minimizer sketches: the set of the (canonical) minimizer hashes of a
+//! sequence, which can be compared to estimate how similar two sequences are
+//! without looking at all their kmers.
+use crate::minimizer::{MinimizerParams, Minimizers};
+
+/// The sorted, deduplicated hashes of the minimizers of one or more sequences
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinimizerSketch {
+    k: u8,
+    w: usize,
+    hashes: Vec<u64>,
+}
+
+impl MinimizerSketch {
+    pub fn new(k: u8, w: usize) -> Self {
+        MinimizerSketch {
+            k,
+            w,
+            hashes: Vec::new(),
+        }
+    }
+
+    /// Adds the minimizers of another sequence to the sketch
+    pub fn add_sequence(&mut self, seq: &[u8]) {
+        let params = MinimizerParams::new(self.k, self.w);
+        self.hashes
+            .extend(Minimizers::new(seq, params).map(|m| m.hash));
+        self.hashes.sort_unstable();
+        self.hashes.dedup();
+    }
+
+    pub fn k(&self) -> u8 {
+        self.k
+    }
+
+    pub fn w(&self) -> usize {
+        self.w
+    }
+
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Number of hashes found in both sketches
+    pub fn intersection_size(&self, other: &MinimizerSketch) -> usize {
+        assert!(
+            self.k == other.k && self.w == other.w,
+            "Can only compare sketches made with the same k and w"
+        );
+        let (mut i, mut j, mut shared) = (0, 0, 0);
+        while i < self.hashes.len() && j < other.hashes.len() {
+            match self.hashes[i].cmp(&other.hashes[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    shared += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        shared
+    }
+
+    /// Jaccard index of the two sets of minimizers (0 if both are empty)
+    pub fn jaccard(&self, other: &MinimizerSketch) -> f64 {
+        let shared = self.intersection_size(other);
+        let union = self.len() + other.len() - shared;
+        if union == 0 {
+            return 0.;
+        }
+        shared as f64 / union as f64
+    }
+
+    /// Fraction of the minimizers of `self` found in `other`, e.g. to check
+    /// whether a read is contained in a contig (0 if `self` is empty)
+    pub fn containment(&self, other: &MinimizerSketch) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        self.intersection_size(other) as f64 / self.len() as f64
+    }
+}
+
+/// Sketches a sequence with minimizers of size `k` over windows of `w` kmers
+///
+/// ```
+/// use needletail::sketch::minimizer_sketch;
+///
+/// let a = minimizer_sketch(b"ACGTAGCTAGCTAGGATCGATCGACTAGCATCAGC", 7, 4);
+/// let b = minimizer_sketch(b"GCTGATGCTAGTCGATCGATCCTAGCTAGCTACGT", 7, 4);
+/// assert_eq!(a.jaccard(&b), 1.0);
+/// ```
+pub fn minimizer_sketch(seq: &[u8], k: u8, w: usize) -> MinimizerSketch {
+    let mut sketch = MinimizerSketch::new(k, w);
+    sketch.add_sequence(seq);
+    sketch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEQ: &[u8] = b"ACGTAGCTAGCTAGGATCGATCGACTAGCATCAGCTAGCGACTAGCATTTTTTTTTT";
+
+    #[test]
+    fn can_compare_sketches() {
+        let full = minimizer_sketch(SEQ, 9, 3);
+        let half = minimizer_sketch(&SEQ[..SEQ.len() / 2], 9, 3);
+        assert!(!half.is_empty());
+        assert!(half.len() < full.len());
+        assert!(half.containment(&full) > 0.8);
+        assert!(full.containment(&half) < 0.8);
+        let jaccard = full.jaccard(&half);
+        assert!(jaccard > 0. && jaccard < 1.);
+        assert_eq!(full.jaccard(&full), 1.);
+
+        let other = minimizer_sketch(b"TTGACCATGACGGATTTACCAGTAGGCATTACAAACG", 9, 3);
+        assert_eq!(full.jaccard(&other), 0.);
+        assert_eq!(MinimizerSketch::new(9, 3).containment(&full), 0.);
+    }
+
+    #[test]
+    fn can_add_sequences() {
+        let mut sketch = MinimizerSketch::new(9, 3);
+        sketch.add_sequence(&SEQ[..30]);
+        sketch.add_sequence(&SEQ[30..]);
+        let full = minimizer_sketch(SEQ, 9, 3);
+        // the minimizers spanning the split are missing
+        assert!(full.containment(&sketch) > 0.7);
+        assert!(sketch.hashes().windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn cant_compare_different_parameters() {
+        minimizer_sketch(SEQ, 9, 3).jaccard(&minimizer_sketch(SEQ, 9, 4));
+    }
+}
@@ -0,0 +1,523 @@
+//! Summary statistics over sequences
+use std::ops::AddAssign;
+
+use crate::errors::ParseError;
+use crate::parser::FastxReader;
+use crate::quality::QualityScores;
+
+/// Classes of bytes counted by `BaseCounts`
+const A: u8 = 0;
+const C: u8 = 1;
+const G: u8 = 2;
+const T: u8 = 3;
+const N: u8 = 4;
+const IUPAC: u8 = 5;
+const GAP: u8 = 6;
+const OTHER: u8 = 7;
+const IGNORED: u8 = 8;
+
+const BASE_CLASSES: [u8; 256] = {
+    let mut table = [OTHER; 256];
+    let mut i = 0;
+    while i < 26 {
+        let (upper, lower) = (b'A' + i, b'a' + i);
+        let class = match upper {
+            b'A' => A,
+            b'C' => C,
+            b'G' => G,
+            b'T' | b'U' => T,
+            b'N' => N,
+            b'R' | b'Y' | b'S' | b'W' | b'K' | b'M' | b'B' | b'D' | b'H' | b'V' => IUPAC,
+            _ => OTHER,
+        };
+        table[upper as usize] = class;
+        table[lower as usize] = class;
+        i += 1;
+    }
+    table[b'-' as usize] = GAP;
+    table[b'.' as usize] = GAP;
+    table[b'\r' as usize] = IGNORED;
+    table[b'\n' as usize] = IGNORED;
+    table
+};
+
+/// Counts of the different kinds of bases of a sequence. Case is ignored, `U`
+/// is counted as a `T` and line endings are skipped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BaseCounts {
+    pub a: u64,
+    pub c: u64,
+    pub g: u64,
+    pub t: u64,
+    pub n: u64,
+    /// IUPAC ambiguity codes other than N (RYSWKMBDHV)
+    pub iupac: u64,
+    /// `-` and `.`
+    pub gap: u64,
+    /// Anything else
+    pub other: u64,
+}
+
+impl BaseCounts {
+    pub fn new(seq: &[u8]) -> Self {
+        let mut counts = BaseCounts::default();
+        counts.add_sequence(seq);
+        counts
+    }
+
+    pub fn add_sequence(&mut self, seq: &[u8]) {
+        let mut counts = [0u64; 9];
+        for base in seq {
+            counts[BASE_CLASSES[*base as usize] as usize] += 1;
+        }
+        *self += BaseCounts {
+            a: counts[A as usize],
+            c: counts[C as usize],
+            g: counts[G as usize],
+            t: counts[T as usize],
+            n: counts[N as usize],
+            iupac: counts[IUPAC as usize],
+            gap: counts[GAP as usize],
+            other: counts[OTHER as usize],
+        };
+    }
+
+    #[inline]
+    fn add_base(&mut self, base: u8) {
+        match BASE_CLASSES[base as usize] {
+            A => self.a += 1,
+            C => self.c += 1,
+            G => self.g += 1,
+            T => self.t += 1,
+            N => self.n += 1,
+            IUPAC => self.iupac += 1,
+            GAP => self.gap += 1,
+            OTHER => self.other += 1,
+            _ => {}
+        }
+    }
+
+    /// Number of A, C, G and T
+    pub fn acgt(&self) -> u64 {
+        self.a + self.c + self.g + self.t
+    }
+
+    /// Total number of bytes counted
+    pub fn total(&self) -> u64 {
+        self.acgt() + self.n + self.iupac + self.gap + self.other
+    }
+
+    /// Fraction of G and C among the A, C, G and T (ambiguous bases are left
+    /// out). 0 if there are none.
+    pub fn gc_content(&self) -> f64 {
+        ratio(self.g + self.c, self.acgt())
+    }
+
+    /// `(A - T) / (A + T)`, 0 if there are none
+    pub fn at_skew(&self) -> f64 {
+        skew(self.a, self.t)
+    }
+
+    /// `(G - C) / (G + C)`, 0 if there are none
+    pub fn gc_skew(&self) -> f64 {
+        skew(self.g, self.c)
+    }
+}
+
+impl AddAssign for BaseCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.a += other.a;
+        self.c += other.c;
+        self.g += other.g;
+        self.t += other.t;
+        self.n += other.n;
+        self.iupac += other.iupac;
+        self.gap += other.gap;
+        self.other += other.other;
+    }
+}
+
+/// Number of different Phred+33 scores, `!` to `~`
+const N_SCORES: usize = 94;
+
+/// Summary of the qualities of a cycle (position in the reads) of a
+/// `QualityProfile`, as plotted by FastQC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CycleQuality {
+    /// Number of reads with a base at this cycle
+    pub count: u64,
+    pub mean: f64,
+    pub p10: u8,
+    pub lower_quartile: u8,
+    pub median: u8,
+    pub upper_quartile: u8,
+    pub p90: u8,
+}
+
+/// Distributions of the qualities and bases at each cycle (position in the
+/// reads) over many reads.
+///
+/// ```
+/// use needletail::stats::QualityProfile;
+///
+/// let mut profile = QualityProfile::new();
+/// profile.add(b"ACGT", b"IIII");
+/// profile.add(b"AGG", b"I5+");
+/// assert_eq!(profile.len(), 4);
+/// assert_eq!(profile.mean(1), 30.);
+/// assert_eq!(profile.base_counts(2).g, 2);
+/// let summary = profile.summary();
+/// assert_eq!(summary[3].count, 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QualityProfile {
+    qualities: Vec<[u64; N_SCORES]>,
+    bases: Vec<BaseCounts>,
+}
+
+impl QualityProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a read and its Phred+33 qualities
+    pub fn add(&mut self, seq: &[u8], qual: &[u8]) {
+        assert_eq!(
+            seq.len(),
+            qual.len(),
+            "The sequence and the quality need to have the same length"
+        );
+        if self.len() < seq.len() {
+            self.qualities.resize(seq.len(), [0; N_SCORES]);
+            self.bases.resize(seq.len(), BaseCounts::default());
+        }
+        for (i, score) in QualityScores::new(qual).enumerate() {
+            self.qualities[i][usize::from(score).min(N_SCORES - 1)] += 1;
+        }
+        for (counts, base) in self.bases.iter_mut().zip(seq) {
+            counts.add_base(*base);
+        }
+    }
+
+    /// Adds all the remaining records of a reader, skipping those without
+    /// qualities
+    pub fn add_reader(&mut self, reader: &mut dyn FastxReader) -> Result<(), ParseError> {
+        while let Some(record) = reader.next() {
+            let record = record?;
+            if let Some(qual) = record.qual() {
+                self.add(&record.seq(), qual);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of cycles, i.e. length of the longest read added
+    pub fn len(&self) -> usize {
+        self.qualities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.qualities.is_empty()
+    }
+
+    /// Number of times each Phred score was seen at a cycle
+    pub fn histogram(&self, cycle: usize) -> &[u64; N_SCORES] {
+        &self.qualities[cycle]
+    }
+
+    /// The bases seen at a cycle
+    pub fn base_counts(&self, cycle: usize) -> &BaseCounts {
+        &self.bases[cycle]
+    }
+
+    /// Number of reads with a base at a cycle
+    pub fn count(&self, cycle: usize) -> u64 {
+        self.qualities[cycle].iter().sum()
+    }
+
+    /// Mean quality of a cycle
+    pub fn mean(&self, cycle: usize) -> f64 {
+        let total: u64 = self.qualities[cycle]
+            .iter()
+            .enumerate()
+            .map(|(score, n)| score as u64 * n)
+            .sum();
+        ratio(total, self.count(cycle))
+    }
+
+    /// Lowest score such that at least the fraction `p` of the qualities of
+    /// a cycle are at or below it, 0 if the cycle is empty
+    pub fn percentile(&self, cycle: usize, p: f64) -> u8 {
+        let count = self.count(cycle);
+        let target = ((p * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (score, n) in self.qualities[cycle].iter().enumerate() {
+            seen += n;
+            if seen >= target {
+                return score as u8;
+            }
+        }
+        0
+    }
+
+    /// Summary of the qualities of every cycle
+    pub fn summary(&self) -> Vec<CycleQuality> {
+        (0..self.len())
+            .map(|cycle| CycleQuality {
+                count: self.count(cycle),
+                mean: self.mean(cycle),
+                p10: self.percentile(cycle, 0.1),
+                lower_quartile: self.percentile(cycle, 0.25),
+                median: self.percentile(cycle, 0.5),
+                upper_quartile: self.percentile(cycle, 0.75),
+                p90: self.percentile(cycle, 0.9),
+            })
+            .collect()
+    }
+}
+
+impl AddAssign<&QualityProfile> for QualityProfile {
+    fn add_assign(&mut self, other: &QualityProfile) {
+        if self.len() < other.len() {
+            self.qualities.resize(other.len(), [0; N_SCORES]);
+            self.bases.resize(other.len(), BaseCounts::default());
+        }
+        for (ours, theirs) in self.qualities.iter_mut().zip(&other.qualities) {
+            for (a, b) in ours.iter_mut().zip(theirs) {
+                *a += b;
+            }
+        }
+        for (ours, theirs) in self.bases.iter_mut().zip(&other.bases) {
+            *ours += *theirs;
+        }
+    }
+}
+
+/// Summary of a `QualityHistogram`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualitySummary {
+    pub reads: u64,
+    pub bases: u64,
+    /// Mean quality of all the bases
+    pub mean_quality: f64,
+    /// Fraction of bases with a quality of at least 20
+    pub q20: f64,
+    /// Fraction of bases with a quality of at least 30
+    pub q30: f64,
+}
+
+/// Distributions of the per-base qualities and of the mean quality of the
+/// reads, accumulated in one pass.
+///
+/// ```
+/// use needletail::stats::QualityHistogram;
+///
+/// let mut histogram = QualityHistogram::new();
+/// histogram.add(b"II55");
+/// histogram.add(b"+?");
+/// let summary = histogram.summary();
+/// assert_eq!((summary.reads, summary.bases), (2, 6));
+/// assert!((summary.q30 - 0.5).abs() < 1e-9);
+/// assert_eq!(histogram.read_means()[30], 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityHistogram {
+    bases: [u64; N_SCORES],
+    read_means: [u64; N_SCORES],
+}
+
+impl Default for QualityHistogram {
+    fn default() -> Self {
+        QualityHistogram {
+            bases: [0; N_SCORES],
+            read_means: [0; N_SCORES],
+        }
+    }
+}
+
+impl QualityHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the Phred+33 qualities of a read
+    pub fn add(&mut self, qual: &[u8]) {
+        let mut total = 0u64;
+        for score in QualityScores::new(qual) {
+            let score = usize::from(score).min(N_SCORES - 1);
+            self.bases[score] += 1;
+            total += score as u64;
+        }
+        let mean = total.checked_div(qual.len() as u64).unwrap_or(0);
+        self.read_means[mean as usize] += 1;
+    }
+
+    /// Adds all the remaining records of a reader, skipping those without
+    /// qualities
+    pub fn add_reader(&mut self, reader: &mut dyn FastxReader) -> Result<(), ParseError> {
+        while let Some(record) = reader.next() {
+            if let Some(qual) = record?.qual() {
+                self.add(qual);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of bases with each Phred score
+    pub fn bases(&self) -> &[u64; N_SCORES] {
+        &self.bases
+    }
+
+    /// Number of reads with each mean Phred score (rounded down)
+    pub fn read_means(&self) -> &[u64; N_SCORES] {
+        &self.read_means
+    }
+
+    /// Fraction of the bases with a quality of at least `score`, 0 if there
+    /// are none
+    pub fn fraction_at_least(&self, score: u8) -> f64 {
+        let total: u64 = self.bases.iter().sum();
+        let above: u64 = self.bases.iter().skip(usize::from(score)).sum();
+        ratio(above, total)
+    }
+
+    /// Fraction of the reads with a mean quality of at least `score`, 0 if
+    /// there are none
+    pub fn read_fraction_at_least(&self, score: u8) -> f64 {
+        let total: u64 = self.read_means.iter().sum();
+        let above: u64 = self.read_means.iter().skip(usize::from(score)).sum();
+        ratio(above, total)
+    }
+
+    pub fn summary(&self) -> QualitySummary {
+        let bases: u64 = self.bases.iter().sum();
+        let total: u64 = self
+            .bases
+            .iter()
+            .enumerate()
+            .map(|(score, n)| score as u64 * n)
+            .sum();
+        QualitySummary {
+            reads: self.read_means.iter().sum(),
+            bases,
+            mean_quality: ratio(total, bases),
+            q20: self.fraction_at_least(20),
+            q30: self.fraction_at_least(30),
+        }
+    }
+}
+
+impl AddAssign<&QualityHistogram> for QualityHistogram {
+    fn add_assign(&mut self, other: &QualityHistogram) {
+        for (a, b) in self.bases.iter_mut().zip(&other.bases) {
+            *a += b;
+        }
+        for (a, b) in self.read_means.iter_mut().zip(&other.read_means) {
+            *a += b;
+        }
+    }
+}
+
+#[inline]
+fn ratio(num: u64, denom: u64) -> f64 {
+    if denom == 0 {
+        0.
+    } else {
+        num as f64 / denom as f64
+    }
+}
+
+#[inline]
+fn skew(x: u64, y: u64) -> f64 {
+    if x + y == 0 {
+        0.
+    } else {
+        (x as f64 - y as f64) / (x + y) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_count_bases() {
+        let counts = BaseCounts::new(b"ACGTacgu\nNnRy-.X*\r\n");
+        assert_eq!(
+            counts,
+            BaseCounts {
+                a: 2,
+                c: 2,
+                g: 2,
+                t: 2,
+                n: 2,
+                iupac: 2,
+                gap: 2,
+                other: 2,
+            }
+        );
+        assert_eq!(counts.total(), 16);
+        assert_eq!(BaseCounts::new(b""), BaseCounts::default());
+    }
+
+    #[test]
+    fn can_compute_ratios() {
+        let counts = BaseCounts::new(b"AAAGGCNNNN");
+        assert!((counts.gc_content() - 0.5).abs() < 1e-9);
+        assert!((counts.at_skew() - 1.).abs() < 1e-9);
+        assert!((counts.gc_skew() - 1. / 3.).abs() < 1e-9);
+        let empty = BaseCounts::new(b"NNN");
+        assert_eq!(empty.gc_content(), 0.);
+        assert_eq!(empty.at_skew(), 0.);
+        assert_eq!(empty.gc_skew(), 0.);
+    }
+
+    #[test]
+    fn can_profile_qualities() {
+        let fastq = b"@a\nACGT\n+\nII+!\n@b\nACG\n+\n5I5\n@c\nTCGA\n+\n+I5!\n";
+        let mut reader = crate::parse_fastx_reader(&fastq[..]).unwrap();
+        let mut profile = QualityProfile::new();
+        profile.add_reader(reader.as_mut()).unwrap();
+        assert_eq!(profile.len(), 4);
+        assert_eq!(profile.count(3), 2);
+        assert_eq!(profile.base_counts(0).a, 2);
+        assert_eq!(profile.base_counts(0).t, 1);
+        let summary = profile.summary();
+        assert!((summary[0].mean - 70. / 3.).abs() < 1e-9);
+        assert_eq!(summary[0].median, 20);
+        assert_eq!((summary[0].p10, summary[0].p90), (10, 40));
+        assert_eq!(summary[1].lower_quartile, 40);
+        assert_eq!(summary[3].median, 0);
+
+        let mut merged = QualityProfile::new();
+        merged += &profile;
+        merged += &profile;
+        assert_eq!(merged.count(0), 6);
+        assert_eq!(merged.summary()[0].median, 20);
+        assert_eq!(merged.base_counts(2).g, 6);
+    }
+
+    #[test]
+    fn can_make_quality_histograms() {
+        let mut histogram = QualityHistogram::new();
+        assert_eq!(histogram.summary().q20, 0.);
+        histogram.add(b"IIII");
+        histogram.add(b"5+!!");
+        histogram.add(b"");
+        let summary = histogram.summary();
+        assert_eq!((summary.reads, summary.bases), (3, 8));
+        assert!((summary.mean_quality - 190. / 8.).abs() < 1e-9);
+        assert!((summary.q20 - 5. / 8.).abs() < 1e-9);
+        assert!((summary.q30 - 0.5).abs() < 1e-9);
+        assert_eq!(histogram.bases()[0], 2);
+        assert_eq!(histogram.read_means()[7], 1);
+        // the empty read counts as a mean of 0
+        assert_eq!(histogram.read_means()[0], 1);
+        assert!((histogram.read_fraction_at_least(20) - 1. / 3.).abs() < 1e-9);
+
+        let mut merged = histogram.clone();
+        merged += &histogram;
+        assert_eq!(merged.summary().reads, 6);
+        assert_eq!(merged.summary().q30, summary.q30);
+    }
+}
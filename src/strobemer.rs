@@ -0,0 +1,284 @@
+//! Strobemers: sets of linked kmers ("strobes") where the first strobe is a
+//! regular kmer and the following ones are picked from windows downstream of
+//! it. They tolerate indels between the strobes much better than a single long
+//! kmer would, which makes them useful for long-read mapping and sketching.
+//!
+//! See Sahlin, K. (2021) "Effective sequence similarity detection with
+//! strobemers", Genome Research.
+use crate::bitkmer::{mix_hash, BitNuclKmer};
+
+/// How the downstream strobes are picked in their windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrobemerMethod {
+    /// Each strobe is the one minimizing the linking function with the
+    /// previous strobes (most sensitive, the default)
+    Randstrobe,
+    /// Each strobe is the minimum hash in its window, independently of the
+    /// previous strobes
+    Minstrobe,
+}
+
+/// The function used to link the hash of the previous strobe(s) to the hash of
+/// a candidate strobe when building randstrobes. The candidate with the lowest
+/// linked value is picked.
+#[derive(Debug, Clone, Copy)]
+pub enum LinkFunction {
+    /// `(h1 + h2) % p`, as in the original strobemer paper; `p` can't be 0
+    ModSum(u64),
+    /// `h1 ^ h2`
+    Xor,
+    /// Any user-provided function
+    Custom(fn(u64, u64) -> u64),
+}
+
+impl LinkFunction {
+    #[inline]
+    fn link(&self, previous: u64, candidate: u64) -> u64 {
+        match self {
+            Self::ModSum(p) => previous.wrapping_add(candidate) % p,
+            Self::Xor => previous ^ candidate,
+            Self::Custom(f) => f(previous, candidate),
+        }
+    }
+}
+
+/// The parameters of a strobemer scheme.
+///
+/// For a strobemer starting at `pos`, the second strobe is picked among the
+/// strobes starting in `pos + w_min..=pos + w_max` and the third one (for
+/// order 3) among the ones starting in `pos + w_max + w_min..=pos + 2 * w_max`.
+#[derive(Debug, Clone, Copy)]
+pub struct StrobemerParams {
+    /// Number of strobes, either 2 or 3
+    pub order: u8,
+    /// Length of each strobe; at most 32
+    pub strobe_len: u8,
+    /// Offset of the start of the window from the previous window start
+    pub w_min: usize,
+    /// Offset of the end of the window (inclusive) from the previous window start
+    pub w_max: usize,
+    pub method: StrobemerMethod,
+    pub link: LinkFunction,
+}
+
+impl StrobemerParams {
+    /// Randstrobes of the given order, linked with `(h1 + h2) % 997`
+    pub fn new(order: u8, strobe_len: u8, w_min: usize, w_max: usize) -> Self {
+        Self {
+            order,
+            strobe_len,
+            w_min,
+            w_max,
+            method: StrobemerMethod::Randstrobe,
+            link: LinkFunction::ModSum(997),
+        }
+    }
+
+    pub fn method(mut self, method: StrobemerMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn link(mut self, link: LinkFunction) -> Self {
+        assert!(
+            !matches!(link, LinkFunction::ModSum(0)),
+            "The modulus can't be 0"
+        );
+        self.link = link;
+        self
+    }
+}
+
+/// A single strobemer, as returned by the `Strobemers` iterator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Strobemer {
+    /// Combined hash of all the strobes, dependent on their order
+    pub hash: u64,
+    positions: [usize; 3],
+    order: u8,
+    strobe_len: u8,
+}
+
+impl Strobemer {
+    /// Position of the first strobe in the sequence
+    #[inline]
+    pub fn pos(&self) -> usize {
+        self.positions[0]
+    }
+
+    /// Start positions of each strobe in the sequence
+    #[inline]
+    pub fn strobe_positions(&self) -> &[usize] {
+        &self.positions[..self.order as usize]
+    }
+
+    /// Number of bases covered from the start of the first strobe to the end
+    /// of the last one
+    #[inline]
+    pub fn span(&self) -> usize {
+        self.positions[self.order as usize - 1] + self.strobe_len as usize - self.positions[0]
+    }
+
+    /// Copies the strobes out of the sequence the strobemer was extracted from and
+    /// concatenates them
+    pub fn to_bytes(&self, seq: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.order as usize * self.strobe_len as usize);
+        for pos in self.strobe_positions() {
+            buf.extend_from_slice(&seq[*pos..*pos + self.strobe_len as usize]);
+        }
+        buf
+    }
+}
+
+/// An iterator over the strobemers of a sequence.
+///
+/// Strobes containing non-ACGT bases are never picked and strobemers are only
+/// emitted when all of their windows fit in the sequence.
+pub struct Strobemers {
+    params: StrobemerParams,
+    hashes: Vec<Option<u64>>,
+    pos: usize,
+}
+
+impl Strobemers {
+    pub fn new(seq: &[u8], params: StrobemerParams) -> Self {
+        assert!(
+            params.order == 2 || params.order == 3,
+            "Only strobemers of order 2 and 3 are supported"
+        );
+        assert!(params.strobe_len > 0 && params.strobe_len <= 32);
+        assert!(params.w_min > 0 && params.w_min <= params.w_max);
+        assert!(
+            !matches!(params.link, LinkFunction::ModSum(0)),
+            "The modulus can't be 0"
+        );
+
+        let n_kmers = (seq.len() + 1).saturating_sub(params.strobe_len as usize);
+        let mut hashes = vec![None; n_kmers];
+        for (pos, kmer, _) in BitNuclKmer::new(seq, params.strobe_len, false) {
+            hashes[pos] = Some(mix_hash(kmer.0));
+        }
+
+        Strobemers {
+            params,
+            hashes,
+            pos: 0,
+        }
+    }
+
+    /// Finds the strobe with the lowest score in the window, leftmost first
+    fn pick(&self, start: usize, end: usize, previous: u64) -> Option<(usize, u64)> {
+        let mut best: Option<(usize, u64, u64)> = None;
+        for (offset, hash) in self.hashes[start..=end].iter().enumerate() {
+            if let Some(h) = *hash {
+                let score = match self.params.method {
+                    StrobemerMethod::Randstrobe => self.params.link.link(previous, h),
+                    StrobemerMethod::Minstrobe => h,
+                };
+                if best.is_none_or(|(_, s, _)| score < s) {
+                    best = Some((start + offset, score, h));
+                }
+            }
+        }
+        best.map(|(pos, _, h)| (pos, h))
+    }
+
+    fn strobemer_at(&self, pos: usize) -> Option<Strobemer> {
+        let StrobemerParams { w_min, w_max, .. } = self.params;
+        let h1 = self.hashes[pos]?;
+        let mut positions = [pos, 0, 0];
+        let (p2, h2) = self.pick(pos + w_min, pos + w_max, h1)?;
+        positions[1] = p2;
+        let mut hash = h1 ^ h2.rotate_left(21);
+        if self.params.order == 3 {
+            let (p3, h3) = self.pick(pos + w_max + w_min, pos + 2 * w_max, h1.wrapping_add(h2))?;
+            positions[2] = p3;
+            hash ^= h3.rotate_left(42);
+        }
+
+        Some(Strobemer {
+            hash,
+            positions,
+            order: self.params.order,
+            strobe_len: self.params.strobe_len,
+        })
+    }
+}
+
+impl Iterator for Strobemers {
+    type Item = Strobemer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the last window has to fully fit for a strobemer to be valid
+        let reach = (self.params.order as usize - 1) * self.params.w_max;
+        while self.pos + reach < self.hashes.len() {
+            let pos = self.pos;
+            self.pos += 1;
+            if let Some(strobemer) = self.strobemer_at(pos) {
+                return Some(strobemer);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEQ: &[u8] = b"ACGTAGCTAGCTAGGATCGATCGACTAGCATCAGCTAGCGACTAGC";
+
+    #[test]
+    fn can_generate_randstrobes() {
+        let params = StrobemerParams::new(2, 5, 3, 6);
+        let strobemers: Vec<_> = Strobemers::new(SEQ, params).collect();
+        // one per position where the window still fits in the sequence
+        assert_eq!(strobemers.len(), SEQ.len() - 5 + 1 - 6);
+        for (i, s) in strobemers.iter().enumerate() {
+            assert_eq!(s.pos(), i);
+            let positions = s.strobe_positions();
+            assert_eq!(positions.len(), 2);
+            assert!(positions[1] >= i + 3 && positions[1] <= i + 6);
+            assert_eq!(s.to_bytes(SEQ).len(), 10);
+        }
+    }
+
+    #[test]
+    fn can_generate_order_3_minstrobes() {
+        let params = StrobemerParams::new(3, 4, 2, 5).method(StrobemerMethod::Minstrobe);
+        let strobemers: Vec<_> = Strobemers::new(SEQ, params).collect();
+        assert_eq!(strobemers.len(), SEQ.len() - 4 + 1 - 10);
+        for s in &strobemers {
+            let positions = s.strobe_positions();
+            assert!(positions[2] >= s.pos() + 7 && positions[2] <= s.pos() + 10);
+            assert_eq!(s.span(), positions[2] + 4 - positions[0]);
+        }
+    }
+
+    #[test]
+    fn strobemers_are_reproducible_across_sequences() {
+        let params = StrobemerParams::new(2, 5, 3, 6).link(LinkFunction::Xor);
+        let a: Vec<u64> = Strobemers::new(SEQ, params).map(|s| s.hash).collect();
+        // a shifted copy should yield the same strobemers, just offset
+        let mut shifted = b"TTT".to_vec();
+        shifted.extend_from_slice(SEQ);
+        let b: Vec<u64> = Strobemers::new(&shifted, params).map(|s| s.hash).collect();
+        assert_eq!(&b[3..], &a[..]);
+    }
+
+    #[test]
+    fn skips_strobes_with_ns() {
+        let params = StrobemerParams::new(2, 3, 1, 2);
+        let strobemers: Vec<_> = Strobemers::new(b"ACGNACGTAC", params).collect();
+        for s in &strobemers {
+            assert!(!s.to_bytes(b"ACGNACGTAC").contains(&b'N'));
+        }
+        assert!(Strobemers::new(b"ACG", params).next().is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_modulus_of_0() {
+        StrobemerParams::new(2, 5, 3, 6).link(LinkFunction::ModSum(0));
+    }
+}
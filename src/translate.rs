@@ -0,0 +1,391 @@
+//! Translation of nucleic acid sequences to proteins using the NCBI genetic
+//! codes (<https://www.ncbi.nlm.nih.gov/Taxonomy/Utils/wprintgc.cgi>).
+use crate::sequence::Strand;
+use crate::Sequence;
+
+/// A genetic code, mapping the 64 codons to amino acids (`*` for stops)
+///
+/// The ciliate codes 27 to 31 aren't supported: in most of them, stop codons
+/// are also read as amino acids depending on their context, which a table
+/// of codons can't express.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodonTable {
+    id: u8,
+    name: &'static str,
+    /// amino acids in the NCBI order: TTT, TTC, TTA, TTG, TCT, ..., GGG
+    amino_acids: &'static [u8; 64],
+}
+
+macro_rules! codon_tables {
+    ($($const_name:ident = ($id:expr, $name:expr, $aas:expr);)*) => {
+        impl CodonTable {
+            $(
+                pub const $const_name: CodonTable = CodonTable {
+                    id: $id,
+                    name: $name,
+                    amino_acids: $aas,
+                };
+            )*
+
+            /// All the supported tables
+            pub const ALL: &'static [CodonTable] = &[$(CodonTable::$const_name),*];
+        }
+    };
+}
+
+codon_tables! {
+    STANDARD = (1, "Standard", b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    VERTEBRATE_MITOCHONDRIAL = (2, "Vertebrate Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG");
+    YEAST_MITOCHONDRIAL = (3, "Yeast Mitochondrial", b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    MOLD_MITOCHONDRIAL = (4, "Mold, Protozoan, and Coelenterate Mitochondrial and Mycoplasma/Spiroplasma", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    INVERTEBRATE_MITOCHONDRIAL = (5, "Invertebrate Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG");
+    CILIATE_NUCLEAR = (6, "Ciliate, Dasycladacean and Hexamita Nuclear", b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    ECHINODERM_MITOCHONDRIAL = (9, "Echinoderm and Flatworm Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG");
+    EUPLOTID_NUCLEAR = (10, "Euplotid Nuclear", b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    BACTERIAL = (11, "Bacterial, Archaeal and Plant Plastid", b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    ALTERNATIVE_YEAST_NUCLEAR = (12, "Alternative Yeast Nuclear", b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    ASCIDIAN_MITOCHONDRIAL = (13, "Ascidian Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG");
+    ALTERNATIVE_FLATWORM_MITOCHONDRIAL = (14, "Alternative Flatworm Mitochondrial", b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG");
+    BLEPHARISMA_MACRONUCLEAR = (15, "Blepharisma Macronuclear", b"FFLLSSSSYY*QCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    CHLOROPHYCEAN_MITOCHONDRIAL = (16, "Chlorophycean Mitochondrial", b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    TREMATODE_MITOCHONDRIAL = (21, "Trematode Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG");
+    SCENEDESMUS_MITOCHONDRIAL = (22, "Scenedesmus obliquus Mitochondrial", b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    THRAUSTOCHYTRIUM_MITOCHONDRIAL = (23, "Thraustochytrium Mitochondrial", b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    RHABDOPLEURIDAE_MITOCHONDRIAL = (24, "Rhabdopleuridae Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG");
+    CANDIDATE_DIVISION_SR1 = (25, "Candidate Division SR1 and Gracilibacteria", b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    PACHYSOLEN_NUCLEAR = (26, "Pachysolen tannophilus Nuclear", b"FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG");
+    CEPHALODISCIDAE_MITOCHONDRIAL = (33, "Cephalodiscidae Mitochondrial", b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG");
+}
+
+impl Default for CodonTable {
+    fn default() -> Self {
+        CodonTable::STANDARD
+    }
+}
+
+/// The unambiguous bases an IUPAC code stands for, as bits in the NCBI order
+/// (T=1, C=2, A=4, G=8)
+#[inline]
+fn iupac_bases(base: u8) -> u8 {
+    match base.to_ascii_uppercase() {
+        b'T' | b'U' => 0b0001,
+        b'C' => 0b0010,
+        b'A' => 0b0100,
+        b'G' => 0b1000,
+        b'Y' => 0b0011,
+        b'W' => 0b0101,
+        b'K' => 0b1001,
+        b'M' => 0b0110,
+        b'S' => 0b1010,
+        b'R' => 0b1100,
+        b'H' => 0b0111,
+        b'B' => 0b1011,
+        b'D' => 0b1101,
+        b'V' => 0b1110,
+        b'N' => 0b1111,
+        _ => 0,
+    }
+}
+
+impl CodonTable {
+    /// Looks up a table by its NCBI identifier (`transl_table`)
+    pub fn from_id(id: u8) -> Option<Self> {
+        CodonTable::ALL.iter().find(|t| t.id == id).copied()
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Translates a single codon. Codons with IUPAC ambiguity codes are
+    /// translated if all the codons they can stand for code for the same
+    /// amino acid (e.g. `GCN` is always alanine) and are `X` otherwise, as is
+    /// anything containing a non-nucleotide character.
+    ///
+    /// ```
+    /// use needletail::translate::CodonTable;
+    ///
+    /// let table = CodonTable::STANDARD;
+    /// assert_eq!(table.translate_codon(b"ATG"), b'M');
+    /// assert_eq!(table.translate_codon(b"gcn"), b'A');
+    /// assert_eq!(table.translate_codon(b"NNN"), b'X');
+    /// assert_eq!(CodonTable::VERTEBRATE_MITOCHONDRIAL.translate_codon(b"TGA"), b'W');
+    /// ```
+    pub fn translate_codon(&self, codon: &[u8]) -> u8 {
+        assert_eq!(codon.len(), 3, "Codons need to be 3 bases long");
+        let sets = [
+            iupac_bases(codon[0]),
+            iupac_bases(codon[1]),
+            iupac_bases(codon[2]),
+        ];
+        let mut aa = None;
+        for b1 in (0..4).filter(|b| sets[0] & (1 << b) != 0) {
+            for b2 in (0..4).filter(|b| sets[1] & (1 << b) != 0) {
+                for b3 in (0..4).filter(|b| sets[2] & (1 << b) != 0) {
+                    let this = self.amino_acids[16 * b1 + 4 * b2 + b3];
+                    match aa {
+                        None => aa = Some(this),
+                        Some(a) if a != this => return b'X',
+                        _ => {}
+                    }
+                }
+            }
+        }
+        aa.unwrap_or(b'X')
+    }
+
+    /// The amino acid of the codon at `index` in the NCBI order (TTT, TTC,
+    /// TTA, ..., GGG)
+    pub(crate) fn amino_acid_at(&self, index: usize) -> u8 {
+        self.amino_acids[index]
+    }
+
+    /// Whether the codon is a stop codon in this table
+    pub fn is_stop(&self, codon: &[u8]) -> bool {
+        self.translate_codon(codon) == b'*'
+    }
+}
+
+/// One of the six reading frames of a sequence: the strand and the number of
+/// bases (0 to 2) skipped at the start of it. For the reverse strand, the
+/// offset is counted from the end of the forward sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Frame {
+    pub strand: Strand,
+    pub offset: u8,
+}
+
+impl Frame {
+    pub fn forward(offset: u8) -> Self {
+        assert!(offset < 3);
+        Frame {
+            strand: Strand::Forward,
+            offset,
+        }
+    }
+
+    pub fn reverse(offset: u8) -> Self {
+        assert!(offset < 3);
+        Frame {
+            strand: Strand::Reverse,
+            offset,
+        }
+    }
+
+    /// The six frames: the 3 forward ones then the 3 reverse ones
+    pub fn all() -> [Frame; 6] {
+        [
+            Frame::forward(0),
+            Frame::forward(1),
+            Frame::forward(2),
+            Frame::reverse(0),
+            Frame::reverse(1),
+            Frame::reverse(2),
+        ]
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Frame::forward(0)
+    }
+}
+
+/// What to do with the 1 or 2 bases left at the end of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PartialCodon {
+    /// Ignore them
+    #[default]
+    Drop,
+    /// Translate them as an `X`
+    Unknown,
+}
+
+/// Translates `seq` in the given frame, appending the amino acids to `out`
+pub fn translate_into(
+    seq: &[u8],
+    table: CodonTable,
+    frame: Frame,
+    partial: PartialCodon,
+    out: &mut Vec<u8>,
+) {
+    let rc;
+    let strand_seq = match frame.strand {
+        Strand::Forward => seq,
+        Strand::Reverse => {
+            rc = seq.reverse_complement();
+            &rc[..]
+        }
+    };
+    let Some(framed) = strand_seq.get(frame.offset as usize..) else {
+        return;
+    };
+    let mut codons = framed.chunks_exact(3);
+    out.extend(codons.by_ref().map(|codon| table.translate_codon(codon)));
+    if !codons.remainder().is_empty() && partial == PartialCodon::Unknown {
+        out.push(b'X');
+    }
+}
+
+/// Translates `seq` in the given frame
+///
+/// ```
+/// use needletail::translate::{translate, CodonTable, Frame, PartialCodon};
+///
+/// let seq = b"ATGGCNTAAC";
+/// let table = CodonTable::STANDARD;
+/// assert_eq!(translate(seq, table, Frame::forward(0), PartialCodon::Drop), b"MA*");
+/// assert_eq!(translate(seq, table, Frame::forward(0), PartialCodon::Unknown), b"MA*X");
+/// assert_eq!(translate(seq, table, Frame::reverse(1), PartialCodon::Drop), b"LXH");
+/// ```
+pub fn translate(seq: &[u8], table: CodonTable, frame: Frame, partial: PartialCodon) -> Vec<u8> {
+    let mut out = Vec::with_capacity(seq.len() / 3 + 1);
+    translate_into(seq, table, frame, partial, &mut out);
+    out
+}
+
+/// The translations of the six frames of a sequence, computed one at a time.
+///
+/// The reverse complement of the sequence is only computed once and the same
+/// buffer is reused for every frame, so this isn't an `Iterator`: each
+/// translation is borrowed until the next call to `next_frame`.
+///
+/// ```
+/// use needletail::Sequence;
+/// use needletail::translate::{CodonTable, Frame};
+///
+/// let mut frames = b"ATGGCCTGA".six_frame_translations(CodonTable::STANDARD);
+/// let (frame, protein) = frames.next_frame().unwrap();
+/// assert_eq!(frame, Frame::forward(0));
+/// assert_eq!(protein, b"MA*");
+/// let mut n_frames = 1;
+/// while let Some((_, _)) = frames.next_frame() {
+///     n_frames += 1;
+/// }
+/// assert_eq!(n_frames, 6);
+/// ```
+pub struct SixFrameTranslations<'a> {
+    seq: &'a [u8],
+    rc: Vec<u8>,
+    table: CodonTable,
+    partial: PartialCodon,
+    frames: std::array::IntoIter<Frame, 6>,
+    buffer: Vec<u8>,
+}
+
+impl<'a> SixFrameTranslations<'a> {
+    pub fn new(seq: &'a [u8], table: CodonTable) -> Self {
+        SixFrameTranslations {
+            seq,
+            rc: Vec::new(),
+            table,
+            partial: PartialCodon::Drop,
+            frames: Frame::all().into_iter(),
+            buffer: Vec::with_capacity(seq.len() / 3 + 1),
+        }
+    }
+
+    /// How to translate the partial codons at the end of the frames
+    pub fn partial_codon(mut self, partial: PartialCodon) -> Self {
+        self.partial = partial;
+        self
+    }
+
+    /// Translates the next frame, in the order of `Frame::all`
+    pub fn next_frame(&mut self) -> Option<(Frame, &[u8])> {
+        let frame = self.frames.next()?;
+        let strand_seq = match frame.strand {
+            Strand::Forward => self.seq,
+            Strand::Reverse => {
+                if self.rc.len() != self.seq.len() {
+                    self.rc = self.seq.reverse_complement();
+                }
+                &self.rc[..]
+            }
+        };
+        self.buffer.clear();
+        // the reverse complement is already done so translate it as a forward frame
+        translate_into(
+            strand_seq,
+            self.table,
+            Frame::forward(frame.offset),
+            self.partial,
+            &mut self.buffer,
+        );
+        Some((frame, &self.buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_are_consistent() {
+        for table in CodonTable::ALL {
+            assert_eq!(CodonTable::from_id(table.id()), Some(*table));
+            assert_eq!(table.translate_codon(b"ATG"), b'M');
+            assert!(table
+                .amino_acids
+                .iter()
+                .all(|aa| b"ACDEFGHIKLMNPQRSTVWY*".contains(aa)));
+        }
+        assert_eq!(CodonTable::from_id(7), None);
+        assert_eq!(CodonTable::from_id(27), None);
+        assert_eq!(CodonTable::PACHYSOLEN_NUCLEAR.translate_codon(b"CTG"), b'A');
+        assert_eq!(
+            CodonTable::CEPHALODISCIDAE_MITOCHONDRIAL.translate_codon(b"TAA"),
+            b'Y'
+        );
+        assert_eq!(CodonTable::default(), CodonTable::STANDARD);
+    }
+
+    #[test]
+    fn can_translate_all_frames() {
+        // the standard code, one codon per amino acid
+        let seq = b"GCTTGTGATGAATTTGGTCATATTAAACTTATGAATCCTCAACGTTCTACTGTTTGGTATTAA";
+        let table = CodonTable::STANDARD;
+        assert_eq!(
+            translate(seq, table, Frame::forward(0), PartialCodon::Drop),
+            b"ACDEFGHIKLMNPQRSTVWY*"
+        );
+        let rc = seq.reverse_complement();
+        for offset in 0..3 {
+            assert_eq!(
+                translate(seq, table, Frame::reverse(offset), PartialCodon::Drop),
+                translate(&rc, table, Frame::forward(offset), PartialCodon::Drop)
+            );
+        }
+        assert!(translate(b"AC", table, Frame::forward(0), PartialCodon::Drop).is_empty());
+        assert!(translate(b"A", table, Frame::forward(2), PartialCodon::Unknown).is_empty());
+    }
+
+    #[test]
+    fn can_translate_ambiguous_codons() {
+        let table = CodonTable::STANDARD;
+        // TTR is always leucine, TTY phenylalanine and TTN either
+        assert_eq!(table.translate_codon(b"TTR"), b'L');
+        assert_eq!(table.translate_codon(b"TTY"), b'F');
+        assert_eq!(table.translate_codon(b"TTN"), b'X');
+        assert_eq!(table.translate_codon(b"AUG"), b'M');
+        assert_eq!(table.translate_codon(b"A-G"), b'X');
+        assert!(table.is_stop(b"TRA"));
+    }
+
+    #[test]
+    fn six_frames_match_translate() {
+        let seq = b"GCTTGTGATGAATTTGGTCATATTAAACTTATGAATCCTCAACGTTCTACTGTTTGGTATTAAC";
+        let table = CodonTable::BACTERIAL;
+        let mut frames = SixFrameTranslations::new(seq, table).partial_codon(PartialCodon::Unknown);
+        for expected_frame in Frame::all() {
+            let (frame, protein) = frames.next_frame().unwrap();
+            assert_eq!(frame, expected_frame);
+            assert_eq!(protein, translate(seq, table, frame, PartialCodon::Unknown));
+        }
+        assert!(frames.next_frame().is_none());
+    }
+}
@@ -0,0 +1,334 @@
+//! Adapter trimming by semi-global alignment, in the style of cutadapt, and
+//! quality trimming
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::parser::SequenceRecord;
+use crate::quality::{error_probability, QualityScores};
+
+/// Where an adapter is ligated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdapterKind {
+    /// The adapter and everything after it is removed. It can be partially
+    /// present at the end of the read.
+    ThreePrime,
+    /// The adapter and everything before it is removed. It can be partially
+    /// present at the start of the read.
+    FivePrime,
+}
+
+/// An adapter to remove from reads
+#[derive(Debug, Clone, PartialEq)]
+pub struct Adapter {
+    pub seq: Vec<u8>,
+    pub kind: AdapterKind,
+    /// Maximum number of errors (mismatches and indels) per aligned base of
+    /// the adapter, 0.1 by default
+    pub max_error_rate: f64,
+    /// Minimum number of bases of the adapter that need to be found at the
+    /// end (or start for 5' adapters) of a read, 3 by default
+    pub min_overlap: usize,
+}
+
+/// Where an adapter was found in a read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdapterMatch {
+    /// Part of the read aligned to the adapter
+    pub read_range: Range<usize>,
+    /// Part of the adapter aligned to the read, all of it unless the adapter
+    /// goes past an end of the read
+    pub adapter_range: Range<usize>,
+    /// Number of mismatches and indels in the alignment
+    pub errors: usize,
+}
+
+impl AdapterMatch {
+    /// Number of aligned adapter bases that are not errors
+    fn score(&self) -> usize {
+        self.adapter_range.len().saturating_sub(self.errors)
+    }
+}
+
+impl Adapter {
+    pub fn new(seq: &[u8], kind: AdapterKind) -> Self {
+        Adapter {
+            seq: seq.to_ascii_uppercase(),
+            kind,
+            max_error_rate: 0.1,
+            min_overlap: 3,
+        }
+    }
+
+    pub fn three_prime(seq: &[u8]) -> Self {
+        Self::new(seq, AdapterKind::ThreePrime)
+    }
+
+    pub fn five_prime(seq: &[u8]) -> Self {
+        Self::new(seq, AdapterKind::FivePrime)
+    }
+
+    pub fn max_error_rate(mut self, max_error_rate: f64) -> Self {
+        self.max_error_rate = max_error_rate;
+        self
+    }
+
+    pub fn min_overlap(mut self, min_overlap: usize) -> Self {
+        self.min_overlap = min_overlap;
+        self
+    }
+
+    /// Finds the best occurrence of the adapter in `read`: the alignment
+    /// with the most matching bases, then the fewest errors, then the
+    /// leftmost (rightmost for 5' adapters) one.
+    pub fn locate(&self, read: &[u8]) -> Option<AdapterMatch> {
+        match self.kind {
+            AdapterKind::ThreePrime => self.locate_three_prime(read.iter(), self.seq.iter()),
+            AdapterKind::FivePrime => {
+                // a 5' adapter is a 3' adapter of the reversed read
+                let m = self.locate_three_prime(read.iter().rev(), self.seq.iter().rev())?;
+                let (n, a) = (read.len(), self.seq.len());
+                Some(AdapterMatch {
+                    read_range: n - m.read_range.end..n - m.read_range.start,
+                    adapter_range: a - m.adapter_range.end..a - m.adapter_range.start,
+                    errors: m.errors,
+                })
+            }
+        }
+    }
+
+    /// Dynamic programming alignment where the adapter can start anywhere in
+    /// the read and go past its end
+    fn locate_three_prime<'a>(
+        &self,
+        read: impl ExactSizeIterator<Item = &'a u8> + Clone,
+        adapter: impl ExactSizeIterator<Item = &'a u8>,
+    ) -> Option<AdapterMatch> {
+        let n = read.len();
+        let m = adapter.len();
+        let max_errors = |len: usize| (self.max_error_rate * len as f64) as usize;
+        // (errors, start in the read) of the alignments of the adapter prefix
+        // ending at each read position
+        let mut prev: Vec<(usize, usize)> = (0..=n).map(|j| (0, j)).collect();
+        let mut cur = prev.clone();
+        let mut best: Option<AdapterMatch> = None;
+        let mut consider = |candidate: AdapterMatch| {
+            let better = match &best {
+                None => true,
+                Some(b) => {
+                    (candidate.score(), std::cmp::Reverse(candidate.errors))
+                        > (b.score(), std::cmp::Reverse(b.errors))
+                }
+            };
+            if better {
+                best = Some(candidate);
+            }
+        };
+
+        for (i, a) in adapter.enumerate() {
+            let i = i + 1;
+            cur[0] = (i, 0);
+            for (j, r) in read.clone().enumerate() {
+                let j = j + 1;
+                let mismatch = usize::from(!bases_match(*a, *r));
+                let diagonal = (prev[j - 1].0 + mismatch, prev[j - 1].1);
+                let deletion = (prev[j].0 + 1, prev[j].1);
+                let insertion = (cur[j - 1].0 + 1, cur[j - 1].1);
+                // prefer the diagonal and then the alignments starting last
+                // so the trimmed part is as short as possible
+                cur[j] = [deletion, insertion].into_iter().fold(diagonal, |acc, x| {
+                    if x.0 < acc.0 || (x.0 == acc.0 && x.1 > acc.1) {
+                        x
+                    } else {
+                        acc
+                    }
+                });
+            }
+            // the adapter runs past the end of the read
+            let (errors, start) = cur[n];
+            if i < m && i >= self.min_overlap && errors <= max_errors(i) && start < n {
+                consider(AdapterMatch {
+                    read_range: start..n,
+                    adapter_range: 0..i,
+                    errors,
+                });
+            }
+            std::mem::swap(&mut prev, &mut cur);
+        }
+
+        // the whole adapter is in the read
+        if m >= self.min_overlap {
+            for (j, (errors, start)) in prev.iter().enumerate() {
+                if *errors <= max_errors(m) && *start < j {
+                    consider(AdapterMatch {
+                        read_range: *start..j,
+                        adapter_range: 0..m,
+                        errors: *errors,
+                    });
+                }
+            }
+        }
+        best
+    }
+
+    /// The part of `read` to keep once the adapter is removed
+    pub fn trim_range(&self, read: &[u8]) -> Range<usize> {
+        match (self.locate(read), self.kind) {
+            (None, _) => 0..read.len(),
+            (Some(m), AdapterKind::ThreePrime) => 0..m.read_range.start,
+            (Some(m), AdapterKind::FivePrime) => m.read_range.end..read.len(),
+        }
+    }
+}
+
+/// Case insensitive base comparison where N matches anything
+#[inline]
+fn bases_match(a: u8, b: u8) -> bool {
+    let (a, b) = (a.to_ascii_uppercase(), b.to_ascii_uppercase());
+    a == b || a == b'N' || b == b'N'
+}
+
+/// Removes a list of adapters from reads, each adapter being searched once
+/// in the order given
+///
+/// ```
+/// use needletail::trim::{Adapter, AdapterTrimmer};
+///
+/// let trimmer = AdapterTrimmer::new(vec![Adapter::three_prime(b"AGATCGGAAGAGC")]);
+/// let (seq, qual) = trimmer.trim(b"ACGTACGTACAGATCGGAAG", Some(b"IIIIIIIIIIIIIIIIIII#"));
+/// assert_eq!(seq, b"ACGTACGTAC");
+/// assert_eq!(qual, Some(&b"IIIIIIIIII"[..]));
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AdapterTrimmer {
+    pub adapters: Vec<Adapter>,
+}
+
+impl AdapterTrimmer {
+    pub fn new(adapters: Vec<Adapter>) -> Self {
+        AdapterTrimmer { adapters }
+    }
+
+    /// The part of `seq` to keep once all the adapters are removed
+    pub fn trim_range(&self, seq: &[u8]) -> Range<usize> {
+        let mut range = 0..seq.len();
+        for adapter in &self.adapters {
+            let kept = adapter.trim_range(&seq[range.clone()]);
+            range = range.start + kept.start..range.start + kept.end;
+        }
+        range
+    }
+
+    /// Trims the sequence and its quality scores, if any, the same way
+    pub fn trim<'a>(&self, seq: &'a [u8], qual: Option<&'a [u8]>) -> (&'a [u8], Option<&'a [u8]>) {
+        let range = self.trim_range(seq);
+        (&seq[range.clone()], qual.map(|q| &q[range]))
+    }
+
+    /// Trims a record, returning its trimmed sequence and quality scores
+    pub fn trim_record<'a>(&self, record: &'a SequenceRecord) -> (Cow<'a, [u8]>, Option<&'a [u8]>) {
+        let range = self.trim_range(&record.seq());
+        record.crop(range.start, range.end)
+    }
+}
+
+/// Finds the part of a read to keep with the modified Mott algorithm of phred
+/// (also used by seqtk and CLC): every base scores the error probability of
+/// `cutoff` minus its own error probability and the kept interval is the one
+/// with the highest total score. The quality is Phred+33 encoded.
+///
+/// Returns an empty range at 0 if no base is above `cutoff`.
+///
+/// ```
+/// use needletail::trim::trim_quality_mott;
+///
+/// assert_eq!(trim_quality_mott(b"##IIIII?#", 20), 2..8);
+/// ```
+pub fn trim_quality_mott(qual: &[u8], cutoff: u8) -> Range<usize> {
+    trim_scores_mott(QualityScores::new(qual), cutoff)
+}
+
+/// Same as `trim_quality_mott` on the Phred scores of the bases, for
+/// qualities in other encodings
+pub fn trim_scores_mott(scores: impl IntoIterator<Item = u8>, cutoff: u8) -> Range<usize> {
+    let limit = f64::from(error_probability(cutoff));
+    let mut best = 0.;
+    let mut best_range = 0..0;
+    let mut score = 0.;
+    let mut start = 0;
+    for (i, q) in scores.into_iter().enumerate() {
+        score += limit - f64::from(error_probability(q));
+        if score <= 0. {
+            score = 0.;
+            start = i + 1;
+        } else if score > best {
+            best = score;
+            best_range = start..i + 1;
+        }
+    }
+    best_range
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fastx_reader;
+
+    const ADAPTER: &[u8] = b"AGATCGGAAGAGC";
+
+    #[test]
+    fn can_find_three_prime_adapters() {
+        let adapter = Adapter::three_prime(ADAPTER);
+        // full, with one mismatch, and partial at the end of the read
+        assert_eq!(adapter.trim_range(b"ACGTACGTAGATCGGAAGAGCTTTT"), 0..8);
+        assert_eq!(adapter.trim_range(b"ACGTACGTAGATCGCAAGAGCTTTT"), 0..8);
+        assert_eq!(adapter.trim_range(b"ACGTACGTACAGAT"), 0..10);
+        // with a deletion in the read
+        let m = adapter.locate(b"ACGTACGTAGATCGGAGAGC").unwrap();
+        assert_eq!((m.read_range, m.errors), (8..20, 1));
+        // too short or too many errors
+        assert_eq!(adapter.trim_range(b"ACGTACGTACGTAG"), 0..14);
+        assert_eq!(adapter.trim_range(b"ACGTACGTAGTTCGCAAGTGCTTTT"), 0..25);
+        assert_eq!(
+            adapter.clone().min_overlap(1).trim_range(b"ACGTACGTCCA"),
+            0..10
+        );
+    }
+
+    #[test]
+    fn can_find_five_prime_adapters() {
+        let adapter = Adapter::five_prime(b"TTTCCCGGG");
+        assert_eq!(adapter.trim_range(b"AATTTCCCGGGACGT"), 11..15);
+        // partial at the start of the read
+        assert_eq!(adapter.trim_range(b"CCGGGACGTACGT"), 5..13);
+        assert_eq!(adapter.trim_range(b"ACGTACGTACGT"), 0..12);
+    }
+
+    #[test]
+    fn can_trim_with_mott() {
+        assert_eq!(trim_quality_mott(b"IIIIII", 20), 0..6);
+        assert_eq!(trim_quality_mott(b"", 20), 0..0);
+        assert_eq!(trim_quality_mott(b"####", 20), 0..0);
+        // a single bad base doesn't outweigh a few good ones...
+        assert_eq!(trim_quality_mott(b"IIIIIIIIIIII5IIIIIII", 20), 0..20);
+        // ...but a very bad one does
+        assert_eq!(trim_quality_mott(b"IIII!IIIIIII", 20), 5..12);
+    }
+
+    #[test]
+    fn can_trim_records() {
+        let fastq = b"@r1\nACGTACGTAGATCGGAAG\n+\n0123456789ABCDEFGH\n@r2\nGGGGCCCC\n+\nIIIIIIII\n";
+        let mut reader = parse_fastx_reader(&fastq[..]).unwrap();
+        let trimmer = AdapterTrimmer::new(vec![
+            Adapter::three_prime(ADAPTER),
+            Adapter::five_prime(b"GGGG"),
+        ]);
+        let rec = reader.next().unwrap().unwrap();
+        let (seq, qual) = trimmer.trim_record(&rec);
+        assert_eq!(&seq[..], b"ACGTACGT");
+        assert_eq!(qual, Some(&b"01234567"[..]));
+        let rec = reader.next().unwrap().unwrap();
+        let (seq, qual) = trimmer.trim_record(&rec);
+        assert_eq!(&seq[..], b"CCCC");
+        assert_eq!(qual, Some(&b"IIII"[..]));
+    }
+}